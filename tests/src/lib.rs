@@ -1,5 +1,6 @@
 use std::ffi::{CStr, CString, c_char, c_void};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::{fs, io};
 
 pub type ObjectHandle = u64;
@@ -9,6 +10,9 @@ unsafe extern "C" {
     fn _test_fetch_string() -> u32;
     fn _test_create_object_a() -> ObjectHandle;
     fn _test_object_a__foo(handle: ObjectHandle) -> i32;
+    fn _analytics_track_event() -> i32;
+    fn _test_sum_buffer(values: *const u32) -> u32;
+    fn _test_sum_floats(values: *const f32) -> f32;
 
     /// For internal use only.
     /// Copies a string from the host's memory to the pointer specified
@@ -17,6 +21,9 @@ unsafe extern "C" {
     /// Copies a Vec<u32> from the host's memory to the pointer specified
     pub fn _host_bufcpy(location: *mut c_void, size: u32);
     /// For internal use only.
+    /// Copies a Vec<f32> from the host's memory to the pointer specified
+    pub fn _host_f32_bufcpy(location: *mut c_void, size: u32);
+    /// For internal use only.
     /// Pushes an f32 to a queue for passing math objects
     pub fn _host_f32_enqueue(f: f32);
     /// For internal use only.
@@ -28,6 +35,22 @@ unsafe extern "C" {
     /// For internal use only.
     /// pops a u32 from the queue for passing buffer lengths
     pub fn _host_u32_dequeue() -> u32;
+    /// Microseconds from the host's monotonic clock
+    pub fn _host_now_micros() -> u64;
+    /// Logs a message at a level, 0 debug, 1 info, 2 warn, 3 error
+    pub fn _host_log(level: u32, msg: *const c_char);
+    /// Sends a string to the host, returning its id
+    pub fn _host_recv_string(location: *const c_char, len: u32) -> u32;
+    /// Game time set by the host
+    pub fn _host_time_seconds() -> f64;
+    /// Uniform in [0, 1) from the host's seeded generator
+    pub fn _host_random() -> f64;
+    /// Uniform in [min, max] from the host's seeded generator
+    pub fn _host_random_range(min: i32, max: i32) -> i32;
+    /// Size of the stored value to copy with `_host_strcpy`, 0 if the key isn't set
+    pub fn _host_storage_get(key: *const c_char) -> u32;
+    /// 0 once stored, 1 if over the storage quota
+    pub fn _host_storage_set(key: *const c_char, value: *const c_char) -> u32;
 
 }
 
@@ -152,8 +175,11 @@ macro_rules! println {
     };
 }
 
+static LOADED: AtomicBool = AtomicBool::new(false);
+
 #[unsafe(no_mangle)]
 extern "C" fn on_load() {
+    LOADED.store(true, Ordering::Relaxed);
     unsafe {
         let s =
             CString::new("log info from wasm!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!").unwrap();
@@ -163,6 +189,12 @@ extern "C" fn on_load() {
     }
 }
 
+/// Whether the host called `on_load` before any other export
+#[unsafe(no_mangle)]
+extern "C" fn loaded_test() -> bool {
+    LOADED.load(Ordering::Relaxed)
+}
+
 #[unsafe(no_mangle)]
 extern "C" fn file_access_test() {
     let current_path = Path::new(env!("CARGO_MANIFEST_DIR"));
@@ -201,11 +233,193 @@ extern "C" fn test_string_fetch() {
     println!("Received string from host: '{}'", string)
 }
 
+#[unsafe(no_mangle)]
+extern "C" fn string_len_test(size: u32) -> u32 {
+    let mut turing_str = vec![0; size as usize];
+    unsafe { _host_strcpy(turing_str.as_mut_ptr(), size) };
+    let turing_str = unsafe { CStr::from_ptr(turing_str.as_ptr() as *const c_char) };
+    turing_str.to_bytes().len() as u32
+}
+
+/// Wraps the json the host passed as `{"echo": <json>}` without parsing it
+#[unsafe(no_mangle)]
+extern "C" fn json_wrap_test(size: u32) -> *const c_char {
+    let mut json = vec![0; size as usize];
+    unsafe { _host_strcpy(json.as_mut_ptr(), size) };
+    let json = unsafe { CStr::from_ptr(json.as_ptr() as *const c_char) };
+    let wrapped = format!("{{\"echo\": {}}}", json.to_string_lossy());
+    CString::new(wrapped).unwrap().into_raw()
+}
+
+/// Sums [1, 2, 3, 4] on the host, the length goes through the u32 queue ahead of the pointer
+#[unsafe(no_mangle)]
+extern "C" fn buffer_host_test() -> u32 {
+    let values = [1u32, 2, 3, 4];
+    unsafe {
+        _host_u32_enqueue(values.len() as u32);
+        _test_sum_buffer(values.as_ptr())
+    }
+}
+
+/// Sums a buffer the host passed, copied in with `_host_bufcpy`
+#[unsafe(no_mangle)]
+extern "C" fn buffer_arg_test(len: u32) -> u32 {
+    let mut values = vec![0u32; len as usize];
+    unsafe { _host_bufcpy(values.as_mut_ptr() as *mut c_void, len) };
+    values.iter().sum()
+}
+
+/// Passes a float buffer to the host, its length goes through the u32 queue
+#[unsafe(no_mangle)]
+extern "C" fn float_buffer_host_test() -> f32 {
+    let values = [0.5f32, 1.5, 2.0, -1.0];
+    unsafe {
+        _host_u32_enqueue(values.len() as u32);
+        _test_sum_floats(values.as_ptr())
+    }
+}
+
+/// Sums a float buffer the host passed, copied in with `_host_f32_bufcpy`
+#[unsafe(no_mangle)]
+extern "C" fn float_buffer_arg_test(len: u32) -> f32 {
+    let mut values = vec![0f32; len as usize];
+    unsafe { _host_f32_bufcpy(values.as_mut_ptr() as *mut c_void, len) };
+    values.iter().sum()
+}
+
+/// Calls into a capability the test host doesn't load
+#[unsafe(no_mangle)]
+extern "C" fn analytics_test() -> i32 {
+    unsafe { _analytics_track_event() }
+}
+
+/// Returns 1 if two successive clock reads are monotonic
+#[unsafe(no_mangle)]
+extern "C" fn now_micros_test() -> u32 {
+    let first = unsafe { _host_now_micros() };
+    let second = unsafe { _host_now_micros() };
+    (second >= first) as u32
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn log_levels_test() {
+    for (level, msg) in [c"d", c"i", c"w", c"e"].iter().enumerate() {
+        unsafe { _host_log(level as u32, msg.as_ptr()) };
+    }
+}
+
+/// Sends two strings in one call, returning their ids packed as `first << 32 | second`
+#[unsafe(no_mangle)]
+extern "C" fn send_strings_test() -> u64 {
+    let first = "builder";
+    let second = "pattern";
+    let first = unsafe { _host_recv_string(first.as_ptr() as *const c_char, first.len() as u32) };
+    let second =
+        unsafe { _host_recv_string(second.as_ptr() as *const c_char, second.len() as u32) };
+    ((first as u64) << 32) | second as u64
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn time_test() -> f64 {
+    unsafe { _host_time_seconds() }
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn random_range_test() -> i32 {
+    unsafe { _host_random_range(1, 1000) }
+}
+
+/// Returns 1 if the host random number is in [0, 1)
+#[unsafe(no_mangle)]
+extern "C" fn host_random_test() -> u32 {
+    let r = unsafe { _host_random() };
+    (0.0..1.0).contains(&r) as u32
+}
+
+/// Writes straight to wasi stdout so the host's output capture sees it
+#[unsafe(no_mangle)]
+extern "C" fn stdout_test() {
+    use io::Write;
+    let mut out = io::stdout();
+    let _ = writeln!(out, "hello from stdout");
+    let _ = out.flush();
+}
+
+/// Returns the bitset unchanged so the host can check it isn't collapsed like a bool
+#[unsafe(no_mangle)]
+extern "C" fn flags_test(flags: u32) -> u32 {
+    flags
+}
+
+/// Mod metadata read by the host at load time, as `key=value` lines
+#[unsafe(link_section = "_meta")]
+#[used]
+static META: [u8; 30] = *b"name=wasm_tests\nauthor=turing\n";
+
+/// api version 1.2.3 of "core", packed as `major << 32 | minor << 16 | patch`
+#[unsafe(no_mangle)]
+extern "C" fn _core_semver() -> u64 {
+    (1 << 32) | (2 << 16) | 3
+}
+
+/// api version 2.0.1 of "physics"
+#[unsafe(no_mangle)]
+extern "C" fn __turing_semver_physics() -> u64 {
+    (2 << 32) | 1
+}
+
+fn storage_get(key: &CStr) -> Option<String> {
+    let sz = unsafe { _host_storage_get(key.as_ptr()) };
+    if sz == 0 {
+        return None;
+    }
+    let mut value = vec![0; sz as usize];
+    unsafe { _host_strcpy(value.as_mut_ptr(), sz) };
+    let value = unsafe { CStr::from_ptr(value.as_ptr() as *const c_char) };
+    Some(value.to_string_lossy().into_owned())
+}
+
+/// Counts how many times it was called, persisted in storage
+#[unsafe(no_mangle)]
+extern "C" fn storage_test() -> u32 {
+    let runs = storage_get(c"runs").map_or(0, |r| r.parse::<u32>().unwrap()) + 1;
+    let value = CString::new(runs.to_string()).unwrap();
+    unsafe { _host_storage_set(c"runs".as_ptr(), value.as_ptr()) };
+    runs
+}
+
+/// Tries to store 100 bytes, returning 1 if that went over the quota
+#[unsafe(no_mangle)]
+extern "C" fn storage_quota_test() -> u32 {
+    let value = CString::new("x".repeat(100)).unwrap();
+    unsafe { _host_storage_set(c"blob".as_ptr(), value.as_ptr()) }
+}
+
+static RENDER_COUNT: AtomicU32 = AtomicU32::new(0);
+
+#[unsafe(no_mangle)]
+extern "C" fn on_render(_delta_time: f32) {
+    RENDER_COUNT.fetch_add(1, Ordering::Relaxed);
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn render_count() -> u32 {
+    RENDER_COUNT.load(Ordering::Relaxed)
+}
+
 #[unsafe(no_mangle)]
 extern "C" fn test_panic() {
     panic!("This is a panic from within wasm!");
 }
 
+#[unsafe(no_mangle)]
+extern "C" fn infinite_loop() {
+    let mut i: u64 = 0;
+    loop {
+        i = std::hint::black_box(i.wrapping_add(1));
+    }
+}
+
 #[unsafe(no_mangle)]
 extern "C" fn object_test(val: ObjectHandle) -> ObjectHandle {
     // Echo back the raw i64 value so host-side opaque pointer handling can be tested.
@@ -1,10 +1,24 @@
 local mod = {}
+
+mod._meta = {
+    name = "lua_test",
+    author = "turing",
+    version = "1.0",
+}
 local api = require("turing_api")
 
+local loaded = false
+
 function mod.on_load()
+    loaded = true
     api.Log.info("Hello from lua!!!!!!!!!!!!!!!")
 end
 
+-- whether the host called on_load before any other function
+function mod.loaded_test()
+    return loaded
+end
+
 function mod.math_ops_test(a, b)
     return a * b
 end
@@ -14,4 +28,227 @@ function mod.string_test(msg)
     return "Now returning a message to Turing."
 end
 
+function mod.dispatch_test(x)
+    return api.dispatched_double(x)
+end
+
+-- calls a function no host registers
+function mod.missing_fn_test()
+    return api.foo(1)
+end
+
+-- api versions, packed as major << 32 | minor << 16 | patch
+function mod._core_semver()
+    return (1 << 32) | (2 << 16) | 3
+end
+
+function mod.__turing_semver_physics()
+    return (2 << 32) | 1
+end
+
+function mod.echo_test(s)
+    return api.echo_string(s)
+end
+
+function mod.find_object(id)
+    if id == 0 then
+        return nil
+    end
+    return id
+end
+
+function mod.null_object_test()
+    return api.is_null_object(nil)
+end
+
+function mod.raw_bytes_test()
+    return "\255ok\0end"
+end
+
+function mod.now_micros_test()
+    local first = api.now_micros()
+    local second = api.now_micros()
+    return second >= first
+end
+
+function mod.now_micros_value()
+    return api.now_micros()
+end
+
+function mod.log_levels_test()
+    api.log.debug("d")
+    api.log.info("i")
+    api.log.warn("w")
+    api.log.error("e")
+end
+
+-- sums {1, 2, 3, 4} on the host
+function mod.buffer_host_test()
+    return api.sum_buffer({1, 2, 3, 4})
+end
+
+-- sums a buffer the host passed as a list
+function mod.buffer_arg_test(values)
+    local total = 0
+    for i = 1, #values do
+        total = total + values[i]
+    end
+    return total
+end
+
+function mod.float_buffer_host_test()
+    return api.sum_floats({0.5, 1.5, 2, -1})
+end
+
+function mod.float_buffer_arg_test(values)
+    local total = 0
+    for i = 1, #values do
+        total = total + values[i]
+    end
+    return total
+end
+
+-- print and warn log through the host like wasm stdout
+function mod.print_test()
+    print("a", 1, true)
+    warn("careful", "!")
+end
+
+function mod.time_test()
+    return api.time_seconds()
+end
+
+function mod.delta_test()
+    return api.delta_time()
+end
+
+function mod.random_range_test()
+    return api.random_range(1, 1000)
+end
+
+function mod.host_random_test()
+    local r = api.random()
+    return r >= 0 and r < 1
+end
+
+function mod.variadic_test()
+    return api.count_args() * 100 + api.count_args(1) * 10 + api.count_args(1, "two", true)
+end
+
+function mod.panic_test()
+    api.do_panic("from lua")
+end
+
+function mod.random_test()
+    return math.random(1, 1000000000)
+end
+
+function mod.queue_from_host_test()
+    api.queue_flags("abcd")
+end
+
+function mod.reenter_test()
+    return api.reenter()
+end
+
+function mod.cancel_test()
+    api.wait_for_cancel()
+    while true do end
+end
+
+function mod.has_track_event()
+    return api.track_event ~= nil
+end
+
+function mod.analytics_test()
+    return api.track_event()
+end
+
+function mod.rust_fn_test(x, label)
+    return api.scale(x, label)
+end
+
+function mod.optional_test()
+    return api.draw_text("a") + api.draw_text("b", 3) + api.draw_text("c", nil)
+end
+
+function mod.missing_arg_test()
+    return api.draw_text()
+end
+
+function mod.fallible_test()
+    return api.always_fails()
+end
+
+function mod.returns_nothing()
+end
+
+function mod.returns_nil()
+    return nil
+end
+
+function mod.returns_wrong_type()
+    return "seven"
+end
+
+function mod.returns_two()
+    return 1, 2
+end
+
+function mod.analytics_spam_test()
+    local total = 0
+    for _ = 1, 5 do
+        total = total + (api.track_event() or 0)
+    end
+    return total
+end
+
+function mod.flags_test(flags)
+    return flags
+end
+
+function mod.json_table_test()
+    return {x = 1, names = {"a", "b"}}
+end
+
+function mod.json_wrap_test(value)
+    return {echo = value}
+end
+
+function mod.json_echo_test(value)
+    value.seen = true
+    return value
+end
+
+-- counts how many times it was called, persisted in storage.
+-- lua converts between the stored string and a number by itself
+function mod.storage_test()
+    local runs = (api.storage.get("runs") or 0) + 1
+    api.storage.set("runs", runs)
+    return runs
+end
+
+-- tries to store 100 bytes, raising an error if that goes over the quota
+function mod.storage_quota_test()
+    api.storage.set("blob", "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx")
+    return 0
+end
+
+local render_count = 0
+
+function mod.on_render(delta_time)
+    render_count = render_count + 1
+end
+
+function mod.render_count()
+    return render_count
+end
+
+function mod.infinite_loop()
+    local i = 0
+    while true do
+        i = i + 1
+    end
+end
+
 return mod
\ No newline at end of file
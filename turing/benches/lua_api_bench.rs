@@ -3,7 +3,7 @@ use std::ffi::{CString, c_void};
 use std::hint::black_box;
 use turing_rs::engine::types::ScriptFnMetadata;
 use turing_rs::interop::params::{DataType, FreeableDataType, Param, Params};
-use turing_rs::interop::types::U32Buffer;
+use turing_rs::interop::types::{F32Buffer, U32Buffer};
 use turing_rs::{ExternalFunctions, Turing};
 
 struct DirectExt {}
@@ -24,12 +24,16 @@ impl ExternalFunctions for DirectExt {
     }
 
     fn free_of_type(ptr: *mut c_void, typ: FreeableDataType) {
-        unsafe { typ.free_ptr(ptr) }
+        unsafe { typ.free_ptr::<DirectExt>(ptr) }
     }
 
     fn free_u32_buffer(buf: U32Buffer) {
         buf.from_rust();
     }
+
+    fn free_f32_buffer(buf: F32Buffer) {
+        buf.from_rust();
+    }
 }
 
 extern "C" fn log_info_wasm(
@@ -97,9 +101,47 @@ fn bench_turing_lua_string_roundtrip(c: &mut Criterion) {
     });
 }
 
+/// Sums 10k floats passed as one buffer against passing them one call at a time
+fn bench_turing_lua_f32_buffer_vs_single_calls(c: &mut Criterion) {
+    let mut turing = setup_turing_for_lua();
+    let lua_path = "../tests/wasm/lua_test.lua";
+    turing.load_script(lua_path, &["test"]).unwrap();
+    let float_buffer_arg_test = turing
+        .get_fn_key("float_buffer_arg_test")
+        .expect("fn key not found");
+    let math_ops_test = turing
+        .get_fn_key("math_ops_test")
+        .expect("fn key not found");
+    let values = (0..10_000).map(|i| i as f32 * 0.5).collect::<Vec<_>>();
+
+    c.bench_function("turing_lua_f32_buffer_10k", |b| {
+        b.iter(|| {
+            let mut params = Params::of_size(1);
+            params.push(Param::F32Buffer(values.clone()));
+
+            let res = turing.call_fn(float_buffer_arg_test, params, DataType::F32);
+            let _ = black_box(res.to_result::<f32>().unwrap());
+        })
+    });
+
+    c.bench_function("turing_lua_f32_single_calls_10k", |b| {
+        b.iter(|| {
+            for v in &values {
+                let mut params = Params::of_size(2);
+                params.push(Param::F32(*v));
+                params.push(Param::F32(1.0));
+
+                let res = turing.call_fn(math_ops_test, params, DataType::F32);
+                let _ = black_box(res.to_result::<f32>().unwrap());
+            }
+        })
+    });
+}
+
 criterion_group!(
     benches,
     bench_turing_lua_math,
-    bench_turing_lua_string_roundtrip
+    bench_turing_lua_string_roundtrip,
+    bench_turing_lua_f32_buffer_vs_single_calls
 );
 criterion_main!(benches);
@@ -8,7 +8,7 @@ use turing_rs::engine::types::ScriptFnMetadata;
 use turing_rs::interop::params::{
     DataType, FfiParam, FfiParamArray, FreeableDataType, Param, Params,
 };
-use turing_rs::interop::types::U32Buffer;
+use turing_rs::interop::types::{F32Buffer, U32Buffer};
 use turing_rs::{ExternalFunctions, Turing};
 
 struct DirectExt {}
@@ -30,12 +30,16 @@ impl ExternalFunctions for DirectExt {
     }
 
     fn free_of_type(ptr: *mut c_void, typ: FreeableDataType) {
-        unsafe { typ.free_ptr(ptr) }
+        unsafe { typ.free_ptr::<DirectExt>(ptr) }
     }
 
     fn free_u32_buffer(buf: U32Buffer) {
         buf.from_rust();
     }
+
+    fn free_f32_buffer(buf: F32Buffer) {
+        buf.from_rust();
+    }
 }
 
 // called from wasm
@@ -161,11 +165,119 @@ fn bench_call_wasm_update_and_fixed(c: &mut Criterion) {
     });
 }
 
+/// Sums 10k floats passed as one buffer against passing them one call at a time
+fn bench_f32_buffer_vs_single_calls(c: &mut Criterion) {
+    let mut turing = setup_turing_with_callbacks();
+
+    // `sum_buffer` copies the staged buffer to address 0 and sums it, `identity` returns its arg
+    let wat = r#"(module
+        (import "env" "_host_f32_bufcpy" (func $bufcpy (param i32 i32)))
+        (memory (export "memory") 1)
+        (func (export "sum_buffer") (param $len i32) (result f32)
+            (local $i i32) (local $sum f32)
+            (call $bufcpy (i32.const 0) (local.get $len))
+            (block $done
+                (loop $next
+                    (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+                    (local.set $sum (f32.add (local.get $sum)
+                        (f32.load (i32.shl (local.get $i) (i32.const 2)))))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br $next)))
+            (local.get $sum))
+        (func (export "identity") (param f32) (result f32) local.get 0))"#;
+    let wasm = wat::parse_str(wat).unwrap();
+
+    let mut path = env::temp_dir();
+    path.push("turing_bench_f32_buffer.wasm");
+    let mut file = File::create(&path).unwrap();
+    file.write_all(&wasm).unwrap();
+
+    turing
+        .load_script(path.to_str().unwrap(), &["test"])
+        .unwrap();
+    let sum_buffer = turing.get_fn_key("sum_buffer").expect("fn key not found");
+    let identity = turing.get_fn_key("identity").expect("fn key not found");
+    let values = (0..10_000).map(|i| i as f32 * 0.5).collect::<Vec<_>>();
+
+    c.bench_function("turing_wasm_f32_buffer_10k", |b| {
+        b.iter(|| {
+            let mut params = Params::of_size(1);
+            params.push(Param::F32Buffer(values.clone()));
+
+            let res = turing.call_fn(sum_buffer, params, DataType::F32);
+            let _ = black_box(res.to_result::<f32>().unwrap());
+        })
+    });
+
+    c.bench_function("turing_wasm_f32_single_calls_10k", |b| {
+        b.iter(|| {
+            for v in &values {
+                let mut params = Params::of_size(1);
+                params.push(Param::F32(*v));
+
+                let res = turing.call_fn(identity, params, DataType::F32);
+                let _ = black_box(res.to_result::<f32>().unwrap());
+            }
+        })
+    });
+}
+
+/// The same three-arg sum through an export the typed path covers and one it doesn't
+fn bench_typed_vs_dynamic_call(c: &mut Criterion) {
+    let mut turing = setup_turing_with_callbacks();
+
+    // mixed param types aren't covered for 3 args, so `sum_mixed` takes the dynamic call
+    let wat = r#"(module (memory (export "memory") 1)
+        (func (export "sum_uniform") (param i32 i32 i32) (result i32)
+            (i32.add (i32.add (local.get 0) (local.get 1)) (local.get 2)))
+        (func (export "sum_mixed") (param i32 f32 i32) (result i32)
+            (i32.add (i32.add (local.get 0) (i32.trunc_f32_s (local.get 1))) (local.get 2))))"#;
+    let wasm = wat::parse_str(wat).unwrap();
+
+    let mut path = env::temp_dir();
+    path.push("turing_bench_typed_vs_dynamic.wasm");
+    let mut file = File::create(&path).unwrap();
+    file.write_all(&wasm).unwrap();
+
+    turing
+        .load_script(path.to_str().unwrap(), &["test"])
+        .unwrap();
+    let uniform = turing.get_fn_key("sum_uniform").expect("fn key not found");
+    let mixed = turing.get_fn_key("sum_mixed").expect("fn key not found");
+
+    let mut group = c.benchmark_group("turing_wasm_typed_vs_dynamic_call");
+    group.bench_function("typed", |b| {
+        b.iter(|| {
+            let mut params = Params::of_size(3);
+            params.push(Param::I32(1));
+            params.push(Param::I32(2));
+            params.push(Param::I32(3));
+
+            let res = turing.call_fn(uniform, params, DataType::I32);
+            let _ = black_box(res.to_result::<i32>().unwrap());
+        })
+    });
+    group.bench_function("dynamic", |b| {
+        b.iter(|| {
+            let mut params = Params::of_size(3);
+            params.push(Param::I32(1));
+            params.push(Param::F32(2.0));
+            params.push(Param::I32(3));
+
+            let res = turing.call_fn(mixed, params, DataType::I32);
+            let _ = black_box(res.to_result::<i32>().unwrap());
+        })
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_call_wasm_add,
     bench_call_tests_wasm_math,
     bench_fetch_string_from_wasm,
     bench_call_wasm_update_and_fixed,
+    bench_f32_buffer_vs_single_calls,
+    bench_typed_vs_dynamic_call,
 );
 criterion_main!(benches);
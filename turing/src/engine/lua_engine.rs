@@ -1,21 +1,38 @@
 use crate::engine::runtime_modules::lua_glam;
-use crate::engine::types::{ScriptCallback, ScriptFnMetadata};
-use crate::interop::params::{DataType, ObjectId, Param, Params};
+use crate::engine::types::{
+    CapabilityMissingPolicy, DispatchCallback, EngineConfig, ErrorKind, ExportSignature,
+    HostCallback, HostFn, LogLevel, ScriptFnMetadata, ScriptFnParameter,
+};
+use crate::engine::{
+    BUDGET_EXCEEDED_MESSAGE, FUNCTION_NOT_FOUND_MESSAGE, INTERRUPTED_MESSAGE, InterruptHandle,
+    TYPE_MISMATCH_MESSAGE, api_version_export_name, panic_message, prefix_log, script_log,
+};
+use crate::interop::params::{DataType, INVALID_CHAR_MESSAGE, NanPolicy, Param, Params};
 use crate::interop::types::Semver;
 use crate::key_vec::KeyVec;
-use crate::{EngineDataState, ExternalFunctions, ScriptFnKey};
+use crate::objects::UNKNOWN_OBJECT_MESSAGE;
+use crate::{
+    CallScope, CallbackCharge, CancelScope, EngineDataState, ExternalFunctions, FastCallHandle,
+    ReentrantCall, ScriptFnKey,
+};
 use anyhow::{Result, anyhow};
 use convert_case::{Case, Casing};
 use mlua::prelude::*;
-use mlua::{Function, MultiValue, Table, Value};
+use mlua::{Function, HookTriggers, MultiValue, Table, Value, VmState};
 use parking_lot::RwLock;
 use rustc_hash::FxHashMap;
 use std::fs;
 use std::marker::PhantomData;
+use std::panic::{AssertUnwindSafe, catch_unwind};
 use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Instant;
 
-fn vec_u32_to_lua_list(lua: &Lua, vec: Vec<u32>) -> mlua::Result<Value> {
+/// How many lua instructions run between checks of the interrupt flag and instruction budget
+const INTERRUPT_CHECK_INTERVAL: u32 = 1000;
+
+fn vec_to_lua_list<T: IntoLua>(lua: &Lua, vec: Vec<T>) -> mlua::Result<Value> {
     let table = lua.create_table_with_capacity(vec.len(), 0)?;
 
     for (i, v) in vec.into_iter().enumerate() {
@@ -26,16 +43,17 @@ fn vec_u32_to_lua_list(lua: &Lua, vec: Vec<u32>) -> mlua::Result<Value> {
     Ok(Value::Table(table))
 }
 
-fn lua_list_to_vec_u32(table: &Table) -> mlua::Result<Vec<u32>> {
+/// Reads a list of numbers, integers and floats are both accepted for float lists
+fn lua_list_to_vec<T: FromLua>(table: &Table) -> mlua::Result<Vec<T>> {
     let len = table.len()? as usize;
     let mut vec = Vec::with_capacity(len);
 
     for i in 1..=len {
-        let v: u32 = table
+        let v: T = table
             .get(i as i64)
             .map_err(|_e| mlua::Error::FromLuaConversionError {
                 from: "Lua value",
-                to: "u32".to_string(),
+                to: std::any::type_name::<T>().to_string(),
                 message: Some(format!("invalid value at index {}", i)),
             })?;
         vec.push(v);
@@ -44,11 +62,126 @@ fn lua_list_to_vec_u32(table: &Table) -> mlua::Result<Vec<u32>> {
     Ok(vec)
 }
 
+/// Deepest table nesting converted to JSON, so a table that contains itself can't recurse forever
+const MAX_JSON_DEPTH: usize = 64;
+
+/// Registry key of the metatable tables made from JSON arrays get,
+/// so an empty one converts back to `[]` rather than `{}`
+const JSON_ARRAY_META: &str = "turing_json_array";
+/// Field set on that metatable
+const JSON_ARRAY_MARKER: &str = "__turing_json_array";
+
+fn json_array_metatable(lua: &Lua) -> mlua::Result<Table> {
+    if let Some(meta) = lua.named_registry_value::<Option<Table>>(JSON_ARRAY_META)? {
+        return Ok(meta);
+    }
+    let meta = lua.create_table()?;
+    meta.raw_set(JSON_ARRAY_MARKER, true)?;
+    lua.set_named_registry_value(JSON_ARRAY_META, meta.clone())?;
+    Ok(meta)
+}
+
+fn is_json_array(table: &Table) -> bool {
+    table
+        .metatable()
+        .is_some_and(|meta| meta.raw_get::<bool>(JSON_ARRAY_MARKER).unwrap_or(false))
+}
+
+fn lua_to_json(val: &Value, depth: usize) -> mlua::Result<serde_json::Value> {
+    Ok(match val {
+        Value::Nil => serde_json::Value::Null,
+        Value::Boolean(b) => serde_json::Value::Bool(*b),
+        Value::Integer(i) => serde_json::Value::from(*i),
+        Value::Number(f) => serde_json::Number::from_f64(*f)
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| mlua::Error::RuntimeError(format!("{f} can't be stored as JSON")))?,
+        Value::String(s) => serde_json::Value::String(s.to_string_lossy()),
+        Value::Table(t) => lua_table_to_json(t, depth + 1)?,
+        other => {
+            return Err(mlua::Error::RuntimeError(format!(
+                "a {} can't be converted to JSON",
+                other.type_name()
+            )));
+        }
+    })
+}
+
+/// A table holding only the keys `1..=n` becomes an array, any other table an object.
+/// An empty table is an object unless it came from a JSON array.
+/// Integer keys of objects are stringified.
+fn lua_table_to_json(table: &Table, depth: usize) -> mlua::Result<serde_json::Value> {
+    if depth > MAX_JSON_DEPTH {
+        return Err(mlua::Error::RuntimeError(
+            "table is nested too deeply to convert to JSON".to_string(),
+        ));
+    }
+
+    let pairs = table
+        .pairs::<Value, Value>()
+        .collect::<mlua::Result<Vec<_>>>()?;
+    let len = table.raw_len();
+    if len > 0 && pairs.len() == len {
+        let mut array = Vec::with_capacity(len);
+        for i in 1..=len {
+            array.push(lua_to_json(&table.raw_get::<Value>(i as i64)?, depth)?);
+        }
+        return Ok(serde_json::Value::Array(array));
+    }
+    if pairs.is_empty() && is_json_array(table) {
+        return Ok(serde_json::Value::Array(Vec::new()));
+    }
+
+    let mut map = serde_json::Map::with_capacity(pairs.len());
+    for (key, val) in pairs {
+        let key = match key {
+            Value::String(s) => s.to_string_lossy(),
+            Value::Integer(i) => i.to_string(),
+            other => {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "a {} key can't be converted to JSON",
+                    other.type_name()
+                )));
+            }
+        };
+        map.insert(key, lua_to_json(&val, depth)?);
+    }
+    Ok(serde_json::Value::Object(map))
+}
+
+/// Arrays become 1-indexed tables, marked so an empty one stays an array,
+/// and objects tables with string keys
+fn json_to_lua(lua: &Lua, json: serde_json::Value) -> mlua::Result<Value> {
+    Ok(match json {
+        serde_json::Value::Null => Value::Nil,
+        serde_json::Value::Bool(b) => Value::Boolean(b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Number(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(s) => Value::String(lua.create_string(&s)?),
+        serde_json::Value::Array(array) => {
+            let table = lua.create_table_with_capacity(array.len(), 0)?;
+            table.set_metatable(Some(json_array_metatable(lua)?))?;
+            for (i, v) in array.into_iter().enumerate() {
+                table.raw_set((i + 1) as i64, json_to_lua(lua, v)?)?;
+            }
+            Value::Table(table)
+        }
+        serde_json::Value::Object(map) => {
+            let table = lua.create_table_with_capacity(0, map.len())?;
+            for (k, v) in map {
+                table.raw_set(k, json_to_lua(lua, v)?)?;
+            }
+            Value::Table(table)
+        }
+    })
+}
+
 impl DataType {
     pub fn to_lua_val_param(
         &self,
         val: &Value,
-        _data: &Arc<RwLock<EngineDataState>>,
+        data: &Arc<RwLock<EngineDataState>>,
     ) -> mlua::Result<Param> {
         match (self, val) {
             (DataType::I8, Value::Integer(i)) => Ok(Param::I8(*i as i8)),
@@ -58,6 +191,14 @@ impl DataType {
             (DataType::U8, Value::Integer(u)) => Ok(Param::U8(*u as u8)),
             (DataType::U16, Value::Integer(u)) => Ok(Param::U16(*u as u16)),
             (DataType::U32, Value::Integer(u)) => Ok(Param::U32(*u as u32)),
+            (DataType::Flags, Value::Integer(u)) => Ok(Param::Flags(*u as u32)),
+            (DataType::Char, Value::Integer(c)) => u32::try_from(*c)
+                .ok()
+                .and_then(char::from_u32)
+                .map(Param::Char)
+                .ok_or_else(|| {
+                    mlua::Error::RuntimeError(format!("{INVALID_CHAR_MESSAGE}: {c:#x}"))
+                }),
             (DataType::U64, Value::Integer(u)) => Ok(Param::U64(*u as u64)),
             (DataType::F32, Value::Number(f)) => Ok(Param::F32(*f as f32)),
             (DataType::F64, Value::Number(f)) => Ok(Param::F64(*f)),
@@ -65,13 +206,22 @@ impl DataType {
             (DataType::RustString | DataType::ExtString, Value::String(s)) => {
                 Ok(Param::String(s.to_string_lossy()))
             }
-            (DataType::Object, Value::Integer(t)) => {
-                let op = *t as u64;
-                Ok(Param::Object(ObjectId::new(op)))
+            (DataType::Object | DataType::OwnedObject, Value::Integer(t)) => {
+                match data.read().objects.to_host(*t as u64) {
+                    Some(id) => Ok(Param::object(id)),
+                    None => Err(mlua::Error::RuntimeError(format!(
+                        "{UNKNOWN_OBJECT_MESSAGE}: {t}"
+                    ))),
+                }
             }
+            (DataType::Object | DataType::OwnedObject, Value::Nil) => Ok(Param::Null),
             (DataType::RustU32Buffer | DataType::ExtU32Buffer, Value::Table(t)) => {
-                Ok(Param::U32Buffer(lua_list_to_vec_u32(t)?))
+                Ok(Param::U32Buffer(lua_list_to_vec(t)?))
             }
+            (DataType::RustF32Buffer | DataType::ExtF32Buffer, Value::Table(t)) => {
+                Ok(Param::F32Buffer(lua_list_to_vec(t)?))
+            }
+            (DataType::RustJson | DataType::ExtJson, val) => Ok(Param::Json(lua_to_json(val, 0)?)),
             _ => Err(mlua::Error::RuntimeError(format!(
                 "Mismatched parameter type: {self} with {val:?}"
             ))),
@@ -80,32 +230,86 @@ impl DataType {
 }
 
 impl Param {
+    /// Converts an extra arg passed to a variadic function, inferring its type from the lua value
+    pub fn from_lua_variadic(val: &Value) -> mlua::Result<Self> {
+        match val {
+            Value::Nil => Ok(Param::Null),
+            Value::Boolean(b) => Ok(Param::Bool(*b)),
+            Value::Integer(i) => Ok(Param::I64(*i)),
+            Value::Number(f) => Ok(Param::F64(*f)),
+            Value::String(s) => Ok(Param::String(s.to_string_lossy())),
+            _ => Err(mlua::Error::RuntimeError(format!(
+                "Unsupported variadic argument: {val:?}"
+            ))),
+        }
+    }
+
     pub fn from_lua_type_val(
         typ: DataType,
         val: Value,
-        _data: &Arc<RwLock<EngineDataState>>,
+        data: &Arc<RwLock<EngineDataState>>,
         _lua: &Lua,
     ) -> Self {
+        let mismatch = |val: &Value| {
+            Param::Error(format!(
+                "{TYPE_MISMATCH_MESSAGE}: expected {typ}, got a lua {}",
+                val.type_name()
+            ))
+        };
+        macro_rules! integer {
+            ( $param:ident, $tp:ty ) => {
+                match val.as_integer() {
+                    Some(i) => Param::$param(i as $tp),
+                    None => mismatch(&val),
+                }
+            };
+        }
         match typ {
-            DataType::I8 => Param::I8(val.as_integer().unwrap() as i8),
-            DataType::I16 => Param::I16(val.as_integer().unwrap() as i16),
-            DataType::I32 => Param::I32(val.as_integer().unwrap() as i32),
-            DataType::I64 => Param::I64(val.as_integer().unwrap()),
-            DataType::U8 => Param::U8(val.as_integer().unwrap() as u8),
-            DataType::U16 => Param::U16(val.as_integer().unwrap() as u16),
-            DataType::U32 => Param::U32(val.as_integer().unwrap() as u32),
-            DataType::U64 => Param::U64(val.as_integer().unwrap() as u64),
-            DataType::F32 => Param::F32(val.as_number().unwrap() as f32),
-            DataType::F64 => Param::F64(val.as_number().unwrap()),
-            DataType::Bool => Param::Bool(val.as_boolean().unwrap()),
+            DataType::I8 => integer!(I8, i8),
+            DataType::I16 => integer!(I16, i16),
+            DataType::I32 => integer!(I32, i32),
+            DataType::I64 => match val.as_integer() {
+                Some(i) => Param::I64(i),
+                None => mismatch(&val),
+            },
+            DataType::U8 => integer!(U8, u8),
+            DataType::U16 => integer!(U16, u16),
+            DataType::U32 => integer!(U32, u32),
+            DataType::Flags => integer!(Flags, u32),
+            DataType::Char => match val.as_integer().map(u32::try_from) {
+                Some(Ok(c)) => Param::char_from_code(c),
+                _ => Param::Error(format!("{INVALID_CHAR_MESSAGE}: {val:?}")),
+            },
+            DataType::U64 => integer!(U64, u64),
+            DataType::F32 => match val.as_number() {
+                Some(f) => Param::F32(f as f32),
+                None => mismatch(&val),
+            },
+            DataType::F64 => match val.as_number() {
+                Some(f) => Param::F64(f),
+                None => mismatch(&val),
+            },
+            DataType::Bool => match val.as_boolean() {
+                Some(b) => Param::Bool(b),
+                None => mismatch(&val),
+            },
             // allocated externally, we copy the string
-            DataType::RustString | DataType::ExtString => {
-                Param::String(val.as_string().unwrap().to_string_lossy())
-            }
-            DataType::Object => Param::Object(ObjectId::new(val.as_integer().unwrap() as u64)),
-            DataType::RustError | DataType::ExtError => {
-                Param::Error(val.as_error().unwrap().to_string())
-            }
+            DataType::RustString | DataType::ExtString => match val.as_string() {
+                Some(s) => Param::String(s.to_string_lossy()),
+                None => mismatch(&val),
+            },
+            DataType::Object | DataType::OwnedObject => match val {
+                Value::Nil => Param::Null,
+                Value::Integer(op) => match data.read().objects.to_host(op as u64) {
+                    Some(id) => Param::object(id),
+                    None => Param::Error(format!("{UNKNOWN_OBJECT_MESSAGE}: {op}")),
+                },
+                _ => mismatch(&val),
+            },
+            DataType::RustError | DataType::ExtError => match val.as_error() {
+                Some(e) => Param::Error(e.to_string()),
+                None => mismatch(&val),
+            },
             DataType::Void => Param::Void,
             DataType::Vec2 => lua_glam::unpack_vec2(val),
             DataType::Vec3 => lua_glam::unpack_vec3(val),
@@ -113,8 +317,23 @@ impl Param {
             DataType::RustQuat | DataType::ExtQuat => lua_glam::unpack_quat(val),
             DataType::RustMat4 | DataType::ExtMat4 => lua_glam::unpack_mat4(val),
             DataType::RustU32Buffer | DataType::ExtU32Buffer => {
-                Param::U32Buffer(lua_list_to_vec_u32(val.as_table().unwrap()).unwrap())
+                match val.as_table().map(lua_list_to_vec) {
+                    Some(Ok(buf)) => Param::U32Buffer(buf),
+                    Some(Err(e)) => Param::Error(format!("{e}")),
+                    None => Param::Error(format!("expected a list of integers, got {val:?}")),
+                }
+            }
+            DataType::RustF32Buffer | DataType::ExtF32Buffer => {
+                match val.as_table().map(lua_list_to_vec) {
+                    Some(Ok(buf)) => Param::F32Buffer(buf),
+                    Some(Err(e)) => Param::Error(format!("{e}")),
+                    None => Param::Error(format!("expected a list of numbers, got {val:?}")),
+                }
             }
+            DataType::RustJson | DataType::ExtJson => match lua_to_json(&val, 0) {
+                Ok(json) => Param::Json(json),
+                Err(e) => Param::Error(format!("{e}")),
+            },
         }
     }
 
@@ -123,8 +342,6 @@ impl Param {
         data: &Arc<RwLock<EngineDataState>>,
         lua: &Lua,
     ) -> mlua::Result<Value> {
-        let _s = data.write();
-
         Ok(match self {
             Param::I8(i) => Value::Integer(i as i64),
             Param::I16(i) => Value::Integer(i as i64),
@@ -133,12 +350,15 @@ impl Param {
             Param::U8(u) => Value::Integer(u as i64),
             Param::U16(u) => Value::Integer(u as i64),
             Param::U32(u) => Value::Integer(u as i64),
+            Param::Flags(u) => Value::Integer(u as i64),
+            Param::Char(c) => Value::Integer(c as i64),
             Param::U64(u) => Value::Integer(u as i64),
             Param::F32(f) => Value::Number(f as f64),
             Param::F64(f) => Value::Number(f),
             Param::Bool(b) => Value::Boolean(b),
             Param::String(s) => Value::String(lua.create_string(&s)?),
-            Param::Object(pointer) => Value::Integer(pointer.as_ffi() as i64),
+            Param::Object(id) => Value::Integer(data.write().objects.to_script(id) as i64),
+            Param::Null => Value::Nil,
             Param::Error(er) => {
                 return Err(mlua::Error::RuntimeError(format!(
                     "Error executing C# function: {er}"
@@ -155,20 +375,26 @@ impl Param {
                 .map_err(|e| mlua::Error::RuntimeError(format!("{}", e)))?,
             Param::Mat4(m) => lua_glam::create_mat4(m, lua)
                 .map_err(|e| mlua::Error::RuntimeError(format!("{}", e)))?,
-            Param::U32Buffer(b) => vec_u32_to_lua_list(lua, b)?,
+            Param::U32Buffer(b) => vec_to_lua_list(lua, b)?,
+            Param::F32Buffer(b) => vec_to_lua_list(lua, b)?,
+            Param::Json(json) => json_to_lua(lua, json)?,
         })
     }
 }
 
 impl Params {
-    pub fn to_lua_args(self, lua: &Lua, data: &Arc<RwLock<EngineDataState>>) -> Result<MultiValue> {
+    /// Moves the params out as lua values, leaving this `Params` empty with its capacity intact
+    pub fn drain_to_lua_args(
+        &mut self,
+        lua: &Lua,
+        data: &Arc<RwLock<EngineDataState>>,
+    ) -> Result<MultiValue> {
         if self.is_empty() {
             return Ok(MultiValue::new());
         }
-        let _s = data.write();
         let vals = self
             .params
-            .into_iter()
+            .drain(..)
             .map(|p| match p {
                 Param::I8(i) => Ok(Value::Integer(i as i64)),
                 Param::I16(i) => Ok(Value::Integer(i as i64)),
@@ -177,12 +403,18 @@ impl Params {
                 Param::U8(u) => Ok(Value::Integer(u as i64)),
                 Param::U16(u) => Ok(Value::Integer(u as i64)),
                 Param::U32(u) => Ok(Value::Integer(u as i64)),
+                Param::Flags(u) => Ok(Value::Integer(u as i64)),
+                Param::Char(c) => Ok(Value::Integer(c as i64)),
                 Param::U64(u) => Ok(Value::Integer(u as i64)),
                 Param::F32(f) => Ok(Value::Number(f as f64)),
                 Param::F64(f) => Ok(Value::Number(f)),
                 Param::Bool(b) => Ok(Value::Boolean(b)),
-                Param::String(s) => Ok(Value::String(lua.create_string(&s).unwrap())),
-                Param::Object(rp) => Ok(Value::Integer(rp.as_ffi() as i64)),
+                Param::String(s) => lua
+                    .create_string(&s)
+                    .map(Value::String)
+                    .map_err(|e| anyhow!("{e}")),
+                Param::Object(id) => Ok(Value::Integer(data.write().objects.to_script(id) as i64)),
+                Param::Null => Ok(Value::Nil),
                 Param::Error(st) => Err(anyhow!("{st}")),
                 Param::Void => unreachable!("Void shouldn't ever be added as an arg"),
                 Param::Vec2(v) => lua_glam::create_vec2(v, lua).map_err(|e| anyhow!("{e}")),
@@ -190,7 +422,9 @@ impl Params {
                 Param::Vec4(v) => lua_glam::create_vec4(v, lua).map_err(|e| anyhow!("{e}")),
                 Param::Quat(q) => lua_glam::create_quat(q, lua).map_err(|e| anyhow!("{e}")),
                 Param::Mat4(m) => lua_glam::create_mat4(m, lua).map_err(|e| anyhow!("{e}")),
-                Param::U32Buffer(b) => vec_u32_to_lua_list(lua, b).map_err(|e| anyhow!("{e}")),
+                Param::U32Buffer(b) => vec_to_lua_list(lua, b).map_err(|e| anyhow!("{e}")),
+                Param::F32Buffer(b) => vec_to_lua_list(lua, b).map_err(|e| anyhow!("{e}")),
+                Param::Json(json) => json_to_lua(lua, json).map_err(|e| anyhow!("{e}")),
             })
             .collect::<Result<Vec<Value>>>()?;
 
@@ -200,32 +434,171 @@ impl Params {
 
 pub struct LuaInterpreter<Ext: ExternalFunctions> {
     lua_fns: FxHashMap<String, ScriptFnMetadata>,
-    func_cache: KeyVec<ScriptFnKey, (String, Function)>,
+    config: EngineConfig,
+    func_cache: Arc<KeyVec<ScriptFnKey, (String, Function)>>,
     data: Arc<RwLock<EngineDataState>>,
     engine: Option<(Lua, Table, Table)>,
+    /// what calls into the loaded script use, None until a script loads
+    target: Option<LuaCallTarget>,
+    /// put in `EngineDataState::reentrant_call` by this interpreter, taken out again when it's dropped
+    reentrant_call: Option<ReentrantCall>,
     fast_calls: FastCallLua,
     pub api_versions: FxHashMap<String, Semver>,
+    /// entries of the module's `_meta` table
+    pub script_meta: FxHashMap<String, String>,
+    interrupt: Arc<AtomicBool>,
+    budget: Arc<InstructionBudget>,
+    /// origin for `now_micros`, captured when the engine is created
+    clock: Instant,
     _ext: PhantomData<Ext>,
 }
 
+/// Per-call instruction limit, counted by the hook in steps of `INTERRUPT_CHECK_INTERVAL`
+#[derive(Default)]
+struct InstructionBudget {
+    /// 0 for unlimited
+    limit: AtomicU64,
+    used: AtomicU64,
+}
+
+impl InstructionBudget {
+    /// Counts one hook interval, returns false once the limit has been passed
+    fn charge(&self) -> bool {
+        let step = INTERRUPT_CHECK_INTERVAL as u64;
+        let used = self.used.fetch_add(step, Ordering::Relaxed) + step;
+        let limit = self.limit.load(Ordering::Relaxed);
+        limit == 0 || used <= limit
+    }
+
+    fn exceeded(&self) -> bool {
+        let limit = self.limit.load(Ordering::Relaxed);
+        limit != 0 && self.used.load(Ordering::Relaxed) > limit
+    }
+}
+
+/// Resets the interrupt flag and instruction budget before calling into lua,
+/// and exposes the flag to host callbacks for the call
+fn begin_call(interrupt: &Arc<AtomicBool>, budget: &InstructionBudget) -> CancelScope {
+    interrupt.store(false, Ordering::Release);
+    budget.used.store(0, Ordering::Relaxed);
+    CancelScope::enter(interrupt)
+}
+
 #[derive(Default)]
 struct FastCallLua {
     update: Option<Function>,
     fixed_update: Option<Function>,
+    /// indexed by `FastCallHandle`, None if the script doesn't define the function
+    /// or defines something that isn't a function
+    named: Vec<Option<Function>>,
+}
+
+/// What a call into the loaded script needs. A clone is kept in `EngineDataState::reentrant_call`,
+/// so host callbacks can nest calls without a second reference to the interpreter.
+#[derive(Clone)]
+struct LuaCallTarget {
+    lua: Lua,
+    module: Table,
+    func_cache: Arc<KeyVec<ScriptFnKey, (String, Function)>>,
+    interrupt: Arc<AtomicBool>,
+    budget: Arc<InstructionBudget>,
+    log_prefix: Option<String>,
+}
+
+impl LuaCallTarget {
+    fn invoke<Ext: ExternalFunctions>(
+        &self,
+        cache_key: ScriptFnKey,
+        params: &mut Params,
+        ret_type: DataType,
+        data: &Arc<RwLock<EngineDataState>>,
+        nested: bool,
+    ) -> Param {
+        let (lua, module) = (&self.lua, &self.module);
+
+        let Some((name, _)) = self.func_cache.try_get(&cache_key) else {
+            return Param::Error(FUNCTION_NOT_FOUND_MESSAGE.to_string());
+        };
+        let name = name.as_str();
+        let _scope = CallScope::script(name);
+
+        let func = module.get::<Value>(name);
+        if let Err(e) = func {
+            return Param::Error(format!("Failed to find function '{name}': {e}"));
+        }
+        let func = func.unwrap();
+        let args = params.drain_to_lua_args(lua, data);
+        if let Err(e) = args {
+            return Param::Error(format!("{e}"));
+        }
+        let args = args.unwrap();
+
+        // nested calls keep the outer call's budget and cancellation
+        let _cancel = (!nested).then(|| begin_call(&self.interrupt, &self.budget));
+        let res = match func {
+            Value::Function(f) => f.call::<MultiValue>(args),
+            _ => return Param::Error(format!("'{name}' is not a function")),
+        };
+
+        let mut values = match res {
+            Ok(values) => values.into_iter(),
+            Err(e) => {
+                return Param::Error(LuaInterpreter::<Ext>::call_error(
+                    &self.interrupt,
+                    &self.budget,
+                    e,
+                ));
+            }
+        };
+        let res = values.next().unwrap_or(Value::Nil);
+        let discarded = values.count();
+        if discarded > 0 {
+            Ext::log_warn(prefix_log(
+                self.log_prefix.as_deref(),
+                format!(
+                    "Lua function '{name}' returned {} values, only the first is used",
+                    discarded + 1
+                ),
+            ));
+        }
+        if res.is_null() || res.is_nil() {
+            return match ret_type {
+                DataType::Void => Param::Void,
+                // a script returning nothing where an object is expected means "no object"
+                DataType::Object | DataType::OwnedObject => Param::Null,
+                _ => Param::Error(format!(
+                    "{TYPE_MISMATCH_MESSAGE}: '{name}' returned nil, expected {ret_type}"
+                )),
+            };
+        }
+
+        Param::from_lua_type_val(ret_type, res, data, lua)
+    }
 }
 
 impl<Ext: ExternalFunctions> LuaInterpreter<Ext> {
     pub fn new(
         lua_functions: &FxHashMap<String, ScriptFnMetadata>,
+        config: &EngineConfig,
         data: Arc<RwLock<EngineDataState>>,
     ) -> Result<Self> {
         Ok(Self {
             lua_fns: lua_functions.clone(),
-            func_cache: KeyVec::new(),
+            config: config.clone(),
+            func_cache: Arc::new(KeyVec::new()),
             data,
             engine: None,
+            target: None,
+            reentrant_call: None,
             fast_calls: FastCallLua::default(),
             api_versions: Default::default(),
+            script_meta: Default::default(),
+            interrupt: Arc::new(AtomicBool::new(false)),
+            budget: Arc::new(InstructionBudget {
+                limit: AtomicU64::new(config.lua_instruction_budget.unwrap_or(0)),
+                used: AtomicU64::new(0),
+            }),
+            clock: Instant::now(),
             _ext: PhantomData,
         })
     }
@@ -235,21 +608,44 @@ impl<Ext: ExternalFunctions> LuaInterpreter<Ext> {
         lua: &Lua,
         table: &Table,
         name: &str,
+        registered_name: &str,
         metadata: &ScriptFnMetadata,
     ) -> Result<()> {
         let cap = metadata.capability.clone();
-        let callback = metadata.callback;
-        let pts = metadata
-            .param_types
-            .iter()
-            .map(|d| d.data_type)
-            .collect::<Vec<_>>();
+        let missing_policy = metadata.capability_missing_policy.clone();
+        let variadic = metadata.variadic;
+        let host_fn = HostFn::resolve(registered_name, metadata, &self.config)?;
+        let pts = metadata.param_types.clone();
         let data = Arc::clone(&self.data);
+        let fn_name = registered_name.to_string();
+        let log_prefix = self.config.log_prefix.clone();
 
         let func = lua
             .create_function(
                 move |lua, args: LuaVariadic<Value>| -> mlua::Result<Value> {
-                    lua_bind_env::<Ext>(&data, lua, &cap, &args, &pts, &callback)
+                    // a panic must not unwind through lua's C frames
+                    catch_unwind(AssertUnwindSafe(|| {
+                        lua_bind_env::<Ext>(
+                            &data,
+                            lua,
+                            &cap,
+                            &missing_policy,
+                            &args,
+                            &pts,
+                            variadic,
+                            &host_fn,
+                        )
+                    }))
+                    .unwrap_or_else(|panic| {
+                        let msg = panic_message(panic.as_ref());
+                        Ext::log_critical(prefix_log(
+                            log_prefix.as_deref(),
+                            format!("Lua function {fn_name} panicked: {msg}"),
+                        ));
+                        Err(mlua::Error::RuntimeError(format!(
+                            "Host function panicked: {msg}"
+                        )))
+                    })
                 },
             )
             .map_err(|e| anyhow!("Failed to create function: {e}"))?;
@@ -321,6 +717,15 @@ impl<Ext: ExternalFunctions> LuaInterpreter<Ext> {
 
     fn bind_lua(&self, api: &Table, lua: &Lua) -> Result<()> {
         for (name, metadata) in self.lua_fns.iter() {
+            if self.config.hide_inactive_capabilities
+                && !self
+                    .data
+                    .read()
+                    .active_capabilities
+                    .contains(&metadata.capability)
+            {
+                continue;
+            }
             if ScriptFnMetadata::is_instance_method(name) {
                 let parts: Vec<&str> = name.splitn(2, ScriptFnMetadata::METHOD_SEPARATOR).collect();
                 let cname = parts[0].to_case(Case::Pascal);
@@ -331,7 +736,7 @@ impl<Ext: ExternalFunctions> LuaInterpreter<Ext> {
                 let Ok(table) = api.raw_get::<Table>(cname.as_str()) else {
                     return Err(anyhow!("table['{cname}'] is not a table"));
                 };
-                self.generate_function(lua, &table, fname.as_str(), metadata)?;
+                self.generate_function(lua, &table, fname.as_str(), name, metadata)?;
             } else if ScriptFnMetadata::is_static_method(name) {
                 let parts: Vec<&str> = name.splitn(2, ScriptFnMetadata::STATIC_SEPARATOR).collect();
                 let cname = parts[0].to_case(Case::Pascal);
@@ -345,22 +750,237 @@ impl<Ext: ExternalFunctions> LuaInterpreter<Ext> {
 
                 Self::generate_new_method(lua, &table)?;
 
-                self.generate_function(lua, &table, fname.as_str(), metadata)?;
+                self.generate_function(lua, &table, fname.as_str(), name, metadata)?;
             } else {
-                let name = name.to_case(Case::Snake);
-                self.generate_function(lua, api, name.as_str(), metadata)?;
+                let fname = name.to_case(Case::Snake);
+                self.generate_function(lua, api, fname.as_str(), name, metadata)?;
             };
         }
 
         lua_glam::create_class_tables(lua, api)?;
 
+        if let Some(on_missing) = self.config.on_missing_function {
+            self.bind_missing_function(api, lua, on_missing)?;
+        }
+
+        // monotonic microseconds since engine creation, scripts get no wall-clock access.
+        // Deterministic scripts read the game time instead.
+        let clock = self.clock;
+        let deterministic = self.config.deterministic;
+        let data = Arc::clone(&self.data);
+        let now_micros = lua
+            .create_function(move |_, ()| {
+                let micros = if deterministic {
+                    data.read().game_time_micros()
+                } else {
+                    clock.elapsed().as_micros() as u64
+                };
+                Ok(micros as i64)
+            })
+            .map_err(|e| anyhow!("Failed to create now_micros function: {e}"))?;
+        api.set("now_micros", now_micros)
+            .map_err(|e| anyhow!("Failed to bind now_micros function: {e}"))?;
+
+        // turing_api.log.<level>(msg), the same as the wasm `_host_log` import
+        let log = lua
+            .create_table()
+            .map_err(|e| anyhow!("Failed to create log table: {e}"))?;
+        for level in LogLevel::ALL {
+            let data = Arc::clone(&self.data);
+            let log_prefix = self.config.log_prefix.clone();
+            let log_fn = lua
+                .create_function(move |_, msg: String| {
+                    script_log::<Ext>(&data, log_prefix.as_deref(), level, &msg);
+                    Ok(())
+                })
+                .map_err(|e| anyhow!("Failed to create log.{} function: {e}", level.name()))?;
+            log.set(level.name(), log_fn)
+                .map_err(|e| anyhow!("Failed to bind log.{} function: {e}", level.name()))?;
+        }
+        api.set("log", log)
+            .map_err(|e| anyhow!("Failed to bind log table: {e}"))?;
+
+        // same host state as the wasm `_host_time_seconds`/`_host_delta_time`/`_host_random*` imports
+        let data = Arc::clone(&self.data);
+        let time_seconds = lua
+            .create_function(move |_, ()| Ok(data.read().time_seconds))
+            .map_err(|e| anyhow!("Failed to create time_seconds function: {e}"))?;
+        api.set("time_seconds", time_seconds)
+            .map_err(|e| anyhow!("Failed to bind time_seconds function: {e}"))?;
+
+        let data = Arc::clone(&self.data);
+        let delta_time = lua
+            .create_function(move |_, ()| Ok(data.read().delta_time))
+            .map_err(|e| anyhow!("Failed to create delta_time function: {e}"))?;
+        api.set("delta_time", delta_time)
+            .map_err(|e| anyhow!("Failed to bind delta_time function: {e}"))?;
+
+        let data = Arc::clone(&self.data);
+        let random = lua
+            .create_function(move |_, ()| Ok(data.write().rng.next_f64()))
+            .map_err(|e| anyhow!("Failed to create random function: {e}"))?;
+        api.set("random", random)
+            .map_err(|e| anyhow!("Failed to bind random function: {e}"))?;
+
+        let data = Arc::clone(&self.data);
+        let random_range = lua
+            .create_function(move |_, (min, max): (i32, i32)| Ok(data.write().rng.range(min, max)))
+            .map_err(|e| anyhow!("Failed to create random_range function: {e}"))?;
+        api.set("random_range", random_range)
+            .map_err(|e| anyhow!("Failed to bind random_range function: {e}"))?;
+
+        // turing_api.storage.get/set, the same as the wasm `_host_storage_*` imports
+        let storage = lua
+            .create_table()
+            .map_err(|e| anyhow!("Failed to create storage table: {e}"))?;
+        let data = Arc::clone(&self.data);
+        let storage_get = lua
+            .create_function(move |_, key: String| {
+                let data = data.read();
+                let value = data
+                    .script_storage_get(&key)
+                    .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+                Ok(value.map(str::to_string))
+            })
+            .map_err(|e| anyhow!("Failed to create storage.get function: {e}"))?;
+        storage
+            .set("get", storage_get)
+            .map_err(|e| anyhow!("Failed to bind storage.get function: {e}"))?;
+        let data = Arc::clone(&self.data);
+        let storage_set = lua
+            .create_function(move |_, (key, value): (String, String)| {
+                data.write()
+                    .script_storage_set(&key, &value)
+                    .map_err(|e| mlua::Error::RuntimeError(e.to_string()))
+            })
+            .map_err(|e| anyhow!("Failed to create storage.set function: {e}"))?;
+        storage
+            .set("set", storage_set)
+            .map_err(|e| anyhow!("Failed to bind storage.set function: {e}"))?;
+        api.set("storage", storage)
+            .map_err(|e| anyhow!("Failed to bind storage table: {e}"))?;
+
+        Ok(())
+    }
+
+    /// Adds `print` and `warn` to the script's env, logging like wasm stdout does.
+    /// `print` tab-separates its arguments and `warn` concatenates them, as in stock lua.
+    fn bind_print(&self, env: &Table, lua: &Lua) -> Result<()> {
+        let log_prefix = self.config.log_prefix.clone();
+        let print = lua
+            .create_function(move |_, args: MultiValue| {
+                let line = args
+                    .iter()
+                    .map(Value::to_string)
+                    .collect::<mlua::Result<Vec<_>>>()?
+                    .join("\t");
+                Ext::log_info(prefix_log(log_prefix.as_deref(), line));
+                Ok(())
+            })
+            .map_err(|e| anyhow!("Failed to create print function: {e}"))?;
+        env.raw_set("print", print)
+            .map_err(|e| anyhow!("Failed to add 'print' to env: {e}"))?;
+
+        let log_prefix = self.config.log_prefix.clone();
+        let warn = lua
+            .create_function(move |_, args: MultiValue| {
+                let line = args
+                    .iter()
+                    .map(Value::to_string)
+                    .collect::<mlua::Result<String>>()?;
+                Ext::log_warn(prefix_log(log_prefix.as_deref(), line));
+                Ok(())
+            })
+            .map_err(|e| anyhow!("Failed to create warn function: {e}"))?;
+        env.raw_set("warn", warn)
+            .map_err(|e| anyhow!("Failed to add 'warn' to env: {e}"))?;
+        Ok(())
+    }
+
+    /// Makes `turing_api.<name>` for any name nothing is bound to a function forwarding its
+    /// name and args to `on_missing`, instead of `nil`
+    fn bind_missing_function(
+        &self,
+        api: &Table,
+        lua: &Lua,
+        on_missing: DispatchCallback,
+    ) -> Result<()> {
+        let data = Arc::clone(&self.data);
+        let log_prefix = self.config.log_prefix.clone();
+        let index = lua
+            .create_function(move |lua, (_, name): (Table, String)| {
+                let host_fn = HostFn::new(HostCallback::Dispatch(on_missing), &name)
+                    .map_err(LuaError::external)?;
+                let data = Arc::clone(&data);
+                let log_prefix = log_prefix.clone();
+                lua.create_function(
+                    move |lua, args: LuaVariadic<Value>| -> mlua::Result<Value> {
+                        catch_unwind(AssertUnwindSafe(|| -> mlua::Result<Value> {
+                            let nan_policy = data.read().nan_policy;
+                            let mut params = Params::of_size(args.len() as u32);
+                            for value in args.iter() {
+                                params.push(nan_checked(
+                                    Param::from_lua_variadic(value)?,
+                                    nan_policy,
+                                )?)
+                            }
+                            // the handler isn't registered under any capability
+                            call_host_fn::<Ext>(&data, lua, params, &host_fn, "")
+                        }))
+                        .unwrap_or_else(|panic| {
+                            let msg = panic_message(panic.as_ref());
+                            Ext::log_critical(prefix_log(
+                                log_prefix.as_deref(),
+                                format!("Missing function handler for {name} panicked: {msg}"),
+                            ));
+                            Err(mlua::Error::RuntimeError(format!(
+                                "Host function panicked: {msg}"
+                            )))
+                        })
+                    },
+                )
+            })
+            .map_err(|e| anyhow!("Failed to create missing function handler: {e}"))?;
+
+        let meta = lua
+            .create_table()
+            .map_err(|e| anyhow!("Failed to create lua table: {e}"))?;
+        meta.raw_set("__index", index)
+            .map_err(|e| anyhow!("Failed to set missing function handler as __index: {e}"))?;
+        api.set_metatable(Some(meta))
+            .map_err(|e| anyhow!("Failed to set turing_api metatable: {e}"))?;
         Ok(())
     }
 
     pub fn load_script(&mut self, path: &Path) -> Result<()> {
-        let lua_src = fs::read_to_string(path)?;
+        self.load_source(&fs::read_to_string(path)?)
+    }
 
+    /// Evaluates a lua module from source that's already in memory
+    pub fn load_source(&mut self, lua_src: &str) -> Result<()> {
         let lua = Lua::new();
+        if let Some(seed) = self.config.effective_random_seed() {
+            seed_random(&lua, seed)?;
+        }
+
+        let interrupt = Arc::clone(&self.interrupt);
+        let budget = Arc::clone(&self.budget);
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(INTERRUPT_CHECK_INTERVAL),
+            move |_, _| {
+                if interrupt.load(Ordering::Acquire) {
+                    return Err(mlua::Error::RuntimeError(INTERRUPTED_MESSAGE.to_string()));
+                }
+                if !budget.charge() {
+                    return Err(mlua::Error::RuntimeError(
+                        BUDGET_EXCEEDED_MESSAGE.to_string(),
+                    ));
+                }
+                Ok(VmState::Continue)
+            },
+        )
+        .map_err(|e| anyhow!("Failed to install interrupt hook: {e}"))?;
+
         let api = lua
             .create_table()
             .map_err(|e| anyhow!("Failed to create lua table: {e}"))?;
@@ -398,12 +1018,30 @@ impl<Ext: ExternalFunctions> LuaInterpreter<Ext> {
         env.raw_set("require", require)
             .map_err(|e| anyhow!("Failed to add 'require' to env: {e}"))?;
 
+        self.bind_print(&env, &lua)?;
+
+        let _cancel = begin_call(&self.interrupt, &self.budget);
         let module: Table = lua
             .load(lua_src)
             .set_environment(env)
             .eval()
             .map_err(|e| anyhow!("Failed to evaluate module: {e}"))?;
 
+        self.script_meta.clear();
+        if let Ok(Value::Table(meta)) = module.get::<Value>("_meta") {
+            for pair in meta.pairs::<String, Value>() {
+                let Ok((key, val)) = pair else { continue };
+                let val = match val {
+                    Value::String(s) => s.to_string_lossy(),
+                    Value::Integer(i) => i.to_string(),
+                    Value::Number(n) => n.to_string(),
+                    Value::Boolean(b) => b.to_string(),
+                    _ => continue,
+                };
+                self.script_meta.insert(key, val);
+            }
+        }
+
         let func = module.get::<Value>("on_update").map_err(|e| e.to_string());
         if let Ok(Value::Function(f)) = func {
             self.fast_calls.update = Some(f);
@@ -415,11 +1053,12 @@ impl<Ext: ExternalFunctions> LuaInterpreter<Ext> {
             self.fast_calls.fixed_update = Some(f);
         }
 
+        let mut func_cache = KeyVec::new();
         for pair in module.pairs::<mlua::String, Function>() {
             let Ok((name, val)) = pair else { continue };
             let name = name.to_string_lossy();
-            self.func_cache.push((name.clone(), val.clone()));
-            if name.starts_with("_") && name.ends_with("_semver") {
+            func_cache.push((name.clone(), val.clone()));
+            if let Some(loaded_mod) = api_version_export_name(&name) {
                 let Ok(version) = val.call::<Value>(MultiValue::new()) else {
                     continue;
                 };
@@ -427,16 +1066,35 @@ impl<Ext: ExternalFunctions> LuaInterpreter<Ext> {
                     Value::Integer(i) => i as u64,
                     _ => continue,
                 };
-                let name = name
-                    .strip_prefix("_")
-                    .unwrap()
-                    .strip_suffix("_semver")
-                    .unwrap()
-                    .to_string();
-                self.api_versions.insert(name, Semver::from_u64(version));
+                self.api_versions
+                    .insert(loaded_mod.to_string(), Semver::from_u64(version));
             }
         }
 
+        self.func_cache = Arc::new(func_cache);
+
+        let target = LuaCallTarget {
+            lua: lua.clone(),
+            module: module.clone(),
+            func_cache: Arc::clone(&self.func_cache),
+            interrupt: Arc::clone(&self.interrupt),
+            budget: Arc::clone(&self.budget),
+            log_prefix: self.config.log_prefix.clone(),
+        };
+        // host callbacks reach the script through the engine data, not through this interpreter,
+        // which the outer call still borrows mutably
+        let nested = target.clone();
+        let reentrant_call: ReentrantCall = Arc::new(
+            move |cache_key: ScriptFnKey,
+                  mut params: Params,
+                  ret_type: DataType,
+                  data: &Arc<RwLock<EngineDataState>>| {
+                nested.invoke::<Ext>(cache_key, &mut params, ret_type, data, true)
+            },
+        );
+        self.data.write().reentrant_call = Some(Arc::clone(&reentrant_call));
+        self.reentrant_call = Some(reentrant_call);
+        self.target = Some(target);
         self.engine = Some((lua, module, api));
 
         Ok(())
@@ -445,43 +1103,14 @@ impl<Ext: ExternalFunctions> LuaInterpreter<Ext> {
     pub fn call_fn(
         &mut self,
         cache_key: ScriptFnKey,
-        params: Params,
+        params: &mut Params,
         ret_type: DataType,
         data: &Arc<RwLock<EngineDataState>>,
     ) -> Param {
-        let Some((lua, module, _)) = &mut self.engine else {
-            return Param::Error("No script is loaded".to_string());
-        };
-
-        // we assume the function exists because we cached it earlier
-        let (name, _) = &self.func_cache.get(&cache_key);
-        let name = name.as_str();
-
-        let func = module.get::<Value>(name);
-        if let Err(e) = func {
-            return Param::Error(format!("Failed to find function '{name}': {e}"));
-        }
-        let func = func.unwrap();
-        let args = params.to_lua_args(lua, data);
-        if let Err(e) = args {
-            return Param::Error(format!("{e}"));
+        match &self.target {
+            Some(target) => target.invoke::<Ext>(cache_key, params, ret_type, data, false),
+            None => Param::Error("No script is loaded".to_string()),
         }
-        let args = args.unwrap();
-
-        let res = match func {
-            Value::Function(f) => f.call::<Value>(args),
-            _ => return Param::Error(format!("'{name}' is not a function")),
-        };
-
-        if let Err(e) = res {
-            return Param::Error(e.to_string());
-        }
-        let res = res.unwrap();
-        if res.is_null() || res.is_nil() {
-            return Param::Void;
-        }
-
-        Param::from_lua_type_val(ret_type, res, data, lua)
     }
 
     pub fn fast_call_update(&mut self, delta_time: f32) -> std::result::Result<(), String> {
@@ -490,9 +1119,10 @@ impl<Ext: ExternalFunctions> LuaInterpreter<Ext> {
         };
 
         if let Some(f) = &self.fast_calls.update {
+            let _cancel = begin_call(&self.interrupt, &self.budget);
             f.call::<Value>(Value::Number(delta_time as f64))
                 .map(|_| ())
-                .map_err(|e| e.to_string())
+                .map_err(|e| Self::call_error(&self.interrupt, &self.budget, e))
         } else {
             Ok(())
         }
@@ -504,43 +1134,244 @@ impl<Ext: ExternalFunctions> LuaInterpreter<Ext> {
         };
 
         if let Some(f) = &self.fast_calls.fixed_update {
+            let _cancel = begin_call(&self.interrupt, &self.budget);
             f.call::<Value>(Value::Number(delta_time as f64))
                 .map(|_| ())
-                .map_err(|e| e.to_string())
+                .map_err(|e| Self::call_error(&self.interrupt, &self.budget, e))
         } else {
             Ok(())
         }
     }
 
+    pub fn register_fast_call(&mut self, name: &str) -> Result<()> {
+        let Some((_, module, _)) = &self.engine else {
+            return Err(anyhow!("No script is loaded"));
+        };
+        let func = match module.get::<Value>(name) {
+            Ok(Value::Function(f)) => Some(f),
+            Ok(Value::Nil) => None,
+            Ok(v) => {
+                return Err(anyhow!(
+                    "'{name}' cannot be used as a fast call, it is a {}",
+                    v.type_name()
+                ));
+            }
+            Err(e) => return Err(anyhow!("Failed to look up '{name}': {e}")),
+        };
+        self.fast_calls.named.push(func);
+        Ok(())
+    }
+
+    pub fn invalidate_fast_call(&mut self) {
+        self.fast_calls.named.push(None);
+    }
+
+    pub fn invoke_fast_call(
+        &mut self,
+        handle: FastCallHandle,
+        delta_time: f32,
+    ) -> std::result::Result<(), String> {
+        let Some(f) = self.fast_calls.named.get(u32::from(handle) as usize) else {
+            return Err("Invalid fast call handle".to_string());
+        };
+        let Some(f) = f else {
+            return Ok(());
+        };
+        let _cancel = begin_call(&self.interrupt, &self.budget);
+        f.call::<Value>(Value::Number(delta_time as f64))
+            .map(|_| ())
+            .map_err(|e| Self::call_error(&self.interrupt, &self.budget, e))
+    }
+
     pub fn get_fn_key(&self, name: &str) -> Option<ScriptFnKey> {
         self.func_cache.key_of(|(n, _)| n == name)
     }
+
+    pub fn fn_name(&self, key: ScriptFnKey) -> Option<&str> {
+        self.func_cache.try_get(&key).map(|(name, _)| name.as_str())
+    }
+
+    pub fn script_exports(&self) -> Vec<String> {
+        self.func_cache
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Lua functions take and return any number of values, so nothing about them is known
+    pub fn export_signature(&self, key: ScriptFnKey) -> Option<ExportSignature> {
+        self.func_cache
+            .try_get(&key)
+            .map(|_| ExportSignature::default())
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle::Lua(Arc::clone(&self.interrupt))
+    }
+
+    pub fn set_instruction_budget(&mut self, budget: Option<u64>) {
+        self.budget
+            .limit
+            .store(budget.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Restarts `math.random` from `seed`, also used for scripts loaded later
+    pub fn set_random_seed(&mut self, seed: u64) -> Result<()> {
+        self.config.random_seed = Some(seed);
+        match &self.engine {
+            Some((lua, _, _)) => seed_random(lua, seed),
+            None => Ok(()),
+        }
+    }
+
+    /// Converts an error from a lua call into a message, reporting interrupted and over-budget calls uniformly
+    fn call_error(interrupt: &AtomicBool, budget: &InstructionBudget, e: mlua::Error) -> String {
+        if interrupt.load(Ordering::Acquire) {
+            INTERRUPTED_MESSAGE.to_string()
+        } else if budget.exceeded() {
+            BUDGET_EXCEEDED_MESSAGE.to_string()
+        } else {
+            e.to_string()
+        }
+    }
+}
+
+impl<Ext: ExternalFunctions> Drop for LuaInterpreter<Ext> {
+    fn drop(&mut self) {
+        // the call holds the lua state, whose host functions hold the engine data
+        let Some(own) = &self.reentrant_call else {
+            return;
+        };
+        let mut data = self.data.write();
+        if data
+            .reentrant_call
+            .as_ref()
+            .is_some_and(|call| Arc::ptr_eq(call, own))
+        {
+            data.reentrant_call = None;
+        }
+    }
+}
+
+/// Seeds `math.random`. Lua 5.4 takes the seed as an integer, so the bits are reused as is.
+fn seed_random(lua: &Lua, seed: u64) -> Result<()> {
+    lua.globals()
+        .get::<Table>("math")
+        .and_then(|math| math.get::<Function>("randomseed"))
+        .and_then(|randomseed| randomseed.call::<()>(seed as i64))
+        .map_err(|e| anyhow!("Failed to seed math.random: {e}"))
+}
+
+/// Applies the host's [`NanPolicy`] to an argument, raising a lua error if it rejects a float
+fn nan_checked(param: Param, policy: NanPolicy) -> mlua::Result<Param> {
+    let is_float = matches!(param, Param::F32(_) | Param::F64(_));
+    match param.apply_nan_policy(policy) {
+        Param::Error(e) if is_float => Err(mlua::Error::RuntimeError(e)),
+        param => Ok(param),
+    }
 }
 
 fn lua_bind_env<Ext: ExternalFunctions>(
     data: &Arc<RwLock<EngineDataState>>,
     lua: &Lua,
     cap: &str,
+    missing_policy: &CapabilityMissingPolicy,
     ps: &LuaVariadic<Value>,
-    p: &[DataType],
-    func: &ScriptCallback,
+    p: &[ScriptFnParameter],
+    variadic: bool,
+    func: &HostFn,
 ) -> mlua::Result<Value> {
     if !data.read().active_capabilities.contains(cap) {
-        return Err(mlua::Error::RuntimeError(format!(
-            "Mod capability '{cap}' is not currently loaded"
-        )));
+        return match missing_policy {
+            CapabilityMissingPolicy::Error => {
+                let msg = format!("Mod capability '{cap}' is not currently loaded");
+                Ext::on_script_error(cap, ErrorKind::MissingCapability, &msg);
+                Err(mlua::Error::RuntimeError(msg))
+            }
+            CapabilityMissingPolicy::SilentDefault(default) => {
+                default.clone().into_lua_val(data, lua)
+            }
+        };
     }
 
-    let mut params = Params::of_size(p.len() as u32);
-    for (exp_typ, value) in p.iter().zip(ps.iter()) {
-        params.push(exp_typ.to_lua_val_param(value, data)?)
+    let charge = EngineDataState::charge_callback(data, cap);
+    if let CallbackCharge::OverBudget { max_calls, first } = charge {
+        if first {
+            Ext::on_script_error(
+                cap,
+                ErrorKind::BudgetExceeded,
+                &format!("Capability '{cap}' exceeded its budget of {max_calls} host calls"),
+            );
+        }
+        // skipped rather than raising, so the rest of the script call still runs
+        return Ok(Value::Nil);
     }
 
+    let invalid = |e: mlua::Error| {
+        Ext::on_script_error(cap, ErrorKind::InvalidArgument, &e.to_string());
+        e
+    };
+    let nan_policy = data.read().nan_policy;
+    let mut params = Params::of_size(p.len().max(ps.len()) as u32);
+    for (i, param) in p.iter().enumerate() {
+        // nil stands in for a left out optional arg, so later ones can still be passed
+        let value = ps.get(i).filter(|v| param.default.is_none() || !v.is_nil());
+        match (value, &param.default) {
+            (Some(value), _) => params.push(
+                param
+                    .data_type
+                    .to_lua_val_param(value, data)
+                    .and_then(|param| nan_checked(param, nan_policy))
+                    .map_err(invalid)?,
+            ),
+            (None, Some(default)) => params.push(default.clone()),
+            (None, None) => {
+                return Err(invalid(mlua::Error::RuntimeError(format!(
+                    "missing argument #{} '{}', expected {}",
+                    i + 1,
+                    param.name,
+                    param.data_type
+                ))));
+            }
+        }
+    }
+    if variadic {
+        for value in ps.iter().skip(p.len()) {
+            params.push(
+                Param::from_lua_variadic(value)
+                    .and_then(|param| nan_checked(param, nan_policy))
+                    .map_err(invalid)?,
+            )
+        }
+    }
+
+    call_host_fn::<Ext>(data, lua, params, func, cap)
+}
+
+/// Passes `params` to the host and converts what it returns back to lua.
+/// `cap` is the capability reported to `ExternalFunctions::on_script_error` if that fails.
+fn call_host_fn<Ext: ExternalFunctions>(
+    data: &Arc<RwLock<EngineDataState>>,
+    lua: &Lua,
+    params: Params,
+    func: &HostFn,
+    cap: &str,
+) -> mlua::Result<Value> {
     let ffi_params = params.to_ffi::<Ext>();
     let ffi_params_struct = ffi_params.as_ffi_array();
 
-    func(ffi_params_struct)
-        .into_param::<Ext>()
-        .map_err(|_| mlua::Error::RuntimeError("unreachable".to_string()))?
-        .into_lua_val(data, lua)
+    let res = func
+        .call_with_metrics(ffi_params_struct, data)
+        .into_param::<Ext>();
+    let kind = match &res {
+        Ok(Param::Error(_)) => ErrorKind::HostError,
+        _ => ErrorKind::InvalidReturn,
+    };
+    let value = res
+        .map_err(|_| mlua::Error::RuntimeError("unreachable".to_string()))
+        .and_then(|param| param.into_lua_val(data, lua));
+    if let Err(e) = &value {
+        Ext::on_script_error(cap, kind, &e.to_string());
+    }
+    value
 }
@@ -1,10 +1,14 @@
 use std::sync::Arc;
+#[cfg(any(feature = "lua", feature = "wasm"))]
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::engine::types::{ExportSignature, LogLevel};
 use crate::interop::types::Semver;
 use crate::{
-    EngineDataState, ExternalFunctions, ScriptFnKey,
+    EngineDataState, ExternalFunctions, FastCallHandle, ScriptFnKey,
     interop::params::{DataType, Param, Params},
 };
+use num_enum::TryFromPrimitive;
 use parking_lot::RwLock;
 use rustc_hash::FxHashMap;
 
@@ -18,6 +22,125 @@ pub mod types;
 
 mod runtime_modules;
 
+/// Error message returned by a script call that was stopped via [`InterruptHandle::interrupt`]
+pub const INTERRUPTED_MESSAGE: &str = "script interrupted";
+
+/// Error message returned by a call with a key that doesn't name a function in the loaded script
+pub const FUNCTION_NOT_FOUND_MESSAGE: &str = "function not found";
+
+/// Script function called by `Turing::load_script` once the script is loaded, if it exports one
+pub const ON_LOAD_FN: &str = "on_load";
+
+/// Script function called before a loaded script is unloaded or replaced, if it exports one
+pub const ON_UNLOAD_FN: &str = "on_unload";
+
+/// Error message returned by a lua call that ran past `EngineConfig::lua_instruction_budget`
+pub const BUDGET_EXCEEDED_MESSAGE: &str = "lua execution budget exceeded";
+
+/// Error a script gets when a storage write would take its capability past the storage quota
+pub const STORAGE_QUOTA_EXCEEDED_MESSAGE: &str = "storage quota exceeded";
+
+/// Error message returned by a call whose args or expected return type don't fit the
+/// signature the script exported the function with, see [`types::ExportSignature`]
+pub const TYPE_MISMATCH_MESSAGE: &str = "type mismatch";
+
+/// Error message returned when a string or buffer has to cross into or out of a wasm module
+/// that exports no memory. Numeric-only calls work on such modules.
+pub const NO_MEMORY_MESSAGE: &str =
+    "this module exports no memory, so string and buffer params are unsupported";
+
+/// Error message returned by `CallHandle::call_fn_reentrant` for engines that can't nest calls.
+/// Use `CallHandle::queue_call` to defer the call until the outer call returns instead.
+pub const REENTRY_UNSUPPORTED_MESSAGE: &str = "engine does not support reentrant calls";
+
+/// Extracts the message from a panic payload caught with `catch_unwind`
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Unknown panic payload".to_string()
+    }
+}
+
+/// Prepends `[prefix] ` to every line of `msg`, so the host can tell which mod logged it
+pub(crate) fn prefix_log(prefix: Option<&str>, msg: String) -> String {
+    let Some(prefix) = prefix else {
+        return msg;
+    };
+    msg.lines()
+        .map(|line| format!("[{prefix}] {line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Logs a message from a script's log api if `level` passes the host's filter,
+/// prefixed with the configured log prefix or else the script's file name
+pub(crate) fn script_log<Ext: ExternalFunctions>(
+    data: &RwLock<EngineDataState>,
+    log_prefix: Option<&str>,
+    level: LogLevel,
+    msg: &str,
+) {
+    let msg = {
+        let data = data.read();
+        if level < data.log_level {
+            return;
+        }
+        prefix_log(
+            Some(log_prefix.unwrap_or(&data.script_name)),
+            msg.to_string(),
+        )
+    };
+    level.emit::<Ext>(msg);
+}
+
+/// Returns the api name if `export` is a script function declaring an api version,
+/// either `__turing_semver_<name>` or the older `_<name>_semver`
+pub(crate) fn api_version_export_name(export: &str) -> Option<&str> {
+    if let Some(name) = export.strip_prefix("__turing_semver_") {
+        return Some(name);
+    }
+    export.strip_prefix("_")?.strip_suffix("_semver")
+}
+
+/// Thread-safe handle that can interrupt the script call currently running on an engine.
+/// A handle is tied to the script that was loaded when it was created.
+#[derive(Clone)]
+pub enum InterruptHandle {
+    /// the engine's epoch is bumped to trap the call, the flag is for host callbacks
+    #[cfg(feature = "wasm")]
+    Wasm(wasmtime::Engine, Arc<AtomicBool>),
+    #[cfg(feature = "lua")]
+    Lua(Arc<AtomicBool>),
+}
+
+impl InterruptHandle {
+    /// Causes the in-flight script call (if any) to stop and return `Param::Error("script interrupted")`
+    pub fn interrupt(&self) {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "wasm")]
+            InterruptHandle::Wasm(engine, flag) => {
+                flag.store(true, Ordering::Release);
+                engine.increment_epoch();
+            }
+            #[cfg(feature = "lua")]
+            InterruptHandle::Lua(flag) => flag.store(true, Ordering::Release),
+            _ => {}
+        }
+    }
+}
+
+/// Which engine runs the loaded script. The values are stable over FFI, 0 means no script is loaded.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+pub enum EngineKind {
+    Wasm = 1,
+    Lua = 2,
+}
+
 #[allow(clippy::large_enum_variant)]
 pub enum Engine<Ext>
 where
@@ -33,6 +156,17 @@ impl<Ext> Engine<Ext>
 where
     Ext: ExternalFunctions + Send + Sync + 'static,
 {
+    pub fn kind(&self) -> EngineKind {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "wasm")]
+            Engine::Wasm(_) => EngineKind::Wasm,
+            #[cfg(feature = "lua")]
+            Engine::Lua(_) => EngineKind::Lua,
+            _ => panic!("No code engine is active"),
+        }
+    }
+
     pub fn get_fn_key(&self, name: &str) -> Option<ScriptFnKey> {
         #[allow(unreachable_patterns)]
         match self {
@@ -44,10 +178,22 @@ where
         }
     }
 
+    /// The name of the script function `key` refers to
+    pub fn fn_name(&self, key: ScriptFnKey) -> Option<&str> {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "wasm")]
+            Engine::Wasm(engine) => engine.fn_name(key),
+            #[cfg(feature = "lua")]
+            Engine::Lua(engine) => engine.fn_name(key),
+            _ => None,
+        }
+    }
+
     pub fn call_fn(
         &mut self,
         cache_key: ScriptFnKey,
-        params: Params,
+        params: &mut Params,
         ret_type: DataType,
         data: &Arc<RwLock<EngineDataState>>,
     ) -> Param {
@@ -83,6 +229,138 @@ where
         }
     }
 
+    pub fn register_fast_call(&mut self, name: &str) -> anyhow::Result<()> {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "wasm")]
+            Engine::Wasm(engine) => engine.register_fast_call(name),
+            #[cfg(feature = "lua")]
+            Engine::Lua(engine) => engine.register_fast_call(name),
+            _ => Err(anyhow::anyhow!("No code engine is active")),
+        }
+    }
+
+    /// Takes up the next fast call handle without resolving anything, invoking it does nothing
+    pub fn invalidate_fast_call(&mut self, name: &str) {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "wasm")]
+            Engine::Wasm(engine) => engine.invalidate_fast_call(name),
+            #[cfg(feature = "lua")]
+            Engine::Lua(engine) => engine.invalidate_fast_call(),
+            _ => {}
+        }
+    }
+
+    pub fn invoke_fast_call(
+        &mut self,
+        handle: FastCallHandle,
+        delta_time: f32,
+    ) -> Result<(), String> {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "wasm")]
+            Engine::Wasm(engine) => engine.invoke_fast_call(handle, delta_time),
+            #[cfg(feature = "lua")]
+            Engine::Lua(engine) => engine.invoke_fast_call(handle, delta_time),
+            _ => Err("No code engine is active".to_string()),
+        }
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "wasm")]
+            Engine::Wasm(engine) => engine.interrupt_handle(),
+            #[cfg(feature = "lua")]
+            Engine::Lua(engine) => engine.interrupt_handle(),
+            _ => panic!("No code engine is active"),
+        }
+    }
+
+    pub fn script_exports(&self) -> Vec<String> {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "wasm")]
+            Engine::Wasm(engine) => engine.script_exports(),
+            #[cfg(feature = "lua")]
+            Engine::Lua(engine) => engine.script_exports(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn set_lua_instruction_budget(&mut self, budget: Option<u64>) {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "lua")]
+            Engine::Lua(engine) => engine.set_instruction_budget(budget),
+            _ => {}
+        }
+    }
+
+    pub fn set_random_seed(&mut self, seed: u64) -> anyhow::Result<()> {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "lua")]
+            Engine::Lua(engine) => engine.set_random_seed(seed),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn module_bytes(&self) -> Option<Vec<u8>> {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "wasm")]
+            Engine::Wasm(engine) => engine.module_bytes(),
+            _ => None,
+        }
+    }
+
+    pub fn compiled_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "wasm")]
+            Engine::Wasm(engine) => engine.compiled_bytes(),
+            _ => Err(anyhow::anyhow!(
+                "Compiled bytes are only available for wasm scripts"
+            )),
+        }
+    }
+
+    pub fn reset_instance(&mut self) -> anyhow::Result<()> {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "wasm")]
+            Engine::Wasm(engine) => engine.reset_instance(),
+            _ => Err(anyhow::anyhow!(
+                "Resetting the instance is only supported for wasm scripts"
+            )),
+        }
+    }
+
+    /// The signature the function behind `key` was exported with, None if the key names nothing
+    pub fn export_signature(&self, key: ScriptFnKey) -> Option<ExportSignature> {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "wasm")]
+            Engine::Wasm(engine) => engine.export_signature(key),
+            #[cfg(feature = "lua")]
+            Engine::Lua(engine) => engine.export_signature(key),
+            _ => None,
+        }
+    }
+
+    pub fn script_meta(&self) -> Option<&FxHashMap<String, String>> {
+        #[allow(unreachable_patterns)]
+        match self {
+            #[cfg(feature = "wasm")]
+            Engine::Wasm(engine) => Some(&engine.script_meta),
+            #[cfg(feature = "lua")]
+            Engine::Lua(engine) => Some(&engine.script_meta),
+            _ => None,
+        }
+    }
+
     pub fn get_api_versions(&self) -> Option<&FxHashMap<String, Semver>> {
         #[allow(unreachable_patterns)]
         let map = match self {
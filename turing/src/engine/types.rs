@@ -1,9 +1,245 @@
-use crate::interop::params::{DataType, FfiParam, FfiParamArray};
+use crate::engine::TYPE_MISMATCH_MESSAGE;
+use crate::interop::params::{DataType, ExtTypes, FfiParam, FfiParamArray, Param, RustTypes};
+use crate::interop::rust_fn::RustCallback;
+use crate::{CallScope, EngineDataState, ExternalFunctions};
 use anyhow::anyhow;
 use convert_case::{Case, Casing};
+use num_enum::TryFromPrimitive;
+use parking_lot::RwLock;
+use std::ffi::{CStr, CString, c_char};
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::Instant;
 
 pub type ScriptCallback = extern "C" fn(FfiParamArray) -> FfiParam;
 
+/// A single callback that receives every call to functions registered without their own callback.
+/// The first argument is the function's registered name.
+pub type DispatchCallback = extern "C" fn(*const c_char, FfiParamArray) -> FfiParam;
+
+/// Stack size wasm scripts get unless `EngineConfig::wasm_max_stack_bytes` says otherwise
+pub const DEFAULT_WASM_STACK_BYTES: usize = 512 * 1024;
+/// Smallest stack `EngineConfig::wasm_max_stack_bytes` accepts
+pub const MIN_WASM_STACK_BYTES: usize = 64 * 1024;
+/// Longest unfinished line of wasm stdout/stderr kept unless `EngineConfig::wasm_output_buffer_bytes` says otherwise
+pub const DEFAULT_WASM_OUTPUT_BUFFER_BYTES: usize = 64 * 1024;
+
+/// Setup options shared by all engines
+#[derive(Clone, Debug, Default)]
+pub struct EngineConfig {
+    /// Receives calls to every function that was registered without a callback
+    pub dispatch: Option<DispatchCallback>,
+    /// Receives calls lua scripts make to `turing_api` functions that were never registered,
+    /// with the name they were called by. Wasm imports are resolved on load, so they can't fall back.
+    pub on_missing_function: Option<DispatchCallback>,
+    /// Max instructions a single lua call may run, None for unlimited
+    pub lua_instruction_budget: Option<u64>,
+    /// Skip binding functions whose capability isn't active when a script loads,
+    /// so the script can't see them at all instead of getting an error when calling them
+    pub hide_inactive_capabilities: bool,
+    /// Mod name prepended as `[name] ` to script output and errors logged from its host calls
+    pub log_prefix: Option<String>,
+    /// Canonicalize wasm NaNs and relaxed SIMD results, seed lua's `math.random`
+    /// with `random_seed` (or 0) and make script clocks read the game time,
+    /// so script runs replay identically across machines
+    pub deterministic: bool,
+    /// Seed for lua's `math.random`, None leaves it randomly seeded unless `deterministic` is set
+    pub random_seed: Option<u64>,
+    /// Max stack a wasm call may use, None for [`DEFAULT_WASM_STACK_BYTES`]
+    pub wasm_max_stack_bytes: Option<usize>,
+    /// Longest line a wasm script may print before it's cut off, None for [`DEFAULT_WASM_OUTPUT_BUFFER_BYTES`]
+    pub wasm_output_buffer_bytes: Option<usize>,
+    /// Time every call into the script for `Turing::take_stats`
+    pub profiling: bool,
+    /// Number objects `1, 2, 3, ...` for scripts instead of passing the host's ids, see [`ObjectTable`]
+    ///
+    /// [`ObjectTable`]: crate::objects::ObjectTable
+    pub sequential_object_ids: bool,
+}
+
+impl EngineConfig {
+    /// The wasm stack size to use, failing if it is below [`MIN_WASM_STACK_BYTES`]
+    pub fn wasm_stack_bytes(&self) -> anyhow::Result<usize> {
+        let bytes = self
+            .wasm_max_stack_bytes
+            .unwrap_or(DEFAULT_WASM_STACK_BYTES);
+        Self::check_wasm_stack_bytes(bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn check_wasm_stack_bytes(bytes: usize) -> anyhow::Result<()> {
+        if bytes < MIN_WASM_STACK_BYTES {
+            return Err(anyhow!(
+                "wasm stack size of {bytes} bytes is below the minimum of {MIN_WASM_STACK_BYTES} bytes"
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn wasm_output_limit(&self) -> usize {
+        self.wasm_output_buffer_bytes
+            .unwrap_or(DEFAULT_WASM_OUTPUT_BUFFER_BYTES)
+    }
+
+    /// The seed scripts' random number generators start from, if they shouldn't be random
+    pub fn effective_random_seed(&self) -> Option<u64> {
+        self.random_seed.or(self.deterministic.then_some(0))
+    }
+}
+
+/// Severity of a message a script logs through its log api, lowest first
+#[repr(u32)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, TryFromPrimitive)]
+pub enum LogLevel {
+    #[default]
+    Debug = 0,
+    Info = 1,
+    Warn = 2,
+    Error = 3,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 4] = [
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+    ];
+
+    /// The name scripts call the level by, e.g. `turing_api.log.warn`
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+
+    /// Passes `msg` to the matching `ExternalFunctions` log method
+    pub fn emit<Ext: ExternalFunctions>(&self, msg: impl ToString) {
+        match self {
+            LogLevel::Debug => Ext::log_debug(msg),
+            LogLevel::Info => Ext::log_info(msg),
+            LogLevel::Warn => Ext::log_warn(msg),
+            LogLevel::Error => Ext::log_critical(msg),
+        }
+    }
+}
+
+/// Why a script's call into a host function failed, passed to `ExternalFunctions::on_script_error`
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive)]
+pub enum ErrorKind {
+    /// The function's capability isn't active for the script
+    MissingCapability = 0,
+    /// The script passed an argument that couldn't be converted, e.g. a bad string pointer
+    InvalidArgument = 1,
+    /// The host function returned an error
+    HostError = 2,
+    /// The host function returned a value that doesn't match its declared return type
+    InvalidReturn = 3,
+    /// The capability used up its callback budget for this script call, the host function was skipped
+    BudgetExceeded = 4,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ErrorKind::MissingCapability => "missing capability",
+            ErrorKind::InvalidArgument => "invalid argument",
+            ErrorKind::HostError => "host error",
+            ErrorKind::InvalidReturn => "invalid return value",
+            ErrorKind::BudgetExceeded => "callback budget exceeded",
+        })
+    }
+}
+
+/// A host function's callback, resolved when the function is bound to a script
+#[derive(Clone, Debug)]
+pub struct HostFn {
+    callback: HostCallback,
+    name: CString,
+    /// `name` for `CallScope`, built once so calls don't allocate it
+    scope_name: Arc<str>,
+}
+
+#[derive(Clone, Debug)]
+pub enum HostCallback {
+    Callback(ScriptCallback),
+    Dispatch(DispatchCallback),
+    Rust(RustCallback),
+}
+
+impl HostFn {
+    pub fn new(callback: HostCallback, name: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            callback,
+            name: CString::new(name)?,
+            scope_name: Arc::from(name),
+        })
+    }
+
+    /// Picks the function's own callback, falling back to the dispatch callback
+    pub fn resolve(
+        name: &str,
+        metadata: &ScriptFnMetadata,
+        config: &EngineConfig,
+    ) -> anyhow::Result<Self> {
+        if let Some(callback) = &metadata.rust_callback {
+            return HostFn::new(HostCallback::Rust(callback.clone()), name);
+        }
+        if let Some(callback) = metadata.callback {
+            return HostFn::new(HostCallback::Callback(callback), name);
+        }
+        let Some(dispatch) = config.dispatch else {
+            return Err(anyhow!(
+                "Function '{name}' has no callback and no dispatch callback is registered"
+            ));
+        };
+        HostFn::new(HostCallback::Dispatch(dispatch), name)
+    }
+
+    /// The registered name of the function
+    pub fn name(&self) -> &CStr {
+        &self.name
+    }
+
+    pub fn call(&self, params: FfiParamArray) -> FfiParam {
+        let _scope = CallScope::host(&self.scope_name);
+        match &self.callback {
+            HostCallback::Callback(callback) => callback(params),
+            HostCallback::Dispatch(dispatch) => dispatch(self.name.as_ptr(), params),
+            HostCallback::Rust(callback) => callback.call(params),
+        }
+    }
+
+    /// Like `call`, also counting and timing the call if `EngineDataState::metrics` is enabled
+    pub fn call_with_metrics(
+        &self,
+        params: FfiParamArray,
+        data: &RwLock<EngineDataState>,
+    ) -> FfiParam {
+        let started = data.read().metrics.then(Instant::now);
+        let res = self.call(params);
+        if let Some(started) = started {
+            data.write()
+                .record_host_call(self.name(), started.elapsed());
+        }
+        res
+    }
+}
+
+/// What happens when a script calls a function whose capability isn't currently loaded
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum CapabilityMissingPolicy {
+    /// The call fails, trapping in wasm and raising an error in lua
+    #[default]
+    Error,
+    /// The host callback is skipped and the call returns this value instead
+    SilentDefault(Param),
+}
+
 // Represents the name of a type used in parameter or return type lists
 pub type DataTypeName = String;
 
@@ -12,12 +248,38 @@ pub struct ScriptFnParameter {
     pub name: String,
     pub data_type: DataType,
     pub data_type_name: DataTypeName,
+    /// Passed in place of the argument when a lua call leaves it out or passes nil,
+    /// see `ScriptFnMetadata::add_optional_param_type`
+    pub default: Option<Param>,
 }
 
 #[derive(Clone, Debug)]
 pub struct ScriptFnMetadata {
     pub capability: String,
-    pub callback: ScriptCallback,
+    /// When None, calls are routed through the dispatch callback in `EngineConfig`
+    pub callback: Option<ScriptCallback>,
+    /// Set by `ScriptFnMetadata::from_rust_fn`, takes precedence over `callback`
+    pub rust_callback: Option<RustCallback>,
+    pub param_types: Vec<ScriptFnParameter>,
+    pub return_type: Vec<(DataType, DataTypeName)>,
+    pub doc_comment: Option<String>,
+    pub capability_missing_policy: CapabilityMissingPolicy,
+    /// Lua calls may pass any number of args after `param_types`, which are forwarded
+    /// with types inferred from their lua values. Wasm imports always use `param_types`.
+    pub variadic: bool,
+    /// The exact symbol wasm scripts import the function as, instead of the converted name
+    pub explicit_symbol: Option<String>,
+    /// When the host callback fails, wasm calls get a zero return value instead of trapping
+    /// and read the error with `_host_last_error`. Lua calls always end with the error,
+    /// the sandbox has no `pcall` to catch it.
+    pub fallible: bool,
+}
+
+/// Snapshot of a registered function, for host-side introspection
+#[derive(Clone, Debug)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub capability: String,
     pub param_types: Vec<ScriptFnParameter>,
     pub return_type: Vec<(DataType, DataTypeName)>,
     pub doc_comment: Option<String>,
@@ -30,10 +292,31 @@ impl ScriptFnMetadata {
     pub fn new(capability: String, callback: ScriptCallback, doc_comment: Option<String>) -> Self {
         Self {
             capability,
-            callback,
+            callback: Some(callback),
+            param_types: Vec::new(),
+            return_type: Vec::new(),
+            doc_comment,
+            capability_missing_policy: CapabilityMissingPolicy::Error,
+            variadic: false,
+            explicit_symbol: None,
+            fallible: false,
+            rust_callback: None,
+        }
+    }
+
+    /// Creates metadata for a function whose calls are routed through the dispatch callback
+    pub fn dispatched(capability: String, doc_comment: Option<String>) -> Self {
+        Self {
+            capability,
+            callback: None,
             param_types: Vec::new(),
             return_type: Vec::new(),
             doc_comment,
+            capability_missing_policy: CapabilityMissingPolicy::Error,
+            variadic: false,
+            explicit_symbol: None,
+            fallible: false,
+            rust_callback: None,
         }
     }
 
@@ -46,15 +329,68 @@ impl ScriptFnMetadata {
         if !p.is_valid_param_type() {
             return Err(anyhow!("DataType '{}' is not a valid parameter type", p));
         }
+        self.check_no_optional_params()?;
+        self.param_types.push(ScriptFnParameter {
+            name: param_name.to_string(),
+            data_type: p,
+            data_type_name: p.as_spec_param_type()?.to_string(),
+            default: None,
+        });
+
+        Ok(self)
+    }
+
+    /// Adds a param lua calls may leave out, `default` is passed in its place.
+    /// Optional params must come after every required one.
+    /// Wasm imports have a fixed signature, so wasm scripts always pass it.
+    /// May error if DataType is not a valid parameter type or `default` isn't one
+    pub fn add_optional_param_type(
+        &mut self,
+        p: DataType,
+        param_name: impl ToString,
+        default: Param,
+    ) -> anyhow::Result<&mut Self> {
+        if !p.is_valid_param_type() {
+            return Err(anyhow!("DataType '{}' is not a valid parameter type", p));
+        }
+        if default.data_type::<RustTypes>() != p && default.data_type::<ExtTypes>() != p {
+            return Err(anyhow!(
+                "default for optional parameter '{}' is a {}, expected {}",
+                param_name.to_string(),
+                default.data_type::<RustTypes>(),
+                p
+            ));
+        }
         self.param_types.push(ScriptFnParameter {
             name: param_name.to_string(),
             data_type: p,
             data_type_name: p.as_spec_param_type()?.to_string(),
+            default: Some(default),
         });
 
         Ok(self)
     }
 
+    /// Engines only bind a single return value
+    fn check_no_return_type(&self) -> anyhow::Result<()> {
+        match self.return_type.first() {
+            Some((_, name)) => Err(anyhow!(
+                "function already returns '{name}', only one return type is supported"
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn check_no_optional_params(&self) -> anyhow::Result<()> {
+        match self.param_types.iter().find(|p| p.default.is_some()) {
+            Some(optional) => Err(anyhow!(
+                "required parameter can't follow optional parameter '{}'",
+                optional.name
+            )),
+            None => Ok(()),
+        }
+    }
+
     /// May error if DataType is not a valid parameter type
     pub fn add_param_type_named(
         &mut self,
@@ -65,26 +401,29 @@ impl ScriptFnMetadata {
         if !p.is_valid_param_type() {
             return Err(anyhow!("DataType '{}' is not a valid parameter type", p));
         }
+        self.check_no_optional_params()?;
         self.param_types.push(ScriptFnParameter {
             name: param_name,
             data_type: p,
             data_type_name: type_name,
+            default: None,
         });
 
         Ok(self)
     }
 
-    /// May error if DataType is not a valid return type
+    /// May error if DataType is not a valid return type or a return type was already added
     pub fn add_return_type(&mut self, r: DataType) -> anyhow::Result<&mut Self> {
         if !r.is_valid_return_type() {
             return Err(anyhow!("DataType '{}' is not a valid return type", r));
         }
+        self.check_no_return_type()?;
         self.return_type
             .push((r, r.as_spec_return_type()?.to_string()));
         Ok(self)
     }
 
-    /// May error if DataType is not a valid return type
+    /// May error if DataType is not a valid return type or a return type was already added
     pub fn add_return_type_named(
         &mut self,
         r: DataType,
@@ -93,10 +432,36 @@ impl ScriptFnMetadata {
         if !r.is_valid_return_type() {
             return Err(anyhow!("DataType '{}' is not a valid return type", r));
         }
+        self.check_no_return_type()?;
         self.return_type.push((r, type_name));
         Ok(self)
     }
 
+    /// Sets what calls return while the function's capability isn't loaded
+    pub fn set_capability_missing_policy(&mut self, policy: CapabilityMissingPolicy) -> &mut Self {
+        self.capability_missing_policy = policy;
+        self
+    }
+
+    /// Lets lua scripts pass extra args after the declared params, see `variadic`
+    pub fn set_variadic(&mut self, variadic: bool) -> &mut Self {
+        self.variadic = variadic;
+        self
+    }
+
+    /// Lets wasm scripts handle the host callback failing, see `fallible`
+    pub fn set_fallible(&mut self, fallible: bool) -> &mut Self {
+        self.fallible = fallible;
+        self
+    }
+
+    /// Pins the symbol wasm scripts import the function as, for names the automatic conversion
+    /// gets differently than the script's bindings, like `HTTPRequest::sendJSON`
+    pub fn with_explicit_symbol(&mut self, symbol: impl ToString) -> &mut Self {
+        self.explicit_symbol = Some(symbol.to_string());
+        self
+    }
+
     /// Determines if function is an instance method
     pub fn is_instance_method(fn_name: &str) -> bool {
         fn_name.contains(Self::METHOD_SEPARATOR)
@@ -108,8 +473,12 @@ impl ScriptFnMetadata {
     }
 
     /// Converts function name to internal representation
-    /// e.g. `Class::functionName` becomes `capability_class_function_name`
+    /// e.g. `Class::functionName` becomes `capability_class_function_name`.
+    /// Returns the explicit symbol instead if one was set.
     pub fn as_internal_name(&self, fn_name: &str) -> String {
+        if let Some(symbol) = &self.explicit_symbol {
+            return symbol.clone();
+        }
         format!(
             "_{}_{}",
             self.capability.to_case(Case::Snake),
@@ -121,7 +490,99 @@ impl ScriptFnMetadata {
     }
 }
 
+/// Low-level type of a value passed to or returned from a script export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptValType {
+    I32,
+    I64,
+    F32,
+    F64,
+    /// Reference and vector types, which no `DataType` is passed as
+    Other,
+}
+
+impl Display for ScriptValType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ScriptValType::I32 => "i32",
+            ScriptValType::I64 => "i64",
+            ScriptValType::F32 => "f32",
+            ScriptValType::F64 => "f64",
+            ScriptValType::Other => "other",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// What a script export takes and returns, recorded when the script loads.
+/// Parts the engine can't tell, like the params of a lua function, are None and never checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportSignature {
+    pub params: Option<Vec<ScriptValType>>,
+    pub results: Option<Vec<ScriptValType>>,
+}
+
+impl ExportSignature {
+    /// Checks a call passing `arg_count` args and expecting `ret_type` against the signature,
+    /// failing with a [`TYPE_MISMATCH_MESSAGE`] error that describes both sides
+    pub fn check_call(&self, name: &str, arg_count: u32, ret_type: DataType) -> anyhow::Result<()> {
+        let params_fit = self
+            .params
+            .as_ref()
+            .is_none_or(|params| params.len() == arg_count as usize);
+        let results_fit =
+            self.results
+                .as_ref()
+                .is_none_or(|results| match ret_type.script_val_type() {
+                    Some(typ) => results[..] == [typ],
+                    None => results.is_empty(),
+                });
+        if params_fit && results_fit {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "{TYPE_MISMATCH_MESSAGE}: '{name}' is exported as {self}, but was called with {arg_count} args expecting {ret_type}"
+        ))
+    }
+}
+
+/// Packs the signature as `(i32, f32) -> i64`, with `?` for the parts the engine couldn't record
+impl Display for ExportSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |types: &[ScriptValType]| {
+            types
+                .iter()
+                .map(ScriptValType::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match &self.params {
+            Some(params) => write!(f, "({})", list(params))?,
+            None => write!(f, "(?)")?,
+        }
+        match self.results.as_deref() {
+            Some([result]) => write!(f, " -> {result}"),
+            Some(results) => write!(f, " -> ({})", list(results)),
+            None => write!(f, " -> ?"),
+        }
+    }
+}
+
 impl DataType {
+    /// The value type scripts pass this as, None for `Void`.
+    /// Strings, buffers and math types are passed as an i32 pointer or length.
+    pub fn script_val_type(&self) -> Option<ScriptValType> {
+        match self {
+            DataType::Void => None,
+            DataType::I64 | DataType::U64 | DataType::Object | DataType::OwnedObject => {
+                Some(ScriptValType::I64)
+            }
+            DataType::F32 => Some(ScriptValType::F32),
+            DataType::F64 => Some(ScriptValType::F64),
+            _ => Some(ScriptValType::I32),
+        }
+    }
+
     /// Returns the corresponding type name for a DataType when used as a parameter type in the function spec
     /// The function spec uses Rust type names for simplicity, but some DataTypes like String and Object require special handling
     ///
@@ -135,13 +596,16 @@ impl DataType {
             DataType::I64 => "i64",
             DataType::U8 => "u8",
             DataType::U16 => "u16",
-            DataType::U32 => "u32",
+            DataType::U32 | DataType::Flags => "u32",
+            DataType::Char => "char",
             DataType::U64 => "u64",
             DataType::F32 => "f32",
             DataType::F64 => "f64",
             DataType::Bool => "bool",
             DataType::RustString | DataType::ExtString => "&str",
-            DataType::Object => return Err(anyhow!("Cannot derive type name from 'Object'")),
+            DataType::Object | DataType::OwnedObject => {
+                return Err(anyhow!("Cannot derive type name from 'Object'"));
+            }
             DataType::RustError | DataType::ExtError => {
                 return Err(anyhow!("Error is not a valid param type"));
             }
@@ -152,6 +616,8 @@ impl DataType {
             DataType::RustQuat | DataType::ExtQuat => "Quat",
             DataType::RustMat4 | DataType::ExtMat4 => "Mat4",
             DataType::RustU32Buffer | DataType::ExtU32Buffer => "&Vu32",
+            DataType::RustF32Buffer | DataType::ExtF32Buffer => "&Vf32",
+            DataType::RustJson | DataType::ExtJson => "Json",
         })
     }
 
@@ -163,13 +629,16 @@ impl DataType {
             DataType::I64 => "i64",
             DataType::U8 => "u8",
             DataType::U16 => "u16",
-            DataType::U32 => "u32",
+            DataType::U32 | DataType::Flags => "u32",
+            DataType::Char => "char",
             DataType::U64 => "u64",
             DataType::F32 => "f32",
             DataType::F64 => "f64",
             DataType::Bool => "bool",
             DataType::RustString | DataType::ExtString => "String",
-            DataType::Object => return Err(anyhow!("Cannot derive type name from 'Object'")),
+            DataType::Object | DataType::OwnedObject => {
+                return Err(anyhow!("Cannot derive type name from 'Object'"));
+            }
             DataType::RustError | DataType::ExtError => {
                 return Err(anyhow!("Error is not a valid param type"));
             }
@@ -180,6 +649,8 @@ impl DataType {
             DataType::RustQuat | DataType::ExtQuat => "Quat",
             DataType::RustMat4 | DataType::ExtMat4 => "Mat4",
             DataType::RustU32Buffer | DataType::ExtU32Buffer => "Vu32",
+            DataType::RustF32Buffer | DataType::ExtF32Buffer => "Vf32",
+            DataType::RustJson | DataType::ExtJson => "Json",
         })
     }
 }
@@ -1,59 +1,117 @@
+use std::fmt::Write;
 use std::fs;
 use std::marker::PhantomData;
 use std::panic::catch_unwind;
 use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 
-use crate::engine::types::{ScriptCallback, ScriptFnMetadata};
+use crate::engine::types::{
+    CapabilityMissingPolicy, EngineConfig, ErrorKind, ExportSignature, HostFn, ScriptFnMetadata,
+    ScriptValType,
+};
+use crate::engine::wasm_engine::clocks::GameClock;
 use crate::engine::wasm_engine::host_helpers::{
-    wasm_host_bufcpy, wasm_host_f32_dequeue, wasm_host_f32_enqueue, wasm_host_strcpy,
-    wasm_host_u32_dequeue, wasm_host_u32_enqueue,
+    wasm_host_bufcpy, wasm_host_f32_bufcpy, wasm_host_f32_dequeue, wasm_host_f32_enqueue,
+    wasm_host_last_error, wasm_host_log, wasm_host_recv_string, wasm_host_storage_get,
+    wasm_host_storage_set, wasm_host_strcpy, wasm_host_u32_dequeue, wasm_host_u32_enqueue,
 };
 use crate::engine::wasm_engine::typed_calls::TypedFuncEntry;
-use crate::engine::wasm_engine::writer::WriterInit;
+use crate::engine::wasm_engine::writer::GuestOutput;
+use crate::engine::{
+    FUNCTION_NOT_FOUND_MESSAGE, INTERRUPTED_MESSAGE, InterruptHandle, NO_MEMORY_MESSAGE,
+    TYPE_MISMATCH_MESSAGE, api_version_export_name, panic_message, prefix_log,
+};
 use crate::interop::params::{DataType, ExtTypes, Param, Params};
 use crate::interop::types::Semver;
 use crate::key_vec::KeyVec;
-use crate::{EngineDataState, ExternalFunctions, ScriptFnKey};
-use anyhow::{Context, Result, anyhow};
+use crate::{
+    CallScope, CallbackCharge, CancelScope, EngineDataState, ExternalFunctions, FastCallHandle,
+    ScriptFnKey,
+};
+use anyhow::{Result, anyhow};
+use convert_case::{Case, Casing};
 use parking_lot::RwLock;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use smallvec::SmallVec;
 use wasmtime::{
-    AsContext, Caller, Config, Engine, Func, FuncType, Instance, Linker, Memory, Module, Store,
-    TypedFunc, Val, ValType,
+    AsContext, Caller, Config, Engine, ExternType, Func, FuncType, Instance, Linker, Memory,
+    Module, Store, Trap, TypedFunc, Val, ValType, WasmBacktrace, WasmBacktraceDetails,
 };
 use wasmtime_wasi::WasiCtxBuilder;
 use wasmtime_wasi::p1::WasiP1Ctx;
 
+mod clocks;
 mod host_helpers;
+mod module_cache;
 mod params;
 mod typed_calls;
+mod wasm_mem;
 mod writer;
 
+pub use module_cache::{DEFAULT_MODULE_CACHE_ENTRIES, ModuleCache};
+
 #[derive(Default)]
 pub struct FastCalls {
     update: Option<TypedFunc<f32, ()>>,
     fixed_update: Option<TypedFunc<f32, ()>>,
+    /// indexed by `FastCallHandle`, None if the script doesn't export the function
+    /// or exports it with a signature that can't be a fast call
+    named: Vec<(String, Option<TypedFunc<f32, ()>>)>,
+}
+
+/// A registered host function, keyed by the name scripts import it as from `env`
+struct HostImport {
+    /// the name it was registered with, like `ClassName.methodName`
+    name: String,
+    capability: String,
+    /// None if it wasn't bound, like when its capability is hidden
+    signature: Option<ExportSignature>,
 }
 
 pub struct WasmInterpreter<Ext: ExternalFunctions> {
     engine: Engine,
+    /// kept to build a fresh store in `reset_instance`
+    config: EngineConfig,
+    /// kept to build a fresh store in `reset_instance`, backs the deterministic wasi clocks
+    data: Arc<RwLock<EngineDataState>>,
     store: Store<WasiP1Ctx>,
     linker: Linker<WasiP1Ctx>,
     script_instance: Option<Instance>,
     memory: Option<Memory>,
+    module: Option<Module>,
+    /// the wasm bytes the loaded module was compiled from
+    module_bytes: Option<Vec<u8>>,
 
-    func_cache: KeyVec<ScriptFnKey, (String, Func, Option<TypedFuncEntry>)>,
+    /// every exported function with its wasm signature, which each call is checked against
+    func_cache: KeyVec<ScriptFnKey, (String, Func, ExportSignature, Option<TypedFuncEntry>)>,
+    /// every registered host function, checked against the module's imports on load
+    host_imports: FxHashMap<String, HostImport>,
 
     fast_calls: FastCalls,
+    /// the store's stdout and stderr, flushed when the store is replaced or dropped
+    output: GuestOutput,
     pub api_versions: FxHashMap<String, Semver>,
+    /// `key=value` lines from the module's `_meta` custom section
+    pub script_meta: FxHashMap<String, String>,
+    /// set by `InterruptHandle::interrupt` alongside the epoch bump, read by `is_call_cancelled`
+    interrupt: Arc<AtomicBool>,
+    /// args of the current call, reused so per-frame calls don't allocate
+    arg_scratch: Vec<Val>,
     _ext: PhantomData<Ext>,
 }
 
+impl<Ext: ExternalFunctions> Drop for WasmInterpreter<Ext> {
+    fn drop(&mut self) {
+        self.output.flush::<Ext>(self.config.log_prefix.as_deref());
+    }
+}
+
 impl<Ext: ExternalFunctions + Send + Sync + 'static> WasmInterpreter<Ext> {
     pub fn new(
         wasm_functions: &FxHashMap<String, ScriptFnMetadata>,
+        engine_config: &EngineConfig,
         data: Arc<RwLock<EngineDataState>>,
     ) -> Result<Self> {
         let mut config = Config::new();
@@ -64,53 +122,96 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> WasmInterpreter<Ext> {
         config.wasm_bulk_memory(true);
         config.wasm_reference_types(true);
         config.wasm_multi_memory(false);
-        config.max_wasm_stack(512 * 1024); // 512KB
+        config.max_wasm_stack(engine_config.wasm_stack_bytes()?);
         config.compiler_inlining(true);
         config.consume_fuel(false);
-
-        let wasi = WasiCtxBuilder::new()
-            .stdout(WriterInit::<Ext>(
-                Arc::new(RwLock::new(Vec::new())),
-                false,
-                PhantomData,
-            ))
-            .stderr(WriterInit::<Ext>(
-                Arc::new(RwLock::new(Vec::new())),
-                true,
-                PhantomData,
-            ))
-            .allow_tcp(false)
-            .allow_udp(false)
-            .build_p1();
+        // trap errors list the guest frames, with source lines if the module has debug info
+        config.wasm_backtrace(true);
+        config.wasm_backtrace_details(WasmBacktraceDetails::Enable);
+        // lets the host interrupt a running call from another thread
+        config.epoch_interruption(true);
+        if engine_config.deterministic {
+            // NaN bit patterns and relaxed SIMD results otherwise depend on the host cpu
+            config.cranelift_nan_canonicalization(true);
+            config.relaxed_simd_deterministic(true);
+        }
 
         let engine = Engine::new(&config)?;
-        let store = Store::new(&engine, wasi);
+        let output = GuestOutput::default();
+        let store = Self::new_store(&engine, engine_config, &output, &data);
 
         let mut linker = <Linker<WasiP1Ctx>>::new(&engine);
 
         wasmtime_wasi::p1::add_to_linker_sync(&mut linker, |t| t)?;
 
-        Self::bind_wasm(&engine, &mut linker, wasm_functions, data)?;
+        let host_imports = Self::bind_wasm(
+            &engine,
+            &mut linker,
+            wasm_functions,
+            engine_config,
+            Arc::clone(&data),
+        )?;
 
         Ok(WasmInterpreter {
             engine,
+            config: engine_config.clone(),
+            data,
             store,
             linker,
             script_instance: None,
             memory: None,
+            module: None,
+            module_bytes: None,
             func_cache: Default::default(),
+            host_imports,
             fast_calls: FastCalls::default(),
+            output,
             api_versions: Default::default(),
+            script_meta: Default::default(),
+            interrupt: Arc::new(AtomicBool::new(false)),
+            arg_scratch: Vec::new(),
             _ext: PhantomData,
         })
     }
 
+    /// A store with its own wasi context, stdout and stderr log through `Ext` via `output`.
+    /// In deterministic mode the wasi clocks read the game time instead of the real one.
+    fn new_store(
+        engine: &Engine,
+        engine_config: &EngineConfig,
+        output: &GuestOutput,
+        data: &Arc<RwLock<EngineDataState>>,
+    ) -> Store<WasiP1Ctx> {
+        let (stdout, stderr) = output.writers::<Ext>(
+            engine_config.log_prefix.clone(),
+            engine_config.wasm_output_limit(),
+        );
+        let mut builder = WasiCtxBuilder::new();
+        builder
+            .stdout(stdout)
+            .stderr(stderr)
+            .allow_tcp(false)
+            .allow_udp(false);
+        if engine_config.deterministic {
+            builder
+                .wall_clock(GameClock::new(Arc::clone(data)))
+                .monotonic_clock(GameClock::new(Arc::clone(data)));
+        }
+        let wasi = builder.build_p1();
+
+        let mut store = Store::new(engine, wasi);
+        // the deadline is re-armed before every call, an epoch bump past it traps the call
+        store.set_epoch_deadline(1);
+        store
+    }
+
     fn bind_wasm(
         engine: &Engine,
         linker: &mut Linker<WasiP1Ctx>,
         wasm_fns: &FxHashMap<String, ScriptFnMetadata>,
+        config: &EngineConfig,
         data: Arc<RwLock<EngineDataState>>,
-    ) -> Result<()> {
+    ) -> Result<FxHashMap<String, HostImport>> {
         // Utility Functions
 
         // _host_strcpy(location: *const c_char, size: u32);
@@ -125,10 +226,25 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> WasmInterpreter<Ext> {
         //    the strings with no way to recover.
         let data_strcpy = Arc::clone(&data);
         let data_bufcpy = Arc::clone(&data);
+        let data_f32_bufcpy = Arc::clone(&data);
         let data_enqueue = Arc::clone(&data);
         let data_dequeue = Arc::clone(&data);
         let data_enqueue2 = Arc::clone(&data);
         let data_dequeue2 = Arc::clone(&data);
+        let data_recv = Arc::clone(&data);
+        let data_log = Arc::clone(&data);
+        let log_prefix = config.log_prefix.clone();
+        let data_time = Arc::clone(&data);
+        let data_delta = Arc::clone(&data);
+        let data_random = Arc::clone(&data);
+        let data_random_range = Arc::clone(&data);
+        let data_storage_get = Arc::clone(&data);
+        let data_storage_set = Arc::clone(&data);
+        let data_last_error = Arc::clone(&data);
+        let data_now = Arc::clone(&data);
+        // origin for _host_now_micros, so scripts get a monotonic clock without wall-clock access
+        let clock = Instant::now();
+        let deterministic = config.deterministic;
         linker.func_new(
             "env",
             "_host_strcpy",
@@ -141,6 +257,28 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> WasmInterpreter<Ext> {
             FuncType::new(engine, vec![ValType::I32, ValType::I32], vec![]),
             move |caller, p, _| wasm_host_bufcpy(&data_bufcpy, caller, p),
         )?;
+        linker.func_new(
+            "env",
+            "_host_f32_bufcpy",
+            FuncType::new(engine, vec![ValType::I32, ValType::I32], vec![]),
+            move |caller, p, _| wasm_host_f32_bufcpy(&data_f32_bufcpy, caller, p),
+        )?;
+        // _host_recv_string(location: *const c_char, len: u32) -> u32;
+        // Sends a string to the host in the middle of a call, the returned id can be passed
+        // to host functions which take it back with `Turing::take_received_string`.
+        linker.func_new(
+            "env",
+            "_host_recv_string",
+            FuncType::new(engine, vec![ValType::I32, ValType::I32], vec![ValType::I32]),
+            move |caller, p, r| wasm_host_recv_string(&data_recv, caller, p, r),
+        )?;
+        // _host_log(level: u32, msg: *const c_char);
+        linker.func_new(
+            "env",
+            "_host_log",
+            FuncType::new(engine, vec![ValType::I32, ValType::I32], vec![]),
+            move |caller, p, _| wasm_host_log::<Ext>(&data_log, log_prefix.as_deref(), caller, p),
+        )?;
         linker.func_new(
             "env",
             "_host_f32_enqueue",
@@ -165,14 +303,122 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> WasmInterpreter<Ext> {
             FuncType::new(engine, Vec::new(), vec![ValType::I32]),
             move |_, _, r| wasm_host_u32_dequeue(&data_dequeue2, r),
         )?;
+        // _host_now_micros() -> u64;
+        linker.func_new(
+            "env",
+            "_host_now_micros",
+            FuncType::new(engine, Vec::new(), vec![ValType::I64]),
+            move |_, _, r| {
+                let micros = if deterministic {
+                    data_now.read().game_time_micros()
+                } else {
+                    clock.elapsed().as_micros() as u64
+                };
+                r[0] = Val::I64(micros as i64);
+                Ok(())
+            },
+        )?;
+        // _host_time_seconds() -> f64;
+        linker.func_new(
+            "env",
+            "_host_time_seconds",
+            FuncType::new(engine, Vec::new(), vec![ValType::F64]),
+            move |_, _, r| {
+                r[0] = Val::F64(data_time.read().time_seconds.to_bits());
+                Ok(())
+            },
+        )?;
+        // _host_delta_time() -> f32;
+        linker.func_new(
+            "env",
+            "_host_delta_time",
+            FuncType::new(engine, Vec::new(), vec![ValType::F32]),
+            move |_, _, r| {
+                r[0] = Val::F32(data_delta.read().delta_time.to_bits());
+                Ok(())
+            },
+        )?;
+        // _host_random() -> f64;
+        linker.func_new(
+            "env",
+            "_host_random",
+            FuncType::new(engine, Vec::new(), vec![ValType::F64]),
+            move |_, _, r| {
+                r[0] = Val::F64(data_random.write().rng.next_f64().to_bits());
+                Ok(())
+            },
+        )?;
+        // _host_random_range(min: i32, max: i32) -> i32;
+        linker.func_new(
+            "env",
+            "_host_random_range",
+            FuncType::new(engine, vec![ValType::I32, ValType::I32], vec![ValType::I32]),
+            move |_, p, r| {
+                let (Some(min), Some(max)) = (p[0].i32(), p[1].i32()) else {
+                    return Err(anyhow!("_host_random_range expects two i32 params"));
+                };
+                r[0] = Val::I32(data_random_range.write().rng.range(min, max));
+                Ok(())
+            },
+        )?;
+        // _host_storage_get(key: *const c_char) -> u32;
+        // Returns the size of the stored value to copy with `_host_strcpy`, or 0 if the key isn't set.
+        linker.func_new(
+            "env",
+            "_host_storage_get",
+            FuncType::new(engine, vec![ValType::I32], vec![ValType::I32]),
+            move |caller, p, r| wasm_host_storage_get(&data_storage_get, caller, p, r),
+        )?;
+        // _host_storage_set(key: *const c_char, value: *const c_char) -> u32;
+        // Returns 0 once stored, 1 if the storage quota would be exceeded.
+        linker.func_new(
+            "env",
+            "_host_storage_set",
+            FuncType::new(engine, vec![ValType::I32, ValType::I32], vec![ValType::I32]),
+            move |caller, p, r| wasm_host_storage_set(&data_storage_set, caller, p, r),
+        )?;
+        // _host_last_error() -> u32;
+        // Takes the error of the last fallible host function that failed, returning its size
+        // to copy with `_host_strcpy`, or 0 if none failed since the script call started.
+        linker.func_new(
+            "env",
+            "_host_last_error",
+            FuncType::new(engine, Vec::new(), vec![ValType::I32]),
+            move |_, _, r| wasm_host_last_error(&data_last_error, r),
+        )?;
 
         // External functions
+        let mut host_imports = FxHashMap::default();
         for (name, metadata) in wasm_fns.iter() {
-            Self::bind_wasm_fn(name, metadata, linker, engine, Arc::clone(&data))
-                .with_context(|| format!("Binding {name} script fn metadata {metadata:#?}"))?;
+            let hidden = config.hide_inactive_capabilities
+                && !data
+                    .read()
+                    .active_capabilities
+                    .contains(&metadata.capability);
+            let symbol = metadata.as_internal_name(name);
+            if let Some(other) = host_imports.get(&symbol) {
+                return Err(anyhow!(
+                    "`{name}` and `{}` are both imported as env::{symbol}, pin one with `ScriptFnMetadata::with_explicit_symbol`",
+                    other.name
+                ));
+            }
+            let signature = if hidden {
+                None
+            } else {
+                Self::bind_wasm_fn(name, metadata, linker, engine, config, Arc::clone(&data))
+                    .with_context(|| format!("Binding {name} script fn metadata {metadata:#?}"))?
+            };
+            host_imports.insert(
+                symbol,
+                HostImport {
+                    name: name.clone(),
+                    capability: metadata.capability.clone(),
+                    signature,
+                },
+            );
         }
 
-        Ok(())
+        Ok(host_imports)
     }
 
     fn bind_wasm_fn(
@@ -180,8 +426,9 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> WasmInterpreter<Ext> {
         metadata: &ScriptFnMetadata,
         linker: &mut Linker<WasiP1Ctx>,
         engine: &Engine,
+        config: &EngineConfig,
         data: Arc<RwLock<EngineDataState>>,
-    ) -> Result<()> {
+    ) -> Result<Option<ExportSignature>> {
         // Convert from `ClassName::functionName` to `_class_name_function_name`
         let internal_name = metadata.as_internal_name(name);
 
@@ -215,7 +462,7 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> WasmInterpreter<Ext> {
                 "WASM functions with multiple return values are not supported: {}",
                 name
             ));
-            return Ok(());
+            return Ok(None);
         }
 
         let r_types = if fn_return_type == DataType::Void {
@@ -225,10 +472,14 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> WasmInterpreter<Ext> {
             // metadata.return_type.iter().map(|d| d.0.to_val_type()).collect::<Result<Vec<ValType>>>()?
         };
         let ft = FuncType::new(engine, param_wasm_types, r_types);
+        let signature = export_signature(&ft);
         let cap = metadata.capability.clone();
-        let callback = metadata.callback;
+        let missing_policy = metadata.capability_missing_policy.clone();
+        let fallible = metadata.fallible;
+        let host_fn = HostFn::resolve(name, metadata, config)?;
 
         let data2 = Arc::clone(&data);
+        let log_prefix = config.log_prefix.clone();
 
         Ext::log_debug(format!(
             "Registered wasm function: env::{internal_name} {}",
@@ -245,54 +496,244 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> WasmInterpreter<Ext> {
                         &data2,
                         caller,
                         &cap,
+                        &missing_policy,
                         ps,
                         rs,
                         param_types.as_slice(),
                         fn_return_type,
-                        &callback,
+                        fallible,
+                        &host_fn,
                     )
                 })) {
                     Ok(Ok(())) => Ok(()),
                     Ok(Err(e)) => {
                         // log errors since wasmtime doesn't propagate them with messages
-                        Ext::log_critical(format!(
-                            "WASM function {internal_name} returned error: {e}"
+                        Ext::log_critical(prefix_log(
+                            log_prefix.as_deref(),
+                            format!("WASM function {internal_name} returned error: {e}"),
                         ));
                         Err(e)
                     }
                     Err(panic) => {
-                        let msg = if let Some(s) = panic.downcast_ref::<&str>() {
-                            (*s).to_string()
-                        } else if let Some(s) = panic.downcast_ref::<String>() {
-                            s.clone()
-                        } else {
-                            "Unknown panic payload".to_string()
-                        };
-                        Ext::log_critical(format!("WASM function {internal_name} panicked: {msg}"));
+                        let msg = panic_message(panic.as_ref());
+                        Ext::log_critical(prefix_log(
+                            log_prefix.as_deref(),
+                            format!("WASM function {internal_name} panicked: {msg}"),
+                        ));
                         Err(anyhow!("WASM function panicked: {msg}"))
                     }
                 }
             },
         )?;
-        Ok(())
+        Ok(Some(signature))
     }
 
     pub fn load_script(&mut self, path: &Path) -> Result<()> {
-        let wasm = fs::read(path)?;
+        self.load_bytes(&fs::read(path)?)
+    }
 
+    /// Compiles and instantiates a wasm module that's already in memory
+    pub fn load_bytes(&mut self, wasm: &[u8]) -> Result<()> {
         let module = Module::new(&self.engine, wasm)?;
+        self.load_module(wasm, module)
+    }
+
+    /// Like `load_bytes`, but takes the compiled module from `cache` if it was compiled before
+    pub fn load_bytes_cached(&mut self, wasm: &[u8], cache: &mut ModuleCache) -> Result<()> {
+        let module = cache.get_or_compile::<Ext>(&self.engine, wasm)?;
+        self.load_module(wasm, module)
+    }
 
-        let instance = self.linker.instantiate(&mut self.store, &module)?;
+    /// Instantiates `module`, which was compiled from `wasm`
+    fn load_module(&mut self, wasm: &[u8], module: Module) -> Result<()> {
+        self.script_meta = custom_section(wasm, "_meta")
+            .map(|section| parse_meta(&String::from_utf8_lossy(section)))
+            .unwrap_or_default();
 
-        // Cache instance and exported memory to avoid repeated lookups per call
-        let memory = instance
-            .get_export(&mut self.store, "memory")
-            .and_then(|m| m.into_memory())
-            .ok_or_else(|| anyhow!("WASM module does not export memory"))?;
+        self.check_imports(&module)?;
+        self.instantiate(&module)?;
+        self.module = Some(module);
+        self.module_bytes = Some(wasm.to_vec());
+
+        Ok(())
+    }
+
+    /// Fails with every import of `module` the host doesn't provide, or provides with another
+    /// signature, in one error rather than wasmtime's first "unknown import".
+    /// Warns about bound host functions the module never imports, which usually means a name
+    /// was converted differently on each side.
+    fn check_imports(&mut self, module: &Module) -> Result<()> {
+        let mut problems = Vec::new();
+        let mut imported = FxHashSet::default();
+        for import in module.imports() {
+            let (module_name, name) = (import.module(), import.name());
+            let host_import = match module_name {
+                "env" => self.host_imports.get(name),
+                _ => None,
+            };
+            match host_import {
+                Some(HostImport {
+                    name: host_name,
+                    signature: Some(signature),
+                    ..
+                }) => {
+                    imported.insert(name);
+                    if let ExternType::Func(ty) = import.ty() {
+                        let wanted = export_signature(&ty);
+                        if wanted != *signature {
+                            problems.push(format!(
+                                "env::{name} (`{host_name}`) is imported as {wanted} but registered as {signature}"
+                            ));
+                        }
+                    }
+                }
+                Some(HostImport {
+                    name: host_name,
+                    capability,
+                    signature: None,
+                }) => problems.push(format!(
+                    "env::{name} (`{host_name}`) isn't bound, its capability '{capability}' isn't loaded or its signature is unsupported"
+                )),
+                None => {
+                    if self.linker.get(&mut self.store, module_name, name).is_some() {
+                        continue;
+                    }
+                    // a differently converted name, e.g. `_http_request` and `_h_t_t_p_request`
+                    if let Some((symbol, host_name)) = self.similar_host_import(module_name, name) {
+                        problems.push(format!(
+                            "{module_name}::{name} is missing, `{host_name}` is bound as env::{symbol} instead, pin its symbol with `ScriptFnMetadata::with_explicit_symbol`"
+                        ));
+                        continue;
+                    }
+                    let guess = match module_name {
+                        "env" => self.guess_host_name(name),
+                        _ => None,
+                    };
+                    problems.push(match guess {
+                        Some(guess) => format!(
+                            "{module_name}::{name} is missing, was `{guess}` registered?"
+                        ),
+                        None => format!("{module_name}::{name} is missing"),
+                    });
+                }
+            }
+        }
+
+        let mut unused = self
+            .host_imports
+            .iter()
+            .filter(|(internal, import)| {
+                import.signature.is_some() && !imported.contains(internal.as_str())
+            })
+            .map(|(internal, import)| format!("`{}` (env::{internal})", import.name))
+            .collect::<Vec<_>>();
+        if !unused.is_empty() {
+            unused.sort();
+            Ext::log_warn(prefix_log(
+                self.config.log_prefix.as_deref(),
+                format!(
+                    "wasm module doesn't import these host functions: {}",
+                    unused.join(", ")
+                ),
+            ));
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "wasm module imports don't match the host functions:\n{}",
+            problems.join("\n")
+        ))
+    }
+
+    /// A bound host function whose symbol only differs from `env::import` by underscores and case
+    fn similar_host_import(&self, module_name: &str, import: &str) -> Option<(&str, &str)> {
+        fn squash(name: &str) -> String {
+            name.chars()
+                .filter(|c| *c != '_')
+                .flat_map(char::to_lowercase)
+                .collect()
+        }
+        if module_name != "env" {
+            return None;
+        }
+        let import = squash(import);
+        self.host_imports
+            .iter()
+            .find(|(symbol, host)| host.signature.is_some() && squash(symbol) == import)
+            .map(|(symbol, host)| (symbol.as_str(), host.name.as_str()))
+    }
+
+    /// Best guess at the registered name behind an `env` import that no host function has,
+    /// reversing `ScriptFnMetadata::as_internal_name` for the longest matching capability
+    fn guess_host_name(&self, import: &str) -> Option<String> {
+        let (_, rest) = self
+            .host_imports
+            .values()
+            .filter_map(|host| {
+                let prefix = format!("_{}_", host.capability.to_case(Case::Snake));
+                Some((prefix.len(), import.strip_prefix(&prefix)?))
+            })
+            .max_by_key(|(len, _)| *len)?;
+        let mut parts = rest.split("__").collect::<Vec<_>>();
+        let function = parts.pop()?.to_case(Case::Camel);
+        if parts.is_empty() {
+            return Some(function);
+        }
+        let class = parts
+            .iter()
+            .map(|part| part.to_case(Case::Pascal))
+            .collect::<Vec<_>>()
+            .join("::");
+        Some(format!("{class}.{function}"))
+    }
+
+    /// Throws away the script's instance and store, then instantiates the loaded module again
+    /// without recompiling it. Use it to start clean after a call traps.
+    /// Function keys and fast call handles stay valid, but everything the script kept in
+    /// its memory or globals is gone.
+    pub fn reset_instance(&mut self) -> Result<()> {
+        let Some(module) = self.module.clone() else {
+            return Err(anyhow!("No script is loaded"));
+        };
+        // nothing from the old store may outlive it, even if instantiating fails
+        self.script_instance = None;
+        self.memory = None;
+        self.func_cache.clear();
+        self.fast_calls.update = None;
+        self.fast_calls.fixed_update = None;
+        let named = std::mem::take(&mut self.fast_calls.named);
+        self.output.flush::<Ext>(self.config.log_prefix.as_deref());
+        self.output = GuestOutput::default();
+        self.store = Self::new_store(&self.engine, &self.config, &self.output, &self.data);
+        self.instantiate(&module)?;
+
+        for (name, func) in named {
+            // same module, so a handle that was invalidated on load stays invalid
+            let func = match func {
+                Some(_) => self.resolve_fast_call(&name)?,
+                None => self.resolve_fast_call(&name).ok().flatten(),
+            };
+            self.fast_calls.named.push((name, func));
+        }
+        Ok(())
+    }
+
+    /// Instantiates `module` into the store and caches its memory and exports
+    fn instantiate(&mut self, module: &Module) -> Result<()> {
+        let instance = self.linker.instantiate(&mut self.store, module)?;
 
-        self.memory = Some(memory);
+        // Cache instance and exported memory to avoid repeated lookups per call.
+        // Pure-compute modules may export none, only strings and buffers need it.
+        self.memory = instance
+            .get_export(&mut self.store, "memory")
+            .and_then(|m| m.into_memory());
         // clear any previous function cache and cache exports lazily
         self.func_cache.clear();
+        self.fast_calls.update = None;
+        self.fast_calls.fixed_update = None;
+        self.api_versions.clear();
 
         // Pre-create typed wrappers for exported functions where possible to avoid first-call overhead.
         // Try a small set of common signatures and cache the TypedFunc if creation succeeds.
@@ -312,6 +753,7 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> WasmInterpreter<Ext> {
             self.func_cache.push((
                 name.to_string(),
                 func,
+                export_signature(&func.ty(&self.store)),
                 TypedFuncEntry::from_func(&mut self.store, func),
             ));
 
@@ -327,106 +769,127 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> WasmInterpreter<Ext> {
                 self.fast_calls.fixed_update = Some(f);
             }
 
-            if name.starts_with("_") && name.ends_with("_semver") {
+            if let Some(loaded_mod) = api_version_export_name(name) {
                 let Ok(f) = func.typed::<(), u64>(&mut self.store) else {
                     continue;
                 };
                 let Ok(ver) = f.call(&mut self.store, ()) else {
                     continue;
                 };
-                let loaded_mod = name
-                    .strip_prefix("_")
-                    .unwrap()
-                    .strip_suffix("_semver")
-                    .unwrap()
-                    .to_string();
                 let version = Semver::from_u64(ver);
-                self.api_versions.insert(loaded_mod, version);
+                self.api_versions.insert(loaded_mod.to_string(), version);
             }
         }
 
         self.script_instance = Some(instance);
-
         Ok(())
     }
 
+    /// The original bytes of the loaded wasm module, None if no script is loaded
+    pub fn module_bytes(&self) -> Option<Vec<u8>> {
+        self.module_bytes.clone()
+    }
+
+    /// Serializes the compiled form of the loaded module.
+    /// The result can only be deserialized by an engine with the same configuration, see `wasmtime_engine`.
+    pub fn compiled_bytes(&self) -> Result<Vec<u8>> {
+        let Some(module) = &self.module else {
+            return Err(anyhow!("No script is loaded"));
+        };
+        Ok(module.serialize()?)
+    }
+
+    /// The wasmtime engine modules are compiled with, needed to deserialize `compiled_bytes`
+    pub fn wasmtime_engine(&self) -> &Engine {
+        &self.engine
+    }
+
     /// Calls a function in the loaded wasm script with the given parameters and return type.
     pub fn call_fn(
         &mut self,
         cache_key: ScriptFnKey,
-        params: Params,
+        params: &mut Params,
         ret_type: DataType,
         data: &Arc<RwLock<EngineDataState>>,
     ) -> Param {
-        // Try cache first to avoid repeated name lookup and Val boxing/unboxing.
-        // This shouldn't be necessary as all exported functions are indexed on load
-        let (f_name, f, typed) = self.func_cache.get(&cache_key);
+        // all exported functions are indexed on load, so a key outside the cache names nothing
+        let Some((f_name, f, signature, typed)) = self.func_cache.try_get(&cache_key) else {
+            return Param::Error(FUNCTION_NOT_FOUND_MESSAGE.to_string());
+        };
+        let _scope = CallScope::script(f_name);
+
+        // an expected type that doesn't match the export would reinterpret whatever it returns
+        if let Err(e) = signature.check_call(f_name, params.len(), ret_type) {
+            return Param::Error(format!("{e}"));
+        }
 
-        // can only do a typed call if all parameters are simple and return type is simple or void, so if we have a cached typed func, we know it will work and skip the Val conversions.
-        let can_typed_call = ret_type.is_wasm_simple()
+        // can only do a typed call if all parameters are simple and return type is simple or void
+        let can_typed_call = (ret_type == DataType::Void || ret_type.is_wasm_simple())
             && params
                 .iter()
                 .all(|r| r.data_type::<ExtTypes>().is_wasm_simple());
 
-        // Fast-path: typed cache (common signatures). Falls back to dynamic call below.
-        if can_typed_call && let Some(typed) = typed {
-            return typed
-                .invoke(&mut self.store, params, data)
-                .unwrap_or_else(|e| {
-                    Param::Error(format!("Error calling wasm function typed: {e}"))
-                });
+        if self.memory.is_none()
+            && (ret_type.needs_wasm_memory()
+                || params
+                    .iter()
+                    .any(|p| p.data_type::<ExtTypes>().needs_wasm_memory()))
+        {
+            return Param::Error(NO_MEMORY_MESSAGE.to_string());
         }
 
-        let args = match params.to_wasm_args(data) {
-            Ok(a) => a,
-            Err(e) => return Param::Error(format!("Params error: {e}")),
-        };
+        let args = &mut self.arg_scratch;
+        if let Err(e) = params.drain_into_wasm_args(data, args) {
+            return Param::Error(format!("Params error: {e}"));
+        }
 
-        // Fallback dynamic path
-        let mut res: SmallVec<[Val; 1]> = match ret_type {
-            DataType::Void => SmallVec::new(),
-            DataType::F32 => SmallVec::from_buf([Val::F32(0)]),
-            DataType::F64 => SmallVec::from_buf([Val::F64(0)]),
-            DataType::ExtString | DataType::RustString => SmallVec::from_buf([Val::I32(0)]),
-            // We use i64 for opaque pointers since we need the full 64 bits to store the pointer
-            DataType::Object => SmallVec::from_buf([Val::I64(0)]),
-            DataType::I64 | DataType::U64 => SmallVec::from_buf([Val::I64(0)]),
-
-            DataType::ExtMat4
-            | DataType::ExtVec4
-            | DataType::ExtQuat
-            | DataType::RustMat4
-            | DataType::RustVec4
-            | DataType::RustQuat => {
-                // these are all passed as length to wasm, so we just need to reserve space for the pointer
-                SmallVec::from_buf([Val::I32(0)])
-            }
+        let _cancel = begin_call(&mut self.store, &self.interrupt);
 
-            // u32 buffer
-            DataType::RustU32Buffer | DataType::ExtU32Buffer => SmallVec::from_buf([Val::I32(0)]),
+        // Fast-path: typed cache, only taken when the export returns what the caller expects
+        // and the args fit its signature. Falls back to the dynamic call below.
+        let typed = typed.as_ref().filter(|_| can_typed_call);
+        if let Some(typed) = typed
+            && !typed.returns(ret_type)
+        {
+            Ext::log_debug(format!(
+                "Typed call to wasm function '{f_name}' skipped, it doesn't return {ret_type}"
+            ));
+        }
+        let typed_res = typed
+            .filter(|typed| typed.returns(ret_type))
+            .and_then(|typed| typed.invoke(&mut self.store, args));
 
-            _ => SmallVec::from_buf([Val::I32(0)]),
+        let rt = match typed_res {
+            Some(Ok(rt)) => rt,
+            Some(Err(e)) => {
+                if is_interrupt(&e) {
+                    return Param::Error(INTERRUPTED_MESSAGE.to_string());
+                }
+                return Param::Error(format!(
+                    "Error calling wasm function typed: {f_name}\n{}",
+                    describe_call_error(&e)
+                ));
+            }
+            None => match call_dynamic(&mut self.store, *f, f_name, args, ret_type) {
+                Ok(rt) => rt,
+                Err(e) => return e,
+            },
         };
 
-        // this are errors raised by wasm execution
-        // e.g. stack overflow, out of bounds memory access, etc.
-        if let Err(e) = f.call(&mut self.store, &args, &mut res) {
-            return Param::Error(format!("Error calling wasm function: {}\n{}", f_name, e));
-        }
         // Return void quickly
-        if res.is_empty() {
+        let Some(rt) = rt else {
             return Param::Void;
-        }
-        let rt = res[0];
-
-        let memory = match &self.memory {
-            Some(m) => m,
-            None => return Param::Error("WASM memory not initialized".to_string()),
         };
 
         // convert Val to Param
         // if an error is returned from wasm, convert to Param::Error
-        Param::from_wasm_type_val(ret_type, rt, data, memory, &self.store.as_context())
+        Param::from_wasm_type_val(
+            ret_type,
+            rt,
+            data,
+            self.memory.as_ref(),
+            &self.store.as_context(),
+        )
     }
 
     pub fn fast_call_update(&mut self, delta_time: f32) -> std::result::Result<(), String> {
@@ -436,8 +899,14 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> WasmInterpreter<Ext> {
         let Some(f) = &self.fast_calls.update else {
             return Ok(());
         };
-        f.call(&mut self.store, delta_time)
-            .map_err(|e| e.to_string())
+        let _cancel = begin_call(&mut self.store, &self.interrupt);
+        f.call(&mut self.store, delta_time).map_err(|e| {
+            if is_interrupt(&e) {
+                INTERRUPTED_MESSAGE.to_string()
+            } else {
+                describe_call_error(&e)
+            }
+        })
     }
 
     pub fn fast_call_fixed_update(&mut self, delta_time: f32) -> std::result::Result<(), String> {
@@ -447,13 +916,264 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> WasmInterpreter<Ext> {
         let Some(f) = &self.fast_calls.fixed_update else {
             return Ok(());
         };
-        f.call(&mut self.store, delta_time)
-            .map_err(|e| e.to_string())
+        let _cancel = begin_call(&mut self.store, &self.interrupt);
+        f.call(&mut self.store, delta_time).map_err(|e| {
+            if is_interrupt(&e) {
+                INTERRUPTED_MESSAGE.to_string()
+            } else {
+                describe_call_error(&e)
+            }
+        })
+    }
+
+    pub fn register_fast_call(&mut self, name: &str) -> Result<()> {
+        let func = self.resolve_fast_call(name)?;
+        self.fast_calls.named.push((name.to_string(), func));
+        Ok(())
+    }
+
+    pub fn invalidate_fast_call(&mut self, name: &str) {
+        self.fast_calls.named.push((name.to_string(), None));
+    }
+
+    fn resolve_fast_call(&mut self, name: &str) -> Result<Option<TypedFunc<f32, ()>>> {
+        let Some(instance) = self.script_instance else {
+            return Err(anyhow!("No script is loaded"));
+        };
+        let Some(func) = instance.get_func(&mut self.store, name) else {
+            return Ok(None);
+        };
+        let func = func
+            .typed::<f32, ()>(&mut self.store)
+            .with_context(|| format!("'{name}' cannot be used as a fast call"))?;
+        Ok(Some(func))
+    }
+
+    pub fn invoke_fast_call(
+        &mut self,
+        handle: FastCallHandle,
+        delta_time: f32,
+    ) -> std::result::Result<(), String> {
+        let Some((_, f)) = self.fast_calls.named.get(u32::from(handle) as usize) else {
+            return Err("Invalid fast call handle".to_string());
+        };
+        let Some(f) = f else {
+            return Ok(());
+        };
+        let _cancel = begin_call(&mut self.store, &self.interrupt);
+        f.call(&mut self.store, delta_time).map_err(|e| {
+            if is_interrupt(&e) {
+                INTERRUPTED_MESSAGE.to_string()
+            } else {
+                describe_call_error(&e)
+            }
+        })
     }
 
     pub fn get_fn_key(&self, name: &str) -> Option<ScriptFnKey> {
         self.func_cache.key_of(|x| x.0 == name)
     }
+
+    pub fn fn_name(&self, key: ScriptFnKey) -> Option<&str> {
+        self.func_cache
+            .try_get(&key)
+            .map(|(name, _, _, _)| name.as_str())
+    }
+
+    pub fn script_exports(&self) -> Vec<String> {
+        self.func_cache
+            .iter()
+            .map(|(name, _, _, _)| name.clone())
+            .collect()
+    }
+
+    pub fn export_signature(&self, key: ScriptFnKey) -> Option<ExportSignature> {
+        let (_, _, signature, _) = self.func_cache.try_get(&key)?;
+        Some(signature.clone())
+    }
+
+    /// Whether calls to `key` expecting `ret_type` can take the typed path, a `TypedFunc` call
+    /// instead of a dynamic `Func::call`. The args still have to fit the export's signature.
+    ///
+    /// Only some signatures of i32/i64/f32/f64 params with a void or scalar result are covered:
+    /// every combination of up to 2 params, and 3 or 4 params only if they all share one type.
+    /// Anything else, e.g. `(i32, f32, i32)`, always takes the dynamic call.
+    /// Args are converted to `Val`s on both paths, so the typed path only saves wasmtime's
+    /// dynamic type checks; `turing_wasm_typed_vs_dynamic_call` in `wasm_api_bench` compares them.
+    pub fn has_typed_call(&self, key: ScriptFnKey, ret_type: DataType) -> bool {
+        self.func_cache
+            .try_get(&key)
+            .and_then(|(_, _, _, typed)| typed.as_ref())
+            .is_some_and(|typed| typed.returns(ret_type))
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle::Wasm(self.engine.clone(), Arc::clone(&self.interrupt))
+    }
+}
+
+/// Re-arms the epoch deadline and exposes the interrupt flag to host callbacks for the call
+fn begin_call(store: &mut Store<WasiP1Ctx>, interrupt: &Arc<AtomicBool>) -> CancelScope {
+    store.set_epoch_deadline(1);
+    interrupt.store(false, Ordering::Release);
+    CancelScope::enter(interrupt)
+}
+
+/// The params and results of an exported function's wasm type
+fn export_signature(ty: &FuncType) -> ExportSignature {
+    let val_type = |typ: ValType| match typ {
+        ValType::I32 => ScriptValType::I32,
+        ValType::I64 => ScriptValType::I64,
+        ValType::F32 => ScriptValType::F32,
+        ValType::F64 => ScriptValType::F64,
+        _ => ScriptValType::Other,
+    };
+    ExportSignature {
+        params: Some(ty.params().map(val_type).collect()),
+        results: Some(ty.results().map(val_type).collect()),
+    }
+}
+
+/// Calls `f` through wasmtime's dynamic path, for signatures without a `TypedFuncEntry`
+fn call_dynamic(
+    store: &mut Store<WasiP1Ctx>,
+    f: Func,
+    f_name: &str,
+    args: &[Val],
+    ret_type: DataType,
+) -> std::result::Result<Option<Val>, Param> {
+    let mut res: SmallVec<[Val; 1]> = match ret_type {
+        DataType::Void => SmallVec::new(),
+        DataType::F32 => SmallVec::from_buf([Val::F32(0)]),
+        DataType::F64 => SmallVec::from_buf([Val::F64(0)]),
+        DataType::ExtString | DataType::RustString | DataType::RustJson | DataType::ExtJson => {
+            SmallVec::from_buf([Val::I32(0)])
+        }
+        // We use i64 for opaque pointers since we need the full 64 bits to store the pointer
+        DataType::Object | DataType::OwnedObject => SmallVec::from_buf([Val::I64(0)]),
+        DataType::I64 | DataType::U64 => SmallVec::from_buf([Val::I64(0)]),
+
+        DataType::ExtMat4
+        | DataType::ExtVec4
+        | DataType::ExtQuat
+        | DataType::RustMat4
+        | DataType::RustVec4
+        | DataType::RustQuat => {
+            // these are all passed as length to wasm, so we just need to reserve space for the pointer
+            SmallVec::from_buf([Val::I32(0)])
+        }
+
+        // u32 buffer
+        DataType::RustU32Buffer | DataType::ExtU32Buffer => SmallVec::from_buf([Val::I32(0)]),
+        DataType::RustF32Buffer | DataType::ExtF32Buffer => SmallVec::from_buf([Val::I32(0)]),
+
+        _ => SmallVec::from_buf([Val::I32(0)]),
+    };
+
+    // this are errors raised by wasm execution
+    // e.g. stack overflow, out of bounds memory access, etc.
+    if let Err(e) = f.call(store, args, &mut res) {
+        if is_interrupt(&e) {
+            return Err(Param::Error(INTERRUPTED_MESSAGE.to_string()));
+        }
+        return Err(Param::Error(format!(
+            "Error calling wasm function: {f_name}\n{}",
+            describe_call_error(&e)
+        )));
+    }
+    Ok(res.first().copied())
+}
+
+/// Finds the payload of the first custom section called `name` in a wasm binary
+fn custom_section<'a>(wasm: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    // skip the magic number and version
+    let mut rest = wasm.get(8..)?;
+    while let Some((&id, after_id)) = rest.split_first() {
+        let (size, n) = read_leb_u32(after_id)?;
+        let body = after_id.get(n..n + size as usize)?;
+        rest = &after_id[n + size as usize..];
+
+        if id != 0 {
+            continue;
+        }
+        let (name_len, n) = read_leb_u32(body)?;
+        if body.get(n..n + name_len as usize)? == name.as_bytes() {
+            return body.get(n + name_len as usize..);
+        }
+    }
+    None
+}
+
+/// Reads an unsigned LEB128 u32, returning the value and how many bytes it took
+fn read_leb_u32(bytes: &[u8]) -> Option<(u32, usize)> {
+    let mut value = 0u32;
+    for (i, byte) in bytes.iter().take(5).enumerate() {
+        value |= ((byte & 0x7f) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Parses `key=value` lines, skipping lines without a `=`
+fn parse_meta(text: &str) -> FxHashMap<String, String> {
+    text.lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect()
+}
+
+/// Returns true if the error is the trap raised when the epoch deadline was reached
+fn is_interrupt(e: &wasmtime::Error) -> bool {
+    matches!(e.downcast_ref::<Trap>(), Some(Trap::Interrupt))
+}
+
+/// The trap or host error that ended a call, followed by the guest frames it happened in,
+/// innermost first, like `0: inner_fn @ 0x1f3 at src/lib.rs:12`
+fn describe_call_error(e: &wasmtime::Error) -> String {
+    let mut msg = e
+        .chain()
+        .filter(|cause| !cause.is::<WasmBacktrace>())
+        .map(|cause| cause.to_string())
+        .collect::<Vec<_>>()
+        .join(": ");
+    let Some(backtrace) = e.downcast_ref::<WasmBacktrace>() else {
+        return msg;
+    };
+    msg.push_str("\nwasm backtrace:");
+    for (i, frame) in backtrace.frames().iter().enumerate() {
+        let name = frame
+            .func_name()
+            .map_or_else(|| format!("<func {}>", frame.func_index()), str::to_string);
+        write!(msg, "\n  {i}: {name}").ok();
+        if let Some(offset) = frame.module_offset() {
+            write!(msg, " @ {offset:#x}").ok();
+        }
+        if let Some(symbol) = frame.symbols().first()
+            && let Some(file) = symbol.file()
+        {
+            write!(msg, " at {file}").ok();
+            if let Some(line) = symbol.line() {
+                write!(msg, ":{line}").ok();
+            }
+        }
+    }
+    msg
+}
+
+/// Returns zero of whatever type the import returns, for host calls that were skipped or failed
+/// without trapping
+fn write_zero_result(rs: &mut [Val], return_type: DataType) -> Result<()> {
+    if let Some(rv) = rs.first_mut() {
+        *rv = match return_type.to_val_type()? {
+            ValType::I64 => Val::I64(0),
+            ValType::F32 => Val::F32(0),
+            ValType::F64 => Val::F64(0),
+            _ => Val::I32(0),
+        };
+    }
+    Ok(())
 }
 
 /// Wraps a call from wasm into the host environment, checking capability availability
@@ -463,54 +1183,118 @@ fn wasm_bind_env<Ext: ExternalFunctions>(
     data: &Arc<RwLock<EngineDataState>>,
     mut caller: Caller<'_, WasiP1Ctx>,
     cap: &str,
+    missing_policy: &CapabilityMissingPolicy,
     ps: &[Val],
     rs: &mut [Val],
     p: &[DataType],
     expected_return_type: DataType,
-    func: &ScriptCallback,
+    fallible: bool,
+    func: &HostFn,
 ) -> Result<()> {
-    if !data.read().active_capabilities.contains(cap) {
-        Ext::log_critical(format!(
-            "Attempted to call mod capability '{}' which is not currently loaded",
-            cap
-        ));
-        return Err(anyhow!("Mod capability '{}' is not currently loaded", cap));
-    }
+    // lets the host know before the error becomes a trap
+    let fail = |kind: ErrorKind, e: anyhow::Error| {
+        Ext::on_script_error(cap, kind, &e.to_string());
+        e
+    };
+    let capability_loaded = data.read().active_capabilities.contains(cap);
 
-    // pre-allocate params to avoid repeated reallocations
-    let mut params = Params::of_size(p.len() as u32);
-    let memory = caller
-        .get_export("memory")
-        .and_then(|m| m.into_memory())
-        .context("WASM memory not found")?;
+    let res = if capability_loaded {
+        let charge = EngineDataState::charge_callback(data, cap);
+        if let CallbackCharge::OverBudget { max_calls, first } = charge {
+            if first {
+                Ext::on_script_error(
+                    cap,
+                    ErrorKind::BudgetExceeded,
+                    &format!("Capability '{cap}' exceeded its budget of {max_calls} host calls"),
+                );
+            }
+            // skipped rather than trapping, so the rest of the script call still runs
+            return write_zero_result(rs, expected_return_type);
+        }
 
-    for (exp_typ, value) in p.iter().zip(ps) {
-        let param =
-            Param::from_wasm_type_val(*exp_typ, *value, data, &memory, &caller.as_context());
-        params.push(param)
-    }
+        // pre-allocate params to avoid repeated reallocations
+        let mut params = Params::of_size(p.len() as u32);
+        let memory = caller.get_export("memory").and_then(|m| m.into_memory());
+
+        let nan_policy = data.read().nan_policy;
+        for (i, (exp_typ, value)) in p.iter().zip(ps).enumerate() {
+            let param = Param::from_wasm_type_val(
+                *exp_typ,
+                *value,
+                data,
+                memory.as_ref(),
+                &caller.as_context(),
+            )
+            .apply_nan_policy(nan_policy);
+            // a bad string pointer, mismatched value or rejected float traps rather than reaching the host
+            if let Param::Error(e) = &param
+                && !matches!(exp_typ, DataType::RustError | DataType::ExtError)
+            {
+                return Err(fail(
+                    ErrorKind::InvalidArgument,
+                    anyhow!(
+                        "Invalid argument {i} for host function '{}': {e}",
+                        func.name().to_string_lossy()
+                    ),
+                ));
+            }
+            params.push(param)
+        }
 
-    let ffi_params = params.to_ffi::<Ext>();
-    let ffi_params_struct = ffi_params.as_ffi_array();
+        let ffi_params = params.to_ffi::<Ext>();
+        let ffi_params_struct = ffi_params.as_ffi_array();
 
-    // Call to C#/rust's provided callback using a clone so we can still cleanup
-    let res = func(ffi_params_struct).into_param::<Ext>()?;
+        // Call to C#/rust's provided callback using a clone so we can still cleanup
+        func.call_with_metrics(ffi_params_struct, data)
+            .into_param::<Ext>()
+            .map_err(|e| fail(ErrorKind::InvalidReturn, e))?
+    } else {
+        let CapabilityMissingPolicy::SilentDefault(default) = missing_policy else {
+            return Err(fail(
+                ErrorKind::MissingCapability,
+                anyhow!("Mod capability '{}' is not currently loaded", cap),
+            ));
+        };
+        default.clone()
+    };
 
     let result_data_type = res.data_type::<ExtTypes>();
     if result_data_type != expected_return_type {
-        return Err(anyhow!(
-            "WASM function returned unexpected type. Expected: {:?}, Got: {:?}",
-            expected_return_type,
-            result_data_type
+        if fallible && let Param::Error(e) = res {
+            Ext::on_script_error(cap, ErrorKind::HostError, &e);
+            data.write().last_host_error = Some(e);
+            return write_zero_result(rs, expected_return_type);
+        }
+        if let Param::Error(e) = &res {
+            return Err(fail(
+                ErrorKind::HostError,
+                anyhow!(
+                    "Host function '{}' failed: {e}",
+                    func.name().to_string_lossy()
+                ),
+            ));
+        }
+        return Err(fail(
+            ErrorKind::InvalidReturn,
+            anyhow!(
+                "{TYPE_MISMATCH_MESSAGE}: host function '{}' is declared to return {expected_return_type}, but returned {result_data_type}",
+                func.name().to_string_lossy()
+            ),
         ));
     }
 
     // Convert Param back to Val for return
     // TODO: Add mechanism for providing error messages to caller
-    let Some(rv) = res.into_wasm_val(data)? else {
+    let Some(rv) = res
+        .into_wasm_val(data)
+        .map_err(|e| fail(ErrorKind::InvalidReturn, e))?
+    else {
         return Ok(());
     };
-    rs[0] = rv;
+    // the import's results come from the declared return type, which the value was checked against
+    if let Some(slot) = rs.first_mut() {
+        *slot = rv;
+    }
 
     Ok(())
 }
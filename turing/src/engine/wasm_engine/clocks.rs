@@ -0,0 +1,39 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::RwLock;
+use wasmtime_wasi::clocks::{HostMonotonicClock, HostWallClock};
+
+use crate::EngineDataState;
+
+/// Wasi wall and monotonic clock that reads the game time the host sets with `Turing::set_time`,
+/// so deterministic scripts can't see real time through wasi either
+pub struct GameClock {
+    data: Arc<RwLock<EngineDataState>>,
+}
+
+impl GameClock {
+    pub fn new(data: Arc<RwLock<EngineDataState>>) -> Self {
+        Self { data }
+    }
+}
+
+impl HostWallClock for GameClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_micros(1)
+    }
+
+    fn now(&self) -> Duration {
+        Duration::from_micros(self.data.read().game_time_micros())
+    }
+}
+
+impl HostMonotonicClock for GameClock {
+    fn resolution(&self) -> u64 {
+        1_000
+    }
+
+    fn now(&self) -> u64 {
+        self.data.read().game_time_micros().saturating_mul(1_000)
+    }
+}
@@ -1,47 +1,57 @@
-use std::{
-    ffi::{CStr, CString},
-    sync::Arc,
-};
+use std::{ffi::CString, sync::Arc};
 
 use anyhow::anyhow;
 use parking_lot::RwLock;
-use wasmtime::{Caller, Memory, MemoryAccessError, Val};
+use wasmtime::{Caller, Val};
 use wasmtime_wasi::p1::WasiP1Ctx;
 
-use crate::EngineDataState;
+use crate::engine::script_log;
+use crate::engine::types::LogLevel;
+use crate::engine::wasm_engine::wasm_mem::{read_bytes, read_cstr, write_bytes_checked};
+use crate::interop::params::INTERIOR_NUL_MESSAGE;
+use crate::{EngineDataState, ExternalFunctions};
 
 /// gets a string out of wasm memory into rust memory.
-pub fn get_wasm_string(message: u32, data: &[u8]) -> String {
-    let c = CStr::from_bytes_until_nul(&data[message as usize..]).expect("Not a valid CStr");
-    match c.to_str() {
-        Ok(s) => s.to_owned(),
-        Err(_) => c.to_string_lossy().into_owned(),
-    }
+/// Invalid UTF-8 is replaced with U+FFFD. Errors if the pointer is outside of memory or the
+/// string has no nul terminator before the end of memory.
+pub fn get_wasm_string(message: u32, data: &[u8]) -> anyhow::Result<String> {
+    read_cstr(data, message, usize::MAX)
 }
 
-/// writes a string from rust memory to wasm memory.
+/// writes a string from rust memory into the `size` bytes the guest allocated at `pointer`.
+/// Errors if the string contains a nul byte, since the guest reads it back as a C string.
 pub fn write_wasm_string(
     pointer: u32,
+    size: u32,
     string: &str,
-    memory: &Memory,
-    caller: Caller<'_, WasiP1Ctx>,
-) -> Result<(), MemoryAccessError> {
-    let c = CString::new(string).unwrap();
-    let bytes = c.into_bytes_with_nul();
-    memory.write(caller, pointer as usize, &bytes)
+    data: &mut [u8],
+) -> anyhow::Result<()> {
+    let c = CString::new(string).map_err(|_| anyhow!(INTERIOR_NUL_MESSAGE))?;
+    write_bytes_checked(data, pointer, c.as_bytes_with_nul(), size)
 }
 
-pub fn write_u32_vec(
-    pointer: u32,
-    buf: &[u32],
-    memory: &Memory,
-    caller: Caller<'_, WasiP1Ctx>,
-) -> Result<(), MemoryAccessError> {
-    let mut bytes = Vec::with_capacity(buf.len() * 4);
-    for (i, num) in buf.iter().enumerate() {
-        bytes[i * 4..i * 4 + 4].copy_from_slice(&num.to_le_bytes())
-    }
-    memory.write(caller, pointer as usize, &bytes)
+/// writes a Vec<u32> into the `len` u32s the guest allocated at `pointer`, little-endian
+pub fn write_u32_vec(pointer: u32, len: u32, buf: &[u32], data: &mut [u8]) -> anyhow::Result<()> {
+    let bytes = buf
+        .iter()
+        .flat_map(|num| num.to_le_bytes())
+        .collect::<Vec<_>>();
+    let alloc_len = len
+        .checked_mul(4)
+        .ok_or_else(|| anyhow!("buffer of {len} u32s is larger than wasm memory"))?;
+    write_bytes_checked(data, pointer, &bytes, alloc_len)
+}
+
+/// writes a Vec<f32> into the `len` f32s the guest allocated at `pointer`, little-endian
+pub fn write_f32_vec(pointer: u32, len: u32, buf: &[f32], data: &mut [u8]) -> anyhow::Result<()> {
+    let bytes = buf
+        .iter()
+        .flat_map(|num| num.to_le_bytes())
+        .collect::<Vec<_>>();
+    let alloc_len = len
+        .checked_mul(4)
+        .ok_or_else(|| anyhow!("buffer of {len} f32s is larger than wasm memory"))?;
+    write_bytes_checked(data, pointer, &bytes, alloc_len)
 }
 
 /// internal for use in the wasm engine only
@@ -57,7 +67,12 @@ pub fn wasm_host_strcpy(
         && next_str.len() + 1 == size as usize
         && let Some(memory) = caller.get_export("memory").and_then(|m| m.into_memory())
     {
-        write_wasm_string(ptr as u32, &next_str, &memory, caller)?;
+        write_wasm_string(
+            ptr as u32,
+            size as u32,
+            &next_str,
+            memory.data_mut(&mut caller),
+        )?;
         return Ok(());
     }
 
@@ -66,6 +81,131 @@ pub fn wasm_host_strcpy(
     ))
 }
 
+/// internal for use in the wasm engine only
+///
+/// Copies `len` bytes at `ptr` out of wasm memory into the host's received strings,
+/// returning the id the guest can hand to host functions.
+pub fn wasm_host_recv_string(
+    data: &Arc<RwLock<EngineDataState>>,
+    mut caller: Caller<'_, WasiP1Ctx>,
+    ps: &[Val],
+    rs: &mut [Val],
+) -> Result<(), anyhow::Error> {
+    let ptr = ps[0].i32().unwrap() as u32;
+    let len = ps[1].i32().unwrap() as u32;
+
+    let memory = caller
+        .get_export("memory")
+        .and_then(|m| m.into_memory())
+        .ok_or_else(|| anyhow!("WASM memory not found"))?;
+    let bytes = read_bytes(memory.data(&caller), ptr, len)?;
+    let s = String::from_utf8_lossy(bytes).into_owned();
+
+    rs[0] = Val::I32(data.write().receive_string(s) as i32);
+    Ok(())
+}
+
+/// internal for use in the wasm engine only
+///
+/// Logs the nul-terminated string at `ps[1]` at the level in `ps[0]`, see [`LogLevel`]
+pub fn wasm_host_log<Ext: ExternalFunctions>(
+    data: &Arc<RwLock<EngineDataState>>,
+    log_prefix: Option<&str>,
+    mut caller: Caller<'_, WasiP1Ctx>,
+    ps: &[Val],
+) -> Result<(), anyhow::Error> {
+    let level = ps[0].i32().unwrap() as u32;
+    let ptr = ps[1].i32().unwrap() as u32;
+
+    let level = LogLevel::try_from(level).map_err(|_| anyhow!("invalid log level {level}"))?;
+    let memory = caller
+        .get_export("memory")
+        .and_then(|m| m.into_memory())
+        .ok_or_else(|| anyhow!("WASM memory not found"))?;
+    let msg = get_wasm_string(ptr, memory.data(&caller))?;
+
+    script_log::<Ext>(data, log_prefix, level, &msg);
+    Ok(())
+}
+
+/// internal for use in the wasm engine only
+///
+/// Looks up the nul-terminated key at `ps[0]` in the script's storage. Returns the size to
+/// allocate and pass to `_host_strcpy` for the value, or 0 if the key isn't set.
+pub fn wasm_host_storage_get(
+    data: &Arc<RwLock<EngineDataState>>,
+    mut caller: Caller<'_, WasiP1Ctx>,
+    ps: &[Val],
+    rs: &mut [Val],
+) -> Result<(), anyhow::Error> {
+    let ptr = ps[0].i32().unwrap() as u32;
+
+    let memory = caller
+        .get_export("memory")
+        .and_then(|m| m.into_memory())
+        .ok_or_else(|| anyhow!("WASM memory not found"))?;
+    let key = get_wasm_string(ptr, memory.data(&caller))?;
+
+    let mut data = data.write();
+    let Some(value) = data.script_storage_get(&key)?.map(str::to_string) else {
+        rs[0] = Val::I32(0);
+        return Ok(());
+    };
+    if value.contains('\0') {
+        return Err(anyhow!(INTERIOR_NUL_MESSAGE));
+    }
+    rs[0] = Val::I32(value.len() as i32 + 1);
+    data.str_cache.push_back(value);
+    Ok(())
+}
+
+/// internal for use in the wasm engine only
+///
+/// Stores the nul-terminated value at `ps[1]` under the key at `ps[0]` in the script's storage.
+/// Returns 0 once stored or 1 if the write would go over the storage quota.
+pub fn wasm_host_storage_set(
+    data: &Arc<RwLock<EngineDataState>>,
+    mut caller: Caller<'_, WasiP1Ctx>,
+    ps: &[Val],
+    rs: &mut [Val],
+) -> Result<(), anyhow::Error> {
+    let key_ptr = ps[0].i32().unwrap() as u32;
+    let value_ptr = ps[1].i32().unwrap() as u32;
+
+    let memory = caller
+        .get_export("memory")
+        .and_then(|m| m.into_memory())
+        .ok_or_else(|| anyhow!("WASM memory not found"))?;
+    let key = get_wasm_string(key_ptr, memory.data(&caller))?;
+    let value = get_wasm_string(value_ptr, memory.data(&caller))?;
+
+    // a script without a storage namespace traps, going over the quota is for the script to handle
+    let mut data = data.write();
+    data.script_storage_mod_id()?;
+    rs[0] = Val::I32(data.script_storage_set(&key, &value).is_err() as i32);
+    Ok(())
+}
+
+/// internal for use in the wasm engine only
+///
+/// Queues the error of the last fallible host function that failed for `_host_strcpy`,
+/// returning the size to allocate, or 0 if there is none.
+pub fn wasm_host_last_error(
+    data: &Arc<RwLock<EngineDataState>>,
+    rs: &mut [Val],
+) -> Result<(), anyhow::Error> {
+    let mut data = data.write();
+    let Some(error) = data.last_host_error.take() else {
+        rs[0] = Val::I32(0);
+        return Ok(());
+    };
+    // escaped like errors crossing into C, so the guest still gets a C string
+    let error = error.replace('\0', "\\0");
+    rs[0] = Val::I32(error.len() as i32 + 1);
+    data.str_cache.push_back(error);
+    Ok(())
+}
+
 pub fn wasm_host_bufcpy(
     data: &Arc<RwLock<EngineDataState>>,
     mut caller: Caller<'_, WasiP1Ctx>,
@@ -78,7 +218,12 @@ pub fn wasm_host_bufcpy(
         && next_buf.len() == size as usize
         && let Some(memory) = caller.get_export("memory").and_then(|m| m.into_memory())
     {
-        write_u32_vec(ptr as u32, &next_buf, &memory, caller)?;
+        write_u32_vec(
+            ptr as u32,
+            size as u32,
+            &next_buf,
+            memory.data_mut(&mut caller),
+        )?;
         return Ok(());
     }
 
@@ -87,6 +232,32 @@ pub fn wasm_host_bufcpy(
     ))
 }
 
+pub fn wasm_host_f32_bufcpy(
+    data: &Arc<RwLock<EngineDataState>>,
+    mut caller: Caller<'_, WasiP1Ctx>,
+    ps: &[Val],
+) -> Result<(), anyhow::Error> {
+    let ptr = ps[0].i32().unwrap();
+    let size = ps[1].i32().unwrap();
+
+    if let Some(next_buf) = data.write().f32_buffer_queue.pop_front()
+        && next_buf.len() == size as usize
+        && let Some(memory) = caller.get_export("memory").and_then(|m| m.into_memory())
+    {
+        write_f32_vec(
+            ptr as u32,
+            size as u32,
+            &next_buf,
+            memory.data_mut(&mut caller),
+        )?;
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "An error occurred whilst copying a Vec<f32> to wasm memory"
+    ))
+}
+
 pub fn wasm_host_f32_dequeue(
     data: &Arc<RwLock<EngineDataState>>,
     rs: &mut [Val],
@@ -146,16 +317,51 @@ pub fn wasm_host_u32_enqueue(
 /// internal for use in the wasm engine only
 ///
 /// This is used for copying a Vec<u32> from the host to wasm memory. The Vec<u32> should be enqueued using `wasm_host_u32_enqueue` before calling this function, and the pointer and length of the buffer in wasm memory should be passed as parameters.
-pub fn get_u32_vec(ptr: u32, len: u32, data: &[u8]) -> Option<Vec<u32>> {
-    let start = ptr as usize;
-    let end = start.checked_add((len as usize).checked_mul(4)?)?;
-    if end > data.len() {
-        return None;
-    }
-    let mut vec = vec![0u32; len as usize];
-    for (i, u) in &mut vec.iter_mut().enumerate() {
-        let offset = start + i * 4;
-        *u = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+pub fn get_u32_vec(ptr: u32, len: u32, data: &[u8]) -> anyhow::Result<Vec<u32>> {
+    let byte_len = len
+        .checked_mul(4)
+        .ok_or_else(|| anyhow!("buffer of {len} u32s is larger than wasm memory"))?;
+    let bytes = read_bytes(data, ptr, byte_len)?;
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect())
+}
+
+/// reads the `len` little-endian f32s the guest wrote at `ptr`
+pub fn get_f32_vec(ptr: u32, len: u32, data: &[u8]) -> anyhow::Result<Vec<f32>> {
+    let byte_len = len
+        .checked_mul(4)
+        .ok_or_else(|| anyhow!("buffer of {len} f32s is larger than wasm memory"))?;
+    let bytes = read_bytes(data, ptr, byte_len)?;
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect())
+}
+
+#[cfg(test)]
+mod host_helpers_tests {
+    use super::get_wasm_string;
+    use crate::HostRng;
+
+    #[test]
+    fn test_get_wasm_string_adversarial() {
+        assert_eq!(get_wasm_string(0, b"abc\0def").unwrap(), "abc");
+        assert_eq!(get_wasm_string(4, b"abc\0def\0").unwrap(), "def");
+        assert_eq!(get_wasm_string(0, b"\xffok\0").unwrap(), "\u{fffd}ok");
+        assert!(get_wasm_string(0, b"no terminator").is_err());
+        assert!(get_wasm_string(3, b"abc").is_err());
+        assert!(get_wasm_string(u32::MAX, b"abc\0").is_err());
+
+        // every pointer into pseudo-random memory either reads up to the next nul or errors
+        let mut rng = HostRng::new(0x2545_f491);
+        let memory = (0..512).map(|_| rng.range(0, 7) as u8).collect::<Vec<_>>();
+        for ptr in 0..=memory.len() as u32 + 1 {
+            let terminated = memory
+                .get(ptr as usize..)
+                .is_some_and(|rest| rest.contains(&0));
+            assert_eq!(get_wasm_string(ptr, &memory).is_ok(), terminated);
+        }
     }
-    Some(vec)
 }
@@ -0,0 +1,133 @@
+//! Compiled modules kept between loads, so reloading an unchanged wasm file skips compilation.
+//!
+//! Each `WasmInterpreter` has its own wasmtime engine, so modules are kept serialized and
+//! deserialized into the next one. That's far cheaper than compiling, and lets the cache be
+//! written to disk and reused after the host restarts.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use wasmtime::{Engine, Module};
+
+use crate::ExternalFunctions;
+
+/// How many compiled modules are kept in memory unless `ModuleCache::set_max_entries` says otherwise
+pub const DEFAULT_MODULE_CACHE_ENTRIES: usize = 8;
+
+/// Serialized compiled modules keyed by a blake3 hash of their wasm bytes
+pub struct ModuleCache {
+    entries: FxHashMap<blake3::Hash, Vec<u8>>,
+    /// oldest first, evicted once there are more than `max_entries`
+    order: VecDeque<blake3::Hash>,
+    max_entries: usize,
+    /// Where compiled modules are also written to, as `<hash>.cwasm`
+    dir: Option<PathBuf>,
+    compilations: u64,
+}
+
+impl Default for ModuleCache {
+    fn default() -> Self {
+        Self {
+            entries: FxHashMap::default(),
+            order: VecDeque::new(),
+            max_entries: DEFAULT_MODULE_CACHE_ENTRIES,
+            dir: None,
+            compilations: 0,
+        }
+    }
+}
+
+impl ModuleCache {
+    /// Returns the module compiled from `wasm`, only compiling it if it isn't cached yet.
+    /// A cached module built with a different engine configuration is compiled again.
+    pub fn get_or_compile<Ext: ExternalFunctions>(
+        &mut self,
+        engine: &Engine,
+        wasm: &[u8],
+    ) -> Result<Module> {
+        let hash = blake3::hash(wasm);
+        let cached = self
+            .entries
+            .get(&hash)
+            .cloned()
+            .or_else(|| self.read_disk(&hash));
+        if let Some(compiled) = cached {
+            // SAFETY: the bytes come from `Module::serialize`, either in this cache or a file
+            // in the host's cache dir, and wasmtime rejects ones from an incompatible engine
+            if let Ok(module) = unsafe { Module::deserialize(engine, &compiled) } {
+                self.insert(hash, compiled);
+                return Ok(module);
+            }
+        }
+
+        let module = Module::new(engine, wasm)?;
+        self.compilations += 1;
+        let compiled = module.serialize()?;
+        if let Some(dir) = &self.dir {
+            let path = dir.join(format!("{}.cwasm", hash.to_hex()));
+            if let Err(e) = fs::create_dir_all(dir).and_then(|_| fs::write(&path, &compiled)) {
+                Ext::log_warn(format!(
+                    "Failed to write compiled module to {}: {e}",
+                    path.display()
+                ));
+            }
+        }
+        self.insert(hash, compiled);
+        Ok(module)
+    }
+
+    fn read_disk(&self, hash: &blake3::Hash) -> Option<Vec<u8>> {
+        let dir = self.dir.as_ref()?;
+        fs::read(dir.join(format!("{}.cwasm", hash.to_hex()))).ok()
+    }
+
+    fn insert(&mut self, hash: blake3::Hash, compiled: Vec<u8>) {
+        if self.entries.insert(hash, compiled).is_some() {
+            self.order.retain(|h| *h != hash);
+        }
+        self.order.push_back(hash);
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.order.len() > self.max_entries {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    /// Also keeps compiled modules in `dir`, so they survive the host restarting.
+    /// Files in it are loaded as trusted compiled code, it must not be writable by mods.
+    pub fn set_dir(&mut self, dir: Option<PathBuf>) {
+        self.dir = dir;
+    }
+
+    /// Applies immediately, evicting the oldest modules past the new limit
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries;
+        self.evict();
+    }
+
+    /// How many modules had to be compiled rather than taken from the cache
+    pub fn compilations(&self) -> u64 {
+        self.compilations
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drops every module kept in memory, files in the cache dir are left alone
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
@@ -7,11 +7,15 @@ use wasmtime::ValType;
 use wasmtime_wasi::p1::WasiP1Ctx;
 
 use crate::EngineDataState;
+use crate::engine::NO_MEMORY_MESSAGE;
+use crate::engine::wasm_engine::host_helpers::get_f32_vec;
 use crate::engine::wasm_engine::host_helpers::get_u32_vec;
 use crate::engine::wasm_engine::host_helpers::get_wasm_string;
+use crate::interop::params::INTERIOR_NUL_MESSAGE;
 use crate::interop::params::ObjectId;
 use crate::interop::params::Param;
 use crate::interop::params::Params;
+use crate::objects::UNKNOWN_OBJECT_MESSAGE;
 
 use wasmtime::StoreContext;
 
@@ -58,6 +62,8 @@ impl DataType {
             | DataType::U8
             | DataType::U16
             | DataType::U32
+            | DataType::Flags
+            | DataType::Char
             | DataType::Bool
             | DataType::RustString
             | DataType::ExtString
@@ -70,9 +76,15 @@ impl DataType {
             | DataType::RustMat4
             | DataType::ExtMat4
             | DataType::ExtU32Buffer
-            | DataType::RustU32Buffer => Ok(ValType::I32),
+            | DataType::RustU32Buffer
+            | DataType::ExtF32Buffer
+            | DataType::RustF32Buffer
+            | DataType::RustJson
+            | DataType::ExtJson => Ok(ValType::I32),
 
-            DataType::I64 | DataType::U64 | DataType::Object => Ok(ValType::I64),
+            DataType::I64 | DataType::U64 | DataType::Object | DataType::OwnedObject => {
+                Ok(ValType::I64)
+            }
 
             DataType::F32 => Ok(ValType::F32),
             DataType::F64 => Ok(ValType::F64),
@@ -90,9 +102,15 @@ impl Param {
         typ: DataType,
         val: Val,
         data: &Arc<RwLock<EngineDataState>>,
-        memory: &Memory,
+        memory: Option<&Memory>,
         caller: &StoreContext<WasiP1Ctx>,
     ) -> Self {
+        // only strings and buffers read guest memory, so memory-less modules can still pass numbers
+        let memory = || {
+            memory
+                .map(|m| m.data(caller))
+                .ok_or_else(|| anyhow!(NO_MEMORY_MESSAGE))
+        };
         match (typ, val) {
             (DataType::I8, Val::I32(i)) => Param::I8(i as i8),
             (DataType::I16, Val::I32(i)) => Param::I16(i as i16),
@@ -101,20 +119,42 @@ impl Param {
             (DataType::U8, Val::I32(u)) => Param::U8(u as u8),
             (DataType::U16, Val::I32(u)) => Param::U16(u as u16),
             (DataType::U32, Val::I32(u)) => Param::U32(u as u32),
+            (DataType::Flags, Val::I32(u)) => Param::Flags(u as u32),
+            (DataType::Char, Val::I32(c)) => Param::char_from_code(c as u32),
             (DataType::U64, Val::I64(u)) => Param::U64(u as u64),
             (DataType::F32, Val::F32(f)) => Param::F32(f32::from_bits(f)),
             (DataType::F64, Val::F64(f)) => Param::F64(f64::from_bits(f)),
             (DataType::Bool, Val::I32(b)) => Param::Bool(b != 0),
             (DataType::RustString | DataType::ExtString, Val::I32(ptr)) => {
                 let ptr = ptr as u32;
-                let st = get_wasm_string(ptr, memory.data(caller));
-                Param::String(st)
+                match memory().and_then(|m| get_wasm_string(ptr, m)) {
+                    Ok(st) => Param::String(st),
+                    Err(e) => Param::Error(format!("{e}")),
+                }
+            }
+            // json crosses as a utf-8 string, so guests need no json library of their own
+            (DataType::RustJson | DataType::ExtJson, Val::I32(ptr)) => {
+                let ptr = ptr as u32;
+                match memory().and_then(|m| get_wasm_string(ptr, m)) {
+                    Ok(st) => match serde_json::from_str(&st) {
+                        Ok(json) => Param::Json(json),
+                        Err(e) => Param::Error(format!("invalid json from wasm: {e}")),
+                    },
+                    Err(e) => Param::Error(format!("{e}")),
+                }
+            }
+            (DataType::Object | DataType::OwnedObject, Val::I64(op)) => {
+                match data.read().objects.to_host(op as u64) {
+                    Some(id) => Param::object(id),
+                    None => Param::Error(format!("{UNKNOWN_OBJECT_MESSAGE}: {op}")),
+                }
             }
-            (DataType::Object, Val::I64(op)) => Param::Object(ObjectId::new(op as u64)),
             (DataType::RustError | DataType::ExtError, Val::I32(ptr)) => {
                 let ptr = ptr as u32;
-                let st = get_wasm_string(ptr, memory.data(caller));
-                Param::Error(format!("WASM Error: {}", st))
+                match memory().and_then(|m| get_wasm_string(ptr, m)) {
+                    Ok(st) => Param::Error(format!("WASM Error: {}", st)),
+                    Err(e) => Param::Error(format!("{e}")),
+                }
             }
             (DataType::Void, _) => Param::Void,
 
@@ -127,8 +167,23 @@ impl Param {
             }
             (DataType::RustU32Buffer | DataType::ExtU32Buffer, Val::I32(ptr)) => {
                 let ptr = ptr as u32;
-                let len = data.write().f32_queue.pop_front().unwrap().to_bits();
-                Param::U32Buffer(get_u32_vec(ptr, len, memory.data(caller)).unwrap())
+                let Some(len) = data.write().f32_queue.pop_front() else {
+                    return Param::Error("missing u32 buffer length".to_string());
+                };
+                match memory().and_then(|m| get_u32_vec(ptr, len.to_bits(), m)) {
+                    Ok(buf) => Param::U32Buffer(buf),
+                    Err(e) => Param::Error(format!("{e}")),
+                }
+            }
+            (DataType::RustF32Buffer | DataType::ExtF32Buffer, Val::I32(ptr)) => {
+                let ptr = ptr as u32;
+                let Some(len) = data.write().f32_queue.pop_front() else {
+                    return Param::Error("missing f32 buffer length".to_string());
+                };
+                match memory().and_then(|m| get_f32_vec(ptr, len.to_bits(), m)) {
+                    Ok(buf) => Param::F32Buffer(buf),
+                    Err(e) => Param::Error(format!("{e}")),
+                }
             }
             // Fallback: if the Val doesn't match the expected variant, return an error Param
             _ => Param::Error(format!(
@@ -139,14 +194,13 @@ impl Param {
     }
 
     pub fn into_wasm_val(self, data: &Arc<RwLock<EngineDataState>>) -> Result<Option<Val>> {
-        let mut s = data.write();
         macro_rules! enqueue {
             ( $v:tt ; $sz:tt ) => {{
-                s.f32_queue.append(&mut $v.to_array().into());
+                data.write().f32_queue.extend($v.to_array());
                 Val::I32($sz)
             }};
             ($m:tt # $sz:tt) => {{
-                s.f32_queue.append(&mut $m.to_cols_array().into());
+                data.write().f32_queue.extend($m.to_cols_array());
                 Val::I32($sz)
             }};
         }
@@ -158,26 +212,26 @@ impl Param {
             Param::U8(u) => Val::I32(u as i32),
             Param::U16(u) => Val::I32(u as i32),
             Param::U32(u) => Val::I32(u as i32),
+            Param::Flags(u) => Val::I32(u as i32),
+            Param::Char(c) => Val::I32(c as i32),
             Param::U64(u) => Val::I64(u as i64),
             Param::F32(f) => Val::F32(f.to_bits()),
             Param::F64(f) => Val::F64(f.to_bits()),
             Param::Bool(b) => Val::I32(if b { 1 } else { 0 }),
             Param::String(st) => {
+                if st.contains('\0') {
+                    return Err(anyhow!(INTERIOR_NUL_MESSAGE));
+                }
                 let l = st.len() + 1;
-                s.str_cache.push_back(st);
+                data.write().str_cache.push_back(st);
                 Val::I32(l as i32)
             }
             Param::Error(er) => {
                 return Err(anyhow!("Error executing host function: {}", er));
             }
-            Param::Object(pointer) => {
-                if pointer.is_null() {
-                    // reserved value for null pointers
-                    return Ok(Some(Val::I64(pointer.as_ffi() as i64)));
-                }
-
-                Val::I64(pointer.as_ffi() as i64)
-            }
+            Param::Object(id) => Val::I64(data.write().objects.to_script(id) as i64),
+            // reserved value for null pointers
+            Param::Null => Val::I64(ObjectId::null().as_ffi() as i64),
             Param::Void => return Ok(None),
             Param::Vec2(v) => enqueue!(v; 2),
             Param::Vec3(v) => enqueue!(v; 3),
@@ -186,72 +240,136 @@ impl Param {
             Param::Mat4(m) => enqueue!(m # 16),
             Param::U32Buffer(v) => {
                 let l = v.len();
-                s.u32_buffer_queue.push_back(v);
+                data.write().u32_buffer_queue.push_back(v);
+                Val::I32(l as i32)
+            }
+            Param::F32Buffer(v) => {
+                let l = v.len();
+                data.write().f32_buffer_queue.push_back(v);
+                Val::I32(l as i32)
+            }
+            // serialized json escapes nul bytes, so it can always be staged as a string
+            Param::Json(v) => {
+                let st = v.to_string();
+                let l = st.len() + 1;
+                data.write().str_cache.push_back(st);
                 Val::I32(l as i32)
             }
         }))
     }
 }
 
-impl Params {
-    /// Converts the Params into a vector of Wasmtime Val types for function calling.
-    pub fn to_wasm_args(self, data: &Arc<RwLock<EngineDataState>>) -> Result<SmallVec<[Val; 4]>> {
-        // Acquire a single write lock for the duration of conversion to avoid
-        // repeated locking/unlocking when pushing strings or registering objects.
-        if self.is_empty() {
-            return Ok(SmallVec::default());
-        }
-
-        let mut s = data.write();
+impl Param {
+    /// Converts an argument for a call into wasm.
+    /// Only params that need staging (strings, vectors, buffers) take the data lock,
+    /// once per push, so nothing that runs while converting can deadlock on it.
+    fn into_wasm_arg(self, data: &Arc<RwLock<EngineDataState>>) -> Result<Val> {
         macro_rules! enqueue {
             ( $v:tt ; $sz:tt ) => {{
-                s.f32_queue.append(&mut $v.to_array().into());
+                data.write().f32_queue.extend($v.to_array());
                 Ok(Val::I32($sz))
             }};
             ($m:tt # $sz:tt) => {{
-                s.f32_queue.append(&mut $m.to_cols_array().into());
+                data.write().f32_queue.extend($m.to_cols_array());
                 Ok(Val::I32($sz))
             }};
         }
 
+        match self {
+            Param::I8(i) => Ok(Val::I32(i as i32)),
+            Param::I16(i) => Ok(Val::I32(i as i32)),
+            Param::I32(i) => Ok(Val::I32(i)),
+            Param::I64(i) => Ok(Val::I64(i)),
+            Param::U8(u) => Ok(Val::I32(u as i32)),
+            Param::U16(u) => Ok(Val::I32(u as i32)),
+            Param::U32(u) => Ok(Val::I32(u as i32)),
+            Param::Flags(u) => Ok(Val::I32(u as i32)),
+            Param::Char(c) => Ok(Val::I32(c as i32)),
+            Param::U64(u) => Ok(Val::I64(u as i64)),
+            Param::F32(f) => Ok(Val::F32(f.to_bits())),
+            Param::F64(f) => Ok(Val::F64(f.to_bits())),
+            Param::Bool(b) => Ok(Val::I32(if b { 1 } else { 0 })),
+            Param::String(st) => {
+                if st.contains('\0') {
+                    return Err(anyhow!(INTERIOR_NUL_MESSAGE));
+                }
+                let l = st.len() + 1;
+                data.write().str_cache.push_back(st);
+                Ok(Val::I32(l as i32))
+            }
+            Param::Object(id) => Ok(Val::I64(data.write().objects.to_script(id) as i64)),
+            Param::Null => Ok(Val::I64(ObjectId::null().as_ffi() as i64)),
+            Param::Error(st) => Err(anyhow!("{st}")),
+            Param::Void => unreachable!("Void shouldn't ever be added as an arg"),
+            Param::Vec2(v) => enqueue!(v; 2),
+            Param::Vec3(v) => enqueue!(v; 3),
+            Param::Vec4(v) => enqueue!(v; 4),
+            Param::Quat(q) => enqueue!(q; 4),
+            Param::Mat4(m) => enqueue!(m # 16),
+            Param::U32Buffer(v) => {
+                let l = v.len();
+                data.write().u32_buffer_queue.push_back(v);
+                Ok(Val::I32(l as i32))
+            }
+            Param::F32Buffer(v) => {
+                let l = v.len();
+                data.write().f32_buffer_queue.push_back(v);
+                Ok(Val::I32(l as i32))
+            }
+            Param::Json(v) => {
+                let st = v.to_string();
+                let l = st.len() + 1;
+                data.write().str_cache.push_back(st);
+                Ok(Val::I32(l as i32))
+            }
+        }
+    }
+}
+
+impl Params {
+    /// Converts the Params into a vector of Wasmtime Val types for function calling.
+    pub fn to_wasm_args(self, data: &Arc<RwLock<EngineDataState>>) -> Result<SmallVec<[Val; 4]>> {
         self.params
             .into_iter()
-            .map(|p| match p {
-                Param::I8(i) => Ok(Val::I32(i as i32)),
-                Param::I16(i) => Ok(Val::I32(i as i32)),
-                Param::I32(i) => Ok(Val::I32(i)),
-                Param::I64(i) => Ok(Val::I64(i)),
-                Param::U8(u) => Ok(Val::I32(u as i32)),
-                Param::U16(u) => Ok(Val::I32(u as i32)),
-                Param::U32(u) => Ok(Val::I32(u as i32)),
-                Param::U64(u) => Ok(Val::I64(u as i64)),
-                Param::F32(f) => Ok(Val::F32(f.to_bits())),
-                Param::F64(f) => Ok(Val::F64(f.to_bits())),
-                Param::Bool(b) => Ok(Val::I32(if b { 1 } else { 0 })),
-                Param::String(st) => {
-                    let l = st.len() + 1;
-                    s.str_cache.push_back(st);
-                    Ok(Val::I32(l as i32))
-                }
-                Param::Object(rp) => Ok(Val::I64(rp.as_ffi() as i64)),
-                Param::Error(st) => Err(anyhow!("{st}")),
-                Param::Void => unreachable!("Void shouldn't ever be added as an arg"),
-                Param::Vec2(v) => enqueue!(v; 2),
-                Param::Vec3(v) => enqueue!(v; 3),
-                Param::Vec4(v) => enqueue!(v; 4),
-                Param::Quat(q) => enqueue!(q; 4),
-                Param::Mat4(m) => enqueue!(m # 16),
-                Param::U32Buffer(v) => {
-                    let l = v.len();
-                    s.u32_buffer_queue.push_back(v);
-                    Ok(Val::I32(l as i32))
-                }
-            })
+            .map(|p| p.into_wasm_arg(data))
             .collect()
     }
+
+    /// Like `to_wasm_args`, but moves the params into `args` instead of allocating, leaving
+    /// this `Params` empty with its capacity intact. Buffers kept across calls stop allocating
+    /// once they've grown to the largest call.
+    pub fn drain_into_wasm_args(
+        &mut self,
+        data: &Arc<RwLock<EngineDataState>>,
+        args: &mut Vec<Val>,
+    ) -> Result<()> {
+        args.clear();
+        for p in self.params.drain(..) {
+            args.push(p.into_wasm_arg(data)?);
+        }
+        Ok(())
+    }
 }
 
 impl DataType {
+    /// Returns true if values of this type are read from or written to guest memory
+    /// when crossing into or out of wasm
+    pub fn needs_wasm_memory(&self) -> bool {
+        matches!(
+            self,
+            DataType::RustString
+                | DataType::ExtString
+                | DataType::RustJson
+                | DataType::ExtJson
+                | DataType::RustError
+                | DataType::ExtError
+                | DataType::RustU32Buffer
+                | DataType::ExtU32Buffer
+                | DataType::RustF32Buffer
+                | DataType::ExtF32Buffer
+        )
+    }
+
     /// Returns true if this Param can be directly represented as a simple WASM value (i32, i64, f32, f64),
     ///  meaning it can be passed to and from WASM without any special handling or conversion.
     pub fn is_wasm_simple(&self) -> bool {
@@ -264,6 +382,8 @@ impl DataType {
                 | DataType::U8
                 | DataType::U16
                 | DataType::U32
+                | DataType::Flags
+                | DataType::Char
                 | DataType::U64
                 | DataType::F32
                 | DataType::F64
@@ -1,157 +1,186 @@
-use std::sync::Arc;
-
-use anyhow::{Result, bail};
-use parking_lot::RwLock;
-use wasmtime::{Func, Store, TypedFunc};
+use anyhow::Result;
+use wasmtime::{Func, Store, TypedFunc, Val, ValType, WasmParams, WasmResults, WasmTy};
 use wasmtime_wasi::p1::WasiP1Ctx;
 
-use crate::{
-    EngineDataState,
-    interop::params::{ObjectId, Param, Params},
-};
-
-pub enum TypedFuncEntry {
-    NoParamsVoid(TypedFunc<(), ()>),
-    NoParamsI32(TypedFunc<(), i32>),
-    NoParamsI64(TypedFunc<(), i64>),
-    NoParamsObject(TypedFunc<(), u64>),
-    NoParamsF32(TypedFunc<(), f32>),
-    NoParamsF64(TypedFunc<(), f64>),
-    // update and fixed update
-    F32ToVoid(TypedFunc<f32, ()>),
-
-    I32ToI32(TypedFunc<i32, i32>),
-    I64ToI64(TypedFunc<i64, i64>),
-    F32ToF32(TypedFunc<f32, f32>),
-    F64ToF64(TypedFunc<f64, f64>),
-    I32I32ToI32(TypedFunc<(i32, i32), i32>),
+use crate::interop::params::DataType;
+
+type TypedInvoke =
+    dyn Fn(&mut Store<WasiP1Ctx>, &[Val]) -> Option<Result<Option<Val>>> + Send + Sync;
+
+/// A `TypedFunc` for an export whose signature only uses i32/i64/f32/f64,
+/// so calls skip wasmtime's dynamic type checks. Args still arrive as `Val`s.
+/// Covers every combination of up to 2 params, plus 3 and 4 params of a single type,
+/// see `WasmInterpreter::has_typed_call`.
+pub struct TypedFuncEntry {
+    invoke: Box<TypedInvoke>,
+    /// None for void functions
+    result: Option<ValType>,
 }
 
-impl TypedFuncEntry {
-    pub fn invoke(
-        &self,
-        store: &mut Store<WasiP1Ctx>,
-        args: Params,
-        _data: &Arc<RwLock<EngineDataState>>,
-    ) -> Result<Param, wasmtime::Error> {
-        let get_object = |id: u64| -> Result<Param> { Ok(Param::Object(ObjectId::new(id))) };
-
-        match self {
-            TypedFuncEntry::NoParamsVoid(t) => t.call(store, ()).map(|_| Param::Void),
-            TypedFuncEntry::NoParamsObject(t) => t.call(store, ()).and_then(get_object),
-            TypedFuncEntry::NoParamsI32(t) => t.call(store, ()).map(Param::I32),
-            TypedFuncEntry::NoParamsI64(t) => t.call(store, ()).map(Param::I64),
-            TypedFuncEntry::NoParamsF32(t) => t.call(store, ()).map(Param::F32),
-            TypedFuncEntry::NoParamsF64(t) => t.call(store, ()).map(Param::F64),
-            TypedFuncEntry::I32ToI32(t) => {
-                if args.len() != 1 {
-                    bail!("Arg length `{}` != 1", args.len())
-                }
-                let a0 = match &args[0] {
-                    Param::I32(v) => *v,
-                    _ => bail!("Arg conversion {:?} -> i32 failed", args[0]),
-                };
-                t.call(store, a0).map(Param::I32)
-            }
-            TypedFuncEntry::I64ToI64(t) => {
-                if args.len() != 1 {
-                    bail!("Arg length `{}` != 1", args.len())
-                }
-                let a0 = match &args[0] {
-                    Param::I64(v) => *v,
-                    _ => bail!("Arg conversion {:?} -> i64 failed", args[0]),
-                };
-                t.call(store, a0).map(Param::I64)
-            }
-            TypedFuncEntry::F32ToF32(t) => {
-                if args.len() != 1 {
-                    bail!("Arg mismatch")
-                }
-                let a0 = match &args[0] {
-                    Param::F32(v) => *v,
-                    _ => bail!("Arg conversion {:?} -> f32 failed", args[0]),
-                };
-                t.call(store, a0).map(Param::F32)
-            }
-            TypedFuncEntry::F32ToVoid(typed_func) => {
-                if args.len() != 1 {
-                    bail!("Arg mismatch")
-                }
-                let a0 = match &args[0] {
-                    Param::F32(v) => *v,
-                    _ => bail!("Arg conversion {:?} -> f32 failed", args[0]),
-                };
-                typed_func.call(store, a0).map(|_| Param::Void)
-            }
-            TypedFuncEntry::F64ToF64(t) => {
-                if args.len() != 1 {
-                    bail!("Arg mismatch")
-                }
-                let a0 = match &args[0] {
-                    Param::F64(v) => *v,
-                    _ => bail!("Arg conversion {:?} -> f64 failed", args[0]),
-                };
-                t.call(store, a0).map(Param::F64)
-            }
-            TypedFuncEntry::I32I32ToI32(t) => {
-                if args.len() != 2 {
-                    bail!("Arg mismatch")
-                }
-                let a0 = match &args[0] {
-                    Param::I32(v) => *v,
-                    _ => bail!("Arg conversion {:?} -> i32 failed", args[0]),
-                };
-                let a1 = match &args[1] {
-                    Param::I32(v) => *v,
-                    _ => bail!("Arg conversion {:?} -> i32 failed", args[1]),
+/// A scalar wasm param
+trait WasmArg: WasmTy {
+    fn from_val(val: &Val) -> Option<Self>;
+}
+
+impl WasmArg for i32 {
+    fn from_val(val: &Val) -> Option<Self> {
+        val.i32()
+    }
+}
+
+impl WasmArg for i64 {
+    fn from_val(val: &Val) -> Option<Self> {
+        val.i64()
+    }
+}
+
+impl WasmArg for f32 {
+    fn from_val(val: &Val) -> Option<Self> {
+        val.f32()
+    }
+}
+
+impl WasmArg for f64 {
+    fn from_val(val: &Val) -> Option<Self> {
+        val.f64()
+    }
+}
+
+/// A tuple of scalar wasm params
+trait WasmArgs: WasmParams {
+    fn from_vals(vals: &[Val]) -> Option<Self>;
+}
+
+macro_rules! wasm_args {
+    ($($arg:ident $val:ident),*) => {
+        impl<$($arg: WasmArg),*> WasmArgs for ($($arg,)*) {
+            fn from_vals(vals: &[Val]) -> Option<Self> {
+                let [$($val),*] = vals else {
+                    return None;
                 };
-                t.call(store, (a0, a1)).map(Param::I32)
+                Some(($($arg::from_val($val)?,)*))
             }
         }
+    };
+}
+
+wasm_args!();
+wasm_args!(A a);
+wasm_args!(A a, B b);
+wasm_args!(A a, B b, C c);
+wasm_args!(A a, B b, C c, D d);
+
+/// A scalar wasm result, or none
+trait WasmRet: WasmResults {
+    const RESULT: Option<ValType>;
+    fn into_val(self) -> Option<Val>;
+}
+
+impl WasmRet for () {
+    const RESULT: Option<ValType> = None;
+    fn into_val(self) -> Option<Val> {
+        None
     }
+}
 
-    pub fn from_func(store: &mut Store<WasiP1Ctx>, func: Func) -> Option<Self> {
-        // try 0 params
-        if let Ok(t) = func.typed::<(), ()>(&store) {
-            return Some(TypedFuncEntry::NoParamsVoid(t));
-        }
-        if let Ok(t) = func.typed::<(), i32>(&store) {
-            return Some(TypedFuncEntry::NoParamsI32(t));
-        }
-        if let Ok(t) = func.typed::<(), i64>(&store) {
-            return Some(TypedFuncEntry::NoParamsI64(t));
-        }
-        if let Ok(t) = func.typed::<(), f32>(&store) {
-            return Some(TypedFuncEntry::NoParamsF32(t));
-        }
-        if let Ok(t) = func.typed::<(), f64>(&store) {
-            return Some(TypedFuncEntry::NoParamsF64(t));
-        }
+macro_rules! wasm_ret {
+    ($($ty:ident => $val_type:ident),*) => {
+        $(impl WasmRet for $ty {
+            const RESULT: Option<ValType> = Some(ValType::$val_type);
+            fn into_val(self) -> Option<Val> {
+                Some(Val::from(self))
+            }
+        })*
+    };
+}
 
-        // 1 param -> same-typed returns
-        if let Ok(t) = func.typed::<f32, ()>(&store) {
-            return Some(TypedFuncEntry::F32ToVoid(t));
-        }
+wasm_ret!(i32 => I32, i64 => I64, f32 => F32, f64 => F64);
 
-        if let Ok(t) = func.typed::<i32, i32>(&store) {
-            return Some(TypedFuncEntry::I32ToI32(t));
-        }
-        if let Ok(t) = func.typed::<i64, i64>(&store) {
-            return Some(TypedFuncEntry::I64ToI64(t));
-        }
-        if let Ok(t) = func.typed::<f32, f32>(&store) {
-            return Some(TypedFuncEntry::F32ToF32(t));
-        }
-        if let Ok(t) = func.typed::<f64, f64>(&store) {
-            return Some(TypedFuncEntry::F64ToF64(t));
+/// Tries each param tuple with every result type, returning the first that matches the export
+macro_rules! try_signatures {
+    ($store:ident, $func:ident; $(($($arg:ty),* $(,)?))*) => {{
+        $(
+            try_signatures!(@rets $store, $func; ($($arg,)*); (), i32, i64, f32, f64);
+        )*
+    }};
+    (@rets $store:ident, $func:ident; $args:ty; $($ret:ty),*) => {
+        $(
+            if let Some(entry) = TypedFuncEntry::typed::<$args, $ret>($store, $func) {
+                return Some(entry);
+            }
+        )*
+    };
+}
+
+impl TypedFuncEntry {
+    fn typed<P: WasmArgs + 'static, R: WasmRet + 'static>(
+        store: &mut Store<WasiP1Ctx>,
+        func: Func,
+    ) -> Option<Self> {
+        let typed: TypedFunc<P, R> = func.typed(&store).ok()?;
+        Some(Self {
+            invoke: Box::new(move |store: &mut Store<WasiP1Ctx>, args: &[Val]| {
+                let args = P::from_vals(args)?;
+                Some(typed.call(store, args).map(R::into_val))
+            }),
+            result: R::RESULT,
+        })
+    }
+
+    pub fn from_func(store: &mut Store<WasiP1Ctx>, func: Func) -> Option<Self> {
+        let ty = func.ty(&store);
+        if ty.results().len() > 1 {
+            return None;
         }
 
-        // 2 params (i32,i32)->i32
-        if let Ok(t) = func.typed::<(i32, i32), i32>(&store) {
-            return Some(TypedFuncEntry::I32I32ToI32(t));
+        // only try signatures with the right arity
+        match ty.params().len() {
+            0 => try_signatures!(store, func; ()),
+            1 => try_signatures!(store, func; (i32,) (i64,) (f32,) (f64,)),
+            2 => try_signatures!(store, func;
+                (i32, i32) (i32, i64) (i32, f32) (i32, f64)
+                (i64, i32) (i64, i64) (i64, f32) (i64, f64)
+                (f32, i32) (f32, i64) (f32, f32) (f32, f64)
+                (f64, i32) (f64, i64) (f64, f32) (f64, f64)
+            ),
+            3 => try_signatures!(store, func;
+                (i32, i32, i32) (i64, i64, i64) (f32, f32, f32) (f64, f64, f64)
+            ),
+            4 => try_signatures!(store, func;
+                (i32, i32, i32, i32) (i64, i64, i64, i64) (f32, f32, f32, f32) (f64, f64, f64, f64)
+            ),
+            _ => {}
         }
 
         // Not a supported typed signature
         None
     }
+
+    /// Whether the export returns the wasm type `ret_type` is passed as,
+    /// so the typed path gives the same result as a dynamic call
+    pub fn returns(&self, ret_type: DataType) -> bool {
+        let Some(result) = &self.result else {
+            return ret_type == DataType::Void;
+        };
+        ret_type != DataType::Void
+            && ret_type.to_val_type().is_ok_and(|expected| {
+                matches!(
+                    (result, expected),
+                    (ValType::I32, ValType::I32)
+                        | (ValType::I64, ValType::I64)
+                        | (ValType::F32, ValType::F32)
+                        | (ValType::F64, ValType::F64)
+                )
+            })
+    }
+
+    /// Calls the export with already converted args.
+    /// Returns None if the args don't fit the signature, so the caller can fall back to a dynamic call.
+    pub fn invoke(
+        &self,
+        store: &mut Store<WasiP1Ctx>,
+        args: &[Val],
+    ) -> Option<Result<Option<Val>>> {
+        (self.invoke)(store, args)
+    }
 }
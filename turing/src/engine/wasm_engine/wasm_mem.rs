@@ -0,0 +1,114 @@
+//! Bounds-checked access to guest memory.
+//!
+//! Pointers and lengths coming from a guest can't be trusted, so every range is checked
+//! against the size of the memory slice (`memory.data(store)`) and bad ones are errors
+//! instead of host panics.
+
+use std::ffi::CStr;
+
+use anyhow::{Result, anyhow};
+
+/// Returns the `len` bytes at `ptr`
+pub fn read_bytes(data: &[u8], ptr: u32, len: u32) -> Result<&[u8]> {
+    let start = ptr as usize;
+    start
+        .checked_add(len as usize)
+        .and_then(|end| data.get(start..end))
+        .ok_or_else(|| {
+            anyhow!(
+                "range at {ptr} with length {len} is outside of wasm memory ({} bytes)",
+                data.len()
+            )
+        })
+}
+
+/// Reads the nul-terminated string at `ptr`, which may be at most `max_len` bytes long
+/// without its nul. Invalid UTF-8 is replaced with U+FFFD.
+pub fn read_cstr(data: &[u8], ptr: u32, max_len: usize) -> Result<String> {
+    let Some(rest) = data.get(ptr as usize..) else {
+        return Err(anyhow!(
+            "string pointer {ptr} is outside of wasm memory ({} bytes)",
+            data.len()
+        ));
+    };
+    let window = &rest[..rest.len().min(max_len.saturating_add(1))];
+    let c = CStr::from_bytes_until_nul(window).map_err(|_| {
+        if window.len() < rest.len() {
+            anyhow!("string at {ptr} is longer than {max_len} bytes")
+        } else {
+            anyhow!("string at {ptr} is not nul-terminated before the end of wasm memory")
+        }
+    })?;
+    Ok(c.to_string_lossy().into_owned())
+}
+
+/// Copies `bytes` to `ptr`, where the guest says it allocated `guest_alloc_len` bytes.
+/// Nothing is written if `bytes` doesn't fit the allocation or the allocation isn't in memory.
+pub fn write_bytes_checked(
+    data: &mut [u8],
+    ptr: u32,
+    bytes: &[u8],
+    guest_alloc_len: u32,
+) -> Result<()> {
+    if bytes.len() > guest_alloc_len as usize {
+        return Err(anyhow!(
+            "{} bytes don't fit the {guest_alloc_len} bytes allocated at {ptr}",
+            bytes.len()
+        ));
+    }
+    let size = data.len();
+    let start = ptr as usize;
+    let Some(dest) = start
+        .checked_add(guest_alloc_len as usize)
+        .and_then(|end| data.get_mut(start..end))
+    else {
+        return Err(anyhow!(
+            "allocation at {ptr} with length {guest_alloc_len} is outside of wasm memory ({size} bytes)"
+        ));
+    };
+    dest[..bytes.len()].copy_from_slice(bytes);
+    Ok(())
+}
+
+#[cfg(test)]
+mod wasm_mem_tests {
+    use super::{read_bytes, read_cstr, write_bytes_checked};
+
+    #[test]
+    fn test_read_bytes_bounds() {
+        let data = b"0123456789";
+        assert_eq!(read_bytes(data, 0, 3).unwrap(), b"012");
+        assert_eq!(read_bytes(data, 7, 3).unwrap(), b"789");
+        assert_eq!(read_bytes(data, 10, 0).unwrap(), b"");
+        assert!(read_bytes(data, 8, 3).is_err());
+        assert!(read_bytes(data, 11, 0).is_err());
+        assert!(read_bytes(data, u32::MAX, u32::MAX).is_err());
+    }
+
+    #[test]
+    fn test_read_cstr_max_len() {
+        let data = b"abc\0defgh\0";
+        assert_eq!(read_cstr(data, 0, 3).unwrap(), "abc");
+        assert_eq!(read_cstr(data, 4, usize::MAX).unwrap(), "defgh");
+        let err = read_cstr(data, 4, 4).unwrap_err();
+        assert!(err.to_string().contains("longer than 4 bytes"));
+        assert_eq!(read_cstr(data, 9, 0).unwrap(), "");
+        assert!(read_cstr(data, 10, usize::MAX).is_err());
+        assert!(read_cstr(b"abc", 0, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_write_bytes_checked() {
+        let mut data = [0u8; 8];
+        write_bytes_checked(&mut data, 2, b"hi", 4).unwrap();
+        assert_eq!(&data, b"\0\0hi\0\0\0\0");
+
+        // the guest allocated less than it's sent
+        assert!(write_bytes_checked(&mut data, 0, b"long", 2).is_err());
+        // the allocation runs past the end of memory, even though the bytes would fit
+        assert!(write_bytes_checked(&mut data, 6, b"x", 4).is_err());
+        assert!(write_bytes_checked(&mut data, 8, b"", 0).is_ok());
+        assert!(write_bytes_checked(&mut data, u32::MAX, b"x", u32::MAX).is_err());
+        assert_eq!(&data, b"\0\0hi\0\0\0\0");
+    }
+}
@@ -5,33 +5,146 @@ use tokio::io::AsyncWrite;
 use wasmtime_wasi::cli::{IsTerminal, StdoutStream};
 
 use crate::ExternalFunctions;
+use crate::engine::prefix_log;
+
+/// Appended to a line that was cut off at the buffer limit
+pub const TRUNCATED_MARKER: &str = " [output truncated]";
+
+/// Output a guest wrote that doesn't end in a newline yet
+#[derive(Debug, Default)]
+pub struct LineBuffer {
+    pending: Vec<u8>,
+    /// The current line went over the limit and was already logged, drop the rest of it
+    truncated: bool,
+}
+
+impl LineBuffer {
+    /// Appends `buf` and returns every line it completed, without their line endings.
+    /// A line longer than `limit` bytes is returned cut off with [`TRUNCATED_MARKER`],
+    /// whether or not it's complete yet, and the rest of it is dropped.
+    pub fn push(&mut self, buf: &[u8], limit: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut rest = buf;
+        while let Some(end) = rest.iter().position(|b| *b == b'\n') {
+            if !self.truncated {
+                let line = self
+                    .append(&rest[..end], limit)
+                    .unwrap_or_else(|| self.take_line());
+                lines.push(line);
+            }
+            self.truncated = false;
+            rest = &rest[end + 1..];
+        }
+
+        if !self.truncated
+            && let Some(line) = self.append(rest, limit)
+        {
+            lines.push(line);
+            self.truncated = true;
+        }
+        lines
+    }
+
+    /// Buffers `bytes`, or returns the line cut off at `limit` if they don't fit
+    fn append(&mut self, bytes: &[u8], limit: usize) -> Option<String> {
+        let room = limit.saturating_sub(self.pending.len());
+        if bytes.len() <= room {
+            self.pending.extend_from_slice(bytes);
+            return None;
+        }
+        self.pending.extend_from_slice(&bytes[..room]);
+        let mut line = self.take_line();
+        line.push_str(TRUNCATED_MARKER);
+        Some(line)
+    }
+
+    /// The buffered partial line, if there is one
+    pub fn take_rest(&mut self) -> Option<String> {
+        self.truncated = false;
+        (!self.pending.is_empty()).then(|| self.take_line())
+    }
+
+    fn take_line(&mut self) -> String {
+        let mut line = std::mem::take(&mut self.pending);
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        String::from_utf8_lossy(&line).into_owned()
+    }
+}
+
+/// The line buffers behind a store's stdout and stderr, kept by the interpreter so a last line
+/// without a newline is still logged when the store goes away
+#[derive(Debug, Default)]
+pub struct GuestOutput {
+    stdout: Arc<RwLock<LineBuffer>>,
+    stderr: Arc<RwLock<LineBuffer>>,
+}
+
+impl GuestOutput {
+    /// Streams for a wasi context, writing into these buffers
+    pub fn writers<Ext: ExternalFunctions>(
+        &self,
+        prefix: Option<String>,
+        limit: usize,
+    ) -> (WriterInit<Ext>, WriterInit<Ext>) {
+        (
+            WriterInit(
+                self.stdout.clone(),
+                false,
+                prefix.clone(),
+                limit,
+                PhantomData,
+            ),
+            WriterInit(self.stderr.clone(), true, prefix, limit, PhantomData),
+        )
+    }
+
+    /// Logs the partial lines the guest never finished
+    pub fn flush<Ext: ExternalFunctions>(&self, prefix: Option<&str>) {
+        if let Some(rest) = self.stdout.write().take_rest() {
+            Ext::log_info(prefix_log(prefix, rest));
+        }
+        if let Some(rest) = self.stderr.write().take_rest() {
+            Ext::log_critical(prefix_log(prefix, rest));
+        }
+    }
+}
 
 pub struct OutputWriter<Ext: ExternalFunctions + Send> {
-    inner: Arc<RwLock<Vec<u8>>>,
+    inner: Arc<RwLock<LineBuffer>>,
     is_err: bool,
+    prefix: Option<String>,
+    limit: usize,
     _ext: PhantomData<Ext>,
 }
 
+impl<Ext: ExternalFunctions + Send> OutputWriter<Ext> {
+    /// Logs every line `buf` completes right away, so progressive output shows up as it's printed
+    fn write_lines(&self, buf: &[u8]) {
+        let lines = self.inner.write().push(buf, self.limit);
+        for line in lines {
+            self.log(line);
+        }
+    }
+
+    fn log(&self, line: String) {
+        let s = prefix_log(self.prefix.as_deref(), line);
+        if self.is_err {
+            Ext::log_critical(s);
+        } else {
+            Ext::log_info(s);
+        }
+    }
+}
+
 impl<Ext: ExternalFunctions + Send> std::io::Write for OutputWriter<Ext> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.inner.write().extend(buf);
+        self.write_lines(buf);
         Ok(buf.len())
     }
     fn flush(&mut self) -> std::io::Result<()> {
-        // Move the inner buffer out so we avoid an extra copy when converting
-        // bytes -> String. Taking the write lock lets us swap the Vec<u8>.
-        let vec = {
-            let mut guard = self.inner.write();
-            std::mem::take(&mut *guard)
-        };
-        if !vec.is_empty() {
-            let s = String::from_utf8_lossy(&vec).into_owned();
-            if self.is_err {
-                Ext::log_critical(s)
-            } else {
-                Ext::log_info(s);
-            }
-        }
+        // wasi flushes after every write, so partial lines stay buffered until their newline
         Ok(())
     }
 }
@@ -42,40 +155,34 @@ impl<Ext: ExternalFunctions + Send> AsyncWrite for OutputWriter<Ext> {
         _cx: &mut std::task::Context<'_>,
         buf: &[u8],
     ) -> Poll<std::result::Result<usize, std::io::Error>> {
-        self.inner.write().extend(buf);
+        self.write_lines(buf);
         Poll::Ready(Ok(buf.len()))
     }
     fn poll_flush(
         self: std::pin::Pin<&mut Self>,
         _cx: &mut std::task::Context<'_>,
     ) -> Poll<std::result::Result<(), std::io::Error>> {
-        // Move the inner buffer out so we avoid an extra copy when converting
-        // bytes -> String. Taking the write lock lets us swap the Vec<u8>.
-        let vec = {
-            let mut guard = self.inner.write();
-            std::mem::take(&mut *guard)
-        };
-        if !vec.is_empty() {
-            let s = String::from_utf8_lossy(&vec).into_owned();
-            if self.is_err {
-                Ext::log_critical(s);
-            } else {
-                Ext::log_info(s);
-            }
-        }
         Poll::Ready(Ok(()))
     }
     fn poll_shutdown(
         self: std::pin::Pin<&mut Self>,
         _cx: &mut std::task::Context<'_>,
     ) -> Poll<std::result::Result<(), std::io::Error>> {
+        let rest = self.inner.write().take_rest();
+        if let Some(rest) = rest {
+            self.log(rest);
+        }
         Poll::Ready(Ok(()))
     }
 }
 
 pub struct WriterInit<Ext: ExternalFunctions>(
-    pub Arc<RwLock<Vec<u8>>>,
+    pub Arc<RwLock<LineBuffer>>,
     pub bool,
+    /// see `EngineConfig::log_prefix`
+    pub Option<String>,
+    /// see `EngineConfig::wasm_output_buffer_bytes`
+    pub usize,
     pub PhantomData<Ext>,
 );
 
@@ -90,7 +197,55 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> StdoutStream for WriterInit
         Box::new(OutputWriter::<Ext> {
             inner: self.0.clone(),
             is_err: self.1,
+            prefix: self.2.clone(),
+            limit: self.3,
             _ext: PhantomData,
         })
     }
 }
+
+#[cfg(test)]
+mod writer_tests {
+    use super::{LineBuffer, TRUNCATED_MARKER};
+
+    #[test]
+    fn test_lines_split_across_writes() {
+        let mut buf = LineBuffer::default();
+        assert!(buf.push(b"Hel", 64).is_empty());
+        assert_eq!(buf.push(b"lo\nWor", 64), vec!["Hello"]);
+        assert_eq!(buf.push(b"ld\r\n\nlast", 64), vec!["World", ""]);
+        assert_eq!(buf.take_rest().as_deref(), Some("last"));
+        assert_eq!(buf.take_rest(), None);
+    }
+
+    #[test]
+    fn test_long_line_truncated() {
+        let mut buf = LineBuffer::default();
+        assert!(buf.push(b"abcd", 8).is_empty());
+        assert_eq!(
+            buf.push(b"efghijkl", 8),
+            vec![format!("abcdefgh{TRUNCATED_MARKER}")]
+        );
+        // the rest of the long line is dropped, the next line logs normally
+        assert!(buf.push(b"mnop", 8).is_empty());
+        assert_eq!(buf.push(b"qr\nnext\n", 8), vec!["next"]);
+        assert_eq!(buf.take_rest(), None);
+    }
+
+    #[test]
+    fn test_long_complete_line_truncated() {
+        let mut buf = LineBuffer::default();
+        // a whole line arriving in one write is held to the same limit
+        assert_eq!(
+            buf.push(b"abcdefghijkl\nnext\n", 8),
+            vec![format!("abcdefgh{TRUNCATED_MARKER}"), "next".to_string()]
+        );
+        assert!(buf.push(b"ab", 8).is_empty());
+        assert_eq!(
+            buf.push(b"cdefghij\n", 8),
+            vec![format!("abcdefgh{TRUNCATED_MARKER}")]
+        );
+        assert_eq!(buf.push(b"12345678\n", 8), vec!["12345678"]);
+        assert_eq!(buf.take_rest(), None);
+    }
+}
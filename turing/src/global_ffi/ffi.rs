@@ -1,23 +1,36 @@
-#![allow(static_mut_refs)]
-
-use crate::engine::types::{ScriptCallback, ScriptFnMetadata};
+use crate::engine::EngineKind;
+use crate::engine::types::{
+    CapabilityMissingPolicy, DispatchCallback, LogLevel, ScriptCallback, ScriptFnMetadata,
+};
 use crate::global_ffi::wrappers::*;
-use crate::interop::params::{DataType, FfiParam, FreeableDataType, Param, Params};
+use crate::interop::params::{
+    DataType, FFI_LAYOUT_VERSION, FfiParam, FfiParamArray, FreeableDataType, NanPolicy, Param,
+    Params, escaped_cstring,
+};
 use crate::interop::types::{Semver, U32Buffer};
-use crate::{Turing, panic_hook, spec_gen};
+use crate::spec_gen::SpecFormat;
+use crate::spec_gen::c_header::c_exports;
+use crate::{CallHandle, ExternalFunctions, Turing, is_call_cancelled, panic_hook, spec_gen};
 use anyhow::{Result, anyhow};
 use core::slice;
 use rustc_hash::FxHashMap;
+use std::cell::Cell;
 use std::ffi::{CStr, CString, c_char, c_void};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::ptr;
 
 pub type ScriptFnMap = FxHashMap<String, ScriptFnMetadata>;
 pub type TuringInstance = Turing<CsFns>;
+pub type TuringCallHandle = CallHandle<CsFns>;
 pub type TuringInitResult = Result<Turing<CsFns>>;
 pub type VersionTable = Vec<(String, Semver)>;
 pub type CacheKey = u32;
 
+thread_local! {
+    /// `Params` bound by `turing_bind_params` for the stateful building api, not owned
+    static ACTIVE_PARAMS: Cell<*mut Params> = const { Cell::new(ptr::null_mut()) };
+}
+
 trait VerTableImpl {
     fn contains_key(&self, key: &str) -> bool;
     fn get_ver(&self, key: &str) -> Option<&Semver>;
@@ -62,10 +75,23 @@ unsafe extern "C" fn turing_free_string(ptr: *mut c_char) {
 
 #[unsafe(no_mangle)]
 /// # Safety
-/// `ptr` must be a valid pointer to a `Mat4`, `Vec4`, or `Quat`.
+/// `ptr` must be a valid pointer to a `Mat4`, `Vec4`, or `Quat`, or the string of a `RustJson` `FfiParam`.
 /// `typ` must be a `FreeableDataType` compatible number, and must match the type the `ptr` points to.
 unsafe extern "C" fn turing_free_of_type(ptr: *mut c_void, typ: FreeableDataType) {
-    unsafe { typ.free_ptr(ptr) }
+    unsafe { typ.free_ptr::<CsFns>(ptr) }
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `ptr` must be null or point to `count` values of `typ` that rust allocated as one array,
+/// e.g. a buffer of `Vec3`s. A `RustJson` array holds string pointers.
+/// `typ` must be a `FreeableDataType` compatible number.
+unsafe extern "C" fn turing_free_slice_of_type(
+    ptr: *mut c_void,
+    typ: FreeableDataType,
+    count: u32,
+) {
+    unsafe { typ.free_slice::<CsFns>(ptr, count) }
 }
 
 #[unsafe(no_mangle)]
@@ -79,7 +105,7 @@ unsafe extern "C" fn turing_free_u32_buffer(buf: U32Buffer) {
 extern "C" fn turing_register_function(name: *const c_char, callback: *const c_void) {
     unsafe {
         let cstr = CStr::from_ptr(name).to_string_lossy().into_owned();
-        CS_FNS.link(&cstr, callback);
+        CS_FNS.write().link(&cstr, callback);
     }
 }
 
@@ -106,9 +132,7 @@ unsafe extern "C" fn turing_fn_map_add_data(
     let map = unsafe { &mut *map };
 
     if map.contains_key(&name) {
-        return CString::new(format!("FnMap already has a function named '{name}'"))
-            .unwrap()
-            .into_raw();
+        return escaped_cstring(&format!("FnMap already has a function named '{name}'")).into_raw();
     }
 
     map.insert(name, data);
@@ -133,11 +157,12 @@ unsafe extern "C" fn turing_delete_fn_map(map: *mut ScriptFnMap) {
 #[unsafe(no_mangle)]
 /// # Safety
 /// `capability` must be a valid C string pointer of valid `UTF-8` or null.
-/// `callback` must be a valid pointer to a function: `extern "C" fn(FfiParamsArray) -> FfiParam`.
+/// `callback` must be a valid pointer to a function: `extern "C" fn(FfiParamsArray) -> FfiParam`,
+/// or null if the function's calls should go through the instance's dispatch callback.
 /// `doc_comment` must be either null or a valid pointer to a string. When null, the function is considered to not have a doc comment.
 unsafe extern "C" fn turing_create_script_data(
     capability: *const c_char,
-    callback: ScriptCallback,
+    callback: Option<ScriptCallback>,
     doc_comment: *const c_char,
 ) -> *mut ScriptFnMetadata {
     if capability.is_null() {
@@ -153,7 +178,8 @@ unsafe extern "C" fn turing_create_script_data(
     } else {
         Some(unsafe { CStr::from_ptr(doc_comment).to_string_lossy().to_string() })
     };
-    let data = ScriptFnMetadata::new(cap, callback, doc);
+    let mut data = ScriptFnMetadata::dispatched(cap, doc);
+    data.callback = callback;
     Box::into_raw(Box::new(data))
 }
 
@@ -188,7 +214,7 @@ unsafe extern "C" fn turing_script_data_add_param_type(
         match ty_ptr.is_null() {
             true => {
                 if let Err(e) = data.add_param_type(ty, name) {
-                    return CString::new(format!("{}", e)).unwrap().into_raw();
+                    return escaped_cstring(&format!("{}", e)).into_raw();
                 }
             }
             false => {
@@ -196,7 +222,7 @@ unsafe extern "C" fn turing_script_data_add_param_type(
                     .to_string_lossy()
                     .into_owned();
                 if let Err(e) = data.add_param_type_named(ty, name, ty_name) {
-                    return CString::new(format!("{}", e)).unwrap().into_raw();
+                    return escaped_cstring(&format!("{}", e)).into_raw();
                 }
             }
         };
@@ -205,6 +231,33 @@ unsafe extern "C" fn turing_script_data_add_param_type(
     ptr::null()
 }
 
+#[unsafe(no_mangle)]
+/// # Safety
+/// `data` must be a valid pointer to a `ScriptFnMetadata`.
+/// `name` must be a valid C string pointer of valid `UTF-8`.
+/// `default` is passed to the callback when a lua script leaves the param out, and is consumed by this call.
+/// Optional params must be added after every required one.
+/// Returns a pointer to an error message, if the pointer is null then no error occurred. Caller is responsible for freeing this string.
+unsafe extern "C" fn turing_script_data_add_optional_param(
+    data: *mut ScriptFnMetadata,
+    param_type: DataType,
+    name: *const c_char,
+    default: FfiParam,
+) -> *const c_char {
+    let data = unsafe { &mut *data };
+    let name = unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned();
+
+    if let Err(e) = default.into_param::<CsFns>().and_then(|default| {
+        data.add_optional_param_type(param_type, name, default)
+            .map(|_| ())
+    }) {
+        return escaped_cstring(&format!("{}", e)).into_raw();
+    }
+    ptr::null()
+}
+
 #[unsafe(no_mangle)]
 /// # Safety
 /// `data` must be a valid pointer to a `ScriptFnMetadata`.
@@ -226,11 +279,67 @@ unsafe extern "C" fn turing_script_data_set_return_type(
         Some(name) => data.add_return_type_named(return_type, name),
         None => data.add_return_type(return_type),
     } {
-        return CString::new(format!("{}", e)).unwrap().into_raw();
+        return escaped_cstring(&format!("{}", e)).into_raw();
     }
     ptr::null()
 }
 
+#[unsafe(no_mangle)]
+/// # Safety
+/// `data` must be a valid pointer to a `ScriptFnMetadata`.
+/// `default` is returned to scripts instead of an error when they call the function while its capability isn't loaded.
+/// `default` is consumed by this call.
+/// Returns a pointer to an error message, if the pointer is null then no error occurred. Caller is responsible for freeing this string.
+unsafe extern "C" fn turing_script_data_set_capability_default(
+    data: *mut ScriptFnMetadata,
+    default: FfiParam,
+) -> *const c_char {
+    let data = unsafe { &mut *data };
+
+    match default.into_param::<CsFns>() {
+        Ok(default) => {
+            data.set_capability_missing_policy(CapabilityMissingPolicy::SilentDefault(default));
+            ptr::null()
+        }
+        Err(e) => escaped_cstring(&format!("{}", e)).into_raw(),
+    }
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `data` must be a valid pointer to a `ScriptFnMetadata`.
+/// When set, lua scripts may pass any number of extra args after the declared params.
+unsafe extern "C" fn turing_script_data_set_variadic(data: *mut ScriptFnMetadata, variadic: bool) {
+    let data = unsafe { &mut *data };
+    data.set_variadic(variadic);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `data` must be a valid pointer to a `ScriptFnMetadata`.
+/// When set, a failing callback returns zero to wasm scripts instead of trapping, they read the error with `_host_last_error`.
+unsafe extern "C" fn turing_script_data_set_fallible(data: *mut ScriptFnMetadata, fallible: bool) {
+    let data = unsafe { &mut *data };
+    data.set_fallible(fallible);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `data` must be a valid pointer to a `ScriptFnMetadata`.
+/// `symbol` must be a valid C string pointer of valid `UTF-8`, or null to convert the name again.
+/// Pins the symbol wasm scripts import the function as.
+unsafe extern "C" fn turing_script_data_set_explicit_symbol(
+    data: *mut ScriptFnMetadata,
+    symbol: *const c_char,
+) {
+    let data = unsafe { &mut *data };
+    if symbol.is_null() {
+        data.explicit_symbol = None;
+        return;
+    }
+    data.with_explicit_symbol(unsafe { CStr::from_ptr(symbol).to_string_lossy() });
+}
+
 #[unsafe(no_mangle)]
 /// # Safety
 /// `turing` must be a valid pointer to a `Turing`.
@@ -246,21 +355,7 @@ unsafe extern "C" fn turing_script_load(
     let turing = unsafe { &mut *turing };
     let source = unsafe { CStr::from_ptr(source).to_string_lossy() };
 
-    let cstr_array =
-        unsafe { slice::from_raw_parts(loaded_capabilities, capability_count as usize) };
-
-    let res = cstr_array
-        .iter()
-        .map(|c_str| {
-            if c_str.is_null() {
-                Err(anyhow!("capability string is null"))
-            } else {
-                Ok(unsafe { CStr::from_ptr(*c_str).to_string_lossy().into_owned() })
-            }
-        })
-        .collect::<Result<Vec<String>>>();
-
-    let capabilities = match res {
+    let capabilities = match unsafe { read_capabilities(loaded_capabilities, capability_count) } {
         Ok(ls) => ls,
         Err(e) => return Param::Error(format!("{}", e)).to_rs_param(),
     };
@@ -274,204 +369,1154 @@ unsafe extern "C" fn turing_script_load(
 }
 
 #[unsafe(no_mangle)]
+/// Loads a script from memory instead of a file, a wasm module if `kind` is 1 or lua source if it's 2.
 /// # Safety
 /// `turing` must be a valid pointer to a `Turing`.
-/// `name_key` must be a cache key, from calling `turing_script_cache_fn_name`.
-/// `params` must be a valid pointer to a `Params`.
-/// If `params` is null, an empty `Params` will be used for the function call instead.
-/// `params` will not be freed.
-unsafe extern "C" fn turing_script_call_fn(
+/// `bytes` must be valid for reads of `length` bytes, they are copied before this returns.
+/// `loaded_capabilities` must be a valid pointer to an array of valid string pointers.
+/// Returns an `FfiParam` that is either void or an error value.
+unsafe extern "C" fn turing_script_load_bytes(
     turing: *mut TuringInstance,
-    name_key: CacheKey,
-    params: *mut Params,
-    expected_return_type: DataType,
+    bytes: *const u8,
+    length: u32,
+    kind: u32,
+    loaded_capabilities: *mut *const c_char,
+    capability_count: u32,
 ) -> FfiParam {
     let turing = unsafe { &mut *turing };
-
-    let params = if params.is_null() {
-        Params::new()
+    let Ok(kind) = EngineKind::try_from(kind) else {
+        return Param::Error(format!("invalid engine kind {kind}")).to_rs_param();
+    };
+    let bytes = if length == 0 {
+        &[]
     } else {
-        unsafe { &*params }.clone()
+        unsafe { slice::from_raw_parts(bytes, length as usize) }
     };
 
-    turing
-        .call_fn((name_key).into(), params, expected_return_type)
-        .to_rs_param()
+    let capabilities = match unsafe { read_capabilities(loaded_capabilities, capability_count) } {
+        Ok(ls) => ls,
+        Err(e) => return Param::Error(format!("{}", e)).to_rs_param(),
+    };
+
+    if let Err(e) = turing.load_script_bytes(bytes, kind, &capabilities) {
+        Param::Error(format!("Error loading script: {e}"))
+    } else {
+        Param::Void
+    }
+    .to_rs_param()
 }
 
 #[unsafe(no_mangle)]
+/// Loads a script like `turing_script_load`, but fails if any api version the script declares
+/// is not compatible with `versions`. The error lists every mismatch.
 /// # Safety
-/// `turing` must be a valid pointer to a `Turing`.
-/// `name` must be a valid pointer to a UTF-8 C-String.
-unsafe extern "C" fn turing_script_get_fn_name(
+/// Same requirements as `turing_script_load`.
+/// `versions` must be a valid pointer to a `VersionTable`, it is not freed.
+unsafe extern "C" fn turing_script_load_checked(
     turing: *mut TuringInstance,
-    name: *const c_char,
-) -> CacheKey {
+    source: *const c_char,
+    loaded_capabilities: *mut *const c_char,
+    capability_count: u32,
+    versions: *mut VersionTable,
+) -> FfiParam {
     let turing = unsafe { &mut *turing };
+    let source = unsafe { CStr::from_ptr(source).to_string_lossy() };
+    let versions = unsafe { &*versions };
 
-    let name = unsafe { CStr::from_ptr(name).to_string_lossy() };
+    let capabilities = match unsafe { read_capabilities(loaded_capabilities, capability_count) } {
+        Ok(ls) => ls,
+        Err(e) => return Param::Error(format!("{}", e)).to_rs_param(),
+    };
 
-    turing
-        .get_fn_key(name.as_ref())
-        .map(|x| x.0)
-        .unwrap_or(u32::MAX)
+    if let Err(e) = turing.load_script_checked(source, &capabilities, versions) {
+        Param::Error(format!("Error loading script: {}", e))
+    } else {
+        Param::Void
+    }
+    .to_rs_param()
 }
 
 #[unsafe(no_mangle)]
+/// Loads the mod in directory `dir` as described by its `mod.toml`, checking the capabilities
+/// and api versions it requests first. Warnings that didn't stop the load are logged.
 /// # Safety
 /// `turing` must be a valid pointer to a `Turing`.
-/// The caller is responsible for freeing the returned error string if not null
-unsafe extern "C" fn turing_script_fast_call_update(
+/// `dir` must be a valid pointer to a UTF-8 C-String.
+/// `allowed_capabilities` must be a valid pointer to an array of `capability_count` valid string pointers.
+/// `versions` must be a valid pointer to a `VersionTable`, it is not freed.
+unsafe extern "C" fn turing_mod_load(
     turing: *mut TuringInstance,
-    delta_time: f32,
-) -> *const c_char {
+    dir: *const c_char,
+    allowed_capabilities: *mut *const c_char,
+    capability_count: u32,
+    versions: *mut VersionTable,
+) -> FfiParam {
     let turing = unsafe { &mut *turing };
+    let dir = unsafe { CStr::from_ptr(dir).to_string_lossy() };
+    let versions = unsafe { &*versions };
 
-    if let Err(e) = turing.fast_call_update(delta_time) {
-        CString::new(e).unwrap().into_raw()
+    let allowed = match unsafe { read_capabilities(allowed_capabilities, capability_count) } {
+        Ok(ls) => ls,
+        Err(e) => return Param::Error(format!("{}", e)).to_rs_param(),
+    };
+
+    if let Err(e) = turing.load_mod(Path::new(dir.as_ref()), &allowed, versions) {
+        Param::Error(format!("Error loading mod: {}", e))
     } else {
-        ptr::null()
+        Param::Void
     }
+    .to_rs_param()
 }
 
 #[unsafe(no_mangle)]
 /// # Safety
 /// `turing` must be a valid pointer to a `Turing`.
-/// The caller is responsible for freeing the returned error string if not null
-unsafe extern "C" fn turing_script_fast_call_fixed_update(
-    turing: *mut TuringInstance,
-    delta_time: f32,
-) -> *const c_char {
-    let turing = unsafe { &mut *turing };
-    if let Err(e) = turing.fast_call_fixed_update(delta_time) {
-        CString::new(e).unwrap().into_raw()
-    } else {
-        ptr::null()
+/// Returns the name from the last loaded mod's manifest, or null if no mod is loaded.
+/// The caller is responsible for freeing the returned string.
+unsafe extern "C" fn turing_mod_get_name(turing: *mut TuringInstance) -> *const c_char {
+    let turing = unsafe { &*turing };
+    match turing.manifest() {
+        Some(manifest) => escaped_cstring(&manifest.name).into_raw(),
+        None => ptr::null(),
     }
 }
 
-/// Dumps the currently loaded script definitions to the specified output directory.
+#[unsafe(no_mangle)]
 /// # Safety
 /// `turing` must be a valid pointer to a `Turing`.
-/// `out_dir` must be a valid pointer to a UTF-8 C-String.
-///
-/// The caller is responsible for freeing the returned error string if not null
-#[unsafe(no_mangle)]
-unsafe extern "C" fn turing_script_dump_sec(
-    out_dir: *const c_char,
-    wasm_fns_ptr: *mut ScriptFnMap,
-    versions: *mut VersionTable,
-) -> *const c_char {
-    let map = unsafe { &*wasm_fns_ptr };
-    let versions = unsafe { &*versions };
-
-    let versions_map = versions.clone().into_iter().collect();
-
-    let out = unsafe { CStr::from_ptr(out_dir).to_string_lossy().into_owned() };
-    let out = std::path::Path::new(&out);
-
-    match spec_gen::generator::generate_specs(map, &versions_map, out) {
-        Ok(_) => ptr::null(),
-        Err(e) => CString::new(format!("{}", e)).unwrap().into_raw(),
-    }
+/// returns the last loaded mod's version as a packed u64 of (major u32, minor u16, patch u16),
+/// or 0 if no mod is loaded.
+unsafe extern "C" fn turing_mod_get_version(turing: *mut TuringInstance) -> u64 {
+    let turing = unsafe { &*turing };
+    turing
+        .manifest()
+        .map_or(0, |manifest| manifest.version.as_u64())
 }
 
 #[unsafe(no_mangle)]
 /// # Safety
-/// `wasm_fns_ptr` must be a valid pointer to a `HashMap<String, ScriptFnMetadata>`.
-/// `wasm_fns_ptr` will be freed during this function and must no longer be used.
-unsafe extern "C" fn turing_create_instance(
-    wasm_fns_ptr: *mut ScriptFnMap,
-) -> *mut TuringInitResult {
-    let map = unsafe { Box::from_raw(wasm_fns_ptr) };
-    let mut turing = Turing::new();
-    turing.script_fns = *map;
-    let turing = Box::new(turing.build());
-    Box::into_raw(turing)
+/// `turing` must be a valid pointer to a `Turing`.
+/// Calls the script's `on_unload` if it exports one, then unloads it.
+unsafe extern "C" fn turing_script_unload(turing: *mut TuringInstance) {
+    let turing = unsafe { &mut *turing };
+    turing.unload_script();
 }
 
 #[unsafe(no_mangle)]
 /// # Safety
-/// `res_ptr` must be a valid pointer to a `Result<Turing>`.
-/// the caller is responsible for freeing the returned string if not null.
-unsafe extern "C" fn turing_instance_check_error(res_ptr: *mut TuringInitResult) -> *const c_char {
-    let res = unsafe { &*res_ptr };
-
-    if let Err(e) = res {
-        CString::new(format!("{}", e)).unwrap().into_raw()
-    } else {
-        ptr::null()
+/// `turing` must be a valid pointer to a `Turing`.
+/// Starts the loaded wasm script over in a fresh instance without recompiling it.
+/// Returns an `FfiParam` that is either void or an error value.
+unsafe extern "C" fn turing_script_reset_instance(turing: *mut TuringInstance) -> FfiParam {
+    let turing = unsafe { &mut *turing };
+    match turing.reset_instance() {
+        Ok(()) => Param::Void,
+        Err(e) => Param::Error(format!("{e}")),
     }
+    .to_rs_param()
 }
 
 #[unsafe(no_mangle)]
 /// # Safety
-/// `res_ptr` must be a valid pointer to a `Result<Turing>`.
-/// `res_ptr` must have been checked with `check_error` and handled if an error was returned.
-/// If `res_ptr` points to an `Err` value, this function will abort the process.
-/// `res_ptr` will be freed during this function and must no longer be used.
-unsafe extern "C" fn turing_instance_unwrap(res_ptr: *mut TuringInitResult) -> *mut TuringInstance {
-    let res = unsafe { *Box::from_raw(res_ptr) };
-
-    let Ok(turing) = res else {
-        eprintln!("unwrap_turing(): res_ptr pointed to Err, aborting process");
-        std::process::abort();
+/// `turing` must be a valid pointer to a `Turing`.
+/// Watches the loaded script's file so `turing_poll_reload` reloads it once it changes.
+/// Returns an `FfiParam` that is either void or an error value, which it always is
+/// if turing was built without the `hot_reload` feature.
+unsafe extern "C" fn turing_enable_hot_reload(turing: *mut TuringInstance) -> FfiParam {
+    let turing = unsafe { &mut *turing };
+    #[cfg(feature = "hot_reload")]
+    let result = turing.enable_hot_reload();
+    #[cfg(not(feature = "hot_reload"))]
+    let result: Result<()> = {
+        let _ = turing;
+        Err(anyhow!("turing was built without the `hot_reload` feature"))
     };
-    let turing = Box::new(turing);
-    Box::into_raw(turing)
+    match result {
+        Ok(()) => Param::Void,
+        Err(e) => Param::Error(format!("{e}")),
+    }
+    .to_rs_param()
 }
 
 #[unsafe(no_mangle)]
 /// # Safety
-/// `turing` must be a valid pointer to a `Turing`
-unsafe extern "C" fn turing_delete_instance(turing: *mut TuringInstance) {
-    let _ = unsafe { Box::from_raw(turing) };
-}
-
-#[unsafe(no_mangle)]
-extern "C" fn turing_create_params(size: u32) -> *mut Params {
-    Box::into_raw(Box::new(if size == 0 {
-        Params::new()
-    } else {
-        Params::of_size(size)
-    }))
+/// `turing` must be a valid pointer to a `Turing`.
+/// Reloads the watched script if its file changed, returning whether it did.
+/// A reload that fails is logged as critical and leaves no script loaded.
+unsafe extern "C" fn turing_poll_reload(turing: *mut TuringInstance) -> bool {
+    let turing = unsafe { &mut *turing };
+    #[cfg(feature = "hot_reload")]
+    {
+        match turing.poll_reload() {
+            Ok(reloaded) => reloaded,
+            Err(e) => {
+                CsFns::log_critical(format!("Error reloading script: {e}"));
+                false
+            }
+        }
+    }
+    #[cfg(not(feature = "hot_reload"))]
+    {
+        let _ = turing;
+        false
+    }
 }
 
 #[unsafe(no_mangle)]
-extern "C" fn turing_params_get_size(params: *mut Params) -> u32 {
-    let params = unsafe { &*params };
-    params.len()
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `mod_id`, `key` and `value` must be valid C string pointers of valid `UTF-8`.
+/// Stores a value the mod `mod_id` can read, ignoring the storage quota.
+unsafe extern "C" fn turing_storage_set(
+    turing: *mut TuringInstance,
+    mod_id: *const c_char,
+    key: *const c_char,
+    value: *const c_char,
+) {
+    let turing = unsafe { &*turing };
+    let mod_id = unsafe { CStr::from_ptr(mod_id).to_string_lossy() };
+    let key = unsafe { CStr::from_ptr(key).to_string_lossy() };
+    let value = unsafe { CStr::from_ptr(value).to_string_lossy() };
+    turing.storage_set(&mod_id, &key, &value);
 }
 
 #[unsafe(no_mangle)]
-extern "C" fn turing_params_clear(params: *mut Params) {
-    let params = unsafe { &mut *params };
-    params.clear();
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `mod_id` and `key` must be valid C string pointers of valid `UTF-8`.
+/// Returns the value the mod `mod_id` stored under `key`, or null if there is none.
+/// The caller is responsible for freeing the returned string.
+unsafe extern "C" fn turing_storage_get(
+    turing: *mut TuringInstance,
+    mod_id: *const c_char,
+    key: *const c_char,
+) -> *const c_char {
+    let turing = unsafe { &*turing };
+    let mod_id = unsafe { CStr::from_ptr(mod_id).to_string_lossy() };
+    let key = unsafe { CStr::from_ptr(key).to_string_lossy() };
+    match turing.storage_get(&mod_id, &key) {
+        Some(value) => escaped_cstring(&value).into_raw(),
+        None => ptr::null(),
+    }
 }
 
 #[unsafe(no_mangle)]
 /// # Safety
-/// `params` must be a valid pointer to a `Params`.
-/// This function silently fails if params is null.
-unsafe extern "C" fn turing_params_add_param(params: *mut Params, param: FfiParam) {
-    let params = unsafe { &mut *params };
-    let param = param.as_param::<CsFns>().unwrap();
-    params.push(param);
+/// `turing` must be a valid pointer to a `Turing`.
+/// Returns all script storage as JSON, or null if it couldn't be serialized.
+/// The caller is responsible for freeing the returned string.
+unsafe extern "C" fn turing_storage_serialize(turing: *mut TuringInstance) -> *const c_char {
+    let turing = unsafe { &*turing };
+    match turing.storage_serialize() {
+        Ok(json) => escaped_cstring(&json).into_raw(),
+        Err(_) => ptr::null(),
+    }
 }
 
 #[unsafe(no_mangle)]
 /// # Safety
-/// `params` must be a valid pointer to a `Params` and must not be used after this call.
-unsafe extern "C" fn turing_delete_params(params: *mut Params) {
-    let _ = unsafe { Box::from_raw(params) };
+/// `turing` must be a valid pointer to a `Turing`.
+/// `json` must be a valid C string pointer of valid `UTF-8`.
+/// Replaces all script storage with `json` from `turing_storage_serialize`.
+/// Returns an `FfiParam` that is either void or an error value, storage is unchanged on error.
+unsafe extern "C" fn turing_storage_deserialize(
+    turing: *mut TuringInstance,
+    json: *const c_char,
+) -> FfiParam {
+    let turing = unsafe { &*turing };
+    let json = unsafe { CStr::from_ptr(json).to_string_lossy() };
+    match turing.storage_deserialize(&json) {
+        Ok(()) => Param::Void,
+        Err(e) => Param::Error(format!("{e}")),
+    }
+    .to_rs_param()
 }
 
-#[unsafe(no_mangle)]
 /// # Safety
-/// `params` must be a valid pointer to a `Params`.
-/// Returns a copy of an `FfiParam` which may be an error value if an error occurs.
-unsafe extern "C" fn turing_params_get_param(params: *mut Params, index: u32) -> FfiParam {
-    let params = unsafe { &*params };
+/// `loaded_capabilities` must point to `capability_count` C-String pointers.
+unsafe fn read_capabilities(
+    loaded_capabilities: *mut *const c_char,
+    capability_count: u32,
+) -> Result<Vec<String>> {
+    let cstr_array =
+        unsafe { slice::from_raw_parts(loaded_capabilities, capability_count as usize) };
+
+    cstr_array
+        .iter()
+        .map(|c_str| {
+            if c_str.is_null() {
+                Err(anyhow!("capability string is null"))
+            } else {
+                Ok(unsafe { CStr::from_ptr(*c_str).to_string_lossy().into_owned() })
+            }
+        })
+        .collect()
+}
+
+/// Moves the arguments out of a host owned `Params` without cloning them
+/// # Safety
+/// `params` must be null or a valid pointer to a `Params`.
+unsafe fn drain_call_params(params: *mut Params) -> Params {
+    if params.is_null() {
+        Params::new()
+    } else {
+        unsafe { &mut *params }.drain_all()
+    }
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `name_key` must be a cache key, from calling `turing_script_cache_fn_name`.
+/// `params` must be a valid pointer to a `Params`.
+/// If `params` is null, an empty `Params` will be used for the function call instead.
+/// `params` will not be freed, but its contents are moved into the call and it is left empty
+/// with its capacity intact, so one `Params` can be reused for every call.
+unsafe extern "C" fn turing_script_call_fn(
+    turing: *mut TuringInstance,
+    name_key: CacheKey,
+    params: *mut Params,
+    expected_return_type: DataType,
+) -> FfiParam {
+    let turing = unsafe { &mut *turing };
+
+    let res = if params.is_null() {
+        turing.call_fn(name_key.into(), Params::new(), expected_return_type)
+    } else {
+        turing.call_fn_draining(
+            name_key.into(),
+            unsafe { &mut *params },
+            expected_return_type,
+        )
+    };
+    res.to_rs_param()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `name` must be a valid pointer to a UTF-8 C-String.
+/// `params` is handled the same as in `turing_script_call_fn`.
+/// Looks the function up by name and calls it in one go, for calls that don't happen often
+/// enough to be worth keeping a cache key. Returns an error param if the name is unknown.
+unsafe extern "C" fn turing_script_call_fn_by_name(
+    turing: *mut TuringInstance,
+    name: *const c_char,
+    params: *mut Params,
+    expected_return_type: DataType,
+) -> FfiParam {
+    let turing = unsafe { &mut *turing };
+
+    let name = unsafe { CStr::from_ptr(name).to_string_lossy() };
+    let params = unsafe { drain_call_params(params) };
+
+    turing
+        .call_fn_by_name(name, params, expected_return_type)
+        .to_rs_param()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `name_key` must be a cache key, from calling `turing_script_cache_fn_name`.
+/// `params` must point to `count` valid `FfiParam`s, or be empty.
+/// Like `turing_script_call_fn`, but takes the arguments in one array instead of a `Params`.
+/// The params are copied, so the caller still owns and frees them.
+unsafe extern "C" fn turing_script_call_fn_packed(
+    turing: *mut TuringInstance,
+    name_key: CacheKey,
+    params: FfiParamArray,
+    expected_return_type: DataType,
+) -> FfiParam {
+    let turing = unsafe { &mut *turing };
+
+    match params.as_params::<CsFns>() {
+        Ok(params) => turing.call_fn(name_key.into(), params, expected_return_type),
+        Err(e) => Param::Error(format!("{e}")),
+    }
+    .to_rs_param()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`, and not be in the middle of a call.
+/// Returns a handle host callbacks call back into the script with, free it with `turing_delete_call_handle`.
+unsafe extern "C" fn turing_instance_call_handle(
+    turing: *mut TuringInstance,
+) -> *mut TuringCallHandle {
+    let turing = unsafe { &*turing };
+    Box::into_raw(Box::new(turing.call_handle()))
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `handle` must be a valid pointer from `turing_instance_call_handle`.
+unsafe extern "C" fn turing_delete_call_handle(handle: *mut TuringCallHandle) {
+    let _ = unsafe { Box::from_raw(handle) };
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `handle` must be a valid pointer from `turing_instance_call_handle`.
+/// `name_key` and `params` are the same as for `turing_script_call_fn`.
+/// May be called from inside a host callback while another call is running.
+/// Returns an error param on engines that can't nest calls.
+unsafe extern "C" fn turing_script_call_fn_reentrant(
+    handle: *mut TuringCallHandle,
+    name_key: CacheKey,
+    params: *mut Params,
+    expected_return_type: DataType,
+) -> FfiParam {
+    let handle = unsafe { &*handle };
+
+    let params = unsafe { drain_call_params(params) };
+
+    handle
+        .call_fn_reentrant((name_key).into(), params, expected_return_type)
+        .to_rs_param()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Returns which engine runs the loaded script: 0 if none is loaded, 1 for wasm, 2 for lua.
+unsafe extern "C" fn turing_engine_kind(turing: *mut TuringInstance) -> u32 {
+    let turing = unsafe { &*turing };
+    turing.engine_kind().map_or(0, |kind| kind as u32)
+}
+
+#[unsafe(no_mangle)]
+/// For host callbacks doing long work: true once the script call running on this thread
+/// has been interrupted, so the callback can return early.
+extern "C" fn turing_call_is_cancelled() -> bool {
+    is_call_cancelled()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `handle` must be a valid pointer from `turing_instance_call_handle`.
+/// `name_key` and `params` are the same as for `turing_script_call_fn`.
+/// Defers the call until the current call returns, the result is passed to the linked
+/// `call_completed` function.
+unsafe extern "C" fn turing_script_queue_call(
+    handle: *mut TuringCallHandle,
+    name_key: CacheKey,
+    params: *mut Params,
+    expected_return_type: DataType,
+) {
+    let handle = unsafe { &*handle };
+
+    let params = unsafe { drain_call_params(params) };
+
+    handle.queue_call((name_key).into(), params, expected_return_type);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `name` must be a valid pointer to a UTF-8 C-String.
+unsafe extern "C" fn turing_script_get_fn_name(
+    turing: *mut TuringInstance,
+    name: *const c_char,
+) -> CacheKey {
+    let turing = unsafe { &mut *turing };
+
+    let name = unsafe { CStr::from_ptr(name).to_string_lossy() };
+
+    turing
+        .get_fn_key(name.as_ref())
+        .map(|x| x.0)
+        .unwrap_or(u32::MAX)
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Returns the signature the function behind `key` was exported with, packed like `(i32, f32) -> i64`
+/// with `?` for parts the engine can't tell, or null if `key` names no function.
+/// The caller is responsible for freeing the returned string.
+unsafe extern "C" fn turing_script_get_export_signature(
+    turing: *mut TuringInstance,
+    key: CacheKey,
+) -> *const c_char {
+    let turing = unsafe { &*turing };
+    match turing.export_signature_of(key.into()) {
+        Some(signature) => escaped_cstring(&signature.to_string()).into_raw(),
+        None => ptr::null(),
+    }
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// The caller is responsible for freeing the returned error string if not null
+unsafe extern "C" fn turing_script_fast_call_update(
+    turing: *mut TuringInstance,
+    delta_time: f32,
+) -> *const c_char {
+    let turing = unsafe { &mut *turing };
+
+    if let Err(e) = turing.fast_call_update(delta_time) {
+        escaped_cstring(&e).into_raw()
+    } else {
+        ptr::null()
+    }
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// The caller is responsible for freeing the returned error string if not null
+unsafe extern "C" fn turing_script_fast_call_fixed_update(
+    turing: *mut TuringInstance,
+    delta_time: f32,
+) -> *const c_char {
+    let turing = unsafe { &mut *turing };
+    if let Err(e) = turing.fast_call_fixed_update(delta_time) {
+        escaped_cstring(&e).into_raw()
+    } else {
+        ptr::null()
+    }
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `name` must be a valid pointer to a UTF-8 C-String.
+/// Returns the handle as a `U32` param, or an error param.
+unsafe extern "C" fn turing_script_register_fast_call(
+    turing: *mut TuringInstance,
+    name: *const c_char,
+) -> FfiParam {
+    let turing = unsafe { &mut *turing };
+
+    let name = unsafe { CStr::from_ptr(name).to_string_lossy() };
+
+    match turing.register_fast_call(name.as_ref()) {
+        Ok(handle) => Param::U32(handle.into()),
+        Err(e) => Param::Error(format!("{e}")),
+    }
+    .to_rs_param()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `handle` must have been returned by `turing_script_register_fast_call` on this instance.
+/// The caller is responsible for freeing the returned error string if not null
+unsafe extern "C" fn turing_script_invoke_fast_call(
+    turing: *mut TuringInstance,
+    handle: u32,
+    delta_time: f32,
+) -> *const c_char {
+    let turing = unsafe { &mut *turing };
+    if let Err(e) = turing.invoke_fast_call(handle.into(), delta_time) {
+        escaped_cstring(&e).into_raw()
+    } else {
+        ptr::null()
+    }
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`
+unsafe extern "C" fn turing_script_meta_count(turing: *mut TuringInstance) -> u32 {
+    let turing = unsafe { &*turing };
+    turing.script_meta().len() as u32
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`
+/// `index` must be within `0..<count` (checked with turing_script_meta_count). Keys are sorted.
+/// Returns null if out of range. The caller is responsible for freeing the returned string.
+unsafe extern "C" fn turing_script_meta_get_key(
+    turing: *mut TuringInstance,
+    index: u32,
+) -> *const c_char {
+    let turing = unsafe { &*turing };
+
+    let mut keys = turing.script_meta().into_keys().collect::<Vec<_>>();
+    keys.sort();
+    let Some(key) = keys.into_iter().nth(index as usize) else {
+        return ptr::null();
+    };
+
+    escaped_cstring(&key).into_raw()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `key` must be a valid pointer to a UTF-8 C-String.
+/// Returns null if the script doesn't declare `key`. The caller is responsible for freeing the returned string.
+unsafe extern "C" fn turing_script_meta_get(
+    turing: *mut TuringInstance,
+    key: *const c_char,
+) -> *const c_char {
+    let turing = unsafe { &*turing };
+
+    let key = unsafe { CStr::from_ptr(key).to_string_lossy() };
+
+    let Some(value) = turing.script_meta().remove(key.as_ref()) else {
+        return ptr::null();
+    };
+
+    escaped_cstring(&value).into_raw()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Takes a string a wasm script sent with `_host_recv_string`. Returns null if `id` is unknown
+/// or was already taken. The caller is responsible for freeing the returned string.
+unsafe extern "C" fn turing_script_take_string(
+    turing: *mut TuringInstance,
+    id: u32,
+) -> *const c_char {
+    let turing = unsafe { &*turing };
+
+    let Some(value) = turing.take_received_string(id) else {
+        return ptr::null();
+    };
+
+    escaped_cstring(&value).into_raw()
+}
+
+/// Copies `bytes` into `out` when it has room and returns the full length as a `U32` param,
+/// so callers can query the size with a null `out` first.
+unsafe fn copy_bytes_out(bytes: &[u8], out: *mut u8, capacity: u32) -> Param {
+    let Ok(len) = u32::try_from(bytes.len()) else {
+        return Param::Error("byte buffer is too large".to_string());
+    };
+    if !out.is_null() && capacity >= len {
+        unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), out, bytes.len()) };
+    }
+    Param::U32(len)
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `out` must be null or valid for writes of `capacity` bytes.
+/// Returns the length of the loaded wasm module's original bytes as a `U32` param, or an error param.
+/// The bytes are only copied into `out` if `capacity` is at least that length.
+unsafe extern "C" fn turing_script_module_bytes(
+    turing: *mut TuringInstance,
+    out: *mut u8,
+    capacity: u32,
+) -> FfiParam {
+    let turing = unsafe { &*turing };
+
+    match turing.module_bytes() {
+        Some(bytes) => unsafe { copy_bytes_out(&bytes, out, capacity) },
+        None => Param::Error("No wasm script is loaded".to_string()),
+    }
+    .to_rs_param()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `out` must be null or valid for writes of `capacity` bytes.
+/// Same as `turing_script_module_bytes`, but for the serialized compiled module.
+/// The module is serialized on every call.
+unsafe extern "C" fn turing_script_compiled_bytes(
+    turing: *mut TuringInstance,
+    out: *mut u8,
+    capacity: u32,
+) -> FfiParam {
+    let turing = unsafe { &*turing };
+
+    match turing.compiled_bytes() {
+        Ok(bytes) => unsafe { copy_bytes_out(&bytes, out, capacity) },
+        Err(e) => Param::Error(format!("{e}")),
+    }
+    .to_rs_param()
+}
+
+/// Dumps the currently loaded script definitions to the specified output directory,
+/// in the output `format` selects: 1 = api specs, 2 = lua defs (`turing_api.lua`),
+/// 3 = typescript defs (`turing.d.ts`), 4 = C header for this ffi surface.
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `out_dir` must be a valid pointer to a UTF-8 C-String.
+///
+/// The caller is responsible for freeing the returned error string if not null
+#[unsafe(no_mangle)]
+unsafe extern "C" fn turing_script_dump_sec(
+    out_dir: *const c_char,
+    wasm_fns_ptr: *mut ScriptFnMap,
+    versions: *mut VersionTable,
+    format: SpecFormat,
+) -> *const c_char {
+    let map = unsafe { &*wasm_fns_ptr };
+    let versions = unsafe { &*versions };
+
+    let versions_map = versions.clone().into_iter().collect();
+
+    let out = unsafe { CStr::from_ptr(out_dir).to_string_lossy().into_owned() };
+    let out = std::path::Path::new(&out);
+
+    let res = match format {
+        SpecFormat::Specs => spec_gen::generator::generate_specs(map, &versions_map, out),
+        SpecFormat::LuaDefs => spec_gen::lua_defs::generate(map, &versions_map, out),
+        SpecFormat::TsDefs => spec_gen::ts_defs::generate(map, &versions_map, out),
+        SpecFormat::CHeader => spec_gen::c_header::generate(C_EXPORTS, out),
+    };
+
+    match res {
+        Ok(_) => ptr::null(),
+        Err(e) => escaped_cstring(&format!("{}", e)).into_raw(),
+    }
+}
+
+/// Returns the api specs as a JSON string, the same as the `specs.json` from `turing_script_dump_sec`.
+/// # Safety
+/// `wasm_fns_ptr` must be a valid pointer to a `ScriptFnMap`.
+/// `versions` must be a valid pointer to a `VersionTable`.
+///
+/// The caller is responsible for freeing the returned string. Returns null if serialization fails.
+#[unsafe(no_mangle)]
+unsafe extern "C" fn turing_script_specs_json(
+    wasm_fns_ptr: *mut ScriptFnMap,
+    versions: *mut VersionTable,
+) -> *const c_char {
+    let map = unsafe { &*wasm_fns_ptr };
+    let versions = unsafe { &*versions };
+
+    let versions_map = versions.clone().into_iter().collect();
+
+    match spec_gen::generator::generate_specs_value(map, &versions_map) {
+        Ok(value) => escaped_cstring(&value.to_string()).into_raw(),
+        Err(_) => ptr::null(),
+    }
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `wasm_fns_ptr` must be a valid pointer to a `HashMap<String, ScriptFnMetadata>`.
+/// `wasm_fns_ptr` will be freed during this function and must no longer be used.
+unsafe extern "C" fn turing_create_instance(
+    wasm_fns_ptr: *mut ScriptFnMap,
+) -> *mut TuringInitResult {
+    let map = unsafe { Box::from_raw(wasm_fns_ptr) };
+    let mut turing = Turing::new();
+    turing.script_fns = *map;
+    let turing = Box::new(turing.build());
+    Box::into_raw(turing)
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `res_ptr` must be a valid pointer to a `Result<Turing>`.
+/// the caller is responsible for freeing the returned string if not null.
+unsafe extern "C" fn turing_instance_check_error(res_ptr: *mut TuringInitResult) -> *const c_char {
+    let res = unsafe { &*res_ptr };
+
+    if let Err(e) = res {
+        escaped_cstring(&format!("{}", e)).into_raw()
+    } else {
+        ptr::null()
+    }
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `res_ptr` must be a valid pointer to a `Result<Turing>`.
+/// `res_ptr` must have been checked with `check_error` and handled if an error was returned.
+/// If `res_ptr` points to an `Err` value, this function will abort the process.
+/// `res_ptr` will be freed during this function and must no longer be used.
+unsafe extern "C" fn turing_instance_unwrap(res_ptr: *mut TuringInitResult) -> *mut TuringInstance {
+    let res = unsafe { *Box::from_raw(res_ptr) };
+
+    let Ok(turing) = res else {
+        eprintln!("unwrap_turing(): res_ptr pointed to Err, aborting process");
+        std::process::abort();
+    };
+    let turing = Box::new(turing);
+    Box::into_raw(turing)
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `dispatch` must be a valid pointer to a function: `extern "C" fn(*const c_char, FfiParamsArray) -> FfiParam`.
+/// The name passed to `dispatch` is only valid for the duration of the call.
+/// Only takes effect for scripts loaded after this call.
+unsafe extern "C" fn turing_instance_set_dispatch(
+    turing: *mut TuringInstance,
+    dispatch: DispatchCallback,
+) {
+    let turing = unsafe { &mut *turing };
+    turing.config.dispatch = Some(dispatch);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `on_missing` must be a valid pointer to a function: `extern "C" fn(*const c_char, FfiParamsArray) -> FfiParam`.
+/// The name passed to `on_missing` is only valid for the duration of the call.
+/// Only takes effect for lua scripts loaded after this call.
+unsafe extern "C" fn turing_instance_set_missing_function(
+    turing: *mut TuringInstance,
+    on_missing: DispatchCallback,
+) {
+    let turing = unsafe { &mut *turing };
+    turing.config.on_missing_function = Some(on_missing);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Drops script log messages below `level` (0 debug, 1 info, 2 warn, 3 error).
+/// Returns an `FfiParam` that is either void or an error value.
+unsafe extern "C" fn turing_instance_set_log_level(
+    turing: *mut TuringInstance,
+    level: u32,
+) -> FfiParam {
+    let turing = unsafe { &*turing };
+    match LogLevel::try_from(level) {
+        Ok(level) => {
+            turing.set_log_level(level);
+            Param::Void
+        }
+        Err(_) => Param::Error(format!("invalid log level {level}")),
+    }
+    .to_rs_param()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Sets the stack size for wasm scripts loaded by the next `turing_script_load`, at least 64KB.
+/// Returns an `FfiParam` that is either void or an error value.
+unsafe extern "C" fn turing_instance_set_wasm_max_stack(
+    turing: *mut TuringInstance,
+    bytes: u32,
+) -> FfiParam {
+    let turing = unsafe { &mut *turing };
+    match turing.set_wasm_max_stack_bytes(bytes as usize) {
+        Ok(()) => Param::Void,
+        Err(e) => Param::Error(format!("{e}")),
+    }
+    .to_rs_param()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Sets what happens to NaN and infinite floats scripts pass to the host (0 passthrough, 1 zero, 2 error).
+/// Returns an `FfiParam` that is either void or an error value.
+unsafe extern "C" fn turing_instance_set_nan_policy(
+    turing: *mut TuringInstance,
+    policy: u32,
+) -> FfiParam {
+    let turing = unsafe { &*turing };
+    match NanPolicy::try_from(policy) {
+        Ok(policy) => {
+            turing.set_nan_policy(policy);
+            Param::Void
+        }
+        Err(_) => Param::Error(format!("invalid nan policy {policy}")),
+    }
+    .to_rs_param()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Sets the longest line wasm scripts loaded by the next `turing_script_load` may print before it's cut off.
+unsafe extern "C" fn turing_instance_set_wasm_output_buffer(
+    turing: *mut TuringInstance,
+    bytes: u32,
+) {
+    let turing = unsafe { &mut *turing };
+    turing.set_wasm_output_buffer_bytes(bytes as usize);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Caps the bytes of keys and values scripts may store per capability.
+unsafe extern "C" fn turing_instance_set_storage_quota(turing: *mut TuringInstance, bytes: u32) {
+    let turing = unsafe { &*turing };
+    turing.set_storage_quota_bytes(bytes as usize);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Makes scripts loaded by the next `turing_script_load` replay identically across machines.
+unsafe extern "C" fn turing_instance_set_deterministic(
+    turing: *mut TuringInstance,
+    deterministic: bool,
+) {
+    let turing = unsafe { &mut *turing };
+    turing.set_deterministic(deterministic);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Numbers objects `1, 2, 3, ...` for scripts loaded by the next `turing_script_load`.
+unsafe extern "C" fn turing_instance_set_sequential_object_ids(
+    turing: *mut TuringInstance,
+    sequential: bool,
+) {
+    let turing = unsafe { &mut *turing };
+    turing.set_sequential_object_ids(sequential);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Records how often each script function is called and how long it runs, read with `turing_stats_count`.
+unsafe extern "C" fn turing_instance_set_profiling(turing: *mut TuringInstance, profiling: bool) {
+    let turing = unsafe { &mut *turing };
+    turing.set_profiling(profiling);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Takes the stats recorded since the last call and returns how many functions they cover.
+/// Read them with `turing_stats_get`, they stay readable until the next `turing_stats_count`.
+unsafe extern "C" fn turing_stats_count(turing: *mut TuringInstance) -> u32 {
+    let turing = unsafe { &mut *turing };
+    turing.take_stats_snapshot() as u32
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `out_calls`, `out_total_ns` and `out_max_ns` must each be null or valid to write a `uint64_t` to.
+/// Returns the function's name, or null if `index` isn't below the last `turing_stats_count`.
+/// Stats are sorted by most total time first. The caller is responsible for freeing the returned string.
+unsafe extern "C" fn turing_stats_get(
+    turing: *mut TuringInstance,
+    index: u32,
+    out_calls: *mut u64,
+    out_total_ns: *mut u64,
+    out_max_ns: *mut u64,
+) -> *const c_char {
+    let turing = unsafe { &*turing };
+    let Some((name, stat)) = turing.stats_snapshot().get(index as usize) else {
+        return ptr::null();
+    };
+    for (out, value) in [
+        (out_calls, stat.calls),
+        (out_total_ns, stat.total.as_nanos() as u64),
+        (out_max_ns, stat.max.as_nanos() as u64),
+    ] {
+        if !out.is_null() {
+            unsafe { *out = value };
+        }
+    }
+    escaped_cstring(name).into_raw()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Restarts the host random functions and lua's `math.random` from `seed`. Returns an `FfiParam` that is either void or an error value.
+unsafe extern "C" fn turing_instance_set_random_seed(
+    turing: *mut TuringInstance,
+    seed: u64,
+) -> FfiParam {
+    let turing = unsafe { &mut *turing };
+    match turing.set_random_seed(seed) {
+        Ok(()) => Param::Void,
+        Err(e) => Param::Error(format!("{e}")),
+    }
+    .to_rs_param()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// When set, functions whose capability isn't loaded aren't bound by the next `turing_script_load`.
+unsafe extern "C" fn turing_instance_set_hide_inactive_capabilities(
+    turing: *mut TuringInstance,
+    hide: bool,
+) {
+    let turing = unsafe { &mut *turing };
+    turing.set_hide_inactive_capabilities(hide);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `capability` must be a valid C string pointer of valid `UTF-8`.
+/// Enables `capability` for the loaded script, starting with its next call.
+unsafe extern "C" fn turing_instance_add_capability(
+    turing: *mut TuringInstance,
+    capability: *const c_char,
+) {
+    let turing = unsafe { &mut *turing };
+    let capability = unsafe { CStr::from_ptr(capability).to_string_lossy() };
+    turing.add_capability(capability);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `capability` must be a valid C string pointer of valid `UTF-8`.
+/// Disables `capability` for the loaded script, starting with its next call.
+unsafe extern "C" fn turing_instance_remove_capability(
+    turing: *mut TuringInstance,
+    capability: *const c_char,
+) {
+    let turing = unsafe { &mut *turing };
+    let capability = unsafe { CStr::from_ptr(capability).to_string_lossy() };
+    turing.remove_capability(capability);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `prefix` must be a valid C string or null, which removes the prefix.
+/// Script output is logged as `[prefix] message` for scripts loaded after this call.
+unsafe extern "C" fn turing_instance_set_log_prefix(
+    turing: *mut TuringInstance,
+    prefix: *const c_char,
+) {
+    let turing = unsafe { &mut *turing };
+    let prefix = (!prefix.is_null()).then(|| {
+        unsafe { CStr::from_ptr(prefix) }
+            .to_string_lossy()
+            .into_owned()
+    });
+    turing.set_log_prefix(prefix);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `dir` must be a valid C string or null, which stops writing compiled modules to disk.
+/// Keeps compiled wasm modules in `dir` as well as in memory, so they aren't recompiled after a restart.
+/// Does nothing if turing was built without wasm support.
+unsafe extern "C" fn turing_instance_set_module_cache_dir(
+    turing: *mut TuringInstance,
+    dir: *const c_char,
+) {
+    let turing = unsafe { &mut *turing };
+    let dir = (!dir.is_null()).then(|| {
+        PathBuf::from(
+            unsafe { CStr::from_ptr(dir) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    });
+    #[cfg(feature = "wasm")]
+    turing.module_cache_mut().set_dir(dir);
+    #[cfg(not(feature = "wasm"))]
+    let _ = (turing, dir);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Limits how many instructions a single lua call may run, 0 removes the limit.
+unsafe extern "C" fn turing_instance_set_lua_instruction_budget(
+    turing: *mut TuringInstance,
+    budget: u64,
+) {
+    let turing = unsafe { &mut *turing };
+    turing.set_lua_instruction_budget((budget != 0).then_some(budget));
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// `capability` must be a valid pointer to a UTF-8 C-String.
+/// Limits how many host functions with `capability` a single script call may call, 0 removes the limit.
+unsafe extern "C" fn turing_instance_set_callback_budget(
+    turing: *mut TuringInstance,
+    capability: *const c_char,
+    max_calls: u32,
+) {
+    let turing = unsafe { &*turing };
+    let capability = unsafe { CStr::from_ptr(capability).to_string_lossy() };
+    turing.set_callback_budget(capability, (max_calls != 0).then_some(max_calls));
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`.
+/// Returns how many host functions the last script call called, including ones over budget.
+unsafe extern "C" fn turing_instance_callback_calls(turing: *mut TuringInstance) -> u32 {
+    let turing = unsafe { &*turing };
+    turing.callback_calls()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`
+unsafe extern "C" fn turing_delete_instance(turing: *mut TuringInstance) {
+    let _ = unsafe { Box::from_raw(turing) };
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn turing_create_params(size: u32) -> *mut Params {
+    Box::into_raw(Box::new(if size == 0 {
+        Params::new()
+    } else {
+        Params::of_size(size)
+    }))
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `params` must point to `count` valid `FfiParam`s, or be empty.
+/// Copies every param into a new `Params` in one call, the caller still owns and frees the originals.
+/// Returns null if a param can't be converted.
+unsafe extern "C" fn turing_create_params_from_array(params: FfiParamArray) -> *mut Params {
+    match params.as_params::<CsFns>() {
+        Ok(params) => Box::into_raw(Box::new(params)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn turing_params_get_size(params: *mut Params) -> u32 {
+    let params = unsafe { &*params };
+    params.len()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `params` must be a valid pointer to a `Params`.
+unsafe extern "C" fn turing_params_clear(params: *mut Params) {
+    let params = unsafe { &mut *params };
+    params.clear();
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `params` must be a valid pointer to a `Params`.
+/// This function silently fails if params is null.
+unsafe extern "C" fn turing_params_add_param(params: *mut Params, param: FfiParam) {
+    let params = unsafe { &mut *params };
+    let param = param
+        .as_param::<CsFns>()
+        .unwrap_or_else(|e| Param::Error(format!("{e}")));
+    params.push(param);
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `params` must be a valid pointer to a `Params` and must not be used after this call.
+unsafe extern "C" fn turing_delete_params(params: *mut Params) {
+    let _ = unsafe { Box::from_raw(params) };
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `params` must be a valid pointer to a `Params`.
+/// Returns a copy of an `FfiParam` which may be an error value if an error occurs.
+unsafe extern "C" fn turing_params_get_param(params: *mut Params, index: u32) -> FfiParam {
+    let params = unsafe { &*params };
 
     if let Some(p) = params.get(index as usize) {
         p.clone().to_rs_param()
@@ -482,23 +1527,69 @@ unsafe extern "C" fn turing_params_get_param(params: *mut Params, index: u32) ->
 #[unsafe(no_mangle)]
 /// # Safety
 /// `params` must be a valid pointer to a `Params`.
-/// Sets the param at the specified index.
-///
-unsafe extern "C" fn turing_params_set_param(params: *mut Params, index: u32, param: FfiParam) {
+/// Sets the param at the specified index, dropping the one it replaces.
+/// Returns a void param on success, or an error param if the index is out of bounds.
+/// The caller owns the returned param and frees it with `turing_delete_param`.
+unsafe extern "C" fn turing_params_set_param(
+    params: *mut Params,
+    index: u32,
+    param: FfiParam,
+) -> FfiParam {
     let params = unsafe { &mut *params };
 
-    let Some(p) = params.get_mut(index as usize) else {
-        return;
-    };
+    let param = param
+        .as_param::<CsFns>()
+        .unwrap_or_else(|e| Param::Error(format!("{e}")));
 
-    let param = param.as_param::<CsFns>().unwrap();
-    *p = param;
+    match params.set(index, param) {
+        Ok(()) => Param::Void,
+        Err(e) => Param::Error(format!("{e}")),
+    }
+    .to_rs_param()
 }
 
 #[unsafe(no_mangle)]
 /// This will correctly (probably) free an FfiParam including rust and ext strings
 extern "C" fn turing_delete_param(param: FfiParam) {
-    let _ = param.into_param::<CsFns>().unwrap();
+    let _ = param.into_param::<CsFns>();
+}
+
+#[unsafe(no_mangle)]
+/// Makes `params` the active params for the calling thread, used by `turing_add_param_to_active`.
+/// Pass null to unbind. Binding does not take ownership, `params` must still be freed with `turing_delete_params`,
+/// and must be unbound or rebound before that happens.
+/// # Safety
+/// `params` must be null or a valid pointer to a `Params`.
+unsafe extern "C" fn turing_bind_params(params: *mut Params) {
+    ACTIVE_PARAMS.set(params);
+}
+
+#[unsafe(no_mangle)]
+/// Returns the params bound with `turing_bind_params` on the calling thread, or null if none are bound.
+/// The returned pointer can be passed anywhere a `Params` pointer is expected.
+extern "C" fn turing_active_params() -> *mut Params {
+    ACTIVE_PARAMS.get()
+}
+
+#[unsafe(no_mangle)]
+/// Pushes `param` onto the active params of the calling thread.
+/// Returns an error if no params are bound. The caller is responsible for freeing the returned string if not null.
+/// # Safety
+/// The bound params must still be valid.
+unsafe extern "C" fn turing_add_param_to_active(param: FfiParam) -> *const c_char {
+    let params = ACTIVE_PARAMS.get();
+    if params.is_null() {
+        return escaped_cstring("No active params are bound").into_raw();
+    }
+    let params = unsafe { &mut *params };
+
+    match param.as_param::<CsFns>() {
+        Ok(param) => {
+            params.push(param);
+            ptr::null()
+        }
+        Err(e) => escaped_cstring(&format!("{e}")).into_raw(),
+    }
 }
 
 #[unsafe(no_mangle)]
@@ -598,7 +1689,7 @@ unsafe extern "C" fn turing_versions_get_mod_name(
         return ptr::null();
     };
 
-    CString::new(name.clone()).unwrap().into_raw()
+    escaped_cstring(name).into_raw()
 }
 
 #[unsafe(no_mangle)]
@@ -617,3 +1708,543 @@ unsafe extern "C" fn turing_versions_get_mod_version_indexed(
 
     v.as_u64()
 }
+
+#[unsafe(no_mangle)]
+/// Formats a packed version as `major.minor.patch`, for logging.
+/// The caller is responsible for freeing the returned string.
+extern "C" fn turing_version_to_string(version: u64) -> *const c_char {
+    escaped_cstring(&Semver::from_u64(version).to_string()).into_raw()
+}
+
+#[unsafe(no_mangle)]
+/// Returns the `FfiParam` layout version this library was built with, see [`FFI_LAYOUT_VERSION`].
+extern "C" fn turing_ffi_layout_version() -> u32 {
+    FFI_LAYOUT_VERSION
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`
+/// Lists the registered functions for `turing_fns_get_*` and returns how many there are.
+/// They stay readable until the next `turing_fns_count`, functions registered since aren't listed.
+unsafe extern "C" fn turing_fns_count(turing: *mut TuringInstance) -> u32 {
+    let turing = unsafe { &mut *turing };
+    turing.take_functions_snapshot() as u32
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`
+/// `index` must be below the last `turing_fns_count`. Functions are sorted by name.
+/// Returns null if out of range. The caller is responsible for freeing the returned string.
+unsafe extern "C" fn turing_fns_get_name(turing: *mut TuringInstance, index: u32) -> *const c_char {
+    let turing = unsafe { &*turing };
+
+    let Some(info) = turing.functions_snapshot().get(index as usize) else {
+        return ptr::null();
+    };
+
+    escaped_cstring(&info.name).into_raw()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`
+/// `index` must be below the last `turing_fns_count`. Functions are sorted by name.
+/// Returns null if out of range. The caller is responsible for freeing the returned string.
+unsafe extern "C" fn turing_fns_get_capability(
+    turing: *mut TuringInstance,
+    index: u32,
+) -> *const c_char {
+    let turing = unsafe { &*turing };
+
+    let Some(info) = turing.functions_snapshot().get(index as usize) else {
+        return ptr::null();
+    };
+
+    escaped_cstring(&info.capability).into_raw()
+}
+
+#[unsafe(no_mangle)]
+/// # Safety
+/// `turing` must be a valid pointer to a `Turing`
+/// `index` must be below the last `turing_fns_count`. Functions are sorted by name.
+/// Returns null if out of range or the function has no doc comment.
+/// The caller is responsible for freeing the returned string.
+unsafe extern "C" fn turing_fns_get_doc(turing: *mut TuringInstance, index: u32) -> *const c_char {
+    let turing = unsafe { &*turing };
+
+    let Some(doc) = turing
+        .functions_snapshot()
+        .get(index as usize)
+        .and_then(|info| info.doc_comment.as_deref())
+    else {
+        return ptr::null();
+    };
+
+    escaped_cstring(doc).into_raw()
+}
+
+c_exports! {
+    fn turing_install_panic_hook(crash_dmp_out: "const char*");
+    fn turing_free_string(ptr: "char*");
+    fn turing_free_of_type(ptr: "void*", typ: "FreeableDataType");
+    fn turing_free_slice_of_type(ptr: "void*", typ: "FreeableDataType", count: "uint32_t");
+    fn turing_free_u32_buffer(buf: "U32Buffer");
+    fn turing_register_function(name: "const char*", callback: "const void*");
+    fn turing_create_fn_map() -> "ScriptFnMap*";
+    fn turing_fn_map_add_data(map: "ScriptFnMap*", name: "const char*", data: "ScriptFnMetadata*") -> "const char*";
+    fn turing_fn_map_copy(map: "ScriptFnMap*") -> "ScriptFnMap*";
+    fn turing_delete_fn_map(map: "ScriptFnMap*");
+    fn turing_create_script_data(capability: "const char*", callback: "ScriptCallback", doc_comment: "const char*") -> "ScriptFnMetadata*";
+    fn turing_script_data_add_param_type(data: "ScriptFnMetadata*", params: "DataType*", param_names: "const char**", param_type_names: "const char**", params_count: "uint32_t") -> "const char*";
+    fn turing_script_data_add_optional_param(data: "ScriptFnMetadata*", param_type: "DataType", name: "const char*", default: "FfiParam") -> "const char*";
+    fn turing_script_data_set_return_type(data: "ScriptFnMetadata*", return_type: "DataType", type_names: "const char*") -> "const char*";
+    fn turing_script_data_set_capability_default(data: "ScriptFnMetadata*", default: "FfiParam") -> "const char*";
+    fn turing_script_data_set_variadic(data: "ScriptFnMetadata*", variadic: "bool");
+    fn turing_script_data_set_fallible(data: "ScriptFnMetadata*", fallible: "bool");
+    fn turing_script_data_set_explicit_symbol(data: "ScriptFnMetadata*", symbol: "const char*");
+    fn turing_script_load(turing: "TuringInstance*", source: "const char*", loaded_capabilities: "const char**", capability_count: "uint32_t") -> "FfiParam";
+    fn turing_script_load_bytes(turing: "TuringInstance*", bytes: "const uint8_t*", length: "uint32_t", kind: "uint32_t", loaded_capabilities: "const char**", capability_count: "uint32_t") -> "FfiParam";
+    fn turing_script_load_checked(turing: "TuringInstance*", source: "const char*", loaded_capabilities: "const char**", capability_count: "uint32_t", versions: "VersionTable*") -> "FfiParam";
+    fn turing_mod_load(turing: "TuringInstance*", dir: "const char*", allowed_capabilities: "const char**", capability_count: "uint32_t", versions: "VersionTable*") -> "FfiParam";
+    fn turing_mod_get_name(turing: "TuringInstance*") -> "const char*";
+    fn turing_mod_get_version(turing: "TuringInstance*") -> "uint64_t";
+    fn turing_script_unload(turing: "TuringInstance*");
+    fn turing_script_reset_instance(turing: "TuringInstance*") -> "FfiParam";
+    fn turing_enable_hot_reload(turing: "TuringInstance*") -> "FfiParam";
+    fn turing_poll_reload(turing: "TuringInstance*") -> "bool";
+    fn turing_storage_set(turing: "TuringInstance*", mod_id: "const char*", key: "const char*", value: "const char*");
+    fn turing_storage_get(turing: "TuringInstance*", mod_id: "const char*", key: "const char*") -> "const char*";
+    fn turing_storage_serialize(turing: "TuringInstance*") -> "const char*";
+    fn turing_storage_deserialize(turing: "TuringInstance*", json: "const char*") -> "FfiParam";
+    fn turing_script_call_fn(turing: "TuringInstance*", name_key: "CacheKey", params: "Params*", expected_return_type: "DataType") -> "FfiParam";
+    fn turing_script_call_fn_by_name(turing: "TuringInstance*", name: "const char*", params: "Params*", expected_return_type: "DataType") -> "FfiParam";
+    fn turing_script_call_fn_packed(turing: "TuringInstance*", name_key: "CacheKey", params: "FfiParamArray", expected_return_type: "DataType") -> "FfiParam";
+    fn turing_instance_call_handle(turing: "TuringInstance*") -> "TuringCallHandle*";
+    fn turing_delete_call_handle(handle: "TuringCallHandle*");
+    fn turing_script_call_fn_reentrant(handle: "TuringCallHandle*", name_key: "CacheKey", params: "Params*", expected_return_type: "DataType") -> "FfiParam";
+    fn turing_engine_kind(turing: "TuringInstance*") -> "uint32_t";
+    fn turing_call_is_cancelled() -> "bool";
+    fn turing_script_queue_call(handle: "TuringCallHandle*", name_key: "CacheKey", params: "Params*", expected_return_type: "DataType");
+    fn turing_script_get_fn_name(turing: "TuringInstance*", name: "const char*") -> "CacheKey";
+    fn turing_script_get_export_signature(turing: "TuringInstance*", key: "CacheKey") -> "const char*";
+    fn turing_script_fast_call_update(turing: "TuringInstance*", delta_time: "float") -> "const char*";
+    fn turing_script_fast_call_fixed_update(turing: "TuringInstance*", delta_time: "float") -> "const char*";
+    fn turing_script_register_fast_call(turing: "TuringInstance*", name: "const char*") -> "FfiParam";
+    fn turing_script_invoke_fast_call(turing: "TuringInstance*", handle: "uint32_t", delta_time: "float") -> "const char*";
+    fn turing_script_meta_count(turing: "TuringInstance*") -> "uint32_t";
+    fn turing_script_meta_get_key(turing: "TuringInstance*", index: "uint32_t") -> "const char*";
+    fn turing_script_meta_get(turing: "TuringInstance*", key: "const char*") -> "const char*";
+    fn turing_script_take_string(turing: "TuringInstance*", id: "uint32_t") -> "const char*";
+    fn turing_script_module_bytes(turing: "TuringInstance*", out: "uint8_t*", capacity: "uint32_t") -> "FfiParam";
+    fn turing_script_compiled_bytes(turing: "TuringInstance*", out: "uint8_t*", capacity: "uint32_t") -> "FfiParam";
+    fn turing_script_dump_sec(out_dir: "const char*", wasm_fns_ptr: "ScriptFnMap*", versions: "VersionTable*", format: "SpecFormat") -> "const char*";
+    fn turing_script_specs_json(wasm_fns_ptr: "ScriptFnMap*", versions: "VersionTable*") -> "const char*";
+    fn turing_create_instance(wasm_fns_ptr: "ScriptFnMap*") -> "TuringInitResult*";
+    fn turing_instance_check_error(res_ptr: "TuringInitResult*") -> "const char*";
+    fn turing_instance_unwrap(res_ptr: "TuringInitResult*") -> "TuringInstance*";
+    fn turing_instance_set_dispatch(turing: "TuringInstance*", dispatch: "DispatchCallback");
+    fn turing_instance_set_missing_function(turing: "TuringInstance*", on_missing: "DispatchCallback");
+    fn turing_instance_set_hide_inactive_capabilities(turing: "TuringInstance*", hide: "bool");
+    fn turing_instance_add_capability(turing: "TuringInstance*", capability: "const char*");
+    fn turing_instance_remove_capability(turing: "TuringInstance*", capability: "const char*");
+    fn turing_instance_set_lua_instruction_budget(turing: "TuringInstance*", budget: "uint64_t");
+    fn turing_instance_set_callback_budget(turing: "TuringInstance*", capability: "const char*", max_calls: "uint32_t");
+    fn turing_instance_callback_calls(turing: "TuringInstance*") -> "uint32_t";
+    fn turing_instance_set_log_prefix(turing: "TuringInstance*", prefix: "const char*");
+    fn turing_instance_set_module_cache_dir(turing: "TuringInstance*", dir: "const char*");
+    fn turing_instance_set_log_level(turing: "TuringInstance*", level: "uint32_t") -> "FfiParam";
+    fn turing_instance_set_nan_policy(turing: "TuringInstance*", policy: "uint32_t") -> "FfiParam";
+    fn turing_instance_set_wasm_max_stack(turing: "TuringInstance*", bytes: "uint32_t") -> "FfiParam";
+    fn turing_instance_set_wasm_output_buffer(turing: "TuringInstance*", bytes: "uint32_t");
+    fn turing_instance_set_storage_quota(turing: "TuringInstance*", bytes: "uint32_t");
+    fn turing_instance_set_deterministic(turing: "TuringInstance*", deterministic: "bool");
+    fn turing_instance_set_sequential_object_ids(turing: "TuringInstance*", sequential: "bool");
+    fn turing_instance_set_profiling(turing: "TuringInstance*", profiling: "bool");
+    fn turing_stats_count(turing: "TuringInstance*") -> "uint32_t";
+    fn turing_stats_get(turing: "TuringInstance*", index: "uint32_t", out_calls: "uint64_t*", out_total_ns: "uint64_t*", out_max_ns: "uint64_t*") -> "const char*";
+    fn turing_instance_set_random_seed(turing: "TuringInstance*", seed: "uint64_t") -> "FfiParam";
+    fn turing_delete_instance(turing: "TuringInstance*");
+    fn turing_create_params(size: "uint32_t") -> "Params*";
+    fn turing_create_params_from_array(params: "FfiParamArray") -> "Params*";
+    fn turing_params_get_size(params: "Params*") -> "uint32_t";
+    fn turing_params_clear(params: "Params*");
+    fn turing_params_add_param(params: "Params*", param: "FfiParam");
+    fn turing_delete_params(params: "Params*");
+    fn turing_params_get_param(params: "Params*", index: "uint32_t") -> "FfiParam";
+    fn turing_params_set_param(params: "Params*", index: "uint32_t", param: "FfiParam") -> "FfiParam";
+    fn turing_delete_param(param: "FfiParam");
+    fn turing_bind_params(params: "Params*");
+    fn turing_active_params() -> "Params*";
+    fn turing_add_param_to_active(param: "FfiParam") -> "const char*";
+    fn turing_versions_get(turing: "TuringInstance*") -> "VersionTable*";
+    fn turing_versions_create() -> "VersionTable*";
+    fn turing_versions_set_api_version(versions: "VersionTable*", name: "const char*", packed_version: "uint64_t");
+    fn turing_versions_contains_mod(versions: "VersionTable*", name: "const char*") -> "bool";
+    fn turing_versions_get_mod_version(versions: "VersionTable*", name: "const char*") -> "uint64_t";
+    fn turing_delete_versions(versions: "VersionTable*");
+    fn turing_versions_get_count(versions: "VersionTable*") -> "uint32_t";
+    fn turing_versions_get_mod_name(versions: "VersionTable*", index: "uint32_t") -> "const char*";
+    fn turing_versions_get_mod_version_indexed(versions: "VersionTable*", index: "uint32_t") -> "uint64_t";
+    fn turing_version_to_string(version: "uint64_t") -> "const char*";
+    fn turing_ffi_layout_version() -> "uint32_t";
+    fn turing_fns_count(turing: "TuringInstance*") -> "uint32_t";
+    fn turing_fns_get_name(turing: "TuringInstance*", index: "uint32_t") -> "const char*";
+    fn turing_fns_get_capability(turing: "TuringInstance*", index: "uint32_t") -> "const char*";
+    fn turing_fns_get_doc(turing: "TuringInstance*", index: "uint32_t") -> "const char*";
+}
+
+#[cfg(test)]
+mod ffi_tests {
+    use super::*;
+    use crate::HostRng;
+    use crate::engine::FUNCTION_NOT_FOUND_MESSAGE;
+    use crate::interop::params::INTERIOR_NUL_MESSAGE;
+
+    /// An instance running a guest with no imports that exports `math_ops_test(a, b) = a * b`
+    #[cfg(feature = "wasm")]
+    fn math_ops_setup() -> Turing<CsFns> {
+        let wasm = wat::parse_str(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "math_ops_test") (param f32 f32) (result f32)
+                    local.get 0
+                    local.get 1
+                    f32.mul))"#,
+        )
+        .unwrap();
+        let mut turing = Turing::<CsFns>::new().build().unwrap();
+        turing
+            .load_script_bytes(&wasm, EngineKind::Wasm, &["test"])
+            .unwrap();
+        turing
+    }
+
+    #[test]
+    fn test_active_params_unbound() {
+        unsafe { turing_bind_params(ptr::null_mut()) };
+        assert!(turing_active_params().is_null());
+
+        let err = unsafe { turing_add_param_to_active(Param::I32(1).to_rs_param()) };
+        assert!(!err.is_null());
+        unsafe { turing_free_string(err as *mut c_char) };
+    }
+
+    #[test]
+    fn test_interior_nul_params() {
+        let params = turing_create_params(0);
+
+        // a string with a nul can't cross as a C string, so it arrives as an error param
+        let param = Param::String("a\0b".to_string()).to_rs_param();
+        unsafe { turing_params_add_param(params, param) };
+        let res = unsafe { turing_params_get_param(params, 0) };
+        assert_eq!(
+            res.into_param::<CsFns>().unwrap(),
+            Param::Error(INTERIOR_NUL_MESSAGE.to_string())
+        );
+
+        unsafe { &mut *params }.push(Param::String("c\0d".to_string()));
+        let res = unsafe { turing_params_get_param(params, 1) };
+        assert_eq!(
+            res.into_param::<CsFns>().unwrap(),
+            Param::Error(INTERIOR_NUL_MESSAGE.to_string())
+        );
+
+        assert_eq!(escaped_cstring("e\0f").as_bytes(), b"e\\0f");
+        unsafe { turing_delete_params(params) };
+    }
+
+    #[test]
+    fn test_adversarial_strings_cross_ffi() {
+        let mut rng = HostRng::new(0x9e37_79b9);
+        for _ in 0..256 {
+            let s = (0..16)
+                .map(|_| char::from_u32(rng.range(0, 0x2ff) as u32).unwrap_or('\0'))
+                .collect::<String>();
+
+            let expected = if s.contains('\0') {
+                Param::Error(INTERIOR_NUL_MESSAGE.to_string())
+            } else {
+                Param::String(s.clone())
+            };
+            let res = Param::String(s.clone()).to_rs_param();
+            assert_eq!(res.into_param::<CsFns>().unwrap(), expected);
+
+            let res = Param::Error(s.clone()).to_rs_param();
+            assert_eq!(
+                res.into_param::<CsFns>().unwrap(),
+                Param::Error(s.replace('\0', "\\0"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_version_to_string() {
+        let s = turing_version_to_string(Semver::new(10, 0, 255).as_u64());
+        assert_eq!(unsafe { CStr::from_ptr(s) }, c"10.0.255");
+        unsafe { turing_free_string(s as *mut c_char) };
+    }
+
+    #[test]
+    fn test_ffi_layout_version() {
+        assert_eq!(turing_ffi_layout_version(), FFI_LAYOUT_VERSION);
+    }
+
+    #[test]
+    fn test_fn_map_rejects_duplicates() {
+        let map = turing_create_fn_map();
+        for expect_err in [false, true] {
+            let data = unsafe { turing_create_script_data(c"test".as_ptr(), None, ptr::null()) };
+            let err =
+                unsafe { turing_script_data_set_return_type(data, DataType::I32, ptr::null()) };
+            assert!(err.is_null());
+            // only one return type is supported
+            let err =
+                unsafe { turing_script_data_set_return_type(data, DataType::F32, ptr::null()) };
+            assert!(!err.is_null());
+            unsafe { turing_free_string(err as *mut c_char) };
+
+            let err = unsafe { turing_fn_map_add_data(map, c"add".as_ptr(), data) };
+            assert_eq!(!err.is_null(), expect_err);
+            if !err.is_null() {
+                unsafe { turing_free_string(err as *mut c_char) };
+            }
+        }
+
+        let map = unsafe { Box::from_raw(map) };
+        assert_eq!(map.len(), 1);
+        assert_eq!(map["add"].return_type.len(), 1);
+    }
+
+    #[test]
+    fn test_fns_snapshot() {
+        let mut setup = Turing::<CsFns>::new();
+        let metadata = ScriptFnMetadata::dispatched("gfx".to_owned(), Some("Draws".to_owned()));
+        setup.add_function("draw", metadata).unwrap();
+        let metadata = ScriptFnMetadata::dispatched("audio".to_owned(), None);
+        setup.add_function("beep", metadata).unwrap();
+        let mut turing = setup.build().unwrap();
+
+        assert_eq!(unsafe { turing_fns_count(&mut turing) }, 2);
+        let read = |s: *const c_char| {
+            let out = unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned();
+            unsafe { turing_free_string(s as *mut c_char) };
+            out
+        };
+        assert_eq!(read(unsafe { turing_fns_get_name(&mut turing, 0) }), "beep");
+        assert_eq!(
+            read(unsafe { turing_fns_get_capability(&mut turing, 0) }),
+            "audio"
+        );
+        assert!(unsafe { turing_fns_get_doc(&mut turing, 0) }.is_null());
+        assert_eq!(read(unsafe { turing_fns_get_doc(&mut turing, 1) }), "Draws");
+        assert!(unsafe { turing_fns_get_name(&mut turing, 2) }.is_null());
+    }
+
+    #[test]
+    fn test_params_set_param() {
+        let params = turing_create_params(0);
+        unsafe { turing_params_add_param(params, Param::I32(1).to_rs_param()) };
+        assert_eq!(turing_params_get_size(params), 1);
+
+        let res = unsafe { turing_params_set_param(params, 0, Param::I32(2).to_rs_param()) };
+        assert_eq!(res.into_param::<CsFns>().unwrap(), Param::Void);
+        let res = unsafe { turing_params_get_param(params, 0) };
+        assert_eq!(res.into_param::<CsFns>().unwrap(), Param::I32(2));
+
+        let res = unsafe { turing_params_set_param(params, 1, Param::I32(3).to_rs_param()) };
+        assert!(matches!(
+            res.into_param::<CsFns>().unwrap(),
+            Param::Error(e) if e.contains("out of bounds")
+        ));
+        assert_eq!(turing_params_get_size(params), 1);
+
+        unsafe { turing_params_clear(params) };
+        assert_eq!(turing_params_get_size(params), 0);
+        unsafe { turing_params_add_param(params, Param::I32(4).to_rs_param()) };
+        let res = unsafe { turing_params_get_param(params, 0) };
+        assert_eq!(res.into_param::<CsFns>().unwrap(), Param::I32(4));
+
+        unsafe { turing_delete_params(params) };
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_active_params_build_then_call() {
+        let params = turing_create_params(0);
+        unsafe { turing_bind_params(params) };
+        assert_eq!(turing_active_params(), params);
+
+        for x in [3.5, 5.0] {
+            let err = unsafe { turing_add_param_to_active(Param::F32(x).to_rs_param()) };
+            assert!(err.is_null());
+        }
+        assert_eq!(turing_params_get_size(turing_active_params()), 2);
+
+        let mut turing = math_ops_setup();
+        let key = unsafe { turing_script_get_fn_name(&mut turing, c"math_ops_test".as_ptr()) };
+
+        let res = unsafe {
+            turing_script_call_fn(&mut turing, key, turing_active_params(), DataType::F32)
+        };
+        assert_eq!(res.into_param::<CsFns>().unwrap(), Param::F32(17.5));
+
+        unsafe {
+            turing_bind_params(ptr::null_mut());
+            turing_delete_params(params);
+        }
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_call_fn_by_name() {
+        let mut turing = math_ops_setup();
+
+        let params = turing_create_params(2);
+        unsafe {
+            turing_params_add_param(params, Param::F32(3.5).to_rs_param());
+            turing_params_add_param(params, Param::F32(5.0).to_rs_param());
+        }
+        let res = unsafe {
+            turing_script_call_fn_by_name(
+                &mut turing,
+                c"math_ops_test".as_ptr(),
+                params,
+                DataType::F32,
+            )
+        };
+        assert_eq!(res.into_param::<CsFns>().unwrap(), Param::F32(17.5));
+        assert_eq!(turing_params_get_size(params), 0);
+
+        let res = unsafe {
+            turing_script_call_fn_by_name(
+                &mut turing,
+                c"not_a_function".as_ptr(),
+                params,
+                DataType::Void,
+            )
+        };
+        assert!(matches!(
+            res.into_param::<CsFns>().unwrap(),
+            Param::Error(e) if e.contains(FUNCTION_NOT_FOUND_MESSAGE)
+        ));
+        unsafe { turing_delete_params(params) };
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_packed_call_matches_incremental() {
+        let mut turing = math_ops_setup();
+        let key = unsafe { turing_script_get_fn_name(&mut turing, c"math_ops_test".as_ptr()) };
+
+        let mut args = Params::new();
+        args.push(Param::F32(3.5));
+        args.push(Param::F32(5.0));
+
+        for _ in 0..100 {
+            // incremental: create + one add per argument + call + delete, 5 ffi transitions
+            let params = turing_create_params(0);
+            for p in args.clone() {
+                unsafe { turing_params_add_param(params, p.to_rs_param()) };
+            }
+            let incremental = unsafe {
+                turing_script_call_fn(&mut turing, key, params, DataType::F32)
+                    .into_param::<CsFns>()
+                    .unwrap()
+            };
+            unsafe { turing_delete_params(params) };
+
+            // packed: a single transition regardless of argument count
+            let packed = FfiParamArray::from_params_borrowed::<CsFns, _>(&args, |array| unsafe {
+                turing_script_call_fn_packed(&mut turing, key, array, DataType::F32)
+                    .into_param::<CsFns>()
+                    .unwrap()
+            });
+
+            assert_eq!(incremental, Param::F32(17.5));
+            assert_eq!(packed, incremental);
+        }
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_params_reused_across_calls() {
+        let mut turing = math_ops_setup();
+        let key = unsafe { turing_script_get_fn_name(&mut turing, c"math_ops_test".as_ptr()) };
+
+        // one Params for every call, refilled after each call drains it
+        let params = turing_create_params(2);
+        for i in 0..100_000 {
+            unsafe {
+                turing_params_add_param(params, Param::F32(i as f32).to_rs_param());
+                turing_params_add_param(params, Param::F32(2.0).to_rs_param());
+            }
+            let res = unsafe { turing_script_call_fn(&mut turing, key, params, DataType::F32) };
+            assert_eq!(
+                res.into_param::<CsFns>().unwrap(),
+                Param::F32(i as f32 * 2.0)
+            );
+            assert_eq!(turing_params_get_size(params), 0);
+        }
+        unsafe { turing_delete_params(params) };
+    }
+
+    #[test]
+    fn test_drain_params_keeps_capacity() {
+        let params = turing_create_params(8);
+        for i in 0..6 {
+            unsafe { turing_params_add_param(params, Param::I32(i).to_rs_param()) };
+        }
+
+        let drained = unsafe { drain_call_params(params) };
+        assert_eq!(drained.len(), 6);
+        assert_eq!(drained.get(5), Some(&Param::I32(5)));
+
+        let params_ref = unsafe { &*params };
+        assert!(params_ref.is_empty());
+        assert!(params_ref.capacity() >= 8);
+        unsafe { turing_delete_params(params) };
+    }
+
+    #[test]
+    fn test_create_params_from_array() {
+        let mut args = Params::new();
+        args.push(Param::I32(7));
+        args.push(Param::String("copied".to_string()));
+
+        let params = FfiParamArray::from_params_borrowed::<CsFns, _>(&args, |array| unsafe {
+            turing_create_params_from_array(array)
+        });
+        assert!(!params.is_null());
+        assert_eq!(turing_params_get_size(params), 2);
+        for (i, expected) in args.clone().into_iter().enumerate() {
+            let param = unsafe { turing_params_get_param(params, i as u32) };
+            assert_eq!(param.into_param::<CsFns>().unwrap(), expected);
+        }
+        unsafe { turing_delete_params(params) };
+
+        let empty = unsafe { turing_create_params_from_array(FfiParamArray::empty()) };
+        assert_eq!(turing_params_get_size(empty), 0);
+        unsafe { turing_delete_params(empty) };
+    }
+
+    static DEBUG_LOGS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    extern "C" fn count_debug_log(_: *const c_char) {
+        DEBUG_LOGS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_register_function_across_threads() {
+        std::thread::spawn(|| {
+            turing_register_function(c"log_debug".as_ptr(), count_debug_log as *const c_void)
+        })
+        .join()
+        .unwrap();
+
+        let before = DEBUG_LOGS.load(std::sync::atomic::Ordering::SeqCst);
+        <CsFns as crate::ExternalFunctions>::log_debug("linked on another thread");
+        assert!(DEBUG_LOGS.load(std::sync::atomic::Ordering::SeqCst) > before);
+    }
+}
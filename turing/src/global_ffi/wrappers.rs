@@ -1,17 +1,32 @@
-#![allow(static_mut_refs, clippy::new_without_default)]
+#![allow(clippy::new_without_default)]
 
-use crate::ExternalFunctions;
-use crate::interop::params::FreeableDataType;
-use crate::interop::types::U32Buffer;
-use std::ffi::{CString, c_char, c_void};
+use crate::engine::types::ErrorKind;
+use crate::interop::params::{FfiParam, FreeableDataType, Param, escaped_cstring};
+use crate::interop::types::{F32Buffer, U32Buffer};
+use crate::{ExternalFunctions, ScriptFnKey};
+use parking_lot::RwLock;
+use std::ffi::{CStr, c_char, c_void};
 use std::mem;
+use std::path::Path;
 
 pub type CsAbort = extern "C" fn(*const c_char, *const c_char);
 pub type CsLog = extern "C" fn(*const c_char);
 pub type CsFree = extern "C" fn(*const c_char);
 pub type CsFreeOfType = extern "C" fn(*const c_void, u32);
 pub type CsFreeBuffer = extern "C" fn(U32Buffer);
+pub type CsFreeF32Buffer = extern "C" fn(F32Buffer);
+/// Receives the cache key and result of a queued call. The result must be freed with `turing_delete_param`
+pub type CsCallCompleted = extern "C" fn(u32, FfiParam);
+/// Duplicates a host object, returning the new object or null
+pub type CsCloneObject = extern "C" fn(*const c_void) -> *const c_void;
+/// Takes ownership of a host object a script returned as an `OWNED_OBJECT`
+pub type CsTakeObject = extern "C" fn(*const c_void);
+/// Receives the path of a script `turing_poll_reload` just reloaded
+pub type CsScriptReloaded = extern "C" fn(*const c_char);
+/// Receives the capability, `ErrorKind` and message of a failed call from a script into the host
+pub type CsScriptError = extern "C" fn(*const c_char, u32, *const c_char);
 
+#[derive(Clone, Copy)]
 pub struct CsFns {
     pub abort: CsAbort,
     pub log_info: CsLog,
@@ -21,6 +36,12 @@ pub struct CsFns {
     pub free_cs_string: CsFree,
     pub free_of_type: CsFreeOfType,
     pub free_u32_buffer: CsFreeBuffer,
+    pub free_f32_buffer: CsFreeF32Buffer,
+    pub call_completed: CsCallCompleted,
+    pub clone_object: CsCloneObject,
+    pub take_object: CsTakeObject,
+    pub on_script_reloaded: CsScriptReloaded,
+    pub on_script_error: CsScriptError,
 }
 
 extern "C" fn null_abort(_: *const c_char, _: *const c_char) {
@@ -40,6 +61,30 @@ extern "C" fn null_free_u32_buffer(_: U32Buffer) {
     eprintln!("null free_u32_buffer called, exiting process.");
     std::process::abort()
 }
+extern "C" fn null_free_f32_buffer(_: F32Buffer) {
+    eprintln!("null free_f32_buffer called, exiting process.");
+    std::process::abort()
+}
+extern "C" fn null_call_completed(_: u32, result: FfiParam) {
+    let _ = result.into_param::<CsFns>();
+}
+extern "C" fn null_clone_object(ptr: *const c_void) -> *const c_void {
+    ptr
+}
+extern "C" fn null_take_object(_: *const c_void) {}
+/// Logs critically like the `ExternalFunctions` default when the host doesn't handle script errors
+extern "C" fn null_script_error(capability: *const c_char, kind: u32, message: *const c_char) {
+    let (capability, message) = unsafe {
+        (
+            CStr::from_ptr(capability).to_string_lossy(),
+            CStr::from_ptr(message).to_string_lossy(),
+        )
+    };
+    let kind = ErrorKind::try_from(kind).map_or_else(|_| kind.to_string(), |k| k.to_string());
+    CsFns::log_critical(format!(
+        "Script error ({kind}) calling into capability '{capability}': {message}"
+    ));
+}
 
 impl CsFns {
     pub const fn new() -> Self {
@@ -52,6 +97,12 @@ impl CsFns {
             free_cs_string: null_free,
             free_of_type: null_free_of_type,
             free_u32_buffer: null_free_u32_buffer,
+            free_f32_buffer: null_free_f32_buffer,
+            call_completed: null_call_completed,
+            clone_object: null_clone_object,
+            take_object: null_take_object,
+            on_script_reloaded: null_log,
+            on_script_error: null_script_error,
         }
     }
 
@@ -78,6 +129,24 @@ impl CsFns {
                 "free_u32_buffer" => {
                     self.free_u32_buffer = mem::transmute::<*const c_void, CsFreeBuffer>(ptr)
                 }
+                "free_f32_buffer" => {
+                    self.free_f32_buffer = mem::transmute::<*const c_void, CsFreeF32Buffer>(ptr)
+                }
+                "call_completed" => {
+                    self.call_completed = mem::transmute::<*const c_void, CsCallCompleted>(ptr)
+                }
+                "clone_object" => {
+                    self.clone_object = mem::transmute::<*const c_void, CsCloneObject>(ptr)
+                }
+                "take_object" => {
+                    self.take_object = mem::transmute::<*const c_void, CsTakeObject>(ptr)
+                }
+                "on_script_reloaded" => {
+                    self.on_script_reloaded = mem::transmute::<*const c_void, CsScriptReloaded>(ptr)
+                }
+                "on_script_error" => {
+                    self.on_script_error = mem::transmute::<*const c_void, CsScriptError>(ptr)
+                }
                 _ => {
                     eprintln!("Invalid function name: '{}', process will abort.", fn_name);
                     std::process::abort()
@@ -87,55 +156,75 @@ impl CsFns {
     }
 }
 
-pub static mut CS_FNS: CsFns = CsFns::new();
+/// Host callbacks linked through `turing_register_function`.
+/// Only held long enough to copy the function pointers out, never across a callback.
+pub static CS_FNS: RwLock<CsFns> = RwLock::new(CsFns::new());
+
+/// Snapshot of the currently linked host callbacks
+fn cs_fns() -> CsFns {
+    *CS_FNS.read()
+}
 
 impl ExternalFunctions for CsFns {
     fn abort(error_type: String, error: String) -> ! {
-        unsafe {
-            let et = CString::new(error_type).unwrap_or_default();
-            let e = CString::new(error).unwrap_or_default();
-            (CS_FNS.abort)(et.as_ptr(), e.as_ptr());
-        }
+        let et = escaped_cstring(&error_type);
+        let e = escaped_cstring(&error);
+        (cs_fns().abort)(et.as_ptr(), e.as_ptr());
         eprintln!("C# abort returned when it shouldn't have, aborting process completely.");
         std::process::abort()
     }
     fn log_info(msg: impl ToString) {
-        unsafe {
-            if let Ok(msg) = CString::new(msg.to_string()) {
-                (CS_FNS.log_info)(msg.as_ptr())
-            }
-        }
+        let msg = escaped_cstring(&msg.to_string());
+        (cs_fns().log_info)(msg.as_ptr())
     }
     fn log_warn(msg: impl ToString) {
-        unsafe {
-            if let Ok(msg) = CString::new(msg.to_string()) {
-                (CS_FNS.log_warn)(msg.as_ptr())
-            }
-        }
+        let msg = escaped_cstring(&msg.to_string());
+        (cs_fns().log_warn)(msg.as_ptr())
     }
     fn log_debug(msg: impl ToString) {
-        unsafe {
-            if let Ok(msg) = CString::new(msg.to_string()) {
-                (CS_FNS.log_debug)(msg.as_ptr())
-            }
-        }
+        let msg = escaped_cstring(&msg.to_string());
+        (cs_fns().log_debug)(msg.as_ptr())
     }
     fn log_critical(msg: impl ToString) {
-        unsafe {
-            if let Ok(msg) = CString::new(msg.to_string()) {
-                (CS_FNS.log_critical)(msg.as_ptr())
-            }
-        }
+        let msg = escaped_cstring(&msg.to_string());
+        (cs_fns().log_critical)(msg.as_ptr())
     }
     fn free_string(ptr: *const c_char) {
-        unsafe { (CS_FNS.free_cs_string)(ptr) }
+        (cs_fns().free_cs_string)(ptr)
     }
 
     fn free_of_type(ptr: *mut c_void, typ: FreeableDataType) {
-        unsafe { (CS_FNS.free_of_type)(ptr, typ as u32) }
+        (cs_fns().free_of_type)(ptr, typ as u32)
     }
 
     fn free_u32_buffer(buf: U32Buffer) {
-        unsafe { (CS_FNS.free_u32_buffer)(buf) }
+        (cs_fns().free_u32_buffer)(buf)
+    }
+
+    fn free_f32_buffer(buf: F32Buffer) {
+        (cs_fns().free_f32_buffer)(buf)
+    }
+
+    fn call_completed(key: ScriptFnKey, result: Param) {
+        (cs_fns().call_completed)(key.into(), result.to_rs_param())
+    }
+
+    fn clone_object(ptr: *const c_void) -> *const c_void {
+        (cs_fns().clone_object)(ptr)
+    }
+
+    fn take_object(ptr: *const c_void) {
+        (cs_fns().take_object)(ptr)
+    }
+
+    fn on_script_reloaded(path: &Path) {
+        let path = escaped_cstring(&path.to_string_lossy());
+        (cs_fns().on_script_reloaded)(path.as_ptr())
+    }
+
+    fn on_script_error(capability: &str, kind: ErrorKind, message: &str) {
+        let capability = escaped_cstring(capability);
+        let message = escaped_cstring(message);
+        (cs_fns().on_script_error)(capability.as_ptr(), kind as u32, message.as_ptr())
     }
 }
@@ -1,2 +1,3 @@
 pub mod params;
+pub mod rust_fn;
 pub mod types;
@@ -1,15 +1,19 @@
 use crate::ExternalFunctions;
-use crate::interop::types::{ExtString, U32Buffer};
+use crate::interop::types::{ExtString, F32Buffer, U32Buffer};
 use anyhow::{Result, anyhow};
 use glam::{Mat2, Mat3, Mat4, Quat, Vec2, Vec3, Vec4};
 use num_enum::TryFromPrimitive;
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
+use std::collections::hash_map::RandomState;
 use std::ffi::{CStr, CString, c_char, c_void};
 use std::fmt::Display;
+use std::hash::BuildHasher;
 use std::marker::PhantomData;
 use std::mem;
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Index};
+use std::slice::SliceIndex;
+use std::sync::OnceLock;
 
 #[repr(u32)]
 #[derive(
@@ -53,23 +57,53 @@ pub enum DataType {
     ExtMat4 = 25,
     RustU32Buffer = 26,
     ExtU32Buffer = 27,
+    Flags = 28,
+    /// Structured data, a nested table in lua. Crosses ffi as a rust-owned JSON C-String
+    RustJson = 29,
+    RustF32Buffer = 30,
+    ExtF32Buffer = 31,
+    /// An object handle passed by value: a script returning one hands ownership to the host
+    /// through `ExternalFunctions::take_object`. Plain `Object` handles are only borrowed
+    OwnedObject = 32,
+    /// A single Unicode scalar value, crossing as its `u32` code point
+    Char = 33,
+    /// A JSON C-String the host allocated, freed through `ExternalFunctions::free_string`
+    ExtJson = 34,
 }
 
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, TryFromPrimitive)]
 pub enum FreeableDataType {
+    /// Only boxed by the host when it asks rust for a slice of them, see `free_slice`
+    Vec2 = DataType::Vec2 as u32,
+    Vec3 = DataType::Vec3 as u32,
     ExtVec4 = DataType::ExtVec4 as u32,
     ExtQuat = DataType::ExtQuat as u32,
     ExtMat4 = DataType::ExtMat4 as u32,
-    // ExtU32BUFFER = DataType::ExtU32Buffer as u32,
+    /// A `U32Buffer` struct whose array rust allocated, as in a `DataType::RustU32Buffer` param
+    RustU32Buffer = DataType::RustU32Buffer as u32,
+    /// `F32Buffer` structs, the array is freed along with its contents
+    RustF32Buffer = DataType::RustF32Buffer as u32,
+    /// An `F32Buffer` whose array the host allocated, handed back to `ExternalFunctions::free_f32_buffer`
+    ExtF32Buffer = DataType::ExtF32Buffer as u32,
+    /// The rust-owned JSON string of a `DataType::RustJson` param
+    RustJson = DataType::RustJson as u32,
 }
 
 impl FreeableDataType {
     /// # Safety
-    /// ptr must be a valid pointer to a Vec4, Quat, or Mat4
-    pub unsafe fn free_ptr(&self, ptr: *mut c_void) {
+    /// ptr must be a valid pointer to a boxed Vec2, Vec3, Vec4, Quat or Mat4,
+    /// a rust-allocated U32Buffer or F32Buffer, an F32Buffer `Ext` allocated,
+    /// or the string of a RustJson param
+    pub unsafe fn free_ptr<Ext: ExternalFunctions>(&self, ptr: *mut c_void) {
         unsafe {
             match self {
+                Self::Vec2 => {
+                    drop(Box::from_raw(ptr as *mut Vec2));
+                }
+                Self::Vec3 => {
+                    drop(Box::from_raw(ptr as *mut Vec3));
+                }
                 Self::ExtVec4 => {
                     drop(Box::from_raw(ptr as *mut Vec4));
                 }
@@ -79,6 +113,69 @@ impl FreeableDataType {
                 Self::ExtMat4 => {
                     drop(Box::from_raw(ptr as *mut Mat4));
                 }
+                Self::RustU32Buffer => {
+                    drop((*(ptr as *mut U32Buffer)).from_rust());
+                }
+                Self::RustF32Buffer => {
+                    drop((*(ptr as *mut F32Buffer)).from_rust());
+                }
+                Self::ExtF32Buffer => {
+                    Ext::free_f32_buffer(*(ptr as *mut F32Buffer));
+                }
+                Self::RustJson => {
+                    drop(CString::from_raw(ptr as *mut c_char));
+                }
+            }
+        }
+    }
+
+    /// Frees `count` values of this type that rust allocated as one boxed slice,
+    /// along with what each of them owns like `free_ptr`. Does nothing for a null `ptr`.
+    /// # Safety
+    /// ptr must be null or point to a boxed slice of exactly `count` values of this type,
+    /// where a RustJson value is a string pointer. The arrays of ExtF32Buffer values go to `Ext`
+    pub unsafe fn free_slice<Ext: ExternalFunctions>(&self, ptr: *mut c_void, count: u32) {
+        /// # Safety
+        /// ptr must point to a boxed slice of `count` `T`s
+        unsafe fn boxed_slice<T>(ptr: *mut c_void, count: u32) -> Box<[T]> {
+            unsafe {
+                Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                    ptr as *mut T,
+                    count as usize,
+                ))
+            }
+        }
+
+        if ptr.is_null() {
+            return;
+        }
+        unsafe {
+            match self {
+                Self::Vec2 => drop(boxed_slice::<Vec2>(ptr, count)),
+                Self::Vec3 => drop(boxed_slice::<Vec3>(ptr, count)),
+                Self::ExtVec4 => drop(boxed_slice::<Vec4>(ptr, count)),
+                Self::ExtQuat => drop(boxed_slice::<Quat>(ptr, count)),
+                Self::ExtMat4 => drop(boxed_slice::<Mat4>(ptr, count)),
+                Self::RustU32Buffer => {
+                    for buf in boxed_slice::<U32Buffer>(ptr, count) {
+                        drop(buf.from_rust());
+                    }
+                }
+                Self::RustF32Buffer => {
+                    for buf in boxed_slice::<F32Buffer>(ptr, count) {
+                        drop(buf.from_rust());
+                    }
+                }
+                Self::ExtF32Buffer => {
+                    for buf in boxed_slice::<F32Buffer>(ptr, count) {
+                        Ext::free_f32_buffer(buf);
+                    }
+                }
+                Self::RustJson => {
+                    for s in boxed_slice::<*mut c_char>(ptr, count) {
+                        drop(CString::from_raw(s));
+                    }
+                }
             }
         }
     }
@@ -91,6 +188,8 @@ pub trait InnerFfiType {
     const QUAT: DataType;
     const MAT4: DataType;
     const U32BUFFER: DataType;
+    const F32BUFFER: DataType;
+    const JSON: DataType;
 }
 
 pub struct RustTypes;
@@ -103,6 +202,8 @@ impl InnerFfiType for RustTypes {
     const QUAT: DataType = DataType::RustQuat;
     const MAT4: DataType = DataType::RustMat4;
     const U32BUFFER: DataType = DataType::RustU32Buffer;
+    const F32BUFFER: DataType = DataType::RustF32Buffer;
+    const JSON: DataType = DataType::RustJson;
 }
 
 impl InnerFfiType for ExtTypes {
@@ -112,6 +213,8 @@ impl InnerFfiType for ExtTypes {
     const QUAT: DataType = DataType::ExtQuat;
     const MAT4: DataType = DataType::ExtMat4;
     const U32BUFFER: DataType = DataType::ExtU32Buffer;
+    const F32BUFFER: DataType = DataType::ExtF32Buffer;
+    const JSON: DataType = DataType::ExtJson;
 }
 
 impl Display for DataType {
@@ -144,6 +247,13 @@ impl Display for DataType {
             DataType::ExtMat4 => "EXT_MAT4",
             DataType::RustU32Buffer => "RUST_U32_BUFFER",
             DataType::ExtU32Buffer => "EXT_U32_BUFFER",
+            DataType::Flags => "FLAGS",
+            DataType::RustJson => "RUST_JSON",
+            DataType::RustF32Buffer => "RUST_F32_BUFFER",
+            DataType::ExtF32Buffer => "EXT_F32_BUFFER",
+            DataType::OwnedObject => "OWNED_OBJECT",
+            DataType::Char => "CHAR",
+            DataType::ExtJson => "EXT_JSON",
         };
         write!(f, "{}", s)
     }
@@ -185,6 +295,12 @@ impl ObjectId {
         self.0
     }
 
+    /// Inverse of [`ObjectId::as_ffi`], for ids coming back across the FFI.
+    /// Ids from scripts go through [`ObjectTable`](crate::objects::ObjectTable) instead.
+    pub fn from_ffi(id: u64) -> Self {
+        ObjectId(id)
+    }
+
     /// This function is useful if the external environment expects a pointer type for objects.
     /// It allows us to treat the ObjectId as an opaque pointer.
     ///
@@ -204,6 +320,44 @@ impl ObjectId {
     }
 }
 
+/// Renders as `#` and a hash of the id, keyed randomly once per process.
+/// The same object always looks the same in one session's logs, but the id, usually an address, can't be read back.
+impl Display for ObjectId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        static KEY: OnceLock<RandomState> = OnceLock::new();
+        let key = KEY.get_or_init(RandomState::new).hash_one(self.0);
+        write!(f, "#{key:016x}")
+    }
+}
+
+/// Error message used wherever a string that must be nul-terminated contains a `\0`
+pub const INTERIOR_NUL_MESSAGE: &str = "string contains interior nul byte";
+
+/// Error message for a NaN or infinite float that can't be passed on, see [`NanPolicy`]
+pub const NON_FINITE_FLOAT_MESSAGE: &str = "non-finite float";
+
+/// Error message for a `Char` code point that is a surrogate or past `U+10FFFF`
+pub const INVALID_CHAR_MESSAGE: &str = "invalid char";
+
+/// What happens to a NaN or infinite `F32`/`F64`/`F32Buffer` element a script passes to the host,
+/// either as an argument to a host function or as the value a script call returns
+#[repr(u32)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive)]
+pub enum NanPolicy {
+    /// The float reaches the host as is
+    #[default]
+    Passthrough = 0,
+    /// The float is replaced with `0.0`
+    Zero = 1,
+    /// The host function call or script call fails with [`NON_FINITE_FLOAT_MESSAGE`]
+    Error = 2,
+}
+
+/// Converts a message to a C string, escaping nul bytes as `\0` so it can always be delivered
+pub fn escaped_cstring(s: &str) -> CString {
+    CString::new(s.replace('\0', "\\0")).unwrap_or_default()
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Param {
     I8(i8),
@@ -219,6 +373,8 @@ pub enum Param {
     Bool(bool),
     String(String),
     Object(ObjectId),
+    /// An absent object. Crosses every boundary as the reserved object id `0`
+    Null,
     Error(String),
     Void,
     Vec2(Vec2),
@@ -227,9 +383,55 @@ pub enum Param {
     Quat(Quat),
     Mat4(Mat4),
     U32Buffer(Vec<u32>),
+    /// Bulk float data such as mesh vertices or audio samples, a list of numbers in lua
+    F32Buffer(Vec<f32>),
+    /// Integer bitset. Unlike `Bool`, the value is never collapsed to 0/1
+    Flags(u32),
+    Json(serde_json::Value),
+    /// A single character, lighter than a `String` for things like keyboard input
+    Char(char),
 }
 
 impl Param {
+    /// Decodes a `Char` code point, giving an error param for surrogates and values past `U+10FFFF`
+    pub fn char_from_code(code: u32) -> Param {
+        match char::from_u32(code) {
+            Some(c) => Param::Char(c),
+            None => Param::Error(format!("{INVALID_CHAR_MESSAGE}: {code:#x}")),
+        }
+    }
+
+    /// Wraps an object id, decoding the reserved null id to [`Param::Null`]
+    pub fn object(id: ObjectId) -> Param {
+        if id.is_null() {
+            Param::Null
+        } else {
+            Param::Object(id)
+        }
+    }
+
+    /// Whether both params are the same variant, ignoring their values.
+    /// Lets a test assert that a call failed without pinning its error message.
+    pub fn same_kind(&self, other: &Param) -> bool {
+        mem::discriminant(self) == mem::discriminant(other)
+    }
+
+    /// Clones the param, duplicating the host object through `ExternalFunctions::clone_object`
+    /// if it is one, rather than sharing the id. A failed clone gives [`Param::Null`].
+    pub fn deep_clone_object<Ext: ExternalFunctions>(&self) -> Param {
+        match self {
+            Param::Object(id) if id.is_null() => Param::Null,
+            Param::Object(id) => {
+                let clone = Ext::clone_object(unsafe { id.as_ptr() });
+                Param::object(ObjectId::from_ptr(clone))
+            }
+            other => other.clone(),
+        }
+    }
+
+    /// Converts to an `FfiParam` whose heap values are owned by rust.
+    /// A string containing a nul byte can't cross as a C string and becomes an error param,
+    /// while error messages have their nul bytes escaped (see [`escaped_cstring`]).
     pub fn to_rs_param(self) -> FfiParam {
         self.into_param_inner::<RustTypes>()
     }
@@ -247,14 +449,21 @@ impl Param {
             Param::U8(x) => FfiParam { type_id: DataType::U8, value: RawParam { u8: x } },
             Param::U16(x) => FfiParam { type_id: DataType::U16, value: RawParam { u16: x } },
             Param::U32(x) => FfiParam { type_id: DataType::U32, value: RawParam { u32: x } },
+            Param::Flags(x) => FfiParam { type_id: DataType::Flags, value: RawParam { u32: x } },
+            Param::Char(c) => FfiParam { type_id: DataType::Char, value: RawParam { u32: c as u32 } },
             Param::U64(x) => FfiParam { type_id: DataType::U64, value: RawParam { u64: x } },
             Param::F32(x) => FfiParam { type_id: DataType::F32, value: RawParam { f32: x } },
             Param::F64(x) => FfiParam { type_id: DataType::F64, value: RawParam { f64: x } },
             Param::Bool(x) => FfiParam { type_id: DataType::Bool, value: RawParam { bool: x } },
             // allocated via CString, must be freed via CString::from_raw
-            Param::String(x) => FfiParam { type_id: T::STRING, value: RawParam { string: CString::new(x).unwrap().into_raw() } },
+            Param::String(x) => match CString::new(x) {
+                Ok(s) => FfiParam { type_id: T::STRING, value: RawParam { string: s.into_raw() } },
+                Err(_) => Param::Error(INTERIOR_NUL_MESSAGE.to_string()).into_param_inner::<T>(),
+            },
             Param::Object(x) => FfiParam { type_id: DataType::Object, value: RawParam { object: x } },
-            Param::Error(x) => FfiParam { type_id: T::ERROR, value: RawParam { error: CString::new(x).unwrap().into_raw() } },
+            Param::Null => FfiParam { type_id: DataType::Object, value: RawParam { object: ObjectId::null() } },
+            // nul bytes are escaped so an error message can always be delivered
+            Param::Error(x) => FfiParam { type_id: T::ERROR, value: RawParam { error: escaped_cstring(&x).into_raw() } },
             Param::Void => FfiParam { type_id: DataType::Void, value: RawParam { void: () } },
             Param::Vec2(v) => FfiParam { type_id: DataType::Vec2, value: RawParam { vec2: v } },
             Param::Vec3(v) => FfiParam { type_id: DataType::Vec3, value: RawParam { vec3: v } },
@@ -268,6 +477,12 @@ impl Param {
                 mem::forget(boxed);
                 FfiParam { type_id: T::U32BUFFER, value: RawParam { u32_buffer: U32Buffer { size: len, array: ptr } } }
             }
+            Param::F32Buffer(arr) => FfiParam { type_id: T::F32BUFFER, value: RawParam { f32_buffer: F32Buffer::from_vec(arr) } },
+            // serialized JSON never contains a raw nul byte, they are escaped as \u0000
+            Param::Json(v) => match CString::new(v.to_string()) {
+                Ok(s) => FfiParam { type_id: T::JSON, value: RawParam { string: s.into_raw() } },
+                Err(_) => Param::Error(INTERIOR_NUL_MESSAGE.to_string()).into_param_inner::<T>(),
+            },
         }
     }
 
@@ -275,6 +490,96 @@ impl Param {
         T::from_param(self)
     }
 
+    /// Applies `policy` to a non-finite `F32`/`F64` or `F32Buffer` element,
+    /// any other param is returned as is
+    pub fn apply_nan_policy(self, policy: NanPolicy) -> Param {
+        let non_finite = match &self {
+            Param::F32(x) => !x.is_finite(),
+            Param::F64(x) => !x.is_finite(),
+            Param::F32Buffer(buf) => buf.iter().any(|x| !x.is_finite()),
+            _ => false,
+        };
+        if !non_finite {
+            return self;
+        }
+        match (policy, self) {
+            (NanPolicy::Zero, Param::F32(_)) => Param::F32(0.0),
+            (NanPolicy::Zero, Param::F64(_)) => Param::F64(0.0),
+            (NanPolicy::Zero, Param::F32Buffer(mut buf)) => {
+                buf.iter_mut()
+                    .filter(|x| !x.is_finite())
+                    .for_each(|x| *x = 0.0);
+                Param::F32Buffer(buf)
+            }
+            (NanPolicy::Error, p) => Param::Error(format!("{NON_FINITE_FLOAT_MESSAGE}: {p}")),
+            (_, p) => p,
+        }
+    }
+
+    /// Converts a JSON value to the closest plain param: null, bools, numbers and strings become
+    /// `Null`, `Bool`, `I64`/`U64`/`F64` and `String`, arrays and objects stay `Json`.
+    /// [`Param::to_serde`] turns the result back into the same JSON value.
+    pub fn from_serde(value: serde_json::Value) -> Param {
+        match value {
+            serde_json::Value::Null => Param::Null,
+            serde_json::Value::Bool(b) => Param::Bool(b),
+            serde_json::Value::Number(n) => {
+                if let Some(i) = n.as_i64() {
+                    Param::I64(i)
+                } else if let Some(u) = n.as_u64() {
+                    Param::U64(u)
+                } else {
+                    Param::F64(n.as_f64().unwrap_or_default())
+                }
+            }
+            serde_json::Value::String(s) => Param::String(s),
+            json => Param::Json(json),
+        }
+    }
+
+    /// Converts the param to a JSON value. Vectors, quaternions and matrices become arrays of
+    /// floats. Fails for objects, errors and NaN or infinite floats, which JSON can't represent.
+    pub fn to_serde(&self) -> Result<serde_json::Value> {
+        fn float(x: f64) -> Result<serde_json::Value> {
+            serde_json::Number::from_f64(x)
+                .map(serde_json::Value::Number)
+                .ok_or_else(|| anyhow!("{NON_FINITE_FLOAT_MESSAGE}: {x} can't be stored as JSON"))
+        }
+        fn floats(xs: &[f32]) -> Result<serde_json::Value> {
+            xs.iter()
+                .map(|x| float(*x as f64))
+                .collect::<Result<_>>()
+                .map(serde_json::Value::Array)
+        }
+
+        Ok(match self {
+            Param::I8(x) => (*x).into(),
+            Param::I16(x) => (*x).into(),
+            Param::I32(x) => (*x).into(),
+            Param::I64(x) => (*x).into(),
+            Param::U8(x) => (*x).into(),
+            Param::U16(x) => (*x).into(),
+            Param::U32(x) | Param::Flags(x) => (*x).into(),
+            Param::U64(x) => (*x).into(),
+            Param::F32(x) => float(*x as f64)?,
+            Param::F64(x) => float(*x)?,
+            Param::Bool(b) => (*b).into(),
+            Param::String(s) => s.clone().into(),
+            Param::Char(c) => c.to_string().into(),
+            Param::Null | Param::Void => serde_json::Value::Null,
+            Param::Vec2(v) => floats(&v.to_array())?,
+            Param::Vec3(v) => floats(&v.to_array())?,
+            Param::Vec4(v) => floats(&v.to_array())?,
+            Param::Quat(q) => floats(&q.to_array())?,
+            Param::Mat4(m) => floats(&m.to_cols_array())?,
+            Param::U32Buffer(buf) => buf.clone().into(),
+            Param::F32Buffer(buf) => floats(buf)?,
+            Param::Json(v) => v.clone(),
+            Param::Object(id) => return Err(anyhow!("object {id} can't be stored as JSON")),
+            Param::Error(e) => return Err(anyhow!("{e}")),
+        })
+    }
+
     pub fn data_type<T: InnerFfiType>(&self) -> DataType {
         match self {
             Param::I8(_) => DataType::I8,
@@ -284,12 +589,14 @@ impl Param {
             Param::U8(_) => DataType::U8,
             Param::U16(_) => DataType::U16,
             Param::U32(_) => DataType::U32,
+            Param::Flags(_) => DataType::Flags,
+            Param::Char(_) => DataType::Char,
             Param::U64(_) => DataType::U64,
             Param::F32(_) => DataType::F32,
             Param::F64(_) => DataType::F64,
             Param::Bool(_) => DataType::Bool,
             Param::String(_) => T::STRING,
-            Param::Object(_) => DataType::Object,
+            Param::Object(_) | Param::Null => DataType::Object,
             Param::Error(_) => T::ERROR,
             Param::Void => DataType::Void,
             Param::Vec2(_) => DataType::Vec2,
@@ -298,6 +605,57 @@ impl Param {
             Param::Quat(_) => T::QUAT,
             Param::Mat4(_) => T::MAT4,
             Param::U32Buffer(_) => T::U32BUFFER,
+            Param::F32Buffer(_) => T::F32BUFFER,
+            Param::Json(_) => T::JSON,
+        }
+    }
+}
+
+/// How many chars of a string or error `Param`'s `Display` shows when no precision is given
+pub const DISPLAY_STRING_LIMIT: usize = 64;
+
+/// Formats a `Param` for logs. Objects show a keyed hash of their id rather than an address,
+/// and strings are cut to the precision (`{:.16}`), or [`DISPLAY_STRING_LIMIT`] chars.
+impl Display for Param {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let limit = f.precision().unwrap_or(DISPLAY_STRING_LIMIT);
+        let truncated = |s: &str| {
+            let len = s.chars().count();
+            if len <= limit {
+                format!("{s:?}")
+            } else {
+                let cut = s.chars().take(limit).collect::<String>();
+                format!("{cut:?}... ({} more chars)", len - limit)
+            }
+        };
+
+        match self {
+            Param::I8(x) => write!(f, "{x}"),
+            Param::I16(x) => write!(f, "{x}"),
+            Param::I32(x) => write!(f, "{x}"),
+            Param::I64(x) => write!(f, "{x}"),
+            Param::U8(x) => write!(f, "{x}"),
+            Param::U16(x) => write!(f, "{x}"),
+            Param::U32(x) => write!(f, "{x}"),
+            Param::U64(x) => write!(f, "{x}"),
+            Param::F32(x) => write!(f, "{x}"),
+            Param::F64(x) => write!(f, "{x}"),
+            Param::Bool(x) => write!(f, "{x}"),
+            Param::Flags(x) => write!(f, "Flags({x:#b})"),
+            Param::Char(c) => write!(f, "{c:?}"),
+            Param::String(s) => write!(f, "{}", truncated(s)),
+            Param::Object(id) => write!(f, "Object({id})"),
+            Param::Null => write!(f, "null"),
+            Param::Error(e) => write!(f, "Error({})", truncated(e)),
+            Param::Void => write!(f, "void"),
+            Param::Vec2(v) => write!(f, "Vec2{v}"),
+            Param::Vec3(v) => write!(f, "Vec3{v}"),
+            Param::Vec4(v) => write!(f, "Vec4{v}"),
+            Param::Quat(q) => write!(f, "Quat{q}"),
+            Param::Mat4(m) => write!(f, "Mat4{m}"),
+            Param::U32Buffer(b) => write!(f, "U32Buffer(len {})", b.len()),
+            Param::F32Buffer(b) => write!(f, "F32Buffer(len {})", b.len()),
+            Param::Json(v) => write!(f, "Json({})", truncated(&v.to_string())),
         }
     }
 }
@@ -338,6 +696,54 @@ deref_param! { Vec3   => Vec3   }
 deref_param! { Vec4   => Vec4   }
 deref_param! { Quat   => Quat   }
 deref_param! { Mat4   => Mat4   }
+deref_param! { serde_json::Value => Json }
+deref_param! { char   => Char   }
+macro_rules! param_from {
+    ( $tp:ty => $case:tt ) => {
+        impl From<$tp> for Param {
+            fn from(value: $tp) -> Self {
+                Param::$case(value)
+            }
+        }
+    };
+}
+param_from! { i8     => I8     }
+param_from! { i16    => I16    }
+param_from! { i32    => I32    }
+param_from! { i64    => I64    }
+param_from! { u8     => U8     }
+param_from! { u16    => U16    }
+param_from! { u32    => U32    }
+param_from! { u64    => U64    }
+param_from! { f32    => F32    }
+param_from! { f64    => F64    }
+param_from! { bool   => Bool   }
+param_from! { char   => Char   }
+param_from! { String => String }
+param_from! { Vec2   => Vec2   }
+param_from! { Vec3   => Vec3   }
+param_from! { Vec4   => Vec4   }
+param_from! { Quat   => Quat   }
+param_from! { Mat4   => Mat4   }
+param_from! { serde_json::Value => Json }
+impl From<&str> for Param {
+    fn from(value: &str) -> Self {
+        Param::String(value.to_string())
+    }
+}
+
+/// Builds [`Params`] inline, converting each value with `Param::from`:
+/// `params![1i32, "hello", 3.5f32]`
+#[macro_export]
+macro_rules! params {
+    ( $( $value:expr ),* $(,)? ) => {{
+        #[allow(unused_mut)]
+        let mut params = $crate::interop::params::Params::new();
+        $( params.push($crate::interop::params::Param::from($value)); )*
+        params
+    }};
+}
+
 impl FromParam for () {
     fn from_param(param: Param) -> Result<Self> {
         match param {
@@ -348,7 +754,7 @@ impl FromParam for () {
     }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Params {
     // SmallVec will spill onto the heap if there are more than 4 params
     pub(crate) params: SmallVec<[Param; 4]>,
@@ -371,14 +777,43 @@ impl Params {
         self.params.push(param);
     }
 
-    pub fn set(&mut self, index: u32, param: Param) {
-        self.params[index as usize] = param;
+    /// Chained `push`, converting `value` with `Param::from`
+    pub fn with(mut self, value: impl Into<Param>) -> Self {
+        self.params.push(value.into());
+        self
+    }
+
+    /// Overwrites the param at `index`, failing if it is out of bounds
+    pub fn set(&mut self, index: u32, param: Param) -> Result<()> {
+        let len = self.len();
+        let Some(p) = self.params.get_mut(index as usize) else {
+            return Err(anyhow!("index {index} out of bounds for {len} params"));
+        };
+        *p = param;
+        Ok(())
     }
 
     pub fn get(&self, idx: usize) -> Option<&Param> {
         self.params.get(idx)
     }
 
+    pub fn as_slice(&self) -> &[Param] {
+        &self.params
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Param> {
+        self.params.iter()
+    }
+
+    /// Converts a copy of the param at `idx`, e.g. to unpack the args a host callback received
+    pub fn try_get<T: FromParam>(&self, idx: usize) -> Result<T> {
+        let len = self.len();
+        let Some(param) = self.params.get(idx) else {
+            return Err(anyhow!("index {idx} out of bounds for {len} params"));
+        };
+        T::from_param(param.clone())
+    }
+
     pub fn len(&self) -> u32 {
         self.params.len() as u32
     }
@@ -387,6 +822,38 @@ impl Params {
         self.params.is_empty()
     }
 
+    /// Appends every param of `other` after this one's
+    pub fn extend(&mut self, other: Params) {
+        self.params.extend(other.params);
+    }
+
+    /// Returns this `Params` followed by `other`, like prepending context args
+    /// (e.g. a `self` object) to args forwarded from a caller
+    pub fn concat(mut self, other: Params) -> Params {
+        self.extend(other);
+        self
+    }
+
+    /// Drops every param but keeps the capacity, so a host can fill, call, clear and refill
+    /// the same `Params` without reallocating
+    pub fn clear(&mut self) {
+        self.params.clear();
+    }
+
+    /// Drops the params after the first `len`, does nothing if there are `len` or fewer
+    pub fn truncate(&mut self, len: u32) {
+        self.params.truncate(len as usize);
+    }
+
+    /// Moves every param out into a new `Params`, leaving this one empty but keeping its
+    /// capacity, for calls that have to own their args. Calls that can borrow them should
+    /// drain them in place instead, see `Turing::call_fn_draining`.
+    pub fn drain_all(&mut self) -> Params {
+        Params {
+            params: self.params.drain(..).collect(),
+        }
+    }
+
     pub fn to_ffi<Ext>(self) -> FfiParams<Ext>
     where
         Ext: ExternalFunctions,
@@ -395,6 +862,23 @@ impl Params {
     }
 }
 
+/// `(a, b, c)`, each param formatted like [`Param`]'s `Display` with the same precision
+impl Display for Params {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(")?;
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match f.precision() {
+                Some(limit) => write!(f, "{param:.limit$}")?,
+                None => write!(f, "{param}")?,
+            }
+        }
+        write!(f, ")")
+    }
+}
+
 impl IntoIterator for Params {
     type Item = Param;
     type IntoIter = smallvec::IntoIter<[Param; 4]>;
@@ -404,6 +888,34 @@ impl IntoIterator for Params {
     }
 }
 
+impl<'a> IntoIterator for &'a Params {
+    type Item = &'a Param;
+    type IntoIter = std::slice::Iter<'a, Param>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.params.iter()
+    }
+}
+
+/// Panics if `idx` is out of bounds, like slice indexing. `get` returns None instead.
+impl<I: SliceIndex<[Param]>> Index<I> for Params {
+    type Output = I::Output;
+
+    fn index(&self, idx: I) -> &I::Output {
+        &self.params[idx]
+    }
+}
+
+impl FromIterator<Param> for Params {
+    fn from_iter<I: IntoIterator<Item = Param>>(iter: I) -> Self {
+        Params {
+            params: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Kept for existing callers, the `SmallVec` behind it may change.
+/// Use `as_slice`, `iter`, `get` and indexing instead.
 impl Deref for Params {
     type Target = SmallVec<[Param; 4]>;
 
@@ -418,6 +930,11 @@ impl DerefMut for Params {
     }
 }
 
+/// Version of the [`FfiParam`]/[`RawParam`] layout, bumped on every breaking change to it.
+/// Hosts should compare it against the version they were built for before exchanging params.
+/// The `object` member is a `u64` [`ObjectId`] holding the host's own id, usually a pointer, unchanged.
+pub const FFI_LAYOUT_VERSION: u32 = 1;
+
 /// C repr of ffi data
 #[repr(C)]
 pub union RawParam {
@@ -444,6 +961,7 @@ pub union RawParam {
     mat3: *const Mat3,
     mat4: *const Mat4,
     u32_buffer: U32Buffer,
+    f32_buffer: F32Buffer,
 }
 
 /// C tagged repr of ffi data
@@ -613,6 +1131,17 @@ impl<'a> FfiParamArray<'a> {
         }
     }
 
+    /// Converts `params` and passes a view of them to `f`, for in-process callbacks that only read
+    /// their arguments. The backing `FfiParams` lives until `f` returns and is then freed, so the
+    /// array can't outlive it.
+    pub fn from_params_borrowed<Ext: ExternalFunctions, R>(
+        params: &Params,
+        f: impl FnOnce(FfiParamArray<'_>) -> R,
+    ) -> R {
+        let ffi_params = FfiParams::<Ext>::from_params(params.params.iter().cloned());
+        f(ffi_params.as_ffi_array())
+    }
+
     pub fn as_slice(&'a self) -> &'a [FfiParam] {
         unsafe { std::slice::from_raw_parts(self.ptr, self.count as usize) }
     }
@@ -650,6 +1179,8 @@ impl FfiParam {
             DataType::U8 => Param::U8(unsafe { self.value.u8 }),
             DataType::U16 => Param::U16(unsafe { self.value.u16 }),
             DataType::U32 => Param::U32(unsafe { self.value.u32 }),
+            DataType::Flags => Param::Flags(unsafe { self.value.u32 }),
+            DataType::Char => Param::char_from_code(unsafe { self.value.u32 }),
             DataType::U64 => Param::U64(unsafe { self.value.u64 }),
             DataType::F32 => Param::F32(unsafe { self.value.f32 }),
             DataType::F64 => Param::F64(unsafe { self.value.f64 }),
@@ -662,7 +1193,7 @@ impl FfiParam {
             DataType::ExtString => {
                 Param::String(unsafe { ExtString::<Ext>::from(self.value.string).to_string() })
             }
-            DataType::Object => Param::Object(unsafe { self.value.object }),
+            DataType::Object | DataType::OwnedObject => Param::object(unsafe { self.value.object }),
             DataType::RustError => Param::Error(unsafe {
                 CString::from_raw(self.value.error as *mut c_char)
                     .to_string_lossy()
@@ -686,6 +1217,20 @@ impl FfiParam {
             DataType::ExtU32Buffer => {
                 Param::U32Buffer(unsafe { self.value.u32_buffer }.from_ext::<Ext>())
             }
+            DataType::RustF32Buffer => {
+                Param::F32Buffer(unsafe { self.value.f32_buffer }.from_rust())
+            }
+            DataType::ExtF32Buffer => {
+                Param::F32Buffer(unsafe { self.value.f32_buffer }.from_ext::<Ext>())
+            }
+            DataType::RustJson => {
+                let json = unsafe { CString::from_raw(self.value.string as *mut c_char) };
+                Param::Json(serde_json::from_slice(json.as_bytes())?)
+            }
+            DataType::ExtJson => {
+                let json = unsafe { ExtString::<Ext>::from(self.value.string) };
+                Param::Json(serde_json::from_slice(json.to_bytes())?)
+            }
         })
     }
 
@@ -708,6 +1253,8 @@ impl FfiParam {
             DataType::U8 => Param::U8(unsafe { self.value.u8 }),
             DataType::U16 => Param::U16(unsafe { self.value.u16 }),
             DataType::U32 => Param::U32(unsafe { self.value.u32 }),
+            DataType::Flags => Param::Flags(unsafe { self.value.u32 }),
+            DataType::Char => Param::char_from_code(unsafe { self.value.u32 }),
             DataType::U64 => Param::U64(unsafe { self.value.u64 }),
             DataType::F32 => Param::F32(unsafe { self.value.f32 }),
             DataType::F64 => Param::F64(unsafe { self.value.f64 }),
@@ -720,7 +1267,7 @@ impl FfiParam {
             DataType::ExtString => {
                 Param::String(unsafe { ExtString::<Ext>::from(self.value.string).to_string() })
             }
-            DataType::Object => Param::Object(unsafe { self.value.object }),
+            DataType::Object | DataType::OwnedObject => Param::object(unsafe { self.value.object }),
             DataType::RustError => Param::Error(unsafe {
                 CStr::from_ptr(self.value.error)
                     .to_string_lossy()
@@ -741,6 +1288,13 @@ impl FfiParam {
             DataType::RustU32Buffer | DataType::ExtU32Buffer => {
                 Param::U32Buffer(unsafe { self.value.u32_buffer }.borrow())
             }
+            DataType::RustF32Buffer | DataType::ExtF32Buffer => {
+                Param::F32Buffer(unsafe { self.value.f32_buffer }.borrow())
+            }
+            DataType::RustJson | DataType::ExtJson => {
+                let json = unsafe { CStr::from_ptr(self.value.string) };
+                Param::Json(serde_json::from_slice(json.to_bytes())?)
+            }
         })
     }
 }
@@ -0,0 +1,163 @@
+//! Host functions written as plain rust closures, see `TuringSetup::add_rust_function`.
+//!
+//! The closure's argument tuple and return type decide the function's param and return types,
+//! so rust hosts don't hand-write an `extern "C"` callback that unpacks an `FfiParamArray`.
+
+use std::fmt::Debug;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+
+use crate::ExternalFunctions;
+use crate::engine::panic_message;
+use crate::engine::types::ScriptFnMetadata;
+use crate::interop::params::{DataType, FfiParam, FfiParamArray, FromParam, Param, Params};
+
+/// A host function implemented by a rust closure
+#[derive(Clone)]
+pub struct RustCallback(Arc<dyn Fn(FfiParamArray) -> FfiParam + Send + Sync>);
+
+impl RustCallback {
+    pub fn call(&self, params: FfiParamArray) -> FfiParam {
+        (self.0)(params)
+    }
+}
+
+impl Debug for RustCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RustCallback")
+    }
+}
+
+/// A value a rust host function takes from scripts
+pub trait ScriptArg: FromParam {
+    const PARAM_TYPE: DataType;
+}
+
+/// A value a rust host function returns to scripts
+pub trait ScriptReturn {
+    const RETURN_TYPE: DataType;
+
+    fn into_return(self) -> Param;
+}
+
+macro_rules! script_type {
+    ( $tp:ty => $param:tt, $ret:tt ) => {
+        impl ScriptArg for $tp {
+            const PARAM_TYPE: DataType = DataType::$param;
+        }
+
+        impl ScriptReturn for $tp {
+            const RETURN_TYPE: DataType = DataType::$ret;
+
+            fn into_return(self) -> Param {
+                Param::from(self)
+            }
+        }
+    };
+}
+script_type! { i8     => I8, I8 }
+script_type! { i16    => I16, I16 }
+script_type! { i32    => I32, I32 }
+script_type! { i64    => I64, I64 }
+script_type! { u8     => U8, U8 }
+script_type! { u16    => U16, U16 }
+script_type! { u32    => U32, U32 }
+script_type! { u64    => U64, U64 }
+script_type! { f32    => F32, F32 }
+script_type! { f64    => F64, F64 }
+script_type! { bool   => Bool, Bool }
+script_type! { char   => Char, Char }
+script_type! { String => RustString, ExtString }
+script_type! { Vec2   => Vec2, Vec2 }
+script_type! { Vec3   => Vec3, Vec3 }
+script_type! { Vec4   => RustVec4, ExtVec4 }
+script_type! { Quat   => RustQuat, ExtQuat }
+script_type! { Mat4   => RustMat4, ExtMat4 }
+script_type! { serde_json::Value => RustJson, ExtJson }
+
+impl ScriptReturn for () {
+    const RETURN_TYPE: DataType = DataType::Void;
+
+    fn into_return(self) -> Param {
+        Param::Void
+    }
+}
+
+/// The argument tuple of a rust host function, up to 6 args
+pub trait ScriptArgs: Sized {
+    fn param_types() -> Vec<DataType>;
+
+    fn from_params(params: Params) -> Result<Self>;
+}
+
+macro_rules! script_args {
+    ( $( $arg:ident ),* ) => {
+        impl<$( $arg: ScriptArg ),*> ScriptArgs for ( $( $arg, )* ) {
+            fn param_types() -> Vec<DataType> {
+                vec![$( $arg::PARAM_TYPE ),*]
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn from_params(params: Params) -> Result<Self> {
+                let count = params.len();
+                let mut params = params.into_iter();
+                let mut next = || {
+                    params
+                        .next()
+                        .ok_or_else(|| anyhow!("missing argument, got {count}"))
+                };
+                Ok(( $( $arg::from_param(next()?)?, )* ))
+            }
+        }
+    };
+}
+script_args!();
+script_args!(A);
+script_args!(A, B);
+script_args!(A, B, C);
+script_args!(A, B, C, D);
+script_args!(A, B, C, D, E);
+script_args!(A, B, C, D, E, F);
+
+impl ScriptFnMetadata {
+    /// Creates metadata for a host function implemented by `f`, with param and return types
+    /// taken from its signature. Params are named `arg0`, `arg1` and so on.
+    /// An error or panic in `f` becomes a failed host call, which the script sees like any other.
+    pub fn from_rust_fn<Ext, Args, R>(
+        capability: String,
+        f: impl Fn(Args) -> Result<R> + Send + Sync + 'static,
+    ) -> Result<Self>
+    where
+        Ext: ExternalFunctions,
+        Args: ScriptArgs,
+        R: ScriptReturn,
+    {
+        let callback = move |params: FfiParamArray| {
+            let res = catch_unwind(AssertUnwindSafe(|| {
+                f(Args::from_params(params.as_params::<Ext>()?)?)
+            }));
+            match res {
+                Ok(Ok(ret)) => ret.into_return(),
+                Ok(Err(e)) => Param::Error(format!("{e}")),
+                Err(payload) => Param::Error(format!(
+                    "host function panicked: {}",
+                    panic_message(payload.as_ref())
+                )),
+            }
+            .to_rs_param()
+        };
+
+        let mut metadata = ScriptFnMetadata::dispatched(capability, None);
+        metadata.rust_callback = Some(RustCallback(Arc::new(callback)));
+        for (i, param_type) in Args::param_types().into_iter().enumerate() {
+            metadata.add_param_type(param_type, format!("arg{i}"))?;
+        }
+        if R::RETURN_TYPE != DataType::Void {
+            metadata.add_return_type(R::RETURN_TYPE)?;
+        }
+        Ok(metadata)
+    }
+}
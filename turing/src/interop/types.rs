@@ -1,3 +1,4 @@
+use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::ffi::{CStr, c_char, c_void};
@@ -5,6 +6,7 @@ use std::fmt::{Display, Formatter};
 use std::hash::Hash;
 use std::marker::PhantomData;
 use std::ops::Deref;
+use std::str::FromStr;
 use std::{ptr, slice};
 
 use crate::ExternalFunctions;
@@ -40,6 +42,12 @@ impl Semver {
     pub fn into_u64(self) -> u64 {
         ((self.major as u64) << 32) | ((self.minor as u64) << 16) | (self.patch as u64)
     }
+
+    /// Whether a host api at this version can serve a script built against `required`.
+    /// The major versions must match and this minor version must be at least `required`'s.
+    pub fn is_compatible_with(&self, required: &Semver) -> bool {
+        self.major == required.major && self.minor >= required.minor
+    }
 }
 
 impl PartialEq for Semver {
@@ -56,7 +64,7 @@ impl PartialOrd for Semver {
 
 impl Ord for Semver {
     fn cmp(&self, other: &Self) -> Ordering {
-        other.as_u64().cmp(&other.as_u64())
+        self.as_u64().cmp(&other.as_u64())
     }
 }
 
@@ -66,6 +74,57 @@ impl Display for Semver {
     }
 }
 
+/// Parses `major.minor.patch`, the form `Display` writes
+impl FromStr for Semver {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("invalid version '{s}', expected major.minor.patch");
+        let parts = s.split('.').collect::<Vec<_>>();
+        let [major, minor, patch] = parts.as_slice() else {
+            return Err(invalid());
+        };
+        // `parse` would also take a leading '+'
+        if [major, minor, patch]
+            .iter()
+            .any(|part| part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()))
+        {
+            return Err(invalid());
+        }
+        Ok(Semver::new(
+            major.parse().map_err(|_| invalid())?,
+            minor.parse().map_err(|_| invalid())?,
+            patch.parse().map_err(|_| invalid())?,
+        ))
+    }
+}
+
+/// An api the loaded script was built against that the host can't serve
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Incompatibility {
+    pub api: String,
+    pub script_version: Semver,
+    /// None if the host doesn't provide the api at all
+    pub host_version: Option<Semver>,
+}
+
+impl Display for Incompatibility {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.host_version {
+            Some(host) => write!(
+                f,
+                "script requires '{}' {}, host provides {}",
+                self.api, self.script_version, host
+            ),
+            None => write!(
+                f,
+                "script requires '{}' {}, host does not provide it",
+                self.api, self.script_version
+            ),
+        }
+    }
+}
+
 /// A string allocated externally, to be managed by the external environment.
 pub struct ExtString<Ext: ExternalFunctions> {
     pub ptr: *const c_char,
@@ -218,3 +277,96 @@ impl U32Buffer {
         slice.to_vec()
     }
 }
+
+#[repr(C)]
+#[derive(Copy)]
+pub struct F32Buffer {
+    pub size: u32,
+    pub array: *mut f32,
+}
+
+impl Clone for F32Buffer {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl F32Buffer {
+    /// Moves a Vec<f32> into a buffer rust owns, freed by `from_rust`
+    pub fn from_vec(vec: Vec<f32>) -> Self {
+        let size = vec.len() as u32;
+        let array = Box::into_raw(vec.into_boxed_slice()) as *mut f32;
+        F32Buffer { size, array }
+    }
+
+    /// Moves the data into a Vec<f32> and frees the underlying data directly
+    pub fn from_rust(self) -> Vec<f32> {
+        let slice = unsafe {
+            Box::from_raw(ptr::slice_from_raw_parts_mut(
+                self.array,
+                self.size as usize,
+            ))
+        };
+        slice.into_vec()
+    }
+
+    /// Copies the data into a Vec<f32> and asks the external code to free the underlying data
+    pub fn from_ext<Ext: ExternalFunctions>(self) -> Vec<f32> {
+        let v = self.borrow();
+        Ext::free_f32_buffer(self);
+        v
+    }
+
+    /// Copies the data into a Vec<f32> without freeing in any way
+    pub fn borrow(&self) -> Vec<f32> {
+        let slice = unsafe { slice::from_raw_parts(self.array, self.size as usize) };
+        slice.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod semver_tests {
+    use super::Semver;
+
+    #[test]
+    fn test_semver_ordering() {
+        let v = Semver::new(1, 4, 2);
+        assert!(v < Semver::new(1, 4, 3));
+        assert!(v < Semver::new(1, 5, 0));
+        assert!(v < Semver::new(2, 0, 0));
+        assert!(v > Semver::new(1, 3, 9));
+        assert_eq!(v, Semver::from_u64(v.as_u64()));
+    }
+
+    #[test]
+    fn test_semver_compatibility() {
+        let host = Semver::new(1, 4, 0);
+        assert!(host.is_compatible_with(&Semver::new(1, 4, 7)));
+        assert!(host.is_compatible_with(&Semver::new(1, 0, 0)));
+        assert!(!host.is_compatible_with(&Semver::new(1, 5, 0)));
+        assert!(!host.is_compatible_with(&Semver::new(2, 0, 0)));
+        assert!(!Semver::new(2, 0, 0).is_compatible_with(&Semver::new(1, 0, 0)));
+    }
+
+    #[test]
+    fn test_semver_string_roundtrip() {
+        let v = "10.0.255".parse::<Semver>().unwrap();
+        assert_eq!(v, Semver::new(10, 0, 255));
+        assert_eq!(v.to_string(), "10.0.255");
+        assert_eq!(Semver::from_u64(v.as_u64()).to_string(), "10.0.255");
+
+        for bad in [
+            "",
+            "1.2",
+            "1.2.3.4",
+            "1..3",
+            "a.b.c",
+            "+1.2.3",
+            "1.2.-3",
+            " 1.2.3",
+            "1.65536.0",
+        ] {
+            assert!(bad.parse::<Semver>().is_err(), "parsed '{bad}'");
+        }
+    }
+}
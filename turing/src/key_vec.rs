@@ -52,6 +52,12 @@ where
         unsafe { self.values.get_unchecked(key.clone().into() as usize) }
     }
 
+    /// Like `get`, but returns None for a key that wasn't handed out by this KeyVec
+    #[inline]
+    pub fn try_get(&self, key: &K) -> Option<&V> {
+        self.values.get(key.clone().into() as usize)
+    }
+
     #[inline]
     pub fn get_mut(&mut self, key: &K) -> &mut V {
         unsafe { self.values.get_unchecked_mut(key.clone().into() as usize) }
@@ -1,22 +1,44 @@
 extern crate core;
 
-use crate::engine::Engine;
-use crate::engine::types::ScriptFnMetadata;
-use crate::interop::params::{DataType, FreeableDataType, Param, Params};
-use crate::interop::types::{Semver, U32Buffer};
+use crate::engine::types::{
+    DispatchCallback, EngineConfig, ErrorKind, ExportSignature, FunctionInfo, LogLevel,
+    ScriptFnMetadata,
+};
+use crate::engine::{
+    Engine, EngineKind, FUNCTION_NOT_FOUND_MESSAGE, InterruptHandle, ON_LOAD_FN, ON_UNLOAD_FN,
+    REENTRY_UNSUPPORTED_MESSAGE,
+};
+use crate::interop::params::{DataType, FreeableDataType, NanPolicy, ObjectId, Param, Params};
+use crate::interop::rust_fn::{ScriptArgs, ScriptReturn};
+use crate::interop::types::{F32Buffer, Incompatibility, Semver, U32Buffer};
+use crate::manifest::{ModLoadReport, ModManifest};
+use crate::objects::ObjectTable;
+use crate::stats::{CallStat, CallStats};
+use crate::storage::ScriptStorage;
 use anyhow::{Result, anyhow};
 use parking_lot::RwLock;
 use rustc_hash::{FxHashMap, FxHashSet};
+use std::cell::RefCell;
 use std::collections::VecDeque;
-use std::ffi::{c_char, c_void};
+use std::ffi::{CStr, c_char, c_void};
+use std::fmt::Display;
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::thread::LocalKey;
+use std::time::{Duration, Instant};
 
 pub mod engine;
 pub mod interop;
 pub mod key_vec;
+pub mod manifest;
+pub mod objects;
 mod spec_gen;
+pub mod stats;
+pub mod storage;
+#[cfg(feature = "hot_reload")]
+pub mod watch;
 
 #[cfg(test)]
 mod tests;
@@ -25,7 +47,12 @@ mod tests;
 mod global_ffi;
 
 pub trait ExternalFunctions {
-    fn abort(error_type: String, error: String) -> !;
+    /// Stops the process after an unrecoverable error.
+    /// Defaults to logging the error critically and calling `std::process::abort`.
+    fn abort(error_type: String, error: String) -> ! {
+        Self::log_critical(format!("{error_type}: {error}"));
+        std::process::abort()
+    }
     fn log_info(msg: impl ToString);
     fn log_warn(msg: impl ToString);
     fn log_debug(msg: impl ToString);
@@ -33,6 +60,38 @@ pub trait ExternalFunctions {
     fn free_string(ptr: *const c_char);
     fn free_of_type(ptr: *mut c_void, typ: FreeableDataType);
     fn free_u32_buffer(buf: U32Buffer);
+    /// Frees the array of an `F32Buffer` the host allocated, as in an `ExtF32Buffer` param.
+    /// The struct itself is passed by value and isn't freed.
+    fn free_f32_buffer(buf: F32Buffer);
+    /// Receives the result of a call deferred with `CallHandle::queue_call`.
+    /// Results are dropped unless the host overrides this.
+    fn call_completed(key: ScriptFnKey, result: Param) {
+        let _ = (key, result);
+    }
+    /// Duplicates the host object behind `ptr` and returns the copy, or null if it can't be cloned.
+    /// Defaults to a shallow clone that returns the same object.
+    fn clone_object(ptr: *const c_void) -> *const c_void {
+        ptr
+    }
+    /// Receives an object a script returned as `DataType::OwnedObject`, the host owns it from now on.
+    /// Does nothing unless the host overrides this.
+    fn take_object(ptr: *const c_void) {
+        let _ = ptr;
+    }
+    /// Called after `Turing::poll_reload` reloaded the script at `path`, so the host can
+    /// refresh anything it cached from the old script. Does nothing unless the host overrides this.
+    fn on_script_reloaded(path: &Path) {
+        let _ = path;
+    }
+    /// Called when a script's call into a host function using `capability` fails, before the
+    /// error reaches the script as a trap or lua error. Lets the host survive a misbehaving mod,
+    /// e.g. by unloading just that one. `capability` is empty for lua's missing function handler.
+    /// Defaults to logging critically.
+    fn on_script_error(capability: &str, kind: ErrorKind, message: &str) {
+        Self::log_critical(format!(
+            "Script error ({kind}) calling into capability '{capability}': {message}"
+        ));
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -66,6 +125,28 @@ impl From<ScriptFnKey> for usize {
     }
 }
 
+/// Handle to a script function cached by `Turing::register_fast_call`.
+/// Stays valid across script reloads.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[repr(transparent)]
+pub struct FastCallHandle(u32);
+
+impl From<u32> for FastCallHandle {
+    fn from(value: u32) -> Self {
+        FastCallHandle(value)
+    }
+}
+
+impl From<FastCallHandle> for u32 {
+    fn from(value: FastCallHandle) -> Self {
+        value.0
+    }
+}
+
+/// How many strings a script may have sent with `_host_recv_string` before the oldest
+/// ones the host never took are dropped
+pub const MAX_RECEIVED_STRINGS: u32 = 1024;
+
 #[derive(Default)]
 pub struct EngineDataState {
     /// queue of strings for wasm to fetch (needed due to reentrancy limitations)
@@ -76,26 +157,388 @@ pub struct EngineDataState {
     pub f32_queue: VecDeque<f32>,
     /// queue for Vec<u32>s
     pub u32_buffer_queue: VecDeque<Vec<u32>>,
+    /// queue for Vec<f32>s
+    pub f32_buffer_queue: VecDeque<Vec<f32>>,
+    /// script calls deferred with `CallHandle::queue_call`
+    pub call_queue: VecDeque<QueuedCall>,
+    /// game time in seconds, fed by the host through `Turing::set_time`
+    pub time_seconds: f64,
+    /// `delta_time` of the last `Turing::fast_call_update`, for scripts to read outside `on_update`
+    pub delta_time: f32,
+    /// backs the `random`/`random_range` functions every engine gives scripts
+    pub rng: HostRng,
+    /// strings wasm guests sent with `_host_recv_string`, by id, until the host takes them
+    pub received_strings: FxHashMap<u32, String>,
+    /// id given to the next received string, 0 is never used
+    pub next_string_id: u32,
+    /// script log messages below this level are dropped, as are turing's own debug messages
+    /// about script calls unless it's `Debug`
+    pub log_level: LogLevel,
+    /// file name of the loaded script, prefixes its log messages if no log prefix is set
+    pub script_name: String,
+    /// host-backed key-value storage, persisted by the host between sessions
+    pub storage: ScriptStorage,
+    /// id of the loaded mod, namespacing its storage
+    pub storage_mod_id: Option<String>,
+    /// applied to floats scripts pass to host functions and return from calls
+    pub nan_policy: NanPolicy,
+    /// host function calls allowed per script call, by capability
+    pub callback_budgets: FxHashMap<String, CallbackBudget>,
+    /// host function calls made during the current or last script call
+    /// atomic so host calls without a budget only need the read lock to count themselves
+    pub callback_calls: AtomicU32,
+    /// whether host function calls are counted and timed into `host_metrics`
+    pub metrics: bool,
+    /// call stats of each host function, by registered name
+    pub host_metrics: CallStats,
+    /// error of the last fallible host function that failed during the current script call,
+    /// until a wasm script takes it with `_host_last_error`
+    pub last_host_error: Option<String>,
+    /// ids the loaded script sees for host objects
+    pub objects: ObjectTable,
+    /// nests a call in the running one for `CallHandle::call_fn_reentrant`, None if the engine can't
+    pub reentrant_call: Option<ReentrantCall>,
+}
+
+/// How many host function calls a capability may make per script call, see `Turing::set_callback_budget`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallbackBudget {
+    pub max_calls: u32,
+    /// calls made during the current or last script call
+    pub used: u32,
+}
+
+/// Whether a host function call fits in its capability's callback budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CallbackCharge {
+    Allowed,
+    /// The call is skipped, `first` only for the call that went past the budget
+    OverBudget {
+        max_calls: u32,
+        first: bool,
+    },
+}
+
+impl EngineDataState {
+    /// `time_seconds` in whole microseconds, what script clocks read in deterministic mode
+    pub fn game_time_micros(&self) -> u64 {
+        (self.time_seconds * 1_000_000.0) as u64
+    }
+
+    /// Stores a string sent by a script and returns the id to take it back with.
+    /// Drops the string sent `MAX_RECEIVED_STRINGS` ids ago if the host never took it.
+    pub fn receive_string(&mut self, s: String) -> u32 {
+        self.next_string_id = self.next_string_id.wrapping_add(1).max(1);
+        self.received_strings
+            .remove(&self.next_string_id.wrapping_sub(MAX_RECEIVED_STRINGS));
+        self.received_strings.insert(self.next_string_id, s);
+        self.next_string_id
+    }
+
+    /// Reads a value from the loaded script's storage namespace
+    pub fn script_storage_get(&self, key: &str) -> Result<Option<&str>> {
+        Ok(self.storage.get(self.script_storage_mod_id()?, key))
+    }
+
+    /// Writes a value to the loaded script's storage namespace, checking the storage quota
+    pub fn script_storage_set(&mut self, key: &str, value: &str) -> Result<()> {
+        let mod_id = self.script_storage_mod_id()?.to_string();
+        self.storage.set(&mod_id, key, value)
+    }
+
+    /// Starts counting host function calls for a new script call
+    pub(crate) fn begin_invocation(&mut self) {
+        *self.callback_calls.get_mut() = 0;
+        self.last_host_error = None;
+        for budget in self.callback_budgets.values_mut() {
+            budget.used = 0;
+        }
+    }
+
+    /// Counts a host function call made with `capability` against its budget.
+    /// Takes the write lock only if the capability has a budget, so host calls from other
+    /// capabilities don't contend on it.
+    pub(crate) fn charge_callback(data: &RwLock<Self>, capability: &str) -> CallbackCharge {
+        {
+            let state = data.read();
+            let _ =
+                state
+                    .callback_calls
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |calls| {
+                        Some(calls.saturating_add(1))
+                    });
+            if !state.callback_budgets.contains_key(capability) {
+                return CallbackCharge::Allowed;
+            }
+        }
+        data.write().charge_budget(capability)
+    }
+
+    fn charge_budget(&mut self, capability: &str) -> CallbackCharge {
+        let Some(budget) = self.callback_budgets.get_mut(capability) else {
+            return CallbackCharge::Allowed;
+        };
+        budget.used = budget.used.saturating_add(1);
+        if budget.used <= budget.max_calls {
+            CallbackCharge::Allowed
+        } else {
+            CallbackCharge::OverBudget {
+                max_calls: budget.max_calls,
+                first: budget.used == budget.max_calls + 1,
+            }
+        }
+    }
+
+    /// Counts a call to the host function `name` that took `elapsed`
+    pub(crate) fn record_host_call(&mut self, name: &CStr, elapsed: Duration) {
+        self.host_metrics.record(&name.to_string_lossy(), elapsed);
+    }
+
+    pub(crate) fn script_storage_mod_id(&self) -> Result<&str> {
+        self.storage_mod_id
+            .as_deref()
+            .ok_or_else(|| anyhow!("storage is unavailable to scripts loaded from memory"))
+    }
+}
+
+/// SplitMix64 generator for scripts, seeded by the host so a run can be replayed
+#[derive(Clone, Debug)]
+pub struct HostRng(u64);
+
+impl HostRng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in `[min, max]`, the bounds may be given in either order
+    pub fn range(&mut self, min: i32, max: i32) -> i32 {
+        let (lo, hi) = if min <= max { (min, max) } else { (max, min) };
+        let span = (hi as i64 - lo as i64 + 1) as u64;
+        (lo as i64 + (self.next_u64() % span) as i64) as i32
+    }
+}
+
+/// Seeded from the clock, use `Turing::set_random_seed` for a reproducible sequence
+impl Default for HostRng {
+    fn default() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Self::new(nanos)
+    }
+}
+
+/// A script call waiting for the current call to return
+pub struct QueuedCall {
+    pub key: ScriptFnKey,
+    pub params: Params,
+    pub expected_return_type: DataType,
+}
+
+/// Runs a script call nested in the one that is running, set by engines that can nest calls
+pub type ReentrantCall = Arc<
+    dyn Fn(ScriptFnKey, Params, DataType, &Arc<RwLock<EngineDataState>>) -> Param + Send + Sync,
+>;
+
+/// Calls back into the loaded script from host callbacks, see `Turing::call_handle`.
+/// Goes through the shared engine data, so it never aliases the `Turing` running the outer call.
+pub struct CallHandle<Ext: ExternalFunctions + Send + Sync + 'static> {
+    data: Arc<RwLock<EngineDataState>>,
+    _ext: PhantomData<Ext>,
+}
+
+impl<Ext: ExternalFunctions + Send + Sync + 'static> Clone for CallHandle<Ext> {
+    fn clone(&self) -> Self {
+        Self {
+            data: Arc::clone(&self.data),
+            _ext: PhantomData,
+        }
+    }
 }
 
-impl EngineDataState {}
+impl<Ext: ExternalFunctions + Send + Sync + 'static> CallHandle<Ext> {
+    /// Calls a script function from inside a host callback, while another call is running.
+    /// Only the lua engine can nest calls, other engines and instances without a script return
+    /// `Param::Error(REENTRY_UNSUPPORTED_MESSAGE)` and should use `queue_call` instead.
+    pub fn call_fn_reentrant(
+        &self,
+        cache_key: ScriptFnKey,
+        params: Params,
+        expected_return_type: DataType,
+    ) -> Param {
+        if !cache_key.is_valid() {
+            return Param::Error(FUNCTION_NOT_FOUND_MESSAGE.to_string());
+        }
+        // cloned out so the lock isn't held while the script runs
+        let Some(call) = self.data.read().reentrant_call.clone() else {
+            return Param::Error(REENTRY_UNSUPPORTED_MESSAGE.to_string());
+        };
+        let res = call(cache_key, params, expected_return_type, &self.data);
+        Turing::<Ext>::script_result(res, expected_return_type, &self.data)
+    }
 
+    /// Defers a script call until the current `call_fn` or fast call returns,
+    /// so host callbacks can call back into a script on any engine.
+    /// The result is delivered to `Ext::call_completed`.
+    pub fn queue_call(
+        &self,
+        cache_key: ScriptFnKey,
+        params: Params,
+        expected_return_type: DataType,
+    ) {
+        // formatting the params isn't free, skip it when debug messages are filtered out
+        if self.data.read().log_level <= LogLevel::Debug {
+            Ext::log_debug(format!(
+                "Queued call to function {} with params {params}",
+                cache_key.0
+            ));
+        }
+        self.data.write().call_queue.push_back(QueuedCall {
+            key: cache_key,
+            params,
+            expected_return_type,
+        });
+    }
+}
+
+/// Every engine is `Send`, so an instance can be built on one thread and driven from another.
+/// Calls still need exclusive access, see `call_handle` for calling from host callbacks.
 pub struct Turing<Ext: ExternalFunctions + Send + Sync + 'static> {
     pub engine: Option<Engine<Ext>>,
     pub data: Arc<RwLock<EngineDataState>>,
     pub script_fns: FxHashMap<String, ScriptFnMetadata>,
+    pub config: EngineConfig,
+    fast_call_names: Vec<String>,
+    /// Manifest of the mod last loaded with `load_mod`
+    manifest: Option<ModManifest>,
+    /// Where the loaded script came from and what it was loaded with
+    #[cfg(feature = "hot_reload")]
+    loaded_source: Option<watch::ScriptSource>,
+    #[cfg(feature = "hot_reload")]
+    hot_reload: Option<watch::HotReload>,
+    /// Compiled wasm modules, kept across loads
+    #[cfg(feature = "wasm")]
+    module_cache: engine::wasm_engine::ModuleCache,
+    /// Recorded while `config.profiling` is set
+    stats: CallStats,
+    /// What the FFI last listed with `list_functions`, read one entry at a time
+    functions_snapshot: Vec<FunctionInfo>,
+    /// registered name of every function by the symbol wasm scripts import it as,
+    /// rebuilt from `script_fns` when built and on every load
+    import_names: FxHashMap<String, String>,
     _ext: PhantomData<Ext>,
 }
 
 pub struct TuringSetup<Ext: ExternalFunctions + Send + Sync + 'static> {
     script_fns: FxHashMap<String, ScriptFnMetadata>,
+    config: EngineConfig,
+    callback_budgets: FxHashMap<String, u32>,
+    metrics: bool,
     _ext: PhantomData<Ext>,
 }
 
 impl<Ext: ExternalFunctions + Send + Sync + 'static> TuringSetup<Ext> {
     pub fn build(self) -> Result<Turing<Ext>> {
         let data = Arc::new(RwLock::new(EngineDataState::default()));
-        Ok(Turing::build(self.script_fns, data))
+        let turing = Turing::build(self.script_fns, self.config, data);
+        for (capability, max_calls) in self.callback_budgets {
+            turing.set_callback_budget(capability, Some(max_calls));
+        }
+        turing.set_metrics(self.metrics);
+        Ok(turing)
+    }
+
+    /// Routes calls to every function registered without its own callback through `dispatch`
+    pub fn set_dispatch(&mut self, dispatch: DispatchCallback) {
+        self.config.dispatch = Some(dispatch);
+    }
+
+    /// Lets lua scripts call `turing_api` functions that were never registered, forwarding
+    /// their name and args to `on_missing` instead of the lookup being `nil`. Lua only,
+    /// a wasm module importing a missing function still fails to load.
+    pub fn set_on_missing_function(&mut self, on_missing: DispatchCallback) {
+        self.config.on_missing_function = Some(on_missing);
+    }
+
+    /// Limits how many instructions a single call into a lua script may run.
+    /// Calls past the budget return `Param::Error("lua execution budget exceeded")`.
+    pub fn set_lua_instruction_budget(&mut self, budget: u64) {
+        self.config.lua_instruction_budget = Some(budget);
+    }
+
+    /// Limits how many times a single script call may call host functions with `capability`,
+    /// so a buggy script can't starve the frame. See `Turing::set_callback_budget`.
+    pub fn set_callback_budget(&mut self, capability: impl ToString, max_calls: u32) {
+        self.callback_budgets
+            .insert(capability.to_string(), max_calls);
+    }
+
+    /// Counts and times every host function call scripts make, see `Turing::take_metrics`
+    pub fn set_metrics(&mut self, enabled: bool) {
+        self.metrics = enabled;
+    }
+
+    /// Functions whose capability isn't loaded won't be bound at all. A wasm module importing
+    /// one fails to load, and in lua the function is `nil`.
+    pub fn set_hide_inactive_capabilities(&mut self, hide: bool) {
+        self.config.hide_inactive_capabilities = hide;
+    }
+
+    /// Prefixes everything the script logs with `[name] `, so output from several mods
+    /// logging through the same `ExternalFunctions` can be told apart
+    pub fn set_log_prefix(&mut self, name: impl ToString) {
+        self.config.log_prefix = Some(name.to_string());
+    }
+
+    /// Records how often each script function is called and how long it runs, see `Turing::take_stats`.
+    /// Off by default so calls don't pay for reading the clock.
+    pub fn set_profiling(&mut self, profiling: bool) {
+        self.config.profiling = profiling;
+    }
+
+    /// Makes scripts replay identically across machines, see `EngineConfig::deterministic`
+    /// and the README for what is covered
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.config.deterministic = deterministic;
+    }
+
+    /// Seeds the host `random` functions and lua's `math.random`,
+    /// so every run of a script draws the same numbers
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.config.random_seed = Some(seed);
+    }
+
+    /// Numbers objects `1, 2, 3, ...` in the order scripts first see them instead of passing the host's ids,
+    /// so script output stays the same across runs. Numbering restarts whenever a script loads.
+    pub fn set_sequential_object_ids(&mut self, sequential: bool) {
+        self.config.sequential_object_ids = sequential;
+    }
+
+    /// Changes how much stack a wasm call may use from the default 512KB.
+    /// Fails if `bytes` is below 64KB.
+    pub fn set_wasm_max_stack_bytes(&mut self, bytes: usize) -> Result<()> {
+        EngineConfig::check_wasm_stack_bytes(bytes)?;
+        self.config.wasm_max_stack_bytes = Some(bytes);
+        Ok(())
+    }
+
+    /// Changes how long a line wasm scripts print may get from the default 64KB.
+    /// Longer lines are logged cut off, so a script printing without newlines can't fill up memory.
+    pub fn set_wasm_output_buffer_bytes(&mut self, bytes: usize) {
+        self.config.wasm_output_buffer_bytes = Some(bytes);
     }
 
     /// Attempts to add a new function. Returns err if the function already exists
@@ -110,6 +553,31 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> TuringSetup<Ext> {
         self.script_fns.insert(name, metadata);
         Ok(())
     }
+
+    /// Adds a host function implemented by a rust closure, with param and return types taken from
+    /// its signature: `setup.add_rust_function("scale", "math", |(x, by): (f32, f32)| Ok(x * by))`.
+    /// Takes a tuple of up to 6 args. Returns err if the function already exists.
+    pub fn add_rust_function<Args: ScriptArgs, R: ScriptReturn>(
+        &mut self,
+        name: impl ToString,
+        capability: impl ToString,
+        f: impl Fn(Args) -> Result<R> + Send + Sync + 'static,
+    ) -> Result<()> {
+        let metadata = ScriptFnMetadata::from_rust_fn::<Ext, _, _>(capability.to_string(), f)?;
+        self.add_function(name, metadata)
+    }
+
+    /// Adds every function in `functions`, stopping at the first name that is already registered.
+    /// Functions before the duplicate stay registered.
+    pub fn add_functions(
+        &mut self,
+        functions: impl IntoIterator<Item = (String, ScriptFnMetadata)>,
+    ) -> Result<()> {
+        for (name, metadata) in functions {
+            self.add_function(name, metadata)?;
+        }
+        Ok(())
+    }
 }
 
 impl<Ext: ExternalFunctions + Send + Sync + 'static> Turing<Ext> {
@@ -117,47 +585,90 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> Turing<Ext> {
     pub fn new() -> TuringSetup<Ext> {
         TuringSetup {
             script_fns: Default::default(),
+            config: Default::default(),
+            callback_budgets: Default::default(),
+            metrics: false,
             _ext: PhantomData,
         }
     }
 
     fn build(
         script_fns: FxHashMap<String, ScriptFnMetadata>,
+        config: EngineConfig,
         data: Arc<RwLock<EngineDataState>>,
     ) -> Self {
+        let import_names = Self::index_import_names(&script_fns);
         Self {
             engine: None,
             script_fns,
+            config,
             data,
+            fast_call_names: Vec::new(),
+            manifest: None,
+            #[cfg(feature = "hot_reload")]
+            loaded_source: None,
+            #[cfg(feature = "hot_reload")]
+            hot_reload: None,
+            #[cfg(feature = "wasm")]
+            module_cache: Default::default(),
+            stats: CallStats::default(),
+            functions_snapshot: Vec::new(),
+            import_names,
             _ext: PhantomData,
         }
     }
 
-    /// Enables a capability for the currently loaded script
-    pub fn register_capability(&mut self, name: impl ToString) {
+    /// Enables a capability for the currently loaded script, e.g. once a mod it depends on has loaded.
+    /// Capabilities are checked each time a script calls a host function, so this takes effect on
+    /// the next call. Bindings hidden by `hide_inactive_capabilities` stay hidden until the next load.
+    pub fn add_capability(&mut self, name: impl ToString) {
         self.data
             .write()
             .active_capabilities
             .insert(name.to_string());
     }
 
-    /// Disables a capability for the currently loaded script
-    pub fn unregister_capability(&mut self, name: impl AsRef<str>) {
+    /// Disables a capability for the currently loaded script.
+    /// Takes effect on the next call: host functions using it then fail, or return their
+    /// `CapabilityMissingPolicy` default. Doing this from a host callback doesn't interrupt the running call.
+    pub fn remove_capability(&mut self, name: impl AsRef<str>) {
         self.data.write().active_capabilities.remove(name.as_ref());
     }
 
+    #[deprecated(note = "renamed to `add_capability`")]
+    pub fn register_capability(&mut self, name: impl ToString) {
+        self.add_capability(name);
+    }
+
+    #[deprecated(note = "renamed to `remove_capability`")]
+    pub fn unregister_capability(&mut self, name: impl AsRef<str>) {
+        self.remove_capability(name);
+    }
+
     pub fn load_script(
         &mut self,
         source: impl ToString,
         loaded_capabilities: &[impl ToString],
     ) -> Result<()> {
-        // drop any existing engine
-        self.engine.take();
+        self.load_script_file(
+            Path::new(&source.to_string()),
+            loaded_capabilities,
+            None,
+            None,
+        )
+    }
 
-        let source = source.to_string();
-        let source = Path::new(&source);
-        let capabilities: FxHashSet<String> =
-            loaded_capabilities.iter().map(|c| c.to_string()).collect();
+    /// Shared by `load_script`, `load_script_checked` and `load_mod`.
+    /// Storage is namespaced by `mod_id`, or the file name without its extension if None.
+    fn load_script_file(
+        &mut self,
+        source: &Path,
+        loaded_capabilities: &[impl ToString],
+        host_versions: Option<&[(String, Semver)]>,
+        mod_id: Option<&str>,
+    ) -> Result<()> {
+        // drop any existing engine
+        self.unload_script();
 
         if let Err(e) = source.metadata() {
             return Err(anyhow!("Script does not exist: {:#?}, {:#?}", source, e));
@@ -168,48 +679,431 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> Turing<Ext> {
                 "script file has no extension, must be either .wasm or .lua"
             ));
         };
+        let kind = match extension.to_string_lossy().as_ref() {
+            "wasm" => EngineKind::Wasm,
+            "lua" => EngineKind::Lua,
+            _ => {
+                return Err(anyhow!(
+                    "Unknown script extension: '{extension:?}' must be .wasm or .lua"
+                ));
+            }
+        };
+        let bytes = std::fs::read(source)?;
+        let script_name = source
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mod_id = mod_id.map_or_else(|| script_name.clone(), str::to_string);
+
+        self.load_from_memory(
+            &bytes,
+            kind,
+            script_name,
+            Some(mod_id),
+            loaded_capabilities,
+            host_versions,
+        )?;
+
+        #[cfg(feature = "hot_reload")]
+        {
+            self.loaded_source = Some(watch::ScriptSource::File {
+                path: source.to_path_buf(),
+                capabilities: loaded_capabilities.iter().map(|c| c.to_string()).collect(),
+                host_versions: host_versions.map(<[_]>::to_vec),
+            });
+        }
+        Ok(())
+    }
+
+    /// Loads a script from memory, e.g. one the host downloaded or embedded, instead of a file.
+    /// `bytes` is a compiled wasm module or utf-8 lua source depending on `kind`.
+    /// Its log messages are prefixed with `wasm` or `lua` unless a log prefix is set.
+    /// It can't be hot reloaded since there's no file to watch, and has no mod id to keep storage under.
+    pub fn load_script_bytes(
+        &mut self,
+        bytes: &[u8],
+        kind: EngineKind,
+        loaded_capabilities: &[impl ToString],
+    ) -> Result<()> {
+        self.unload_script();
+        let script_name = match kind {
+            EngineKind::Wasm => "wasm",
+            EngineKind::Lua => "lua",
+        };
+        self.load_from_memory(
+            bytes,
+            kind,
+            script_name.to_string(),
+            None,
+            loaded_capabilities,
+            None,
+        )
+    }
+
+    /// Shared by `load_script` and `load_script_bytes`, expects the old script to be unloaded.
+    /// With `host_versions` the script's api versions are checked before `on_load` runs,
+    /// so an incompatible script never gets to do anything.
+    /// The script's storage is namespaced by `mod_id`, without one it can't use storage.
+    fn load_from_memory(
+        &mut self,
+        bytes: &[u8],
+        kind: EngineKind,
+        script_name: String,
+        mod_id: Option<String>,
+        loaded_capabilities: &[impl ToString],
+        host_versions: Option<&[(String, Semver)]>,
+    ) -> Result<()> {
+        let capabilities: FxHashSet<String> =
+            loaded_capabilities.iter().map(|c| c.to_string()).collect();
 
         for cap in &capabilities {
             Ext::log_info(format!("Registered capability: {}", cap));
         }
-        match extension.to_string_lossy().as_ref() {
+        // active while the script loads, so binding can see them
+        self.data.write().active_capabilities = capabilities;
+        self.data.write().storage_mod_id = mod_id;
+        self.data.write().received_strings.clear();
+        self.data.write().objects = ObjectTable::new(self.config.sequential_object_ids);
+        self.data.write().script_name = script_name;
+        self.import_names = Self::index_import_names(&self.script_fns);
+        if let Some(seed) = self.config.effective_random_seed() {
+            self.data.write().rng = HostRng::new(seed);
+        }
+
+        match kind {
             #[cfg(feature = "wasm")]
-            "wasm" => {
+            EngineKind::Wasm => {
                 let mut wasm_interpreter = engine::wasm_engine::WasmInterpreter::new(
                     &self.script_fns,
+                    &self.config,
                     Arc::clone(&self.data),
                 )?;
-                wasm_interpreter.load_script(source)?;
+                wasm_interpreter.load_bytes_cached(bytes, &mut self.module_cache)?;
                 self.engine = Some(Engine::Wasm(wasm_interpreter));
             }
             #[cfg(feature = "lua")]
-            "lua" => {
+            EngineKind::Lua => {
+                let source = std::str::from_utf8(bytes)
+                    .map_err(|e| anyhow!("lua script isn't valid utf-8: {e}"))?;
                 let mut lua_interpreter = engine::lua_engine::LuaInterpreter::new(
                     &self.script_fns,
+                    &self.config,
                     Arc::clone(&self.data),
                 )?;
-                lua_interpreter.load_script(source)?;
+                lua_interpreter.load_source(source)?;
                 self.engine = Some(Engine::Lua(lua_interpreter));
             }
+            #[allow(unreachable_patterns)]
             _ => {
+                return Err(anyhow!("turing was built without {kind:?} support"));
+            }
+        }
+
+        if let Some(engine) = &mut self.engine {
+            for (handle, name) in self.fast_call_names.iter().enumerate() {
+                // one stale handle shouldn't cost the host the whole script
+                if let Err(e) = engine.register_fast_call(name) {
+                    Ext::log_warn(format!(
+                        "Fast call handle {handle} ('{name}') was invalidated: {e}"
+                    ));
+                    engine.invalidate_fast_call(name);
+                }
+            }
+        }
+
+        if let Some(host_versions) = host_versions {
+            let incompatibilities = self.check_compatibility(host_versions);
+            if !incompatibilities.is_empty() {
+                // on_load never ran, so there is nothing for on_unload to undo
+                self.engine.take();
                 return Err(anyhow!(
-                    "Unknown script extension: '{extension:?}' must be .wasm or .lua"
+                    "Script is incompatible with the host api:\n{}",
+                    incompatibilities
+                        .iter()
+                        .map(|i| format!("  {i}"))
+                        .collect::<Vec<_>>()
+                        .join("\n")
                 ));
             }
         }
 
-        let mut write = self.data.write();
-        write.active_capabilities = capabilities;
+        if let Some(Param::Error(e)) = self.call_lifecycle_fn(ON_LOAD_FN) {
+            // on_unload isn't called for a script that never finished loading
+            self.engine.take();
+            return Err(anyhow!("Script failed in {ON_LOAD_FN}: {e}"));
+        }
 
         Ok(())
     }
 
-    pub fn get_fn_key(&self, arg: &str) -> Option<ScriptFnKey> {
-        let Some(engine) = &self.engine else {
-            panic!("Engine not initialized");
+    /// Calls the loaded script's `on_unload` if it exports one, then drops the engine.
+    /// An `on_unload` error is logged and doesn't stop the unload. Does nothing if no script is loaded.
+    /// Stops any hot reload watch, which is for the script that was loaded.
+    pub fn unload_script(&mut self) {
+        self.manifest = None;
+        #[cfg(feature = "hot_reload")]
+        {
+            self.loaded_source = None;
+            self.hot_reload = None;
+        }
+        if let Some(Param::Error(e)) = self.call_lifecycle_fn(ON_UNLOAD_FN) {
+            Ext::log_warn(format!("Script failed in {ON_UNLOAD_FN}: {e}"));
+        }
+        self.engine.take();
+    }
+
+    /// Starts the loaded wasm script over in a fresh instance without recompiling it,
+    /// for recovering after a call traps. Function keys and fast call handles stay valid,
+    /// and `on_load` runs again since the script's state is gone. `on_unload` isn't called.
+    /// Errors for lua scripts, whose state survives a failed call.
+    pub fn reset_instance(&mut self) -> Result<()> {
+        let Some(engine) = &mut self.engine else {
+            return Err(anyhow!("No script is loaded"));
         };
+        engine.reset_instance()?;
+        {
+            // anything a trapped call left queued would be read by the next one
+            let mut data = self.data.write();
+            data.str_cache.clear();
+            data.f32_queue.clear();
+            data.u32_buffer_queue.clear();
+            data.f32_buffer_queue.clear();
+        }
 
-        engine.get_fn_key(arg)
+        if let Some(Param::Error(e)) = self.call_lifecycle_fn(ON_LOAD_FN) {
+            self.engine.take();
+            return Err(anyhow!("Script failed in {ON_LOAD_FN}: {e}"));
+        }
+        Ok(())
+    }
+
+    /// Starts watching the loaded script's file so `poll_reload` can reload it once it changes.
+    /// The script is reloaded the way it was loaded: a mod through `load_mod` with the same
+    /// allowlist, a script with the same capabilities and host versions.
+    /// Loading another script or unloading this one stops the watch.
+    #[cfg(feature = "hot_reload")]
+    pub fn enable_hot_reload(&mut self) -> Result<()> {
+        let Some(source) = &self.loaded_source else {
+            return Err(anyhow!("No script is loaded"));
+        };
+        self.hot_reload = Some(watch::HotReload::new(source.clone())?);
+        Ok(())
+    }
+
+    #[cfg(feature = "hot_reload")]
+    pub fn disable_hot_reload(&mut self) {
+        self.hot_reload = None;
+    }
+
+    /// Reloads the watched script if its file changed since it was last loaded, calling
+    /// `on_unload` on the old script and `on_load` on the new one, then `Ext::on_script_reloaded`.
+    /// Meant to be called from the host's frame loop, it only checks a flag until the file changes.
+    /// Returns whether the script was reloaded. If the new version fails to load, no script is
+    /// loaded and the error is returned, the file stays watched so fixing it reloads again.
+    #[cfg(feature = "hot_reload")]
+    pub fn poll_reload(&mut self) -> Result<bool> {
+        let Some(hot_reload) = &mut self.hot_reload else {
+            return Ok(false);
+        };
+        if !hot_reload.take_change() {
+            return Ok(false);
+        }
+        let source = hot_reload.source().clone();
+        Ext::log_info(format!(
+            "Reloading changed script: {}",
+            source.path().display()
+        ));
+        // loading unloads the old script, which would end the watch
+        let hot_reload = self.hot_reload.take();
+        let result = match &source {
+            watch::ScriptSource::File {
+                path,
+                capabilities,
+                host_versions,
+            } => self.load_script_file(path, capabilities, host_versions.as_deref(), None),
+            watch::ScriptSource::Mod {
+                dir,
+                allowed_capabilities,
+                host_versions,
+                ..
+            } => self
+                .load_mod(dir, allowed_capabilities, host_versions)
+                .map(|_| ()),
+        };
+        self.hot_reload = hot_reload;
+        result?;
+        Ext::on_script_reloaded(source.path());
+        Ok(true)
+    }
+
+    /// Calls a lifecycle hook with no params, None if no script is loaded or it doesn't export `name`
+    fn call_lifecycle_fn(&mut self, name: &str) -> Option<Param> {
+        let key = self.get_fn_key(name)?;
+        Some(self.call_fn(key, Params::new(), DataType::Void))
+    }
+
+    /// Like `load_script`, but refuses to load the script if any api version it declares
+    /// can't be served by `host_versions`. The check runs before the script's `on_load`,
+    /// which an incompatible script never gets to run. The error lists every mismatch.
+    pub fn load_script_checked(
+        &mut self,
+        source: impl ToString,
+        loaded_capabilities: &[impl ToString],
+        host_versions: &[(String, Semver)],
+    ) -> Result<()> {
+        self.load_script_file(
+            Path::new(&source.to_string()),
+            loaded_capabilities,
+            Some(host_versions),
+            None,
+        )
+    }
+
+    /// Loads the mod in `dir` as described by its `mod.toml`.
+    /// Fails without loading anything if the manifest requests a capability no registered
+    /// function uses or that isn't in `allowed_capabilities`, or needs an api version
+    /// `host_versions` can't serve. The allowlist is the host's decision per mod, a manifest
+    /// can't grant itself more than that.
+    /// The entry script is loaded with exactly the requested capabilities and checked like
+    /// `load_script_checked`. Anything suspicious that didn't stop the load is logged and returned.
+    pub fn load_mod(
+        &mut self,
+        dir: &Path,
+        allowed_capabilities: &[impl AsRef<str>],
+        host_versions: &[(String, Semver)],
+    ) -> Result<ModLoadReport> {
+        self.unload_script();
+
+        let manifest = ModManifest::read(dir)?;
+        let mut report = ModLoadReport::default();
+
+        let unknown = manifest
+            .capabilities
+            .iter()
+            .filter(|cap| !self.script_fns.values().any(|f| &f.capability == *cap))
+            .map(|cap| format!("'{cap}'"))
+            .collect::<Vec<_>>();
+        if !unknown.is_empty() {
+            return Err(anyhow!(
+                "mod '{}' requests unknown capabilities: {}",
+                manifest.name,
+                unknown.join(", ")
+            ));
+        }
+
+        let denied = manifest
+            .capabilities
+            .iter()
+            .filter(|cap| !allowed_capabilities.iter().any(|a| a.as_ref() == *cap))
+            .map(|cap| format!("'{cap}'"))
+            .collect::<Vec<_>>();
+        if !denied.is_empty() {
+            return Err(anyhow!(
+                "mod '{}' requests capabilities it is not allowed: {}",
+                manifest.name,
+                denied.join(", ")
+            ));
+        }
+
+        let incompatibilities = manifest
+            .api
+            .iter()
+            .filter_map(|(api, required)| {
+                let host_version = host_versions
+                    .iter()
+                    .find_map(|(name, v)| (name == api).then_some(*v));
+                match host_version {
+                    Some(host) if host.is_compatible_with(required) => None,
+                    _ => Some(Incompatibility {
+                        api: api.clone(),
+                        script_version: *required,
+                        host_version,
+                    }),
+                }
+            })
+            .map(|i| format!("  {i}"))
+            .collect::<Vec<_>>();
+        if !incompatibilities.is_empty() {
+            return Err(anyhow!(
+                "mod '{}' is incompatible with the host api:\n{}",
+                manifest.name,
+                incompatibilities.join("\n")
+            ));
+        }
+
+        let entry = manifest.entry_path(dir)?;
+        self.load_script_file(
+            &entry,
+            &manifest.capabilities,
+            Some(host_versions),
+            Some(&manifest.name),
+        )?;
+
+        for (api, version) in self.get_api_versions().into_iter().flatten() {
+            match manifest.api.get(api) {
+                None => report.warnings.push(format!(
+                    "script uses api '{api}' {version}, which mod.toml doesn't list"
+                )),
+                Some(listed) if listed != version => report.warnings.push(format!(
+                    "mod.toml lists api '{api}' {listed}, but the script was built against {version}"
+                )),
+                _ => {}
+            }
+        }
+        for warning in &report.warnings {
+            Ext::log_warn(format!("mod '{}': {warning}", manifest.name));
+        }
+
+        self.manifest = Some(manifest);
+        #[cfg(feature = "hot_reload")]
+        {
+            self.loaded_source = Some(watch::ScriptSource::Mod {
+                dir: dir.to_path_buf(),
+                entry,
+                allowed_capabilities: allowed_capabilities
+                    .iter()
+                    .map(|c| c.as_ref().to_string())
+                    .collect(),
+                host_versions: host_versions.to_vec(),
+            });
+        }
+        Ok(report)
+    }
+
+    /// Manifest of the mod last loaded with `load_mod`, None if the last load wasn't a mod or failed
+    pub fn manifest(&self) -> Option<&ModManifest> {
+        self.manifest.as_ref()
+    }
+
+    /// Compares the api versions declared by the loaded script against `host_versions`.
+    /// Returns an empty list if no script is loaded or everything is compatible.
+    pub fn check_compatibility(&self, host_versions: &[(String, Semver)]) -> Vec<Incompatibility> {
+        let Some(script_versions) = self.get_api_versions() else {
+            return Vec::new();
+        };
+
+        let mut incompatibilities = script_versions
+            .iter()
+            .filter_map(|(api, script_version)| {
+                let host_version = host_versions
+                    .iter()
+                    .find_map(|(name, v)| (name == api).then_some(*v));
+                match host_version {
+                    Some(host) if host.is_compatible_with(script_version) => None,
+                    _ => Some(Incompatibility {
+                        api: api.clone(),
+                        script_version: *script_version,
+                        host_version,
+                    }),
+                }
+            })
+            .collect::<Vec<_>>();
+        incompatibilities.sort_by(|a, b| a.api.cmp(&b.api));
+        incompatibilities
+    }
+
+    pub fn get_fn_key(&self, arg: &str) -> Option<ScriptFnKey> {
+        self.engine.as_ref()?.get_fn_key(arg)
     }
 
     pub fn call_fn_by_name(
@@ -224,27 +1118,131 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> Turing<Ext> {
         let key = engine.get_fn_key(&name.to_string());
 
         let Some(key) = key else {
-            return Param::Error(format!("Function '{}' not found", name.to_string()));
+            return Param::Error(format!(
+                "{FUNCTION_NOT_FOUND_MESSAGE}: {}",
+                name.to_string()
+            ));
         };
         self.call_fn(key, params, expected_return_type)
     }
 
+    /// Like `call_fn_by_name`, for args that aren't in a `Params` yet
+    pub fn call_fn_args(
+        &mut self,
+        name: impl ToString,
+        args: impl IntoIterator<Item = Param>,
+        expected_return_type: DataType,
+    ) -> Param {
+        self.call_fn_by_name(name, args.into_iter().collect(), expected_return_type)
+    }
+
     pub fn call_fn(
         &mut self,
         cache_key: ScriptFnKey,
-        params: Params,
+        mut params: Params,
+        expected_return_type: DataType,
+    ) -> Param {
+        self.call_fn_draining(cache_key, &mut params, expected_return_type)
+    }
+
+    /// Like `call_fn`, but moves the args out of `params` and leaves it empty with its capacity
+    /// intact, so a caller can refill the same `Params` for every call without allocating
+    pub fn call_fn_draining(
+        &mut self,
+        cache_key: ScriptFnKey,
+        params: &mut Params,
+        expected_return_type: DataType,
+    ) -> Param {
+        let res = self.invoke_fn(cache_key, params, expected_return_type);
+        // calls that fail before reaching the script still consume their args
+        params.clear();
+        res
+    }
+
+    fn invoke_fn(
+        &mut self,
+        cache_key: ScriptFnKey,
+        params: &mut Params,
         expected_return_type: DataType,
     ) -> Param {
-        // let name = name.to_string();
         let Some(engine) = &mut self.engine else {
             return Param::Error("No code engine is active".to_string());
         };
 
         if !cache_key.is_valid() {
-            return Param::Error("Invalid function key".to_string());
+            return Param::Error(FUNCTION_NOT_FOUND_MESSAGE.to_string());
         }
 
-        engine.call_fn(cache_key, params, expected_return_type, &self.data)
+        self.data.write().begin_invocation();
+        let started = self.config.profiling.then(Instant::now);
+        let res = engine.call_fn(cache_key, params, expected_return_type, &self.data);
+        if let Some(started) = started {
+            let elapsed = started.elapsed();
+            if let Some(name) = engine.fn_name(cache_key) {
+                self.stats.record(name, elapsed);
+            }
+        }
+        let res = Self::script_result(res, expected_return_type, &self.data);
+        self.drain_call_queue();
+        res
+    }
+
+    /// Applies the NaN policy to a script's return value, and hands an object returned
+    /// as `DataType::OwnedObject` to `Ext::take_object`
+    fn script_result(
+        res: Param,
+        expected_return_type: DataType,
+        data: &RwLock<EngineDataState>,
+    ) -> Param {
+        let res = res.apply_nan_policy(data.read().nan_policy);
+        if expected_return_type == DataType::OwnedObject
+            && let Param::Object(id) = &res
+        {
+            Ext::take_object(unsafe { id.as_ptr() });
+        }
+        res
+    }
+
+    /// A handle host callbacks use to call back into the script while a call is running,
+    /// since the running call already borrows this `Turing` mutably
+    pub fn call_handle(&self) -> CallHandle<Ext> {
+        CallHandle {
+            data: Arc::clone(&self.data),
+            _ext: PhantomData,
+        }
+    }
+
+    /// Runs queued calls, including any queued while draining, and reports their results
+    fn drain_call_queue(&mut self) {
+        loop {
+            let Some(mut call) = self.data.write().call_queue.pop_front() else {
+                return;
+            };
+            let res = match &mut self.engine {
+                Some(_) if !call.key.is_valid() => {
+                    Param::Error(FUNCTION_NOT_FOUND_MESSAGE.to_string())
+                }
+                Some(engine) => {
+                    self.data.write().begin_invocation();
+                    let started = self.config.profiling.then(Instant::now);
+                    let res = engine.call_fn(
+                        call.key,
+                        &mut call.params,
+                        call.expected_return_type,
+                        &self.data,
+                    );
+                    if let Some(started) = started {
+                        let elapsed = started.elapsed();
+                        if let Some(name) = engine.fn_name(call.key) {
+                            self.stats.record(name, elapsed);
+                        }
+                    }
+                    Self::script_result(res, call.expected_return_type, &self.data)
+                }
+                None => Param::Error("No code engine is active".to_string()),
+            };
+            Ext::call_completed(call.key, res);
+        }
     }
 
     pub fn fast_call_update(&mut self, delta_time: f32) -> std::result::Result<(), String> {
@@ -252,7 +1250,15 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> Turing<Ext> {
             return Err("Engine not initialized".to_string());
         };
 
-        engine.fast_call_update(delta_time)
+        self.data.write().delta_time = delta_time;
+        self.data.write().begin_invocation();
+        let started = self.config.profiling.then(Instant::now);
+        let res = engine.fast_call_update(delta_time);
+        if let Some(started) = started {
+            Self::record_fast_call(&mut self.stats, engine, "on_update", started);
+        }
+        self.drain_call_queue();
+        res
     }
 
     pub fn fast_call_fixed_update(&mut self, delta_time: f32) -> std::result::Result<(), String> {
@@ -260,7 +1266,377 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> Turing<Ext> {
             return Err("Engine not initialized".to_string());
         };
 
-        engine.fast_call_fixed_update(delta_time)
+        self.data.write().begin_invocation();
+        let started = self.config.profiling.then(Instant::now);
+        let res = engine.fast_call_fixed_update(delta_time);
+        if let Some(started) = started {
+            Self::record_fast_call(&mut self.stats, engine, "on_fixed_update", started);
+        }
+        self.drain_call_queue();
+        res
+    }
+
+    /// Records a fast call by name, unless the script doesn't export it and nothing ran
+    fn record_fast_call(stats: &mut CallStats, engine: &Engine<Ext>, name: &str, started: Instant) {
+        let elapsed = started.elapsed();
+        if engine.get_fn_key(name).is_some() {
+            stats.record(name, elapsed);
+        }
+    }
+
+    /// Caches the script function `name` so it can be called every frame through `invoke_fast_call`
+    /// without a name lookup. The function must take a single `f32` and return nothing.
+    /// May be called before a script is loaded, handles are re-resolved whenever a script loads.
+    /// Errors if the loaded script exports `name` with an incompatible signature. A script loaded
+    /// later that does so still loads, the handle is logged and does nothing until the next load.
+    pub fn register_fast_call(&mut self, name: &str) -> Result<FastCallHandle> {
+        if let Some(engine) = &mut self.engine {
+            engine.register_fast_call(name)?;
+        }
+        self.fast_call_names.push(name.to_string());
+        Ok(FastCallHandle((self.fast_call_names.len() - 1) as u32))
+    }
+
+    /// Calls a function registered with `register_fast_call`.
+    /// Does nothing if the loaded script doesn't export it, like `fast_call_update`.
+    pub fn invoke_fast_call(
+        &mut self,
+        handle: FastCallHandle,
+        delta_time: f32,
+    ) -> std::result::Result<(), String> {
+        let Some(engine) = &mut self.engine else {
+            return Err("Engine not initialized".to_string());
+        };
+
+        self.data.write().begin_invocation();
+        let started = self.config.profiling.then(Instant::now);
+        let res = engine.invoke_fast_call(handle, delta_time);
+        if let Some(started) = started
+            && let Some(name) = self.fast_call_names.get(handle.0 as usize)
+        {
+            Self::record_fast_call(&mut self.stats, engine, name, started);
+        }
+        self.drain_call_queue();
+        res
+    }
+
+    /// Like `TuringSetup::set_profiling`, applies to the next call
+    pub fn set_profiling(&mut self, profiling: bool) {
+        self.config.profiling = profiling;
+    }
+
+    /// Returns how often each script function was called and how long it ran since the last
+    /// time this was called, most total time first. Empty unless profiling is enabled.
+    /// Nested calls made with `CallHandle::call_fn_reentrant` count towards the call they happen in.
+    pub fn take_stats(&mut self) -> Vec<(String, CallStat)> {
+        self.stats.take()
+    }
+
+    /// `take_stats` for the FFI, which reads the stats one at a time with `stats_snapshot`
+    pub(crate) fn take_stats_snapshot(&mut self) -> usize {
+        self.stats.take_snapshot()
+    }
+
+    pub(crate) fn stats_snapshot(&self) -> &[(String, CallStat)] {
+        self.stats.snapshot()
+    }
+
+    /// Whether a script is currently loaded
+    pub fn is_loaded(&self) -> bool {
+        self.engine.is_some()
+    }
+
+    /// Which engine runs the currently loaded script, None if no script is loaded
+    pub fn engine_kind(&self) -> Option<EngineKind> {
+        self.engine.as_ref().map(|engine| engine.kind())
+    }
+
+    /// Returns a handle that can interrupt calls into the currently loaded script from another thread.
+    /// Returns None if no script is loaded.
+    pub fn interrupt_handle(&self) -> Option<InterruptHandle> {
+        self.engine.as_ref().map(|engine| engine.interrupt_handle())
+    }
+
+    /// Interrupts the script call currently in flight, which will return `Param::Error("script interrupted")`.
+    /// Use `interrupt_handle` to interrupt from a thread that doesn't own this instance.
+    pub fn request_interrupt(&self) {
+        if let Some(handle) = self.interrupt_handle() {
+            handle.interrupt();
+        }
+    }
+
+    /// Changes the lua instruction budget for subsequent calls, None removes the limit.
+    /// Also applies to the currently loaded script.
+    pub fn set_lua_instruction_budget(&mut self, budget: Option<u64>) {
+        self.config.lua_instruction_budget = budget;
+        if let Some(engine) = &mut self.engine {
+            engine.set_lua_instruction_budget(budget);
+        }
+    }
+
+    /// Like `TuringSetup::set_hide_inactive_capabilities`, takes effect on the next `load_script`
+    pub fn set_hide_inactive_capabilities(&mut self, hide: bool) {
+        self.config.hide_inactive_capabilities = hide;
+    }
+
+    /// Like `TuringSetup::set_log_prefix`, None removes the prefix. Takes effect on the next `load_script`
+    pub fn set_log_prefix(&mut self, name: Option<String>) {
+        self.config.log_prefix = name;
+    }
+
+    /// Removes and returns a string the script sent with `_host_recv_string`,
+    /// None if `id` is unknown or was already taken
+    pub fn take_received_string(&self, id: u32) -> Option<String> {
+        self.data.write().received_strings.remove(&id)
+    }
+
+    /// Drops messages scripts log below `level` before they reach `ExternalFunctions`,
+    /// and turing's own call debug messages unless `level` is `Debug`.
+    /// Applies immediately, including to the loaded script.
+    pub fn set_log_level(&self, level: LogLevel) {
+        self.data.write().log_level = level;
+    }
+
+    /// Limits how many times a single script call may call host functions with `capability`,
+    /// None removes the limit. Calls past it skip the host and return zero, or nil in lua,
+    /// so the rest of the script call still runs. The first one is reported through
+    /// `ExternalFunctions::on_script_error` as `ErrorKind::BudgetExceeded`.
+    /// Nested `CallHandle::call_fn_reentrant` calls count towards the call they happen in.
+    pub fn set_callback_budget(&self, capability: impl ToString, max_calls: Option<u32>) {
+        let mut data = self.data.write();
+        let capability = capability.to_string();
+        match max_calls {
+            Some(max_calls) => {
+                data.callback_budgets
+                    .insert(capability, CallbackBudget { max_calls, used: 0 });
+            }
+            None => {
+                data.callback_budgets.remove(&capability);
+            }
+        }
+    }
+
+    /// Counts and times every host function call scripts make, see `take_metrics`.
+    /// Off by default so host calls don't pay for reading the clock.
+    pub fn set_metrics(&self, enabled: bool) {
+        self.data.write().metrics = enabled;
+    }
+
+    /// Returns how often each host function was called and how long the host took since the last
+    /// time this was called, grouped by capability, most total time first within each group.
+    /// Empty unless metrics are enabled.
+    pub fn take_metrics(&mut self) -> FxHashMap<String, Vec<(String, CallStat)>> {
+        let stats = self.data.write().host_metrics.take();
+        let mut metrics: FxHashMap<String, Vec<(String, CallStat)>> = FxHashMap::default();
+        for (name, stat) in stats {
+            let capability = self
+                .script_fns
+                .get(&name)
+                .map(|metadata| metadata.capability.clone())
+                .unwrap_or_default();
+            metrics.entry(capability).or_default().push((name, stat));
+        }
+        metrics
+    }
+
+    /// How many host functions the last script call called, including ones over budget
+    pub fn callback_calls(&self) -> u32 {
+        self.data.read().callback_calls.load(Ordering::Relaxed)
+    }
+
+    /// Caps how many bytes of keys and values scripts may store per mod,
+    /// [`storage::DEFAULT_STORAGE_QUOTA_BYTES`] by default
+    pub fn set_storage_quota_bytes(&self, bytes: usize) {
+        self.data.write().storage.set_quota_bytes(bytes);
+    }
+
+    /// Reads a value the mod `mod_id` stored
+    pub fn storage_get(&self, mod_id: &str, key: &str) -> Option<String> {
+        self.data
+            .read()
+            .storage
+            .get(mod_id, key)
+            .map(str::to_string)
+    }
+
+    /// Stores a value for the mod `mod_id`, the quota only limits scripts
+    pub fn storage_set(&self, mod_id: &str, key: &str, value: &str) {
+        self.data.write().storage.insert(mod_id, key, value);
+    }
+
+    /// Serializes all script storage as JSON, for the host to save between sessions
+    pub fn storage_serialize(&self) -> Result<String> {
+        self.data.read().storage.to_json()
+    }
+
+    /// Replaces all script storage with JSON from `storage_serialize`
+    pub fn storage_deserialize(&self, json: &str) -> Result<()> {
+        self.data.write().storage.load_json(json)
+    }
+
+    /// Sets what happens to NaN and infinite floats scripts pass to host functions or return,
+    /// they pass through unchanged by default
+    pub fn set_nan_policy(&self, policy: NanPolicy) {
+        self.data.write().nan_policy = policy;
+    }
+
+    /// The frame delta scripts read through `delta_time`, as passed to the last `fast_call_update`
+    pub fn delta_time(&self) -> f32 {
+        self.data.read().delta_time
+    }
+
+    /// Sets the game time scripts read through `time_seconds`
+    pub fn set_time(&self, seconds: f64) {
+        self.data.write().time_seconds = seconds;
+    }
+
+    /// Like `TuringSetup::set_wasm_max_stack_bytes`, takes effect on the next `load_script`
+    pub fn set_wasm_max_stack_bytes(&mut self, bytes: usize) -> Result<()> {
+        EngineConfig::check_wasm_stack_bytes(bytes)?;
+        self.config.wasm_max_stack_bytes = Some(bytes);
+        Ok(())
+    }
+
+    /// Like `TuringSetup::set_wasm_output_buffer_bytes`, takes effect on the next `load_script`
+    pub fn set_wasm_output_buffer_bytes(&mut self, bytes: usize) {
+        self.config.wasm_output_buffer_bytes = Some(bytes);
+    }
+
+    /// Like `TuringSetup::set_deterministic`, takes effect on the next `load_script`
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.config.deterministic = deterministic;
+    }
+
+    /// Like `TuringSetup::set_sequential_object_ids`, takes effect on the next `load_script`
+    pub fn set_sequential_object_ids(&mut self, sequential: bool) {
+        self.config.sequential_object_ids = sequential;
+    }
+
+    /// Restarts the host `random` functions and lua's `math.random` from `seed`,
+    /// for the loaded script and the ones loaded later
+    pub fn set_random_seed(&mut self, seed: u64) -> Result<()> {
+        self.config.random_seed = Some(seed);
+        self.data.write().rng = HostRng::new(seed);
+        match &mut self.engine {
+            Some(engine) => engine.set_random_seed(seed),
+            None => Ok(()),
+        }
+    }
+
+    /// Asks the host to duplicate the object behind `id` through `ExternalFunctions::clone_object`.
+    /// Returns None for the null object or if the host couldn't clone it.
+    pub fn clone_object(&self, id: ObjectId) -> Option<ObjectId> {
+        match Param::Object(id).deep_clone_object::<Ext>() {
+            Param::Object(clone) => Some(clone),
+            _ => None,
+        }
+    }
+
+    /// Lists every function registered with the host, sorted by name
+    pub fn list_functions(&self) -> Vec<FunctionInfo> {
+        let mut fns = self
+            .script_fns
+            .iter()
+            .map(|(name, metadata)| FunctionInfo {
+                name: name.clone(),
+                capability: metadata.capability.clone(),
+                param_types: metadata.param_types.clone(),
+                return_type: metadata.return_type.clone(),
+                doc_comment: metadata.doc_comment.clone(),
+            })
+            .collect::<Vec<_>>();
+        fns.sort_by(|a, b| a.name.cmp(&b.name));
+        fns
+    }
+
+    /// `list_functions` for the FFI, which reads the functions one at a time with `functions_snapshot`
+    pub(crate) fn take_functions_snapshot(&mut self) -> usize {
+        self.functions_snapshot = self.list_functions();
+        self.functions_snapshot.len()
+    }
+
+    pub(crate) fn functions_snapshot(&self) -> &[FunctionInfo] {
+        &self.functions_snapshot
+    }
+
+    /// Lists the functions exported by the currently loaded script, empty if none is loaded
+    pub fn script_exports(&self) -> Vec<String> {
+        let Some(engine) = &self.engine else {
+            return Vec::new();
+        };
+
+        engine.script_exports()
+    }
+
+    /// The signature the loaded script exported `name` with, which `call_fn` checks calls against.
+    /// Returns None if no script is loaded or it doesn't export `name`.
+    pub fn export_signature(&self, name: &str) -> Option<ExportSignature> {
+        self.export_signature_of(self.get_fn_key(name)?)
+    }
+
+    /// Like `export_signature`, for a function key
+    pub fn export_signature_of(&self, key: ScriptFnKey) -> Option<ExportSignature> {
+        self.engine.as_ref()?.export_signature(key)
+    }
+
+    /// The symbol wasm scripts import the registered function `name` as from `env`.
+    /// Returns None if no function is registered as `name`.
+    pub fn resolve_import_name(&self, name: &str) -> Option<String> {
+        let metadata = self.script_fns.get(name)?;
+        Some(metadata.as_internal_name(name))
+    }
+
+    /// The registered function wasm scripts import as `env::symbol`, the reverse of `resolve_import_name`
+    pub fn import_host_name(&self, symbol: &str) -> Option<String> {
+        self.import_names.get(symbol).cloned()
+    }
+
+    fn index_import_names(
+        script_fns: &FxHashMap<String, ScriptFnMetadata>,
+    ) -> FxHashMap<String, String> {
+        script_fns
+            .iter()
+            .map(|(name, metadata)| (metadata.as_internal_name(name), name.clone()))
+            .collect()
+    }
+
+    /// The original bytes of the loaded wasm module.
+    /// Returns None if no script is loaded or the script isn't wasm.
+    pub fn module_bytes(&self) -> Option<Vec<u8>> {
+        self.engine
+            .as_ref()
+            .and_then(|engine| engine.module_bytes())
+    }
+
+    /// Compiled wasm modules kept so loading the same bytes again skips compilation
+    #[cfg(feature = "wasm")]
+    pub fn module_cache(&self) -> &engine::wasm_engine::ModuleCache {
+        &self.module_cache
+    }
+
+    /// For setting a directory to keep compiled modules in across restarts, or the cache size
+    #[cfg(feature = "wasm")]
+    pub fn module_cache_mut(&mut self) -> &mut engine::wasm_engine::ModuleCache {
+        &mut self.module_cache
+    }
+
+    /// Serializes the compiled form of the loaded wasm module, for caching precompiled mods
+    pub fn compiled_bytes(&self) -> Result<Vec<u8>> {
+        let Some(engine) = &self.engine else {
+            return Err(anyhow!("No script is loaded"));
+        };
+
+        engine.compiled_bytes()
+    }
+
+    /// Metadata the loaded script declares about itself, such as its name and author.
+    /// Read from a `_meta` table in lua scripts, or a `_meta` custom section of `key=value` lines in wasm.
+    pub fn script_meta(&self) -> FxHashMap<String, String> {
+        self.engine
+            .as_ref()
+            .and_then(|engine| engine.script_meta())
+            .cloned()
+            .unwrap_or_default()
     }
 
     pub fn get_api_versions(&self) -> Option<&FxHashMap<String, Semver>> {
@@ -272,25 +1648,141 @@ impl<Ext: ExternalFunctions + Send + Sync + 'static> Turing<Ext> {
     }
 }
 
+impl<Ext: ExternalFunctions + Send + Sync + 'static> Drop for Turing<Ext> {
+    fn drop(&mut self) {
+        self.unload_script();
+    }
+}
+
+thread_local! {
+    /// Script function currently being called on this thread, reported by `panic_hook`
+    static CURRENT_SCRIPT_FN: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+    /// Host function a script is currently calling on this thread, reported by `panic_hook`
+    static CURRENT_HOST_FN: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+    /// Interrupt flag of the script call running on this thread, read by `is_call_cancelled`
+    static CURRENT_CANCEL: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// Whether the script call running on this thread has been interrupted.
+/// Host callbacks doing long work can poll this and return early, the call itself
+/// returns `Param::Error("script interrupted")` once control is back in the script.
+pub fn is_call_cancelled() -> bool {
+    CURRENT_CANCEL.with_borrow(|flag| flag.as_ref().is_some_and(|f| f.load(Ordering::Acquire)))
+}
+
+/// Makes `flag` the one `is_call_cancelled` reads until dropped, restoring the previous one
+pub(crate) struct CancelScope {
+    prev: Option<Arc<AtomicBool>>,
+}
+
+impl CancelScope {
+    pub(crate) fn enter(flag: &Arc<AtomicBool>) -> Self {
+        let prev = CURRENT_CANCEL.with(|s| s.replace(Some(Arc::clone(flag))));
+        Self { prev }
+    }
+}
+
+impl Drop for CancelScope {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        CURRENT_CANCEL.with(|s| *s.borrow_mut() = prev);
+    }
+}
+
+/// Records the function running on this thread until dropped, restoring the previous one
+/// so reentrant calls are reported correctly.
+pub(crate) struct CallScope {
+    slot: &'static LocalKey<RefCell<Option<Arc<str>>>>,
+    prev: Option<Arc<str>>,
+}
+
+impl CallScope {
+    pub(crate) fn script(name: &str) -> Self {
+        Self::enter(&CURRENT_SCRIPT_FN, Arc::from(name))
+    }
+
+    pub(crate) fn host(name: &Arc<str>) -> Self {
+        Self::enter(&CURRENT_HOST_FN, Arc::clone(name))
+    }
+
+    fn enter(slot: &'static LocalKey<RefCell<Option<Arc<str>>>>, name: Arc<str>) -> Self {
+        let prev = slot.with(|s| s.replace(Some(name)));
+        Self { slot, prev }
+    }
+}
+
+impl Drop for CallScope {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        self.slot.with(|s| *s.borrow_mut() = prev);
+    }
+}
+
+/// Everything `panic_hook` knows about a panic
+#[derive(Debug, Clone)]
+pub struct PanicReport {
+    pub message: String,
+    pub location: String,
+    /// Script function that was running on the panicking thread, if any
+    pub script_fn: Option<String>,
+    /// Host function the script was calling, if any
+    pub host_fn: Option<String>,
+    pub backtrace: String,
+    /// Seconds since the unix epoch when the panic happened
+    pub timestamp: u64,
+}
+
+impl PanicReport {
+    pub fn capture(info: &std::panic::PanicHookInfo) -> Self {
+        let message = info
+            .payload_as_str()
+            .unwrap_or("Unknown panic payload")
+            .to_string();
+
+        let location = if let Some(location) = info.location() {
+            format!("file '{}' at line {}", location.file(), location.line())
+        } else {
+            "unknown location".to_string()
+        };
+
+        PanicReport {
+            message,
+            location,
+            script_fn: CURRENT_SCRIPT_FN.with(|s| s.borrow().as_deref().map(str::to_string)),
+            host_fn: CURRENT_HOST_FN.with(|s| s.borrow().as_deref().map(str::to_string)),
+            backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+        }
+    }
+
+    /// The panic message without the backtrace
+    pub fn summary(&self) -> String {
+        format!(
+            "Panic occurred at {} (unix time {}): {}\nScript function: {}\nHost function: {}",
+            self.location,
+            self.timestamp,
+            self.message,
+            self.script_fn.as_deref().unwrap_or("none"),
+            self.host_fn.as_deref().unwrap_or("none"),
+        )
+    }
+}
+
+impl Display for PanicReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\nBacktrace:\n{}", self.summary(), self.backtrace)
+    }
+}
+
 /// Panic hook that logs panic information using the provided external functions.
+/// The report includes the script and host functions that were running when the panic happened.
 pub fn panic_hook<Ext>(file_out: Option<PathBuf>, info: &std::panic::PanicHookInfo)
 where
     Ext: ExternalFunctions + Send + Sync + 'static,
 {
-    let msg = info.payload_as_str().unwrap_or("Unknown panic payload");
-
-    let location = if let Some(location) = info.location() {
-        format!("file '{}' at line {}", location.file(), location.line())
-    } else {
-        "unknown location".to_string()
-    };
-
-    let full_msg = format!("Panic occurred at {}: {}", location, msg);
-    // Capture a backtrace and include it in outputs
-    let backtrace = std::backtrace::Backtrace::force_capture();
-    let backtrace_str = format!("{}", backtrace);
-
-    let full_msg_with_bt = format!("{}\nBacktrace:\n{}", full_msg, backtrace_str);
+    let report = PanicReport::capture(info);
 
     Ext::log_critical(format!(
         "Writing panic information to file and stderr: {:?}",
@@ -298,18 +1790,19 @@ where
     ));
     if let Some(file_path) = file_out
         && let Ok(mut file) = std::fs::OpenOptions::new()
+            .write(true)
             .create(true)
             .truncate(true)
             .open(file_path)
     {
         use std::io::Write;
-        let _ = writeln!(file, "{}", full_msg_with_bt);
+        let _ = writeln!(file, "{report}");
         let _ = file.flush();
     }
 
-    eprintln!("{}", full_msg);
-    eprintln!("Backtrace:\n{}", backtrace_str);
+    eprintln!("{}", report.summary());
+    eprintln!("Backtrace:\n{}", report.backtrace);
 
     // Log as critical error (include backtrace)
-    Ext::log_critical(full_msg_with_bt);
+    Ext::log_critical(report);
 }
@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Deserializer};
+
+use crate::interop::types::Semver;
+
+/// File name `Turing::load_mod` looks for in a mod's directory
+pub const MANIFEST_FILE_NAME: &str = "mod.toml";
+
+/// A mod's `mod.toml`, describing what to load and what the mod needs from the host
+/// ```toml
+/// name = "example"
+/// version = "1.0.0"
+/// entry = "example.wasm"
+/// capabilities = ["core", "physics"]
+///
+/// [api]
+/// core = "1.2.0"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModManifest {
+    pub name: String,
+    #[serde(deserialize_with = "deserialize_version")]
+    pub version: Semver,
+    /// Script to load, a relative path that stays inside the mod's directory
+    pub entry: String,
+    /// Capabilities the entry script is loaded with
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Minimum version of each host api the mod needs
+    #[serde(default, deserialize_with = "deserialize_versions")]
+    pub api: BTreeMap<String, Semver>,
+}
+
+impl ModManifest {
+    pub fn parse(source: &str) -> Result<Self> {
+        let manifest: ModManifest =
+            toml::from_str(source).map_err(|e| anyhow!("invalid {MANIFEST_FILE_NAME}: {e}"))?;
+        if manifest.name.trim().is_empty() {
+            return Err(anyhow!("{MANIFEST_FILE_NAME} has an empty name"));
+        }
+        if manifest.entry.trim().is_empty() {
+            return Err(anyhow!("{MANIFEST_FILE_NAME} has an empty entry"));
+        }
+        // absolute paths, drive prefixes and `..` could point the entry anywhere on disk
+        if Path::new(&manifest.entry)
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(anyhow!(
+                "{MANIFEST_FILE_NAME} entry '{}' must be a relative path inside the mod's directory",
+                manifest.entry
+            ));
+        }
+        Ok(manifest)
+    }
+
+    /// Resolves `entry` against the mod's directory, failing if it doesn't exist or a symlink
+    /// leads it out of the directory
+    pub fn entry_path(&self, dir: &Path) -> Result<PathBuf> {
+        let root = dir
+            .canonicalize()
+            .map_err(|e| anyhow!("could not resolve {}: {e}", dir.display()))?;
+        let path = root
+            .join(&self.entry)
+            .canonicalize()
+            .map_err(|e| anyhow!("could not resolve entry '{}': {e}", self.entry))?;
+        if !path.starts_with(&root) {
+            return Err(anyhow!(
+                "entry '{}' resolves outside the mod's directory",
+                self.entry
+            ));
+        }
+        Ok(path)
+    }
+
+    /// Reads `mod.toml` from a mod's directory
+    pub fn read(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE_NAME);
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow!("could not read {}: {e}", path.display()))?;
+        Self::parse(&source)
+    }
+}
+
+/// Things about a mod that didn't stop it from loading but may be mistakes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModLoadReport {
+    pub warnings: Vec<String>,
+}
+
+impl Display for ModLoadReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.warnings.join("\n"))
+    }
+}
+
+fn deserialize_version<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Semver, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    s.trim().parse().map_err(serde::de::Error::custom)
+}
+
+fn deserialize_versions<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, Semver>, D::Error> {
+    BTreeMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(api, ver)| Ok((api, ver.trim().parse().map_err(serde::de::Error::custom)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod manifest_tests {
+    use super::ModManifest;
+    use crate::interop::types::Semver;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = ModManifest::parse(
+            r#"
+name = "example"
+version = "1.0.2"
+entry = "example.wasm"
+capabilities = ["test"]
+
+[api]
+core = "1.2.0"
+"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.name, "example");
+        assert_eq!(manifest.version, Semver::new(1, 0, 2));
+        assert_eq!(manifest.capabilities, vec!["test"]);
+        assert_eq!(manifest.api.get("core"), Some(&Semver::new(1, 2, 0)));
+
+        let minimal = ModManifest::parse("name = \"a\"\nversion = \"0.1.0\"\nentry = \"a.lua\"");
+        assert!(minimal.unwrap().capabilities.is_empty());
+    }
+
+    #[test]
+    fn test_parse_manifest_errors() {
+        let bad_version = ModManifest::parse("name = \"a\"\nversion = \"1.0\"\nentry = \"a.lua\"");
+        assert!(
+            bad_version
+                .unwrap_err()
+                .to_string()
+                .contains("major.minor.patch")
+        );
+
+        let missing_entry = ModManifest::parse("name = \"a\"\nversion = \"1.0.0\"");
+        assert!(missing_entry.is_err());
+
+        let unknown_field = ModManifest::parse(
+            "name = \"a\"\nversion = \"1.0.0\"\nentry = \"a.lua\"\nauthor = \"b\"",
+        );
+        assert!(unknown_field.is_err());
+
+        for entry in ["../a.lua", "sub/../../a.lua", "/etc/passwd"] {
+            let escaping = ModManifest::parse(&format!(
+                "name = \"a\"\nversion = \"1.0.0\"\nentry = \"{entry}\""
+            ));
+            assert!(
+                escaping
+                    .unwrap_err()
+                    .to_string()
+                    .contains("must be a relative path inside the mod's directory"),
+                "{entry} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn test_entry_path() {
+        let dir = std::env::temp_dir().join(format!("turing_entry_path_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("scripts")).unwrap();
+        std::fs::write(dir.join("scripts/main.lua"), "").unwrap();
+
+        let manifest =
+            ModManifest::parse("name = \"a\"\nversion = \"1.0.0\"\nentry = \"./scripts/main.lua\"")
+                .unwrap();
+        let path = manifest.entry_path(&dir).unwrap();
+        assert!(path.ends_with("scripts/main.lua"));
+
+        let missing =
+            ModManifest::parse("name = \"a\"\nversion = \"1.0.0\"\nentry = \"other.lua\"").unwrap();
+        assert!(missing.entry_path(&dir).is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(std::env::temp_dir(), dir.join("outside")).unwrap();
+            let linked =
+                ModManifest::parse("name = \"a\"\nversion = \"1.0.0\"\nentry = \"outside\"")
+                    .unwrap();
+            assert!(
+                linked
+                    .entry_path(&dir)
+                    .unwrap_err()
+                    .to_string()
+                    .contains("resolves outside the mod's directory")
+            );
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use rustc_hash::FxHashMap;
+
+use crate::interop::params::ObjectId;
+
+/// Error message for an object id a script passed that was never given to it
+pub const UNKNOWN_OBJECT_MESSAGE: &str = "unknown object id";
+
+/// Ids scripts see in place of host objects.
+/// Off by default, scripts then see the host's own ids, which are usually pointers and change between runs.
+/// With sequential ids on, objects are numbered `1, 2, 3, ...` in the order scripts first see them,
+/// so script output and snapshots stay the same across runs. Id 0 is always the null object.
+#[derive(Debug, Default)]
+pub struct ObjectTable {
+    sequential: bool,
+    /// host id -> script id
+    ids: FxHashMap<ObjectId, u64>,
+    /// host ids, indexed by script id - 1
+    objects: Vec<ObjectId>,
+}
+
+impl ObjectTable {
+    pub fn new(sequential: bool) -> Self {
+        Self {
+            sequential,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_sequential(&self) -> bool {
+        self.sequential
+    }
+
+    /// The id a script sees for `object`, numbering it if scripts haven't seen it yet
+    pub fn to_script(&mut self, object: ObjectId) -> u64 {
+        if !self.sequential || object.is_null() {
+            return object.as_ffi();
+        }
+        if let Some(&id) = self.ids.get(&object) {
+            return id;
+        }
+        self.objects.push(object);
+        let id = self.objects.len() as u64;
+        self.ids.insert(object, id);
+        id
+    }
+
+    /// The host object behind an id a script passed, None if it was never given to a script
+    pub fn to_host(&self, id: u64) -> Option<ObjectId> {
+        if !self.sequential || id == 0 {
+            return Some(ObjectId::from_ffi(id));
+        }
+        self.objects.get(id as usize - 1).copied()
+    }
+}
+
+#[cfg(test)]
+mod object_table_tests {
+    use super::ObjectTable;
+    use crate::interop::params::ObjectId;
+
+    #[test]
+    fn test_sequential_ids() {
+        let first = ObjectId::new(0x7ffd_1234_5678);
+        let second = ObjectId::new(0x5581_0000_0040);
+
+        let mut table = ObjectTable::new(true);
+        assert_eq!(table.to_script(first), 1);
+        assert_eq!(table.to_script(second), 2);
+        // an object keeps its id
+        assert_eq!(table.to_script(first), 1);
+        assert_eq!(table.to_script(ObjectId::null()), 0);
+
+        assert_eq!(table.to_host(1), Some(first));
+        assert_eq!(table.to_host(2), Some(second));
+        assert_eq!(table.to_host(0), Some(ObjectId::null()));
+        assert_eq!(table.to_host(3), None);
+    }
+
+    #[test]
+    fn test_host_ids_by_default() {
+        let object = ObjectId::new(0x7ffd_1234_5678);
+        let mut table = ObjectTable::default();
+        assert_eq!(table.to_script(object), object.as_ffi());
+        assert_eq!(table.to_host(object.as_ffi()), Some(object));
+    }
+}
@@ -0,0 +1,230 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+
+use crate::interop::params::{DataType, FFI_LAYOUT_VERSION};
+use crate::spec_gen::SpecFormat;
+
+/// C signature of one exported FFI function
+pub struct CFnDecl {
+    pub name: &'static str,
+    /// `(c type, argument name)` pairs
+    pub args: &'static [(&'static str, &'static str)],
+    pub ret: &'static str,
+}
+
+/// Declares the C signatures of the exported FFI functions as `C_EXPORTS`.
+/// Every `#[unsafe(no_mangle)]` function must have an entry here, which is checked by a test.
+#[cfg(feature = "global_ffi")]
+macro_rules! c_exports {
+    ($(fn $name:ident($($arg:ident: $ty:literal),* $(,)?) $(-> $ret:literal)?;)*) => {
+        pub const C_EXPORTS: &[$crate::spec_gen::c_header::CFnDecl] = &[$(
+            $crate::spec_gen::c_header::CFnDecl {
+                name: stringify!($name),
+                args: &[$(($ty, stringify!($arg))),*],
+                ret: c_exports!(@ret $($ret)?),
+            },
+        )*];
+    };
+    (@ret) => { "void" };
+    (@ret $ret:literal) => { $ret };
+}
+#[cfg(feature = "global_ffi")]
+pub(crate) use c_exports;
+
+/// Writes `turing.h` into `output_directory`
+pub fn generate(exports: &[CFnDecl], output_directory: &Path) -> Result<()> {
+    if !output_directory.is_dir() {
+        return Err(anyhow!("output directory must be an existing directory"));
+    }
+
+    let path = output_directory.join("turing.h");
+    fs::write(path, generate_c_header(exports))?;
+    Ok(())
+}
+
+const PRELUDE: &str = r#"typedef uint32_t FreeableDataType;
+typedef uint32_t CacheKey;
+typedef uint64_t ObjectId;
+
+typedef struct ScriptFnMap ScriptFnMap;
+typedef struct ScriptFnMetadata ScriptFnMetadata;
+typedef struct TuringInstance TuringInstance;
+typedef struct TuringCallHandle TuringCallHandle;
+typedef struct TuringInitResult TuringInitResult;
+typedef struct Params Params;
+typedef struct VersionTable VersionTable;
+
+typedef struct U32Buffer {
+    uint32_t size;
+    uint32_t *array;
+} U32Buffer;
+
+typedef struct F32Buffer {
+    uint32_t size;
+    float *array;
+} F32Buffer;
+
+typedef struct Vec2 {
+    float x, y;
+} Vec2;
+
+typedef struct Vec3 {
+    float x, y, z;
+} Vec3;
+
+typedef union RawParam {
+    int8_t i8;
+    int16_t i16;
+    int32_t i32;
+    int64_t i64;
+    uint8_t u8;
+    uint16_t u16;
+    uint32_t u32;
+    uint64_t u64;
+    float f32;
+    double f64;
+    bool boolean;
+    const char *string;
+    ObjectId object;
+    const char *error;
+    Vec2 vec2;
+    Vec3 vec3;
+    const float *vec4;
+    const float *quat;
+    const float *mat2;
+    const float *mat3;
+    const float *mat4;
+    U32Buffer u32_buffer;
+    F32Buffer f32_buffer;
+} RawParam;
+
+typedef struct FfiParam {
+    DataType type_id;
+    RawParam value;
+} FfiParam;
+
+typedef struct FfiParamArray {
+    uint32_t count;
+    const FfiParam *ptr;
+} FfiParamArray;
+
+typedef FfiParam (*ScriptCallback)(FfiParamArray params);
+typedef FfiParam (*DispatchCallback)(const char *name, FfiParamArray params);
+"#;
+
+/// Builds the contents of `turing.h`
+pub fn generate_c_header(exports: &[CFnDecl]) -> String {
+    let mut out = String::new();
+
+    out += "// This file was generated by Turing.rs, please DO NOT modify it.\n";
+    out += "#ifndef TURING_H\n#define TURING_H\n\n";
+    out += "#include <stdbool.h>\n#include <stdint.h>\n\n";
+    out += &format!("#define TURING_FFI_LAYOUT_VERSION {FFI_LAYOUT_VERSION}\n\n");
+
+    out += "typedef uint32_t DataType;\nenum {\n";
+    // DataType discriminants are contiguous from 1
+    for ty in (1..).map_while(|i| DataType::try_from(i).ok()) {
+        out += &format!(
+            "    TURING_{} = {},\n",
+            upper_snake(&format!("{ty:?}")),
+            ty as u32
+        );
+    }
+    out += "};\n\n";
+
+    out += "typedef uint32_t SpecFormat;\nenum {\n";
+    for format in (1..).map_while(|i| SpecFormat::try_from(i).ok()) {
+        out += &format!(
+            "    TURING_SPEC_FORMAT_{} = {},\n",
+            upper_snake(&format!("{format:?}")),
+            format as u32
+        );
+    }
+    out += "};\n\n";
+
+    out += PRELUDE;
+
+    out += "\n#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n";
+    for decl in exports {
+        let args = if decl.args.is_empty() {
+            "void".to_string()
+        } else {
+            decl.args
+                .iter()
+                .map(|(ty, name)| format!("{ty} {name}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        out += &format!("{} {}({args});\n", decl.ret, decl.name);
+    }
+    out += "\n#ifdef __cplusplus\n}\n#endif\n\n#endif // TURING_H\n";
+
+    out
+}
+
+/// `RustU32Buffer` -> `RUST_U32_BUFFER`, `CHeader` -> `C_HEADER`
+fn upper_snake(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        if c.is_ascii_uppercase()
+            && prev.is_some_and(|p| {
+                p.is_ascii_lowercase()
+                    || p.is_ascii_digit()
+                    || (p.is_ascii_uppercase() && next.is_some_and(|n| n.is_ascii_lowercase()))
+            })
+        {
+            out.push('_');
+        }
+        out.push(c.to_ascii_uppercase());
+    }
+    out
+}
+
+#[cfg(all(test, feature = "global_ffi"))]
+mod c_header_tests {
+    use super::generate_c_header;
+    use crate::global_ffi::ffi::C_EXPORTS;
+
+    /// Names of all `#[unsafe(no_mangle)]` functions in the ffi module source
+    fn no_mangle_symbols() -> Vec<String> {
+        let src = include_str!("../global_ffi/ffi.rs");
+        let mut symbols = Vec::new();
+        let mut pending = false;
+
+        for line in src.lines() {
+            if line.trim() == "#[unsafe(no_mangle)]" {
+                pending = true;
+            } else if pending && let Some((_, rest)) = line.split_once("extern \"C\" fn ") {
+                let name = rest.split('(').next().unwrap_or_default();
+                symbols.push(name.trim().to_string());
+                pending = false;
+            }
+        }
+        symbols
+    }
+
+    #[test]
+    fn test_c_header_covers_ffi() {
+        let header = generate_c_header(C_EXPORTS);
+        let symbols = no_mangle_symbols();
+        assert!(!symbols.is_empty());
+
+        for symbol in symbols {
+            assert!(
+                header.contains(&format!(" {symbol}(")),
+                "'{symbol}' is missing from C_EXPORTS in global_ffi/ffi.rs"
+            );
+        }
+
+        assert!(header.contains("TURING_RUST_U32_BUFFER = 26,"));
+        assert!(header.contains("TURING_SPEC_FORMAT_C_HEADER = 4,"));
+        assert!(header.contains("#define TURING_FFI_LAYOUT_VERSION 1\n"));
+        assert!(header.contains("typedef struct FfiParam {"));
+        assert!(header.contains("FfiParam turing_script_call_fn(TuringInstance* turing, CacheKey name_key, Params* params, DataType expected_return_type);"));
+    }
+}
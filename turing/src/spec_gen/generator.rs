@@ -42,6 +42,15 @@ pub fn generate_specs(
     Ok(())
 }
 
+/// The same information as the `specs.json` written by `generate_specs`, without touching the filesystem
+pub fn generate_specs_value(
+    metadata: &FxHashMap<String, ScriptFnMetadata>,
+    api_versions: &FxHashMap<String, Semver>,
+) -> Result<serde_json::Value> {
+    let json = json_generator::generate_specs_json(metadata, api_versions)?;
+    Ok(serde_json::to_value(json)?)
+}
+
 fn generate_spec(
     api: &str,
     ver: Semver,
@@ -176,10 +185,39 @@ impl ScriptFnMetadata {
 
 #[cfg(test)]
 mod generator_tests {
+    use super::generate_specs_value;
+    use crate::engine::types::ScriptFnMetadata;
+    use crate::interop::params::{DataType, Param};
+    use crate::interop::types::Semver;
     use anyhow::Result;
+    use rustc_hash::FxHashMap;
 
     #[test]
     fn test_generator_turing() -> Result<()> {
         Ok(())
     }
+
+    #[test]
+    fn test_generate_specs_value() -> Result<()> {
+        let mut map = FxHashMap::default();
+        let mut data = ScriptFnMetadata::dispatched("test".to_owned(), None);
+        data.add_param_type(DataType::I64, "start_at")?;
+        data.add_optional_param_type(DataType::Bool, "wrap", Param::Bool(false))?;
+        data.add_return_type(DataType::Bool)?;
+        map.insert("Mesh.set_start".to_owned(), data);
+
+        let mut versions = FxHashMap::default();
+        versions.insert("test".to_owned(), Semver::new(1, 2, 3));
+
+        let value = generate_specs_value(&map, &versions)?;
+        let method = &value["specs"]["Mesh"]["functions"][0];
+        assert_eq!(method["name"], "set_start");
+        assert_eq!(method["return_type"], "Bool");
+        assert_eq!(method["param_types"][0]["name"], "start_at");
+        assert_eq!(method["param_types"][0]["data_type"], "I64");
+        assert_eq!(method["param_types"][0]["optional"], false);
+        assert_eq!(method["param_types"][1]["optional"], true);
+        assert!(value["api_versions"].get("test").is_some());
+        Ok(())
+    }
 }
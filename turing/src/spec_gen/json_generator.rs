@@ -35,6 +35,8 @@ pub struct SpecParam {
     pub name: String,
     pub data_type_name: DataTypeName,
     pub data_type: DataType,
+    /// Lua calls may leave the param out, wasm imports always take it
+    pub optional: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -104,6 +106,7 @@ pub fn generate_specs_json(
                     name: p.name.clone(),
                     data_type_name: p.data_type_name.clone(),
                     data_type: p.data_type,
+                    optional: p.default.is_some(),
                 })
                 .collect(),
         });
@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use convert_case::{Case, Casing};
+use rustc_hash::FxHashMap;
+
+use crate::engine::types::ScriptFnMetadata;
+use crate::interop::params::DataType;
+use crate::interop::types::Semver;
+
+/// Writes `turing_api.lua` into `output_directory`, annotating every registered function
+/// for the lua language server
+pub fn generate(
+    metadata: &FxHashMap<String, ScriptFnMetadata>,
+    api_versions: &FxHashMap<String, Semver>,
+    output_directory: &Path,
+) -> Result<()> {
+    if !output_directory.is_dir() {
+        return Err(anyhow!("output directory must be an existing directory"));
+    }
+
+    let path = output_directory.join("turing_api.lua");
+    fs::write(path, generate_lua_defs(metadata, api_versions))?;
+    Ok(())
+}
+
+/// Builds the contents of `turing_api.lua`, named the way the lua engine binds the functions.
+/// Output is sorted so regenerating is diff-stable.
+pub fn generate_lua_defs(
+    metadata: &FxHashMap<String, ScriptFnMetadata>,
+    api_versions: &FxHashMap<String, Semver>,
+) -> String {
+    let mut out = String::new();
+
+    out += "---@meta\n";
+    out += "-- This file was generated by Turing.rs, please DO NOT modify it.\n";
+
+    let versions = api_versions.iter().collect::<BTreeMap<_, _>>();
+    for (api, ver) in versions {
+        out += &format!("-- api {api} {ver}\n");
+    }
+
+    out += r#"
+---@class Vec2
+---@class Vec3
+---@class Vec4
+---@class Quat
+---@class Mat4
+---@alias ObjectHandle integer
+
+---@class turing_api
+turing_api = {}
+"#;
+
+    let mut globals = BTreeMap::new();
+    let mut classes: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+
+    for (name, data) in metadata {
+        if ScriptFnMetadata::is_instance_method(name) {
+            let names = name
+                .splitn(2, ScriptFnMetadata::METHOD_SEPARATOR)
+                .collect::<Vec<&str>>();
+            let class = names[0].to_case(Case::Pascal);
+            let func_name = names[1].to_case(Case::Snake);
+            let decl = data.lua_declaration(&format!("turing_api.{class}.{func_name}"), true);
+            classes.entry(class).or_default().insert(func_name, decl);
+        } else if ScriptFnMetadata::is_static_method(name) {
+            let names = name
+                .splitn(2, ScriptFnMetadata::STATIC_SEPARATOR)
+                .collect::<Vec<&str>>();
+            let class = names[0].to_case(Case::Pascal);
+            let func_name = names[1].to_case(Case::Snake);
+            let decl = data.lua_declaration(&format!("turing_api.{class}.{func_name}"), false);
+            classes.entry(class).or_default().insert(func_name, decl);
+        } else {
+            let func_name = name.to_case(Case::Snake);
+            let decl = data.lua_declaration(&format!("turing_api.{func_name}"), false);
+            globals.insert(func_name, decl);
+        }
+    }
+
+    for decl in globals.values() {
+        out += "\n";
+        out += decl;
+    }
+
+    for (class, functions) in classes {
+        out += &format!("\n---@class turing_api.{class}\nturing_api.{class} = {{}}\n");
+        for decl in functions.values() {
+            out += "\n";
+            out += decl;
+        }
+    }
+
+    out
+}
+
+impl ScriptFnMetadata {
+    /// Doc comment, `---@param`/`---@return` annotations and an empty stub for `path`.
+    /// Instance methods take the object id as an explicit `self` parameter.
+    fn lua_declaration(&self, path: &str, is_instance_method: bool) -> String {
+        let mut out = String::new();
+        if let Some(doc) = &self.doc_comment {
+            for line in doc.lines() {
+                out += &format!("---{}\n", line.trim_end());
+            }
+        }
+
+        let mut params = Vec::new();
+        if is_instance_method {
+            out += &format!("---@param self {}\n", DataType::Object.as_lua_type());
+            params.push("self".to_string());
+        }
+        for p in &self.param_types {
+            let name = p.name.to_case(Case::Snake);
+            out += &format!(
+                "---@param {name}{} {}\n",
+                if p.default.is_some() { "?" } else { "" },
+                p.data_type.as_lua_type()
+            );
+            params.push(name);
+        }
+        if self.variadic {
+            out += "---@param ... integer|number|boolean|string|nil\n";
+            params.push("...".to_string());
+        }
+
+        let ret = self.return_type.first().map_or(DataType::Void, |r| r.0);
+        if ret != DataType::Void {
+            out += &format!("---@return {}\n", ret.as_lua_type());
+        }
+
+        out += &format!("function {path}({}) end\n", params.join(", "));
+        out
+    }
+}
+
+impl DataType {
+    /// Lua annotation type, glam values use the classes the lua engine binds
+    pub fn as_lua_type(&self) -> &'static str {
+        match self {
+            DataType::I8
+            | DataType::I16
+            | DataType::I32
+            | DataType::I64
+            | DataType::U8
+            | DataType::U16
+            | DataType::U32
+            | DataType::U64
+            | DataType::Flags
+            | DataType::Char => "integer",
+            DataType::F32 | DataType::F64 => "number",
+            DataType::Object | DataType::OwnedObject => "ObjectHandle",
+            DataType::Bool => "boolean",
+            DataType::RustString
+            | DataType::ExtString
+            | DataType::RustError
+            | DataType::ExtError => "string",
+            DataType::Void => "nil",
+            DataType::Vec2 => "Vec2",
+            DataType::Vec3 => "Vec3",
+            DataType::RustVec4 | DataType::ExtVec4 => "Vec4",
+            DataType::RustQuat | DataType::ExtQuat => "Quat",
+            DataType::RustMat4 | DataType::ExtMat4 => "Mat4",
+            DataType::RustU32Buffer | DataType::ExtU32Buffer => "integer[]",
+            DataType::RustF32Buffer | DataType::ExtF32Buffer => "number[]",
+            DataType::RustJson | DataType::ExtJson => "any",
+        }
+    }
+}
+
+#[cfg(test)]
+mod lua_defs_tests {
+    use super::generate_lua_defs;
+    use crate::engine::types::ScriptFnMetadata;
+    use crate::interop::params::{DataType, Param};
+    use crate::interop::types::Semver;
+    use anyhow::Result;
+    use rustc_hash::FxHashMap;
+
+    #[test]
+    fn test_lua_defs() -> Result<()> {
+        let mut map = FxHashMap::default();
+
+        let mut data =
+            ScriptFnMetadata::dispatched("test".to_owned(), Some("Logs a message".to_owned()));
+        data.add_param_type(DataType::RustString, "msg")?;
+        data.add_optional_param_type(DataType::I32, "level", Param::I32(1))?;
+        map.insert("log_message".to_owned(), data);
+
+        let mut data = ScriptFnMetadata::dispatched("test".to_owned(), None);
+        data.add_param_type(DataType::RustU32Buffer, "indices")?;
+        data.add_return_type(DataType::Bool)?;
+        map.insert("Mesh::set_indices".to_owned(), data);
+
+        let mut data = ScriptFnMetadata::dispatched(
+            "test".to_owned(),
+            Some("Position of the entity\nin world space".to_owned()),
+        );
+        data.add_return_type(DataType::Vec3)?;
+        map.insert("Entity.get_position".to_owned(), data);
+
+        let mut versions = FxHashMap::default();
+        versions.insert("test".to_owned(), Semver::new(1, 2, 3));
+
+        let out = generate_lua_defs(&map, &versions);
+
+        let expected = r#"---@meta
+-- This file was generated by Turing.rs, please DO NOT modify it.
+-- api test 1.2.3
+
+---@class Vec2
+---@class Vec3
+---@class Vec4
+---@class Quat
+---@class Mat4
+---@alias ObjectHandle integer
+
+---@class turing_api
+turing_api = {}
+
+---Logs a message
+---@param msg string
+---@param level? integer
+function turing_api.log_message(msg, level) end
+
+---@class turing_api.Entity
+turing_api.Entity = {}
+
+---Position of the entity
+---in world space
+---@param self ObjectHandle
+---@return Vec3
+function turing_api.Entity.get_position(self) end
+
+---@class turing_api.Mesh
+turing_api.Mesh = {}
+
+---@param indices integer[]
+---@return boolean
+function turing_api.Mesh.set_indices(indices) end
+"#;
+        assert_eq!(out, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lua_defs_variadic() -> Result<()> {
+        let mut map = FxHashMap::default();
+        let mut data = ScriptFnMetadata::dispatched("test".to_owned(), None);
+        data.add_param_type(DataType::RustString, "fmt")?;
+        data.set_variadic(true);
+        map.insert("format".to_owned(), data);
+
+        let out = generate_lua_defs(&map, &FxHashMap::default());
+        assert!(out.contains(
+            "---@param fmt string\n---@param ... integer|number|boolean|string|nil\nfunction turing_api.format(fmt, ...) end\n"
+        ));
+        Ok(())
+    }
+}
@@ -1,2 +1,21 @@
+pub mod c_header;
 pub mod generator;
 pub mod json_generator;
+pub mod lua_defs;
+pub mod ts_defs;
+
+use num_enum::TryFromPrimitive;
+
+/// Which output `turing_script_dump_sec` writes
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+pub enum SpecFormat {
+    /// One `<api>.txt` per api plus `specs.json`, see `generator::generate_specs`
+    Specs = 1,
+    /// `turing_api.lua`, see `lua_defs::generate`
+    LuaDefs = 2,
+    /// `turing.d.ts`, see `ts_defs::generate`
+    TsDefs = 3,
+    /// `turing.h` for this ffi surface, see `c_header::generate`
+    CHeader = 4,
+}
@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use convert_case::{Case, Casing};
+use rustc_hash::FxHashMap;
+
+use crate::engine::types::ScriptFnMetadata;
+use crate::interop::params::DataType;
+use crate::interop::types::Semver;
+
+/// Writes `turing.d.ts` into `output_directory`, declaring every registered function for JS mods
+pub fn generate(
+    metadata: &FxHashMap<String, ScriptFnMetadata>,
+    api_versions: &FxHashMap<String, Semver>,
+    output_directory: &Path,
+) -> Result<()> {
+    if !output_directory.is_dir() {
+        return Err(anyhow!("output directory must be an existing directory"));
+    }
+
+    let path = output_directory.join("turing.d.ts");
+    fs::write(path, generate_ts_defs(metadata, api_versions))?;
+    Ok(())
+}
+
+/// Builds the contents of `turing.d.ts`. Output is sorted so regenerating is diff-stable.
+pub fn generate_ts_defs(
+    metadata: &FxHashMap<String, ScriptFnMetadata>,
+    api_versions: &FxHashMap<String, Semver>,
+) -> String {
+    let mut out = String::new();
+
+    out += "// This file was generated by Turing.rs, please DO NOT modify it.\n";
+
+    let versions = api_versions.iter().collect::<BTreeMap<_, _>>();
+    for (api, ver) in versions {
+        out += &format!("// api {api} {ver}\n");
+    }
+
+    out += r#"
+type Vec2 = [number, number];
+type Vec3 = [number, number, number];
+type Vec4 = [number, number, number, number];
+type Quat = [number, number, number, number];
+type Mat4 = number[];
+type ObjectHandle = bigint & { readonly __brand: "ObjectHandle" };
+"#;
+
+    let mut globals = BTreeMap::new();
+    let mut namespaces: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+
+    for (name, data) in metadata {
+        if ScriptFnMetadata::is_instance_method(name) {
+            let names = name
+                .splitn(2, ScriptFnMetadata::METHOD_SEPARATOR)
+                .collect::<Vec<&str>>();
+            let func_name = names[1].to_case(Case::Camel);
+            namespaces
+                .entry(names[0].to_case(Case::Pascal))
+                .or_default()
+                .insert(func_name.clone(), data.ts_declaration(&func_name, true));
+        } else if ScriptFnMetadata::is_static_method(name) {
+            let names = name
+                .splitn(2, ScriptFnMetadata::STATIC_SEPARATOR)
+                .collect::<Vec<&str>>();
+            let func_name = names[1].to_case(Case::Camel);
+            namespaces
+                .entry(names[0].to_case(Case::Pascal))
+                .or_default()
+                .insert(func_name.clone(), data.ts_declaration(&func_name, false));
+        } else {
+            let func_name = name.to_case(Case::Camel);
+            let decl = format!(
+                "{}declare {}\n",
+                data.ts_doc(),
+                data.ts_signature(&func_name, false)
+            );
+            globals.insert(func_name, decl);
+        }
+    }
+
+    for decl in globals.values() {
+        out += "\n";
+        out += decl;
+    }
+
+    for (namespace, functions) in namespaces {
+        out += &format!("\ndeclare namespace {namespace} {{\n");
+        out += &functions
+            .values()
+            .map(|decl| indent(decl, "    "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        out += "}\n";
+    }
+
+    out
+}
+
+fn indent(text: &str, prefix: &str) -> String {
+    text.lines().map(|l| format!("{prefix}{l}\n")).collect()
+}
+
+impl ScriptFnMetadata {
+    fn ts_declaration(&self, func_name: &str, is_instance_method: bool) -> String {
+        format!(
+            "{}{}",
+            self.ts_doc(),
+            self.ts_signature(func_name, is_instance_method)
+        )
+    }
+
+    /// The doc comment as a JSDoc block, empty if there is none
+    fn ts_doc(&self) -> String {
+        let Some(doc) = &self.doc_comment else {
+            return String::new();
+        };
+
+        let mut out = "/**\n".to_string();
+        for line in doc.lines() {
+            out += &format!(" * {}\n", line.trim_end());
+        }
+        out += " */\n";
+        out
+    }
+
+    /// `function name(params): ret;`
+    /// Instance methods take the object id as an explicit `self` parameter.
+    fn ts_signature(&self, func_name: &str, is_instance_method: bool) -> String {
+        let mut params = Vec::new();
+        if is_instance_method {
+            params.push(format!("self: {}", DataType::Object.as_ts_type()));
+        }
+        params.extend(self.param_types.iter().map(|p| {
+            format!(
+                "{}{}: {}",
+                p.name.to_case(Case::Camel),
+                if p.default.is_some() { "?" } else { "" },
+                p.data_type.as_ts_type()
+            )
+        }));
+        if self.variadic {
+            params.push("...args: (number | bigint | boolean | string | null)[]".to_string());
+        }
+
+        let ret = self
+            .return_type
+            .first()
+            .map_or(DataType::Void, |r| r.0)
+            .as_ts_type();
+
+        format!("function {func_name}({}): {ret};", params.join(", "))
+    }
+}
+
+impl DataType {
+    /// 64-bit integers don't fit in a JS number, so they cross as `bigint`.
+    /// Object ids are branded bigints so a plain number can't be passed where an object is expected.
+    pub fn as_ts_type(&self) -> &'static str {
+        match self {
+            DataType::I8
+            | DataType::I16
+            | DataType::I32
+            | DataType::U8
+            | DataType::U16
+            | DataType::U32
+            | DataType::Flags
+            | DataType::Char
+            | DataType::F32
+            | DataType::F64 => "number",
+            DataType::I64 | DataType::U64 => "bigint",
+            DataType::Object | DataType::OwnedObject => "ObjectHandle",
+            DataType::Bool => "boolean",
+            DataType::RustString
+            | DataType::ExtString
+            | DataType::RustError
+            | DataType::ExtError => "string",
+            DataType::Void => "void",
+            DataType::Vec2 => "Vec2",
+            DataType::Vec3 => "Vec3",
+            DataType::RustVec4 | DataType::ExtVec4 => "Vec4",
+            DataType::RustQuat | DataType::ExtQuat => "Quat",
+            DataType::RustMat4 | DataType::ExtMat4 => "Mat4",
+            DataType::RustU32Buffer | DataType::ExtU32Buffer => "Uint32Array",
+            DataType::RustF32Buffer | DataType::ExtF32Buffer => "Float32Array",
+            DataType::RustJson | DataType::ExtJson => "unknown",
+        }
+    }
+}
+
+#[cfg(test)]
+mod ts_defs_tests {
+    use super::generate_ts_defs;
+    use crate::engine::types::ScriptFnMetadata;
+    use crate::interop::params::{DataType, Param};
+    use crate::interop::types::Semver;
+    use anyhow::Result;
+    use rustc_hash::FxHashMap;
+
+    #[test]
+    fn test_ts_defs() -> Result<()> {
+        let mut map = FxHashMap::default();
+
+        let mut data =
+            ScriptFnMetadata::dispatched("test".to_owned(), Some("Logs a message".to_owned()));
+        data.add_param_type(DataType::RustString, "msg")?;
+        map.insert("log_message".to_owned(), data);
+
+        let mut data = ScriptFnMetadata::dispatched("test".to_owned(), None);
+        data.add_param_type(DataType::RustU32Buffer, "indices")?;
+        data.add_param_type(DataType::I64, "start_at")?;
+        data.add_return_type(DataType::Bool)?;
+        map.insert("Mesh::set_indices".to_owned(), data);
+
+        let mut data = ScriptFnMetadata::dispatched(
+            "test".to_owned(),
+            Some("Position of the entity\nin world space".to_owned()),
+        );
+        data.add_return_type(DataType::Vec3)?;
+        map.insert("Entity.get_position".to_owned(), data);
+
+        let mut versions = FxHashMap::default();
+        versions.insert("test".to_owned(), Semver::new(1, 2, 3));
+
+        let out = generate_ts_defs(&map, &versions);
+
+        let expected = r#"// This file was generated by Turing.rs, please DO NOT modify it.
+// api test 1.2.3
+
+type Vec2 = [number, number];
+type Vec3 = [number, number, number];
+type Vec4 = [number, number, number, number];
+type Quat = [number, number, number, number];
+type Mat4 = number[];
+type ObjectHandle = bigint & { readonly __brand: "ObjectHandle" };
+
+/**
+ * Logs a message
+ */
+declare function logMessage(msg: string): void;
+
+declare namespace Entity {
+    /**
+     * Position of the entity
+     * in world space
+     */
+    function getPosition(self: ObjectHandle): Vec3;
+}
+
+declare namespace Mesh {
+    function setIndices(indices: Uint32Array, startAt: bigint): boolean;
+}
+"#;
+        assert_eq!(out, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ts_defs_object_handles() -> Result<()> {
+        let mut map = FxHashMap::default();
+
+        let mut data = ScriptFnMetadata::dispatched("test".to_owned(), None);
+        data.add_param_type(DataType::U64, "seed")?;
+        data.add_return_type_named(DataType::Object, "Entity".to_owned())?;
+        map.insert("spawn_entity".to_owned(), data);
+
+        let mut data =
+            ScriptFnMetadata::dispatched("test".to_owned(), Some("Removes the entity".to_owned()));
+        data.add_param_type_named(DataType::Object, "entity".to_owned(), "Entity".to_owned())?;
+        data.add_param_type(DataType::F32, "delay")?;
+        map.insert("despawn_entity".to_owned(), data);
+
+        let out = generate_ts_defs(&map, &FxHashMap::default());
+
+        let expected = r#"// This file was generated by Turing.rs, please DO NOT modify it.
+
+type Vec2 = [number, number];
+type Vec3 = [number, number, number];
+type Vec4 = [number, number, number, number];
+type Quat = [number, number, number, number];
+type Mat4 = number[];
+type ObjectHandle = bigint & { readonly __brand: "ObjectHandle" };
+
+/**
+ * Removes the entity
+ */
+declare function despawnEntity(entity: ObjectHandle, delay: number): void;
+
+declare function spawnEntity(seed: bigint): ObjectHandle;
+"#;
+        assert_eq!(out, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ts_defs_variadic() -> Result<()> {
+        let mut map = FxHashMap::default();
+        let mut data = ScriptFnMetadata::dispatched("test".to_owned(), None);
+        data.add_param_type(DataType::RustString, "fmt")?;
+        data.set_variadic(true);
+        map.insert("format".to_owned(), data);
+
+        let out = generate_ts_defs(&map, &FxHashMap::default());
+        assert!(out.contains(
+            "declare function format(fmt: string, ...args: (number | bigint | boolean | string | null)[]): void;"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ts_defs_optional_params() -> Result<()> {
+        let mut map = FxHashMap::default();
+        let mut data = ScriptFnMetadata::dispatched("test".to_owned(), None);
+        data.add_param_type(DataType::RustString, "text")?;
+        data.add_optional_param_type(DataType::I32, "text_color", Param::I32(7))?;
+        map.insert("draw_text".to_owned(), data);
+
+        let out = generate_ts_defs(&map, &FxHashMap::default());
+        assert!(out.contains("declare function drawText(text: string, textColor?: number): void;"));
+        Ok(())
+    }
+}
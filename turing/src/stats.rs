@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use rustc_hash::FxHashMap;
+
+/// How often a function was called and how long it ran, see `Turing::take_stats` and `take_metrics`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CallStat {
+    pub calls: u64,
+    /// Wall time of every call added up, including host functions the script called
+    pub total: Duration,
+    /// Longest single call
+    pub max: Duration,
+}
+
+impl CallStat {
+    pub fn record(&mut self, elapsed: Duration) {
+        self.calls += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+}
+
+/// Per-function call stats, recorded while profiling is enabled
+#[derive(Debug, Default)]
+pub struct CallStats {
+    stats: FxHashMap<String, CallStat>,
+    /// What the FFI last took, read one entry at a time
+    snapshot: Vec<(String, CallStat)>,
+}
+
+impl CallStats {
+    pub fn record(&mut self, name: &str, elapsed: Duration) {
+        // only allocates the first time a function is called
+        if let Some(stat) = self.stats.get_mut(name) {
+            stat.record(elapsed);
+        } else {
+            self.stats
+                .entry(name.to_string())
+                .or_default()
+                .record(elapsed);
+        }
+    }
+
+    /// Returns every function's stats, most total time first, and starts over
+    pub fn take(&mut self) -> Vec<(String, CallStat)> {
+        let mut stats = self.stats.drain().collect::<Vec<_>>();
+        stats.sort_by(|(a_name, a), (b_name, b)| b.total.cmp(&a.total).then(a_name.cmp(b_name)));
+        stats
+    }
+
+    /// Takes the stats into a snapshot the FFI reads with `snapshot`, returning its length
+    pub(crate) fn take_snapshot(&mut self) -> usize {
+        self.snapshot = self.take();
+        self.snapshot.len()
+    }
+
+    pub(crate) fn snapshot(&self) -> &[(String, CallStat)] {
+        &self.snapshot
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use std::time::Duration;
+
+    use super::{CallStat, CallStats};
+
+    #[test]
+    fn test_call_stats_take() {
+        let mut stats = CallStats::default();
+        stats.record("on_update", Duration::from_micros(5));
+        stats.record("on_update", Duration::from_micros(20));
+        stats.record("on_load", Duration::from_millis(1));
+
+        let taken = stats.take();
+        assert_eq!(
+            taken,
+            vec![
+                (
+                    "on_load".to_string(),
+                    CallStat {
+                        calls: 1,
+                        total: Duration::from_millis(1),
+                        max: Duration::from_millis(1),
+                    }
+                ),
+                (
+                    "on_update".to_string(),
+                    CallStat {
+                        calls: 2,
+                        total: Duration::from_micros(25),
+                        max: Duration::from_micros(20),
+                    }
+                ),
+            ]
+        );
+        // taking starts over
+        assert!(stats.take().is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, anyhow};
+use rustc_hash::FxHashMap;
+
+use crate::engine::STORAGE_QUOTA_EXCEEDED_MESSAGE;
+
+/// How many bytes of keys and values each mod may store by default
+pub const DEFAULT_STORAGE_QUOTA_BYTES: usize = 64 * 1024;
+
+/// Key-value storage scripts persist settings and progress in, without filesystem access.
+/// Entries are namespaced per mod id so mods can't read each other's keys.
+/// The host saves and restores the whole store with `to_json`/`load_json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptStorage {
+    /// mod id -> key -> value
+    entries: FxHashMap<String, FxHashMap<String, String>>,
+    /// Max bytes of keys and values a script may store under one mod id
+    quota_bytes: usize,
+}
+
+impl Default for ScriptStorage {
+    fn default() -> Self {
+        Self {
+            entries: FxHashMap::default(),
+            quota_bytes: DEFAULT_STORAGE_QUOTA_BYTES,
+        }
+    }
+}
+
+impl ScriptStorage {
+    pub fn get(&self, mod_id: &str, key: &str) -> Option<&str> {
+        self.entries.get(mod_id)?.get(key).map(String::as_str)
+    }
+
+    /// Stores a value for a script, failing with [`STORAGE_QUOTA_EXCEEDED_MESSAGE`] if the
+    /// mod's keys and values would take more than the quota. Nothing is stored on failure.
+    pub fn set(&mut self, mod_id: &str, key: &str, value: &str) -> Result<()> {
+        let replaced = self.get(mod_id, key).map_or(0, |old| key.len() + old.len());
+        let usage = self.usage(mod_id) - replaced + key.len() + value.len();
+        if usage > self.quota_bytes {
+            return Err(anyhow!(
+                "{STORAGE_QUOTA_EXCEEDED_MESSAGE}: '{mod_id}' would use {usage} of {} bytes",
+                self.quota_bytes
+            ));
+        }
+        self.insert(mod_id, key, value);
+        Ok(())
+    }
+
+    /// Stores a value without checking the quota, for the host
+    pub fn insert(&mut self, mod_id: &str, key: &str, value: &str) {
+        self.entries
+            .entry(mod_id.to_string())
+            .or_default()
+            .insert(key.to_string(), value.to_string());
+    }
+
+    pub fn remove(&mut self, mod_id: &str, key: &str) -> Option<String> {
+        self.entries.get_mut(mod_id)?.remove(key)
+    }
+
+    /// Bytes of keys and values stored under `mod_id`
+    pub fn usage(&self, mod_id: &str) -> usize {
+        self.entries.get(mod_id).map_or(0, |entries| {
+            entries.iter().map(|(k, v)| k.len() + v.len()).sum()
+        })
+    }
+
+    pub fn quota_bytes(&self) -> usize {
+        self.quota_bytes
+    }
+
+    /// Applies to later writes, existing entries over the new quota are kept
+    pub fn set_quota_bytes(&mut self, bytes: usize) {
+        self.quota_bytes = bytes;
+    }
+
+    /// Serializes every entry as `{"mod_id": {"key": "value"}}`, sorted so saves diff cleanly
+    pub fn to_json(&self) -> Result<String> {
+        let sorted = self
+            .entries
+            .iter()
+            .map(|(id, entries)| (id, entries.iter().collect::<BTreeMap<_, _>>()))
+            .collect::<BTreeMap<_, _>>();
+        Ok(serde_json::to_string(&sorted)?)
+    }
+
+    /// Replaces every entry with the ones in `json`, as written by `to_json`.
+    /// The quota isn't checked, and the storage is left unchanged if `json` is invalid.
+    pub fn load_json(&mut self, json: &str) -> Result<()> {
+        self.entries =
+            serde_json::from_str(json).map_err(|e| anyhow!("invalid storage json: {e}"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod storage_tests {
+    use super::ScriptStorage;
+    use crate::engine::STORAGE_QUOTA_EXCEEDED_MESSAGE;
+
+    #[test]
+    fn test_storage_namespaced() {
+        let mut storage = ScriptStorage::default();
+        storage.set("mod_a", "volume", "0.5").unwrap();
+        storage.set("mod_b", "volume", "1.0").unwrap();
+        assert_eq!(storage.get("mod_a", "volume"), Some("0.5"));
+        assert_eq!(storage.get("mod_b", "volume"), Some("1.0"));
+        assert_eq!(storage.get("mod_c", "volume"), None);
+        assert_eq!(storage.usage("mod_a"), "volume".len() + "0.5".len());
+    }
+
+    #[test]
+    fn test_storage_quota() {
+        let mut storage = ScriptStorage::default();
+        storage.set_quota_bytes(8);
+        storage.set("a", "key", "12345").unwrap();
+        // overwriting frees the old value's bytes first
+        storage.set("a", "key", "abcde").unwrap();
+
+        let err = storage.set("a", "key2", "x").unwrap_err();
+        assert!(err.to_string().contains(STORAGE_QUOTA_EXCEEDED_MESSAGE));
+        assert_eq!(storage.get("a", "key2"), None);
+        // other mods have their own quota
+        storage.set("b", "key", "12345").unwrap();
+
+        // the host isn't limited
+        storage.insert("a", "key2", "long value");
+        assert_eq!(storage.get("a", "key2"), Some("long value"));
+    }
+
+    #[test]
+    fn test_storage_json_roundtrip() {
+        let mut storage = ScriptStorage::default();
+        storage.set("b", "z", "1").unwrap();
+        storage.set("b", "a", "\"quoted\"").unwrap();
+        storage.set("a", "k", "v").unwrap();
+        let json = storage.to_json().unwrap();
+        assert_eq!(json, r#"{"a":{"k":"v"},"b":{"a":"\"quoted\"","z":"1"}}"#);
+
+        let mut loaded = ScriptStorage::default();
+        loaded.set("old", "k", "v").unwrap();
+        loaded.load_json(&json).unwrap();
+        assert_eq!(loaded, storage);
+
+        assert!(loaded.load_json("[1, 2]").is_err());
+        assert_eq!(loaded, storage);
+    }
+}
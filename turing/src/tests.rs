@@ -1,12 +1,32 @@
-use crate::engine::types::ScriptFnMetadata;
+use crate::engine::types::{
+    CapabilityMissingPolicy, ErrorKind, ExportSignature, LogLevel, ScriptFnMetadata, ScriptValType,
+};
+use crate::engine::{
+    BUDGET_EXCEEDED_MESSAGE, EngineKind, FUNCTION_NOT_FOUND_MESSAGE, INTERRUPTED_MESSAGE,
+    NO_MEMORY_MESSAGE, REENTRY_UNSUPPORTED_MESSAGE, STORAGE_QUOTA_EXCEEDED_MESSAGE,
+    TYPE_MISMATCH_MESSAGE,
+};
+#[cfg(feature = "wasm")]
+use crate::engine::{Engine, wasm_engine::WasmInterpreter};
 use crate::interop::params::{
-    DataType, FfiParam, FfiParamArray, FfiParams, FreeableDataType, ObjectId, Param, Params,
+    DataType, FfiParam, FfiParamArray, FfiParams, FreeableDataType, INTERIOR_NUL_MESSAGE,
+    INVALID_CHAR_MESSAGE, NON_FINITE_FLOAT_MESSAGE, NanPolicy, ObjectId, Param, Params, RustTypes,
+};
+use crate::interop::types::{F32Buffer, Semver, U32Buffer};
+use crate::objects::UNKNOWN_OBJECT_MESSAGE;
+use crate::{
+    CallHandle, CallScope, ExternalFunctions, FastCallHandle, ScriptFnKey, Turing, TuringSetup,
+    is_call_cancelled, panic_hook,
 };
-use crate::interop::types::U32Buffer;
-use crate::{ExternalFunctions, Turing};
 use anyhow::Result;
-use glam::{Mat4, Vec2, Vec4};
-use std::ffi::{CString, c_char, c_void};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+use std::cell::{Cell, RefCell};
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::panic::PanicHookInfo;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
 
 struct DirectExt {}
 impl ExternalFunctions for DirectExt {
@@ -15,34 +35,109 @@ impl ExternalFunctions for DirectExt {
     }
 
     fn log_info(msg: impl ToString) {
-        println!("\x1b[38;2;50;200;50m[info]: {}\x1b[0m", msg.to_string())
+        let msg = msg.to_string();
+        println!("\x1b[38;2;50;200;50m[info]: {}\x1b[0m", msg);
+        LEVEL_LOGS.with_borrow_mut(|logs| logs.push((LogLevel::Info, msg.clone())));
+        LOGGED.with_borrow_mut(|logs| logs.push(msg));
     }
 
     fn log_warn(msg: impl ToString) {
-        println!("\x1b[38;2;255;127;30m[warn]: {}\x1b[0m", msg.to_string())
+        let msg = msg.to_string();
+        println!("\x1b[38;2;255;127;30m[warn]: {}\x1b[0m", msg);
+        LEVEL_LOGS.with_borrow_mut(|logs| logs.push((LogLevel::Warn, msg)));
     }
 
     fn log_debug(msg: impl ToString) {
-        println!("\x1b[38;2;20;200;200m[debug]: {}\x1b[0m", msg.to_string())
+        let msg = msg.to_string();
+        println!("\x1b[38;2;20;200;200m[debug]: {}\x1b[0m", msg);
+        LEVEL_LOGS.with_borrow_mut(|logs| logs.push((LogLevel::Debug, msg)));
     }
 
     fn log_critical(msg: impl ToString) {
-        println!("\x1b[38;2;200;20;20m[critical]: {}\x1b[0m", msg.to_string())
+        let msg = msg.to_string();
+        println!("\x1b[38;2;200;20;20m[critical]: {}\x1b[0m", msg);
+        LEVEL_LOGS.with_borrow_mut(|logs| logs.push((LogLevel::Error, msg.clone())));
+        LOGGED.with_borrow_mut(|logs| logs.push(msg));
     }
 
     fn free_string(ptr: *const c_char) {
+        STRINGS_FREED.set(STRINGS_FREED.get() + 1);
         let _ = unsafe { CString::from_raw(ptr as *mut c_char) };
     }
 
     fn free_of_type(ptr: *mut c_void, typ: FreeableDataType) {
-        unsafe { typ.free_ptr(ptr) }
+        unsafe { typ.free_ptr::<DirectExt>(ptr) }
     }
 
     fn free_u32_buffer(buf: U32Buffer) {
         buf.from_rust();
     }
+
+    fn free_f32_buffer(buf: F32Buffer) {
+        F32_BUFFERS_FREED.set(F32_BUFFERS_FREED.get() + 1);
+        buf.from_rust();
+    }
+
+    fn call_completed(key: ScriptFnKey, result: Param) {
+        COMPLETED_CALLS.with_borrow_mut(|calls| calls.push((key, result)));
+    }
+
+    fn clone_object(ptr: *const c_void) -> *const c_void {
+        OBJECT_CLONES.set(OBJECT_CLONES.get() + 1);
+        // a mock clone, the copy of object n is object n + 1000
+        (ptr as u64 + 1000) as *const c_void
+    }
+
+    fn take_object(ptr: *const c_void) {
+        TAKEN_OBJECTS.with_borrow_mut(|taken| taken.push(ObjectId::from_ptr(ptr)));
+    }
+
+    fn on_script_error(capability: &str, kind: ErrorKind, message: &str) {
+        SCRIPT_ERRORS.with_borrow_mut(|errors| {
+            errors.push((capability.to_string(), kind, message.to_string()))
+        });
+    }
+
+    fn on_script_reloaded(path: &std::path::Path) {
+        RELOADED_SCRIPTS.with_borrow_mut(|reloaded| reloaded.push(path.to_path_buf()));
+    }
 }
 
+thread_local! {
+    /// results delivered to `DirectExt::call_completed` on this test's thread
+    static COMPLETED_CALLS: RefCell<Vec<(ScriptFnKey, Param)>> = const { RefCell::new(Vec::new()) };
+    /// the handle `reenter` and `queue_flags` call back into the script with, and `flags_test`'s key
+    static REENTRY_TARGET: RefCell<Option<(CallHandle<DirectExt>, ScriptFnKey)>> = const { RefCell::new(None) };
+    /// whether `wait_for_cancel` saw its call get cancelled
+    static SAW_CANCEL: Cell<bool> = const { Cell::new(false) };
+    /// info and critical messages logged through `DirectExt` on this test's thread
+    static LOGGED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    /// every message logged through `DirectExt` on this test's thread, with the method it went to
+    static LEVEL_LOGS: RefCell<Vec<(LogLevel, String)>> = const { RefCell::new(Vec::new()) };
+    /// how many times `DirectExt::clone_object` ran on this test's thread
+    static OBJECT_CLONES: Cell<u32> = const { Cell::new(0) };
+    /// how many strings `DirectExt::free_string` freed on this test's thread
+    static STRINGS_FREED: Cell<u32> = const { Cell::new(0) };
+    /// how many buffers `DirectExt::free_f32_buffer` freed on this test's thread
+    static F32_BUFFERS_FREED: Cell<u32> = const { Cell::new(0) };
+    /// names and args `on_missing_test` was called with on this test's thread
+    static MISSING_CALLS: RefCell<Vec<(String, Vec<Param>)>> = const { RefCell::new(Vec::new()) };
+    /// objects handed to `DirectExt::take_object` on this test's thread
+    static TAKEN_OBJECTS: RefCell<Vec<ObjectId>> = const { RefCell::new(Vec::new()) };
+    /// errors passed to `DirectExt::on_script_error` on this test's thread
+    static SCRIPT_ERRORS: RefCell<Vec<(String, ErrorKind, String)>> = const { RefCell::new(Vec::new()) };
+    /// paths passed to `DirectExt::on_script_reloaded` on this test's thread
+    static RELOADED_SCRIPTS: RefCell<Vec<std::path::PathBuf>> = const { RefCell::new(Vec::new()) };
+}
+
+// instances must be movable to a worker thread, and params shared between threads
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<Turing<DirectExt>>();
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Param>();
+};
+
 struct ObjectA {
     value: u32,
 }
@@ -104,6 +199,132 @@ extern "C" fn object_a_foo(params: FfiParamArray) -> FfiParam {
     Param::I32((obj.value + 1) as i32).to_ext_param()
 }
 
+extern "C" fn echo_string(params: FfiParamArray) -> FfiParam {
+    let Ok(local) = params.as_params::<DirectExt>() else {
+        return Param::Error("Failed to unpack params".to_string()).to_ext_param();
+    };
+
+    match local.first() {
+        Some(Param::String(s)) => Param::String(s.clone()).to_ext_param(),
+        Some(Param::Error(e)) => Param::Error(e.clone()).to_ext_param(),
+        other => Param::Error(format!("Expected a string, got {other:?}")).to_ext_param(),
+    }
+}
+
+extern "C" fn is_null_object(params: FfiParamArray) -> FfiParam {
+    let Ok(local) = params.as_params::<DirectExt>() else {
+        return Param::Error("Failed to unpack params".to_string()).to_ext_param();
+    };
+
+    Param::Bool(matches!(local.first(), Some(Param::Null))).to_ext_param()
+}
+
+/// Variadic, returns how many args it got or -1 if three args aren't `1, "two", true`
+extern "C" fn count_args(params: FfiParamArray) -> FfiParam {
+    let Ok(local) = params.as_params::<DirectExt>() else {
+        return Param::Error("Failed to unpack params".to_string()).to_ext_param();
+    };
+
+    if local.len() == 3
+        && local[..]
+            != [
+                Param::I64(1),
+                Param::String("two".to_string()),
+                Param::Bool(true),
+            ]
+    {
+        return Param::I32(-1).to_ext_param();
+    }
+    Param::I32(local.len() as i32).to_ext_param()
+}
+
+extern "C" fn reenter(_params: FfiParamArray) -> FfiParam {
+    let Some((handle, key)) = REENTRY_TARGET.with_borrow(|target| target.clone()) else {
+        return Param::Error("no reentry target".to_string()).to_ext_param();
+    };
+
+    let mut params = Params::new();
+    params.push(Param::Flags(6));
+    handle
+        .call_fn_reentrant(key, params, DataType::Flags)
+        .to_ext_param()
+}
+
+/// Queues `flags_test` with the length of its string arg, which takes the engine data lock
+/// while the host callback is running
+extern "C" fn queue_flags(params: FfiParamArray) -> FfiParam {
+    let Ok(local) = params.as_params::<DirectExt>() else {
+        return Param::Error("Failed to unpack params".to_string()).to_ext_param();
+    };
+    let (Some(Param::String(msg)), Some((handle, key))) = (
+        local.first(),
+        REENTRY_TARGET.with_borrow(|target| target.clone()),
+    ) else {
+        return Param::Error("Unexpected queue_flags call".to_string()).to_ext_param();
+    };
+
+    let mut params = Params::new();
+    params.push(Param::Flags(msg.len() as u32));
+    handle.queue_call(key, params, DataType::Flags);
+    Param::Void.to_ext_param()
+}
+
+extern "C" fn wait_for_cancel(_params: FfiParamArray) -> FfiParam {
+    for _ in 0..500 {
+        if is_call_cancelled() {
+            SAW_CANCEL.set(true);
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    Param::Void.to_ext_param()
+}
+
+extern "C" fn track_event(_params: FfiParamArray) -> FfiParam {
+    Param::I32(1).to_ext_param()
+}
+
+extern "C" fn dispatch_test(name: *const c_char, params: FfiParamArray) -> FfiParam {
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+    let Ok(local) = params.as_params::<DirectExt>() else {
+        return Param::Error("Failed to unpack params".to_string()).to_ext_param();
+    };
+
+    match (name.as_ref(), local.first()) {
+        ("dispatched_double", Some(Param::I32(x))) => Param::I32(x * 2).to_ext_param(),
+        _ => Param::Error(format!("Unexpected dispatch to '{name}'")).to_ext_param(),
+    }
+}
+
+extern "C" fn on_missing_test(name: *const c_char, params: FfiParamArray) -> FfiParam {
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+    let Ok(local) = params.as_params::<DirectExt>() else {
+        return Param::Error("Failed to unpack params".to_string()).to_ext_param();
+    };
+    MISSING_CALLS.with_borrow_mut(|calls| calls.push((name.to_string(), local.to_vec())));
+    Param::I32(7).to_ext_param()
+}
+
+extern "C" fn sum_buffer(params: FfiParamArray) -> FfiParam {
+    let Ok(local) = params.as_params::<DirectExt>() else {
+        return Param::Error("Failed to unpack params".to_string()).to_ext_param();
+    };
+    match local.get(0) {
+        Some(Param::U32Buffer(values)) => Param::U32(values.iter().sum()).to_ext_param(),
+        other => Param::Error(format!("expected a U32Buffer, got {other:?}")).to_ext_param(),
+    }
+}
+
+extern "C" fn sum_floats(params: FfiParamArray) -> FfiParam {
+    let Ok(local) = params.as_params::<DirectExt>() else {
+        return Param::Error("Failed to unpack params".to_string()).to_ext_param();
+    };
+    match local.get(0) {
+        Some(Param::F32Buffer(values)) => Param::F32(values.iter().sum()).to_ext_param(),
+        other => Param::Error(format!("expected an F32Buffer, got {other:?}")).to_ext_param(),
+    }
+}
+
 fn common_setup_direct(source: &str) -> Result<Turing<DirectExt>> {
     let mut turing = Turing::new();
 
@@ -119,6 +340,16 @@ fn common_setup_direct(source: &str) -> Result<Turing<DirectExt>> {
     metadata.add_param_type(DataType::RustString, "msg")?;
     turing.add_function("do_panic", metadata)?;
 
+    let mut metadata = ScriptFnMetadata::new("test".to_owned(), sum_buffer, None);
+    metadata.add_param_type(DataType::RustU32Buffer, "values")?;
+    metadata.add_return_type(DataType::U32)?;
+    turing.add_function("sum_buffer", metadata)?;
+
+    let mut metadata = ScriptFnMetadata::new("test".to_owned(), sum_floats, None);
+    metadata.add_param_type(DataType::RustF32Buffer, "values")?;
+    metadata.add_return_type(DataType::F32)?;
+    turing.add_function("sum_floats", metadata)?;
+
     let mut metadata = ScriptFnMetadata::new("test".to_owned(), create_object_a, None);
     metadata.add_return_type_named(DataType::Object, "ObjectA".to_string())?;
     turing.add_function("create_ObjectA", metadata)?;
@@ -127,6 +358,12 @@ fn common_setup_direct(source: &str) -> Result<Turing<DirectExt>> {
     metadata.add_return_type(DataType::I32)?;
     turing.add_function("ObjectA.foo", metadata)?;
 
+    // the test scripts are loaded without this capability
+    let mut metadata = ScriptFnMetadata::new("analytics".to_owned(), track_event, None);
+    metadata.add_return_type(DataType::I32)?;
+    metadata.set_capability_missing_policy(CapabilityMissingPolicy::SilentDefault(Param::I32(-1)));
+    turing.add_function("track_event", metadata)?;
+
     let mut turing = turing.build()?;
     setup_test_script(&mut turing, source)?;
 
@@ -146,6 +383,489 @@ fn setup_test_script<Ext: ExternalFunctions + Send + Sync + 'static>(
     Ok(())
 }
 
+#[test]
+pub fn test_add_functions_duplicate() -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+    turing.add_function(
+        "fetch_string",
+        ScriptFnMetadata::new("test".to_owned(), fetch_string, None),
+    )?;
+
+    let batch = vec![
+        (
+            "log::info".to_string(),
+            ScriptFnMetadata::new("test".to_owned(), log_info_wasm, None),
+        ),
+        (
+            "fetch_string".to_string(),
+            ScriptFnMetadata::new("test".to_owned(), fetch_string, None),
+        ),
+    ];
+    let err = turing.add_functions(batch).unwrap_err();
+    assert!(err.to_string().contains("'fetch_string'"));
+    Ok(())
+}
+
+#[test]
+pub fn test_list_functions() -> Result<()> {
+    let turing = common_setup_direct(WASM_SCRIPT)?;
+
+    let fns = turing.list_functions();
+    let names = fns.iter().map(|f| f.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "ObjectA.foo",
+            "create_ObjectA",
+            "do_panic",
+            "fetch_string",
+            "log::info",
+            "sum_buffer",
+            "sum_floats",
+            "track_event"
+        ]
+    );
+
+    let log = &fns[4];
+    assert_eq!(log.capability, "test");
+    assert_eq!(log.param_types[0].name, "msg");
+    assert_eq!(log.param_types[0].data_type, DataType::RustString);
+    assert!(log.return_type.is_empty());
+    assert_eq!(fns[3].return_type[0].0, DataType::ExtString);
+    assert_eq!(fns[6].param_types[0].data_type, DataType::RustF32Buffer);
+    Ok(())
+}
+
+fn test_script_exports(turing: Turing<DirectExt>) -> Result<()> {
+    let exports = turing.script_exports();
+    assert!(exports.iter().any(|e| e == "math_ops_test"));
+    assert!(exports.iter().any(|e| e == "on_render"));
+    Ok(())
+}
+
+#[test]
+pub fn test_script_exports_wasm() -> Result<()> {
+    let turing = common_setup_direct(WASM_SCRIPT)?;
+    test_script_exports(turing)
+}
+
+#[test]
+pub fn test_script_exports_lua() -> Result<()> {
+    let turing = common_setup_direct(LUA_SCRIPT)?;
+    test_script_exports(turing)
+}
+
+fn test_on_load(source: &str) -> Result<()> {
+    let mut turing = common_setup_direct(source)?;
+    let res = turing.call_fn_by_name("loaded_test", Params::new(), DataType::Bool);
+    assert_eq!(res, Param::Bool(true));
+
+    turing.unload_script();
+    assert!(!turing.is_loaded());
+    Ok(())
+}
+
+#[test]
+pub fn test_on_load_wasm() -> Result<()> {
+    test_on_load(WASM_SCRIPT)
+}
+
+#[test]
+pub fn test_on_load_lua() -> Result<()> {
+    test_on_load(LUA_SCRIPT)
+}
+
+/// Writes a lua script to a temp file, named after the test using it
+fn write_temp_lua(name: &str, source: &str) -> Result<std::path::PathBuf> {
+    let path = std::env::temp_dir().join(format!("turing_{name}_{}.lua", std::process::id()));
+    std::fs::write(&path, source)?;
+    Ok(path)
+}
+
+/// Rewrites a watched script with an mtime that can't match its last one, then polls until
+/// the reload is seen, since the watcher reports the change from its own thread
+#[cfg(feature = "hot_reload")]
+fn rewrite_and_poll(
+    turing: &mut Turing<DirectExt>,
+    path: &std::path::Path,
+    source: &str,
+    generation: u64,
+) -> Result<bool> {
+    std::fs::write(path, source)?;
+    std::fs::File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(std::time::SystemTime::now() + Duration::from_secs(generation * 60))?;
+    for _ in 0..200 {
+        if turing.poll_reload()? {
+            return Ok(true);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    Ok(false)
+}
+
+#[test]
+#[cfg(feature = "hot_reload")]
+pub fn test_hot_reload() -> Result<()> {
+    let script = |version: u32| {
+        format!(
+            "local mod = {{}}\nlocal loaded = 0\nfunction mod.on_load() loaded = {version} end\nfunction mod.version() return loaded end\nreturn mod"
+        )
+    };
+    let path = write_temp_lua("hot_reload", &script(1))?;
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+    // nothing to watch before a script is loaded
+    turing.unload_script();
+    assert!(turing.enable_hot_reload().is_err());
+
+    turing.load_script(path.to_string_lossy(), &["test"])?;
+    turing.enable_hot_reload()?;
+    assert!(!turing.poll_reload()?);
+    RELOADED_SCRIPTS.take();
+
+    assert!(rewrite_and_poll(&mut turing, &path, &script(2), 1)?);
+    // on_load ran again in the new script
+    let res = turing.call_fn_by_name("version", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(2));
+    assert_eq!(RELOADED_SCRIPTS.take(), vec![path.clone()]);
+    assert!(!turing.poll_reload()?);
+
+    // a broken save unloads the script, and fixing it loads it again
+    assert!(rewrite_and_poll(&mut turing, &path, "local mod = {", 2).is_err());
+    assert!(!turing.is_loaded());
+    assert!(rewrite_and_poll(&mut turing, &path, &script(3), 3)?);
+    let res = turing.call_fn_by_name("version", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(3));
+
+    // loading a different script stops watching the old one
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+    std::fs::write(&path, script(4))?;
+    assert!(!turing.poll_reload()?);
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "hot_reload")]
+pub fn test_hot_reload_mod() -> Result<()> {
+    let script = |version: u32| {
+        format!("local mod = {{}}\nfunction mod.version() return {version} end\nreturn mod")
+    };
+    let dir = std::env::temp_dir().join(format!("turing_mod_hot_reload_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(
+        dir.join("mod.toml"),
+        "name = \"reloaded\"\nversion = \"1.0.0\"\nentry = \"main.lua\"\ncapabilities = [\"test\"]\n",
+    )?;
+    let entry = dir.join("main.lua");
+    std::fs::write(&entry, script(1))?;
+
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+    turing.load_mod(&dir, &["test"], &[])?;
+    turing.enable_hot_reload()?;
+    assert!(rewrite_and_poll(&mut turing, &entry, &script(2), 1)?);
+    // reloaded as a mod, not as a bare script
+    assert_eq!(turing.manifest().map(|m| m.name.as_str()), Some("reloaded"));
+    assert!(turing.data.read().active_capabilities.contains("test"));
+    let res = turing.call_fn_by_name("version", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(2));
+
+    // unloading stops the watch
+    turing.unload_script();
+    assert!(!rewrite_and_poll(&mut turing, &entry, &script(3), 2)?);
+    assert!(!turing.is_loaded());
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+pub fn test_lifecycle_errors() -> Result<()> {
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+
+    let failing_load = write_temp_lua(
+        "failing_on_load",
+        // the sandbox has no `error`, indexing a missing global fails instead
+        "local mod = {}\nfunction mod.on_load() return assets.count end\nreturn mod",
+    )?;
+    let err = turing
+        .load_script(failing_load.to_string_lossy(), &["test"])
+        .unwrap_err();
+    std::fs::remove_file(&failing_load)?;
+    assert!(err.to_string().contains("on_load"));
+    assert!(err.to_string().contains("'assets'"));
+    assert!(!turing.is_loaded());
+
+    let failing_unload = write_temp_lua(
+        "failing_on_unload",
+        "local mod = {}\nfunction mod.on_unload() return busy.count end\nreturn mod",
+    )?;
+    turing.load_script(failing_unload.to_string_lossy(), &["test"])?;
+    std::fs::remove_file(&failing_unload)?;
+
+    // replacing the script unloads the old one, the on_unload error is only logged
+    LEVEL_LOGS.take();
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+    assert!(LEVEL_LOGS.take().iter().any(|(level, msg)| {
+        *level == LogLevel::Warn && msg.contains("on_unload") && msg.contains("'busy'")
+    }));
+    let res = turing.call_fn_by_name("loaded_test", Params::new(), DataType::Bool);
+    assert_eq!(res, Param::Bool(true));
+    Ok(())
+}
+
+fn test_api_versions(turing: Turing<DirectExt>) -> Result<()> {
+    let versions = turing.get_api_versions().expect("script exports a version");
+    assert_eq!(versions.get("core"), Some(&Semver::new(1, 2, 3)));
+    assert_eq!(versions.get("physics"), Some(&Semver::new(2, 0, 1)));
+    assert_eq!(versions.len(), 2);
+    Ok(())
+}
+
+#[test]
+pub fn test_script_meta_lua() -> Result<()> {
+    let turing = common_setup_direct(LUA_SCRIPT)?;
+
+    let meta = turing.script_meta();
+    assert_eq!(meta.get("name").map(String::as_str), Some("lua_test"));
+    assert_eq!(meta.get("author").map(String::as_str), Some("turing"));
+    assert_eq!(meta.get("version").map(String::as_str), Some("1.0"));
+    assert_eq!(meta.len(), 3);
+    Ok(())
+}
+
+#[test]
+pub fn test_script_meta_wasm() -> Result<()> {
+    let turing = common_setup_direct(WASM_SCRIPT)?;
+
+    let meta = turing.script_meta();
+    assert_eq!(meta.get("name").map(String::as_str), Some("wasm_tests"));
+    assert_eq!(meta.get("author").map(String::as_str), Some("turing"));
+    Ok(())
+}
+
+#[test]
+pub fn test_api_versions_wasm() -> Result<()> {
+    let turing = common_setup_direct(WASM_SCRIPT)?;
+    test_api_versions(turing)
+}
+
+#[test]
+pub fn test_api_versions_lua() -> Result<()> {
+    let turing = common_setup_direct(LUA_SCRIPT)?;
+    test_api_versions(turing)
+}
+
+#[test]
+pub fn test_load_script_checked() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    let caps = ["test"];
+
+    // the wasm script declares core 1.2.3 and physics 2.0.1
+    let host = [
+        ("core".to_string(), Semver::new(1, 4, 0)),
+        ("physics".to_string(), Semver::new(2, 0, 0)),
+    ];
+    turing.load_script_checked(WASM_SCRIPT, &caps, &host)?;
+    assert!(turing.check_compatibility(&host).is_empty());
+
+    let host = [
+        ("core".to_string(), Semver::new(1, 1, 0)),
+        ("physics".to_string(), Semver::new(2, 0, 0)),
+    ];
+    let err = turing
+        .load_script_checked(WASM_SCRIPT, &caps, &host)
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("'core' 1.2.3, host provides 1.1.0")
+    );
+    assert!(turing.engine.is_none());
+
+    let err = turing
+        .load_script_checked(WASM_SCRIPT, &caps, &[])
+        .unwrap_err();
+    assert!(err.to_string().contains("host does not provide it"));
+    Ok(())
+}
+
+#[test]
+pub fn test_load_script_checked_before_on_load() -> Result<()> {
+    let path = write_temp_lua(
+        "checked_on_load",
+        "local mod = {}\nfunction mod.on_load() require(\"turing_api\").log.info(\"on_load ran\") end\nfunction mod._core_semver() return 2 << 32 end\nreturn mod",
+    )?;
+    let mut turing = Turing::<DirectExt>::new().build()?;
+
+    LEVEL_LOGS.take();
+    let err = turing
+        .load_script_checked(
+            path.to_string_lossy(),
+            &["test"],
+            &[("core".to_string(), Semver::new(1, 0, 0))],
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("incompatible with the host api"));
+    assert!(turing.engine.is_none());
+    assert!(
+        !LEVEL_LOGS
+            .take()
+            .iter()
+            .any(|(_, msg)| msg.contains("on_load ran"))
+    );
+
+    turing.load_script_checked(
+        path.to_string_lossy(),
+        &["test"],
+        &[("core".to_string(), Semver::new(2, 0, 0))],
+    )?;
+    std::fs::remove_file(&path)?;
+    assert!(
+        LEVEL_LOGS
+            .take()
+            .iter()
+            .any(|(_, msg)| msg.contains("on_load ran"))
+    );
+    Ok(())
+}
+
+/// Writes a mod directory holding `manifest` and a copy of the wasm test script
+fn write_test_mod(name: &str, manifest: &str) -> Result<std::path::PathBuf> {
+    let dir = std::env::temp_dir().join(format!("turing_mod_{name}_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("mod.toml"), manifest)?;
+    std::fs::copy(WASM_SCRIPT, dir.join("main.wasm"))?;
+    Ok(dir)
+}
+
+#[test]
+pub fn test_load_mod() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    let dir = write_test_mod(
+        "valid",
+        r#"
+name = "example"
+version = "1.4.2"
+entry = "main.wasm"
+capabilities = ["test"]
+
+[api]
+core = "1.2.0"
+"#,
+    )?;
+
+    // the wasm script declares core 1.2.3 and physics 2.0.1
+    let host = [
+        ("core".to_string(), Semver::new(1, 4, 0)),
+        ("physics".to_string(), Semver::new(2, 0, 0)),
+    ];
+    let report = turing.load_mod(&dir, &["test"], &host)?;
+    std::fs::remove_dir_all(&dir)?;
+
+    assert!(turing.engine.is_some());
+    let manifest = turing.manifest().unwrap();
+    assert_eq!(manifest.name, "example");
+    assert_eq!(manifest.version, Semver::new(1, 4, 2));
+    assert!(turing.data.read().active_capabilities.contains("test"));
+
+    assert_eq!(report.warnings.len(), 2);
+    assert!(report.warnings.iter().any(|w| w.contains("'physics'")));
+    assert!(
+        report
+            .warnings
+            .iter()
+            .any(|w| w.contains("lists api 'core' 1.2.0"))
+    );
+
+    // a mod's storage is keyed by its manifest name
+    let res = turing.call_fn_by_name("storage_test", Params::new(), DataType::U32);
+    assert_eq!(res, Param::U32(1));
+    assert_eq!(turing.storage_get("example", "runs").as_deref(), Some("1"));
+
+    test_math(turing)
+}
+
+#[test]
+pub fn test_load_mod_unknown_capability() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    let dir = write_test_mod(
+        "unknown_cap",
+        r#"
+name = "example"
+version = "1.0.0"
+entry = "main.wasm"
+capabilities = ["test", "filesystem"]
+"#,
+    )?;
+
+    let err = turing
+        .load_mod(&dir, &["test", "filesystem"], &[])
+        .unwrap_err();
+    std::fs::remove_dir_all(&dir)?;
+    assert!(
+        err.to_string()
+            .contains("unknown capabilities: 'filesystem'")
+    );
+    assert!(turing.engine.is_none());
+    assert!(turing.manifest().is_none());
+    Ok(())
+}
+
+#[test]
+pub fn test_load_mod_capability_not_allowed() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    let dir = write_test_mod(
+        "not_allowed",
+        r#"
+name = "example"
+version = "1.0.0"
+entry = "main.wasm"
+capabilities = ["test"]
+"#,
+    )?;
+
+    // "test" is registered, but this host only lets the mod use "physics"
+    let err = turing.load_mod(&dir, &["physics"], &[]).unwrap_err();
+    std::fs::remove_dir_all(&dir)?;
+    assert!(
+        err.to_string()
+            .contains("requests capabilities it is not allowed: 'test'")
+    );
+    assert!(turing.engine.is_none());
+    assert!(turing.manifest().is_none());
+    Ok(())
+}
+
+#[test]
+pub fn test_load_mod_version_conflict() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    let dir = write_test_mod(
+        "version_conflict",
+        r#"
+name = "example"
+version = "1.0.0"
+entry = "main.wasm"
+capabilities = ["test"]
+
+[api]
+core = "1.5.0"
+"#,
+    )?;
+
+    let host = [
+        ("core".to_string(), Semver::new(1, 4, 0)),
+        ("physics".to_string(), Semver::new(2, 0, 0)),
+    ];
+    let err = turing.load_mod(&dir, &["test"], &host).unwrap_err();
+    std::fs::remove_dir_all(&dir)?;
+    assert!(
+        err.to_string()
+            .contains("'core' 1.5.0, host provides 1.4.0")
+    );
+    assert!(turing.engine.is_none());
+    Ok(())
+}
+
 #[test]
 pub fn test_file_access() -> Result<()> {
     let mut turing = common_setup_direct(WASM_SCRIPT)?;
@@ -171,6 +891,13 @@ fn test_math(mut turing: Turing<DirectExt>) -> Result<()> {
     );
     assert!((res.to_result::<f32>()? - 17.5).abs() < f32::EPSILON);
 
+    let res = turing.call_fn_args(
+        "math_ops_test",
+        [Param::F32(2.0), Param::F32(3.0)],
+        DataType::F32,
+    );
+    assert_eq!(res, Param::F32(6.0));
+
     Ok(())
 }
 
@@ -219,6 +946,28 @@ pub fn test_lua_string_fetch() -> Result<()> {
     Ok(())
 }
 
+#[test]
+pub fn test_interior_nul_string() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+
+    let mut params = Params::new();
+    params.push(Param::String("abc".to_string()));
+    let res = turing.call_fn_by_name("string_len_test", params, DataType::U32);
+    assert_eq!(res, Param::U32(3));
+
+    let mut params = Params::new();
+    params.push(Param::String("a\0b".to_string()));
+    let res = turing.call_fn_by_name("string_len_test", params, DataType::U32);
+    assert!(matches!(res, Param::Error(e) if e.contains(INTERIOR_NUL_MESSAGE)));
+
+    // crossing the ffi boundary turns the string into an error instead of panicking
+    let res = Param::String("a\0b".to_string())
+        .to_ext_param()
+        .into_param::<DirectExt>()?;
+    assert_eq!(res, Param::Error(INTERIOR_NUL_MESSAGE.to_string()));
+    Ok(())
+}
+
 #[test]
 pub fn test_wasm_panic() -> Result<()> {
     let mut turing = common_setup_direct(WASM_SCRIPT)?;
@@ -231,17 +980,95 @@ pub fn test_wasm_panic() -> Result<()> {
 
     let err = res.unwrap_err().to_string();
     assert!(err.contains("panic") || err.contains("unreachable") || err.contains("trap"));
+    assert!(err.contains("wasm backtrace:"));
+    assert!(err.contains("test_panic"));
     Ok(())
 }
 
+/// Traps two calls deep, in functions named by the module's name section
+#[cfg(feature = "wasm")]
+const NESTED_TRAP_WAT: &str = r#"(module
+    (memory (export "memory") 1)
+    (func $explode unreachable)
+    (func $middle call $explode)
+    (func (export "crash") call $middle))"#;
+
+#[cfg(feature = "wasm")]
 #[test]
-pub fn test_wasm_object_call() -> Result<()> {
-    // Use the pre-built wasm_tests.wasm produced by the `tests` crate build.
-    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+pub fn test_wasm_trap_backtrace() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("turing_nested_trap_{}.wasm", std::process::id()));
+    std::fs::write(&path, wat::parse_str(NESTED_TRAP_WAT)?)?;
+    let mut turing = Turing::<DirectExt>::new().build()?;
+    turing.load_script(path.to_string_lossy(), &["test"])?;
 
-    // create a boxed value and take a pointer to it
-    let boxed = Box::new(0xCAFEBABEu64);
-    let ptr = boxed.as_ref() as *const u64 as *const c_void;
+    let Param::Error(err) = turing.call_fn_by_name("crash", Params::new(), DataType::Void) else {
+        panic!("trap didn't return an error");
+    };
+    assert!(err.contains("unreachable"));
+    // innermost frame first
+    let explode = err.find("0: explode").expect("missing trapping frame");
+    let middle = err.find("1: middle").expect("missing caller frame");
+    assert!(explode < middle);
+
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+type PanicHook = dyn Fn(&PanicHookInfo<'_>) + Sync + Send + 'static;
+
+#[test]
+pub fn test_panic_hook_dump() -> Result<()> {
+    let dump = std::env::temp_dir().join(format!("turing_panic_dump_{}.txt", std::process::id()));
+
+    // the hook is process-wide, so only handle panics from this test's thread
+    let test_thread = thread::current().id();
+    // shared so the previous hook can be put back once the test is done
+    let prev_hook = Arc::<PanicHook>::from(std::panic::take_hook());
+    let hook_dump = dump.clone();
+    let other_threads_hook = Arc::clone(&prev_hook);
+    std::panic::set_hook(Box::new(move |info| {
+        if thread::current().id() == test_thread {
+            panic_hook::<DirectExt>(Some(hook_dump.clone()), info);
+        } else {
+            other_threads_hook(info);
+        }
+    }));
+
+    let res = std::panic::catch_unwind(|| {
+        let _script = CallScope::script("on_update");
+        let _host = CallScope::host(&Arc::from("do_panic"));
+        panic!("Deliberate host callback panic");
+    });
+    assert!(res.is_err());
+
+    let contents = std::fs::read_to_string(&dump)?;
+    assert!(contents.contains("Deliberate host callback panic"));
+    assert!(contents.contains("Script function: on_update"));
+    assert!(contents.contains("Host function: do_panic"));
+    assert!(contents.contains("Backtrace:"));
+    assert!(contents.contains("(unix time "));
+
+    // every panic on this thread is dumped, not just the first
+    let res = std::panic::catch_unwind(|| panic!("Second panic"));
+    drop(std::panic::take_hook());
+    std::panic::set_hook(Box::new(move |info| prev_hook(info)));
+    assert!(res.is_err());
+
+    let contents = std::fs::read_to_string(&dump)?;
+    std::fs::remove_file(&dump)?;
+    assert!(contents.contains("Second panic"));
+    assert!(contents.contains("Backtrace:"));
+    Ok(())
+}
+
+#[test]
+pub fn test_wasm_object_call() -> Result<()> {
+    // Use the pre-built wasm_tests.wasm produced by the `tests` crate build.
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+
+    // create a boxed value and take a pointer to it
+    let boxed = Box::new(0xCAFEBABEu64);
+    let ptr = boxed.as_ref() as *const u64 as *const c_void;
 
     let mut params = Params::new();
     params.push(Param::Object(ObjectId::from_ptr(ptr)));
@@ -256,6 +1083,43 @@ pub fn test_wasm_object_call() -> Result<()> {
     Ok(())
 }
 
+/// `echo_fn` returns the object it's passed, or nothing for object 0
+fn test_owned_object(source: &str, echo_fn: &str) -> Result<()> {
+    let mut turing = common_setup_direct(source)?;
+    TAKEN_OBJECTS.take();
+
+    let mut params = Params::new();
+    params.push(Param::Object(ObjectId::new(7)));
+    let res = turing.call_fn_by_name(echo_fn, params, DataType::Object);
+    assert_eq!(res, Param::Object(ObjectId::new(7)));
+    // borrowed handles stay with the script
+    assert!(TAKEN_OBJECTS.take().is_empty());
+
+    let mut params = Params::new();
+    params.push(Param::Object(ObjectId::new(8)));
+    let res = turing.call_fn_by_name(echo_fn, params, DataType::OwnedObject);
+    assert_eq!(res, Param::Object(ObjectId::new(8)));
+    assert_eq!(TAKEN_OBJECTS.take(), [ObjectId::new(8)]);
+
+    // there's nothing to take from a null object
+    let mut params = Params::new();
+    params.push(Param::Null);
+    let res = turing.call_fn_by_name(echo_fn, params, DataType::OwnedObject);
+    assert_eq!(res, Param::Null);
+    assert!(TAKEN_OBJECTS.take().is_empty());
+    Ok(())
+}
+
+#[test]
+pub fn test_owned_object_wasm() -> Result<()> {
+    test_owned_object(WASM_SCRIPT, "object_test")
+}
+
+#[test]
+pub fn test_owned_object_lua() -> Result<()> {
+    test_owned_object(LUA_SCRIPT, "find_object")
+}
+
 #[test]
 pub fn test_wasm_object_method_roundtrip() -> Result<()> {
     let mut turing = common_setup_direct(WASM_SCRIPT)?;
@@ -341,3 +1205,2920 @@ pub fn test_mat4_wasm() -> Result<()> {
     assert!((r.w_axis.w - 4.0).abs() < f32::EPSILON);
     Ok(())
 }
+
+#[test]
+#[cfg(feature = "wasm")]
+pub fn test_wasm_module_bytes() -> Result<()> {
+    use crate::engine::Engine;
+
+    let turing = common_setup_direct(WASM_SCRIPT)?;
+    assert_eq!(turing.module_bytes(), Some(std::fs::read(WASM_SCRIPT)?));
+
+    let compiled = turing.compiled_bytes()?;
+    let Some(Engine::Wasm(wasm)) = &turing.engine else {
+        panic!("expected the wasm engine");
+    };
+    // Safety: the bytes were just produced by `Module::serialize` on the same engine
+    let module = unsafe { wasmtime::Module::deserialize(wasm.wasmtime_engine(), &compiled)? };
+
+    let mut exports = module
+        .exports()
+        .filter(|e| e.ty().func().is_some())
+        .map(|e| e.name().to_string())
+        .collect::<Vec<_>>();
+    let mut expected = turing.script_exports();
+    exports.sort();
+    expected.sort();
+    assert_eq!(exports, expected);
+
+    let turing = common_setup_direct(LUA_SCRIPT)?;
+    assert_eq!(turing.module_bytes(), None);
+    assert!(turing.compiled_bytes().is_err());
+    Ok(())
+}
+
+fn test_flags_roundtrip(mut turing: Turing<DirectExt>) -> Result<()> {
+    let mut params = Params::new();
+    params.push(Param::Flags(0b1010));
+    let res = turing.call_fn_by_name("flags_test", params, DataType::Flags);
+    assert_eq!(res, Param::Flags(0b1010));
+    Ok(())
+}
+
+#[test]
+pub fn test_flags_roundtrip_wasm() -> Result<()> {
+    let turing = common_setup_direct(WASM_SCRIPT)?;
+    test_flags_roundtrip(turing)
+}
+
+#[test]
+pub fn test_flags_roundtrip_lua() -> Result<()> {
+    let turing = common_setup_direct(LUA_SCRIPT)?;
+    test_flags_roundtrip(turing)
+}
+
+/// `flags_test` returns its u32 as is, so it echoes a char's code point
+fn test_char_roundtrip(mut turing: Turing<DirectExt>) -> Result<()> {
+    let mut params = Params::new();
+    params.push(Param::Char('λ'));
+    let res = turing.call_fn_by_name("flags_test", params, DataType::Char);
+    assert_eq!(res, Param::Char('λ'));
+
+    // a surrogate isn't a char
+    let mut params = Params::new();
+    params.push(Param::Flags(0xD800));
+    let res = turing.call_fn_by_name("flags_test", params, DataType::Char);
+    assert!(matches!(res, Param::Error(e) if e.contains(INVALID_CHAR_MESSAGE)));
+    Ok(())
+}
+
+#[test]
+pub fn test_char_roundtrip_wasm() -> Result<()> {
+    let turing = common_setup_direct(WASM_SCRIPT)?;
+    test_char_roundtrip(turing)
+}
+
+#[test]
+pub fn test_char_roundtrip_lua() -> Result<()> {
+    let turing = common_setup_direct(LUA_SCRIPT)?;
+    test_char_roundtrip(turing)
+}
+
+#[test]
+pub fn test_char_ffi() -> Result<()> {
+    let ffi = Param::Char('λ').to_rs_param();
+    assert_eq!(ffi.type_id, DataType::Char);
+    assert_eq!(ffi.into_param::<DirectExt>()?, Param::Char('λ'));
+
+    for code in [0xD800, 0xDFFF, 0x110000, u32::MAX] {
+        let mut ffi = Param::U32(code).to_rs_param();
+        ffi.type_id = DataType::Char;
+        let Param::Error(e) = ffi.into_param::<DirectExt>()? else {
+            panic!("{code:#x} was accepted as a char");
+        };
+        assert!(e.contains(INVALID_CHAR_MESSAGE));
+    }
+    assert_eq!(Param::char_from_code(0x10FFFF), Param::Char('\u{10FFFF}'));
+    assert_eq!(Param::Char('λ').to_string(), "'λ'");
+    Ok(())
+}
+
+#[test]
+pub fn test_json_table_lua() -> Result<()> {
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+
+    let res = turing.call_fn_by_name("json_table_test", Params::new(), DataType::RustJson);
+    assert_eq!(
+        res,
+        Param::Json(serde_json::json!({ "x": 1, "names": ["a", "b"] }))
+    );
+
+    let mut params = Params::new();
+    params.push(Param::Json(
+        serde_json::json!({ "nested": { "list": [1.5, "two", false], "gone": null } }),
+    ));
+    // null object values become nil, so the key is gone
+    let res = turing.call_fn_by_name("json_echo_test", params, DataType::RustJson);
+    assert_eq!(
+        res,
+        Param::Json(serde_json::json!({ "nested": { "list": [1.5, "two", false] }, "seen": true }))
+    );
+    Ok(())
+}
+
+fn test_fast_call(mut turing: Turing<DirectExt>) -> Result<()> {
+    let render = turing.register_fast_call("on_render")?;
+    let missing = turing.register_fast_call("on_late_update")?;
+
+    turing
+        .invoke_fast_call(render, 0.016)
+        .map_err(anyhow::Error::msg)?;
+    turing
+        .invoke_fast_call(render, 0.016)
+        .map_err(anyhow::Error::msg)?;
+    // scripts that don't define a registered function are skipped, like on_update
+    turing
+        .invoke_fast_call(missing, 0.016)
+        .map_err(anyhow::Error::msg)?;
+    assert!(
+        turing
+            .invoke_fast_call(FastCallHandle::from(99), 0.016)
+            .is_err()
+    );
+
+    let res = turing.call_fn_by_name("render_count", Params::new(), DataType::U32);
+    assert_eq!(res, Param::U32(2));
+    Ok(())
+}
+
+#[test]
+pub fn test_reset_instance_wasm() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    let render = turing.register_fast_call("on_render")?;
+    let render_count = turing
+        .get_fn_key("render_count")
+        .expect("render_count not found");
+    turing
+        .invoke_fast_call(render, 0.016)
+        .map_err(anyhow::Error::msg)?;
+
+    let res = turing.call_fn_by_name("test_panic", Params::new(), DataType::Void);
+    assert!(matches!(res, Param::Error(_)));
+
+    turing.reset_instance()?;
+    // the script starts over, but keys and handles from before the reset still work
+    let res = turing.call_fn(render_count, Params::new(), DataType::U32);
+    assert_eq!(res, Param::U32(0));
+    turing
+        .invoke_fast_call(render, 0.016)
+        .map_err(anyhow::Error::msg)?;
+    let res = turing.call_fn(render_count, Params::new(), DataType::U32);
+    assert_eq!(res, Param::U32(1));
+    let res = turing.call_fn_by_name("loaded_test", Params::new(), DataType::Bool);
+    assert_eq!(res, Param::Bool(true));
+    Ok(())
+}
+
+#[test]
+pub fn test_reset_instance_lua() -> Result<()> {
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+    assert!(turing.reset_instance().is_err());
+    // the script is still loaded
+    let res = turing.call_fn_by_name("loaded_test", Params::new(), DataType::Bool);
+    assert_eq!(res, Param::Bool(true));
+    Ok(())
+}
+
+#[test]
+pub fn test_stale_fast_call_lua() -> Result<()> {
+    let path = write_temp_lua(
+        "stale_fast_call",
+        "local mod = {}\nlocal ticks = 0\nmod.on_stale = 5\nfunction mod.on_tick() ticks = ticks + 1 end\nfunction mod.tick_count() return ticks end\nreturn mod",
+    )?;
+    let mut turing = Turing::<DirectExt>::new().build()?;
+    let stale = turing.register_fast_call("on_stale")?;
+    let tick = turing.register_fast_call("on_tick")?;
+
+    LEVEL_LOGS.take();
+    turing.load_script(path.to_string_lossy(), &["test"])?;
+    std::fs::remove_file(&path)?;
+    assert!(LEVEL_LOGS.take().iter().any(|(level, msg)| {
+        *level == LogLevel::Warn && msg.contains("'on_stale') was invalidated")
+    }));
+
+    // the stale handle does nothing and the handles after it still line up
+    turing
+        .invoke_fast_call(stale, 0.016)
+        .map_err(anyhow::Error::msg)?;
+    turing
+        .invoke_fast_call(tick, 0.016)
+        .map_err(anyhow::Error::msg)?;
+    let res = turing.call_fn_by_name("tick_count", Params::new(), DataType::U32);
+    assert_eq!(res, Param::U32(1));
+    Ok(())
+}
+
+#[test]
+pub fn test_fast_call_wasm() -> Result<()> {
+    let turing = common_setup_direct(WASM_SCRIPT)?;
+    test_fast_call(turing)
+}
+
+#[test]
+pub fn test_fast_call_lua() -> Result<()> {
+    let turing = common_setup_direct(LUA_SCRIPT)?;
+    test_fast_call(turing)
+}
+
+fn test_interrupt(mut turing: Turing<DirectExt>) -> Result<()> {
+    let handle = turing.interrupt_handle().expect("script should be loaded");
+
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        handle.interrupt();
+    });
+
+    let res = turing.call_fn_by_name("infinite_loop", Params::new(), DataType::Void);
+    interrupter.join().unwrap();
+
+    assert_eq!(res, Param::Error("script interrupted".to_string()));
+    Ok(())
+}
+
+#[test]
+pub fn test_interrupt_wasm() -> Result<()> {
+    let turing = common_setup_direct(WASM_SCRIPT)?;
+    test_interrupt(turing)
+}
+
+#[test]
+pub fn test_interrupt_lua() -> Result<()> {
+    let turing = common_setup_direct(LUA_SCRIPT)?;
+    test_interrupt(turing)
+}
+
+#[test]
+pub fn test_lua_instruction_budget() -> Result<()> {
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+    turing.set_lua_instruction_budget(Some(100_000));
+
+    let res = turing.call_fn_by_name("infinite_loop", Params::new(), DataType::Void);
+    assert_eq!(res, Param::Error(BUDGET_EXCEEDED_MESSAGE.to_string()));
+
+    // the budget is per call, so short calls still run afterwards
+    let res = turing.call_fn_by_name("render_count", Params::new(), DataType::U32);
+    assert_eq!(res, Param::U32(0));
+    Ok(())
+}
+
+fn test_capability_missing_default(mut turing: Turing<DirectExt>) -> Result<()> {
+    let res = turing.call_fn_by_name("analytics_test", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(-1));
+
+    turing.add_capability("analytics");
+    let res = turing.call_fn_by_name("analytics_test", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(1));
+
+    turing.remove_capability("analytics");
+    let res = turing.call_fn_by_name("analytics_test", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(-1));
+    Ok(())
+}
+
+#[test]
+pub fn test_capability_missing_default_wasm() -> Result<()> {
+    let turing = common_setup_direct(WASM_SCRIPT)?;
+    test_capability_missing_default(turing)
+}
+
+#[test]
+pub fn test_capability_missing_default_lua() -> Result<()> {
+    let turing = common_setup_direct(LUA_SCRIPT)?;
+    test_capability_missing_default(turing)
+}
+
+#[test]
+pub fn test_capability_missing_error_lua() -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+
+    let mut metadata = ScriptFnMetadata::new("analytics".to_owned(), track_event, None);
+    metadata.add_return_type(DataType::I32)?;
+    turing.add_function("track_event", metadata)?;
+
+    let mut turing = turing.build()?;
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+
+    let res = turing.call_fn_by_name("analytics_test", Params::new(), DataType::I32);
+    assert!(
+        matches!(&res, Param::Error(e) if e.contains("Mod capability 'analytics' is not currently loaded")),
+        "unexpected result: {res:?}"
+    );
+
+    turing.add_capability("analytics");
+    let res = turing.call_fn_by_name("analytics_test", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(1));
+
+    turing.remove_capability("analytics");
+    let res = turing.call_fn_by_name("analytics_test", Params::new(), DataType::I32);
+    assert!(matches!(res, Param::Error(_)));
+    Ok(())
+}
+
+fn test_on_script_error(source: &str) -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+
+    let mut metadata = ScriptFnMetadata::new("analytics".to_owned(), track_event, None);
+    metadata.add_return_type(DataType::I32)?;
+    turing.add_function("track_event", metadata)?;
+
+    let mut turing = turing.build()?;
+    setup_test_script(&mut turing, source)?;
+
+    SCRIPT_ERRORS.take();
+    let res = turing.call_fn_by_name("analytics_test", Params::new(), DataType::I32);
+    assert!(matches!(res, Param::Error(_)));
+    let errors = SCRIPT_ERRORS.take();
+    assert_eq!(errors.len(), 1, "unexpected errors: {errors:?}");
+    let (capability, kind, message) = &errors[0];
+    assert_eq!(capability, "analytics");
+    assert_eq!(*kind, ErrorKind::MissingCapability);
+    assert!(message.contains("not currently loaded"));
+
+    // the instance survives, so the host can decide what to do with the mod
+    turing.add_capability("analytics");
+    let res = turing.call_fn_by_name("analytics_test", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(1));
+    assert!(SCRIPT_ERRORS.take().is_empty());
+    Ok(())
+}
+
+/// Only imports `track_event`, the test wasm script needs every function in `common_setup_direct`
+#[cfg(feature = "wasm")]
+const ANALYTICS_WAT: &str = r#"(module
+    (import "env" "track_event" (func $track (result i32)))
+    (memory (export "memory") 1)
+    (func (export "analytics_test") (result i32)
+        call $track))"#;
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_on_script_error_wasm() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("turing_analytics_{}.wasm", std::process::id()));
+    std::fs::write(&path, wat::parse_str(ANALYTICS_WAT)?)?;
+    let res = test_on_script_error(&path.to_string_lossy());
+    std::fs::remove_file(path)?;
+    res
+}
+
+#[test]
+pub fn test_on_script_error_lua() -> Result<()> {
+    test_on_script_error(LUA_SCRIPT)
+}
+
+#[test]
+pub fn test_charge_callback() {
+    use crate::{CallbackBudget, CallbackCharge, EngineDataState};
+    use parking_lot::RwLock;
+
+    let data = RwLock::new(EngineDataState::default());
+    data.write().callback_budgets.insert(
+        "net".to_string(),
+        CallbackBudget {
+            max_calls: 1,
+            used: 0,
+        },
+    );
+
+    // capabilities without a budget are only counted
+    for _ in 0..2 {
+        let charge = EngineDataState::charge_callback(&data, "gfx");
+        assert_eq!(charge, CallbackCharge::Allowed);
+    }
+    assert_eq!(
+        EngineDataState::charge_callback(&data, "net"),
+        CallbackCharge::Allowed
+    );
+    for first in [true, false] {
+        assert_eq!(
+            EngineDataState::charge_callback(&data, "net"),
+            CallbackCharge::OverBudget {
+                max_calls: 1,
+                first
+            }
+        );
+    }
+    assert_eq!(data.read().callback_calls.load(Ordering::Relaxed), 5);
+
+    data.write().begin_invocation();
+    assert_eq!(data.read().callback_calls.load(Ordering::Relaxed), 0);
+    assert_eq!(data.read().callback_budgets["net"].used, 0);
+}
+
+#[test]
+pub fn test_received_strings_capped() {
+    use crate::{EngineDataState, MAX_RECEIVED_STRINGS};
+
+    let mut data = EngineDataState::default();
+    let first = data.receive_string("first".to_string());
+    let second = data.receive_string("second".to_string());
+    for i in 2..MAX_RECEIVED_STRINGS {
+        data.receive_string(i.to_string());
+    }
+    assert_eq!(data.received_strings.len(), MAX_RECEIVED_STRINGS as usize);
+
+    // the oldest string the host never took makes room for the new one
+    let last = data.receive_string("last".to_string());
+    assert_eq!(data.received_strings.len(), MAX_RECEIVED_STRINGS as usize);
+    assert!(!data.received_strings.contains_key(&first));
+    assert_eq!(data.received_strings[&second], "second");
+    assert_eq!(data.received_strings[&last], "last");
+}
+
+fn test_callback_budget(source: &str) -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+
+    let mut metadata = ScriptFnMetadata::new("analytics".to_owned(), track_event, None);
+    metadata.add_return_type(DataType::I32)?;
+    turing.add_function("track_event", metadata)?;
+    turing.set_callback_budget("analytics", 3);
+
+    let mut turing = turing.build()?;
+    setup_test_script(&mut turing, source)?;
+    turing.add_capability("analytics");
+
+    // calls past the budget are skipped, but the script call still finishes
+    SCRIPT_ERRORS.take();
+    let res = turing.call_fn_by_name("analytics_spam_test", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(3));
+    assert_eq!(turing.callback_calls(), 5);
+    let errors = SCRIPT_ERRORS.take();
+    assert_eq!(errors.len(), 1, "unexpected errors: {errors:?}");
+    assert_eq!(errors[0].0, "analytics");
+    assert_eq!(errors[0].1, ErrorKind::BudgetExceeded);
+
+    // every script call starts with a fresh budget
+    let res = turing.call_fn_by_name("analytics_spam_test", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(3));
+    assert_eq!(SCRIPT_ERRORS.take().len(), 1);
+
+    turing.set_callback_budget("analytics", None);
+    let res = turing.call_fn_by_name("analytics_spam_test", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(5));
+    assert!(SCRIPT_ERRORS.take().is_empty());
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_callback_budget_wasm() -> Result<()> {
+    let wat = r#"(module
+        (import "env" "track_event" (func $track (result i32)))
+        (memory (export "memory") 1)
+        (func (export "analytics_spam_test") (result i32)
+            call $track
+            call $track
+            i32.add
+            call $track
+            i32.add
+            call $track
+            i32.add
+            call $track
+            i32.add))"#;
+    let path = std::env::temp_dir().join(format!("turing_budget_{}.wasm", std::process::id()));
+    std::fs::write(&path, wat::parse_str(wat)?)?;
+    let res = test_callback_budget(&path.to_string_lossy());
+    std::fs::remove_file(path)?;
+    res
+}
+
+#[test]
+pub fn test_callback_budget_lua() -> Result<()> {
+    test_callback_budget(LUA_SCRIPT)
+}
+
+fn test_host_metrics(source: &str) -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+
+    let mut metadata = ScriptFnMetadata::new("analytics".to_owned(), track_event, None);
+    metadata.add_return_type(DataType::I32)?;
+    turing.add_function("track_event", metadata)?;
+    turing.set_metrics(true);
+
+    let mut turing = turing.build()?;
+    setup_test_script(&mut turing, source)?;
+    turing.add_capability("analytics");
+
+    for _ in 0..2 {
+        let res = turing.call_fn_by_name("analytics_test", Params::new(), DataType::I32);
+        assert_eq!(res, Param::I32(1));
+    }
+    let metrics = turing.take_metrics();
+    assert_eq!(metrics.len(), 1);
+    let analytics = &metrics["analytics"];
+    assert_eq!(analytics.len(), 1);
+    assert_eq!(analytics[0].0, "track_event");
+    assert_eq!(analytics[0].1.calls, 2);
+    assert!(turing.take_metrics().is_empty());
+
+    turing.set_metrics(false);
+    turing.call_fn_by_name("analytics_test", Params::new(), DataType::I32);
+    assert!(turing.take_metrics().is_empty());
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_host_metrics_wasm() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("turing_metrics_{}.wasm", std::process::id()));
+    std::fs::write(&path, wat::parse_str(ANALYTICS_WAT)?)?;
+    let res = test_host_metrics(&path.to_string_lossy());
+    std::fs::remove_file(path)?;
+    res
+}
+
+#[test]
+pub fn test_host_metrics_lua() -> Result<()> {
+    test_host_metrics(LUA_SCRIPT)
+}
+
+#[test]
+pub fn test_add_rust_function_lua() -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+    let factor = 2.5;
+    turing.add_rust_function("scale", "test", move |(x, label): (f32, String)| {
+        if label.is_empty() {
+            return Err(anyhow::anyhow!("missing label"));
+        }
+        Ok(format!("{label}: {}", x * factor))
+    })?;
+    assert!(
+        turing
+            .add_rust_function("scale", "test", |(): ()| Ok(()))
+            .is_err()
+    );
+
+    let mut turing = turing.build()?;
+    let info = turing
+        .list_functions()
+        .into_iter()
+        .find(|f| f.name == "scale")
+        .unwrap();
+    assert_eq!(info.param_types.len(), 2);
+    assert_eq!(info.param_types[1].data_type, DataType::RustString);
+    assert_eq!(info.return_type[0].0, DataType::ExtString);
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+
+    let params = Params::new().with(2.0f32).with("x");
+    let res = turing.call_fn_by_name("rust_fn_test", params, DataType::ExtString);
+    assert_eq!(res, Param::String("x: 5".to_string()));
+
+    let params = Params::new().with(2.0f32).with("");
+    let res = turing.call_fn_by_name("rust_fn_test", params, DataType::ExtString);
+    assert!(
+        matches!(&res, Param::Error(e) if e.contains("missing label")),
+        "unexpected result: {res:?}"
+    );
+    Ok(())
+}
+
+/// Returns the text color, so the test can see whether the default was passed
+extern "C" fn draw_text(params: FfiParamArray) -> FfiParam {
+    match params.as_params::<DirectExt>().map(|p| p.get(1).cloned()) {
+        Ok(Some(Param::I32(color))) => Param::I32(color),
+        res => Param::Error(format!("unexpected params: {res:?}")),
+    }
+    .to_ext_param()
+}
+
+#[test]
+pub fn test_optional_params_lua() -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+    let mut metadata = ScriptFnMetadata::new("test".to_owned(), draw_text, None);
+    metadata
+        .add_param_type(DataType::RustString, "text")?
+        .add_optional_param_type(DataType::I32, "color", Param::I32(7))?
+        .add_return_type(DataType::I32)?;
+    turing.add_function("draw_text", metadata)?;
+
+    let mut turing = turing.build()?;
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+
+    // 7 for the left out color, 3 passed, 7 for the nil color
+    let res = turing.call_fn_by_name("optional_test", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(17));
+
+    // a required param can't be left out
+    let res = turing.call_fn_by_name("missing_arg_test", Params::new(), DataType::I32);
+    assert!(
+        matches!(&res, Param::Error(e) if e.contains("missing argument #1 'text'")),
+        "unexpected result: {res:?}"
+    );
+    Ok(())
+}
+
+#[test]
+pub fn test_optional_param_order() -> Result<()> {
+    let mut metadata = ScriptFnMetadata::dispatched("test".to_owned(), None);
+    metadata.add_optional_param_type(DataType::I32, "color", Param::I32(7))?;
+    assert!(
+        metadata
+            .add_param_type(DataType::RustString, "text")
+            .is_err()
+    );
+    assert!(
+        metadata
+            .add_optional_param_type(DataType::F32, "scale", Param::I32(1))
+            .is_err()
+    );
+    metadata.add_optional_param_type(
+        DataType::RustString,
+        "font",
+        Param::String("mono".to_string()),
+    )?;
+    assert_eq!(metadata.param_types.len(), 2);
+    Ok(())
+}
+
+#[test]
+pub fn test_rust_fn_callback() -> Result<()> {
+    let call = |metadata: &ScriptFnMetadata, params: Params| {
+        let callback = metadata.rust_callback.as_ref().unwrap();
+        FfiParamArray::from_params_borrowed::<DirectExt, _>(&params, |array| {
+            callback.call(array).into_param::<DirectExt>()
+        })
+    };
+
+    let add = ScriptFnMetadata::from_rust_fn::<DirectExt, _, _>(
+        "test".to_string(),
+        |(a, b): (i32, i32)| Ok(a + b),
+    )?;
+    assert_eq!(call(&add, crate::params![2, 3])?, Param::I32(5));
+    // a wrong or missing argument fails the call instead of reaching the closure
+    assert!(matches!(
+        call(&add, crate::params![2, "3"])?,
+        Param::Error(_)
+    ));
+    assert!(matches!(call(&add, crate::params![2])?, Param::Error(_)));
+
+    let explode = ScriptFnMetadata::from_rust_fn::<DirectExt, _, _>(
+        "test".to_string(),
+        |(): ()| -> Result<()> { panic!("boom") },
+    )?;
+    assert!(explode.return_type.is_empty());
+    let res = call(&explode, Params::new())?;
+    assert!(
+        matches!(&res, Param::Error(e) if e.contains("boom")),
+        "unexpected result: {res:?}"
+    );
+    Ok(())
+}
+
+#[test]
+pub fn test_interior_nul_host_callback_lua() -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+
+    let mut metadata = ScriptFnMetadata::new("test".to_owned(), echo_string, None);
+    metadata.add_param_type(DataType::RustString, "s")?;
+    metadata.add_return_type(DataType::ExtString)?;
+    turing.add_function("echo_string", metadata)?;
+
+    let mut turing = turing.build()?;
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+
+    let mut params = Params::new();
+    params.push(Param::String("abc".to_string()));
+    let res = turing.call_fn_by_name("echo_test", params, DataType::ExtString);
+    assert_eq!(res, Param::String("abc".to_string()));
+
+    let mut params = Params::new();
+    params.push(Param::String("a\0b".to_string()));
+    let res = turing.call_fn_by_name("echo_test", params, DataType::ExtString);
+    assert!(
+        matches!(&res, Param::Error(e) if e.contains(INTERIOR_NUL_MESSAGE)),
+        "unexpected result: {res:?}"
+    );
+    Ok(())
+}
+
+#[test]
+pub fn test_raw_bytes_string_lua() -> Result<()> {
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+
+    // invalid UTF-8 is replaced and embedded nuls are kept until the string crosses into C
+    let res = turing.call_fn_by_name("raw_bytes_test", Params::new(), DataType::RustString);
+    assert_eq!(res, Param::String("\u{fffd}ok\0end".to_string()));
+    assert_eq!(
+        res.to_rs_param().into_param::<DirectExt>()?,
+        Param::Error(INTERIOR_NUL_MESSAGE.to_string())
+    );
+    Ok(())
+}
+
+#[test]
+pub fn test_now_micros_wasm() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    let res = turing.call_fn_by_name("now_micros_test", Params::new(), DataType::U32);
+    assert_eq!(res, Param::U32(1));
+    Ok(())
+}
+
+/// Reads `_host_now_micros` and the wasi clock with id `clock`, in nanoseconds
+#[cfg(feature = "wasm")]
+const CLOCKS_WAT: &str = r#"(module
+    (import "env" "_host_now_micros" (func $now_micros (result i64)))
+    (import "wasi_snapshot_preview1" "clock_time_get"
+        (func $clock_time_get (param i32 i64 i32) (result i32)))
+    (memory (export "memory") 1)
+    (func (export "now_micros_value") (result i64)
+        call $now_micros)
+    (func (export "wasi_clock_value") (param $clock i32) (result i64)
+        (drop (call $clock_time_get (local.get $clock) (i64.const 1) (i32.const 8)))
+        (i64.load (i32.const 8))))"#;
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_deterministic_clocks_wasm() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("turing_clocks_{}.wasm", std::process::id()));
+    std::fs::write(&path, wat::parse_str(CLOCKS_WAT)?)?;
+
+    let mut setup = Turing::<DirectExt>::new();
+    setup.set_deterministic(true);
+    let mut turing = setup.build()?;
+    let res = setup_test_script(&mut turing, &path.to_string_lossy());
+    std::fs::remove_file(path)?;
+    res?;
+
+    turing.set_time(2.5);
+    let res = turing.call_fn_by_name("now_micros_value", Params::new(), DataType::I64);
+    assert_eq!(res, Param::I64(2_500_000));
+    // realtime and monotonic
+    for clock in [0i32, 1] {
+        let res = turing.call_fn_by_name("wasi_clock_value", crate::params![clock], DataType::I64);
+        assert_eq!(res, Param::I64(2_500_000_000));
+    }
+    Ok(())
+}
+
+/// Hands whatever pointer `pass_pointer` gets straight to the `read_str` host function.
+/// The last byte of memory isn't a nul, so a string starting there runs off the end.
+#[cfg(feature = "wasm")]
+const RAW_POINTER_WAT: &str = r#"(module
+    (import "env" "_test_read_str" (func $read_str (param i32)))
+    (memory (export "memory") 1)
+    (data (i32.const 0) "ok\00")
+    (data (i32.const 65535) "x")
+    (func (export "pass_pointer") (param i32)
+        local.get 0
+        call $read_str))"#;
+
+/// Imports one host function with the wrong signature and two that aren't registered
+#[cfg(feature = "wasm")]
+const BAD_IMPORTS_WAT: &str = r#"(module
+    (import "env" "_test_read_str" (func (param i64)))
+    (import "env" "_test_spawn_thing" (func))
+    (import "env" "_test_entity__despawn" (func (param i64)))
+    (memory (export "memory") 1))"#;
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_wasm_import_validation() -> Result<()> {
+    let dir = std::env::temp_dir();
+    let bad = dir.join(format!("turing_bad_imports_{}.wasm", std::process::id()));
+    std::fs::write(&bad, wat::parse_str(BAD_IMPORTS_WAT)?)?;
+    let good = dir.join(format!("turing_good_imports_{}.wasm", std::process::id()));
+    std::fs::write(&good, wat::parse_str(RAW_POINTER_WAT)?)?;
+
+    let mut turing = Turing::<DirectExt>::new();
+    let mut metadata = ScriptFnMetadata::new("test".to_owned(), log_info_wasm, None);
+    metadata.add_param_type(DataType::RustString, "msg")?;
+    turing.add_function("read_str", metadata.clone())?;
+    turing.add_function("log::info", metadata)?;
+    let mut turing = turing.build()?;
+
+    // every problem is reported at once
+    let err = format!(
+        "{:#}",
+        turing
+            .load_script(bad.to_string_lossy(), &["test"])
+            .unwrap_err()
+    );
+    assert!(err.contains(
+        "env::_test_read_str (`read_str`) is imported as (i64) -> () but registered as (i32) -> ()"
+    ));
+    assert!(err.contains("env::_test_spawn_thing is missing, was `spawnThing` registered?"));
+    assert!(
+        err.contains("env::_test_entity__despawn is missing, was `Entity.despawn` registered?")
+    );
+
+    // registered functions the module doesn't import are only warned about
+    LEVEL_LOGS.take();
+    turing.load_script(good.to_string_lossy(), &["test"])?;
+    let logs = LEVEL_LOGS.take();
+    assert!(logs.iter().any(|(level, msg)| {
+        *level == LogLevel::Warn
+            && msg
+                .contains("doesn't import these host functions: `log::info` (env::_test_log__info)")
+    }));
+
+    std::fs::remove_file(bad)?;
+    std::fs::remove_file(good)?;
+    Ok(())
+}
+
+#[test]
+pub fn test_import_name_mapping() -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+    for (name, capability) in [
+        ("HTTPRequest::sendJSON", "net"),
+        ("Vec2Utils::lerp2D", "math"),
+        ("Entity.getID", "test"),
+        ("getHP", "test"),
+    ] {
+        let metadata = ScriptFnMetadata::new(capability.to_owned(), log_info_wasm, None);
+        turing.add_function(name, metadata)?;
+    }
+    let mut metadata = ScriptFnMetadata::new("math".to_owned(), log_info_wasm, None);
+    metadata.with_explicit_symbol("_math_vec2_utils__lerp2d");
+    turing.add_function("Vec2Utils.lerp2D", metadata)?;
+    let turing = turing.build()?;
+
+    for (name, symbol) in [
+        ("HTTPRequest::sendJSON", "_net_http_request__send_json"),
+        ("Vec2Utils::lerp2D", "_math_vec_2_utils__lerp_2_d"),
+        ("Entity.getID", "_test_entity__get_id"),
+        ("getHP", "_test_get_hp"),
+        ("Vec2Utils.lerp2D", "_math_vec2_utils__lerp2d"),
+    ] {
+        assert_eq!(turing.resolve_import_name(name).as_deref(), Some(symbol));
+        assert_eq!(turing.import_host_name(symbol).as_deref(), Some(name));
+    }
+    assert_eq!(turing.resolve_import_name("sendJSON"), None);
+    assert_eq!(
+        turing.import_host_name("_net_h_t_t_p_request__send_j_s_o_n"),
+        None
+    );
+    Ok(())
+}
+
+/// Imports `HTTPRequest::sendJSON` the way a binding generator splitting every capital would
+#[cfg(feature = "wasm")]
+const SPLIT_CAPITALS_WAT: &str = r#"(module
+    (import "env" "_net_h_t_t_p_request__send_j_s_o_n" (func))
+    (memory (export "memory") 1))"#;
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_import_name_mismatch_wasm() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("turing_split_caps_{}.wasm", std::process::id()));
+    std::fs::write(&path, wat::parse_str(SPLIT_CAPITALS_WAT)?)?;
+    let send_json = || ScriptFnMetadata::new("net".to_owned(), log_info_wasm, None);
+
+    // the diagnostic points at the function bound under the other conversion
+    let mut turing = Turing::<DirectExt>::new();
+    turing.add_function("HTTPRequest::sendJSON", send_json())?;
+    let mut turing = turing.build()?;
+    let err = format!(
+        "{:#}",
+        turing
+            .load_script(path.to_string_lossy(), &["net"])
+            .unwrap_err()
+    );
+    assert!(
+        err.contains(
+            "`HTTPRequest::sendJSON` is bound as env::_net_http_request__send_json instead"
+        )
+    );
+
+    let mut turing = Turing::<DirectExt>::new();
+    let mut metadata = send_json();
+    metadata.with_explicit_symbol("_net_h_t_t_p_request__send_j_s_o_n");
+    turing.add_function("HTTPRequest::sendJSON", metadata)?;
+    let mut turing = turing.build()?;
+    turing.load_script(path.to_string_lossy(), &["net"])?;
+
+    // two names converting to the same symbol can't both be bound
+    let mut turing = Turing::<DirectExt>::new();
+    turing.add_function("HTTPRequest::sendJSON", send_json())?;
+    turing.add_function("http_request::send_json", send_json())?;
+    let mut turing = turing.build()?;
+    let err = format!(
+        "{:#}",
+        turing
+            .load_script(path.to_string_lossy(), &["net"])
+            .unwrap_err()
+    );
+    assert!(err.contains("are both imported as env::_net_http_request__send_json"));
+
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_host_string_bad_pointers_wasm() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("turing_raw_pointer_{}.wasm", std::process::id()));
+    std::fs::write(&path, wat::parse_str(RAW_POINTER_WAT)?)?;
+
+    let mut turing = Turing::<DirectExt>::new();
+    let mut metadata = ScriptFnMetadata::new("test".to_owned(), log_info_wasm, None);
+    metadata.add_param_type(DataType::RustString, "msg")?;
+    turing.add_function("read_str", metadata)?;
+    let mut turing = turing.build()?;
+    turing.load_script(path.to_string_lossy(), &["test"])?;
+
+    let mut pass_pointer = |ptr: u32| {
+        let mut params = Params::new();
+        params.push(Param::I32(ptr as i32));
+        turing.call_fn_by_name("pass_pointer", params, DataType::Void)
+    };
+    assert_eq!(pass_pointer(0), Param::Void);
+    // data_size - 1, data_size and u32::MAX error without reaching the host or panicking
+    for ptr in [u16::MAX as u32, u16::MAX as u32 + 1, u32::MAX] {
+        assert!(
+            matches!(pass_pointer(ptr), Param::Error(_)),
+            "pointer {ptr} was read"
+        );
+    }
+    // the instance is still usable afterwards
+    assert_eq!(pass_pointer(1), Param::Void);
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+fn wasm_engine(turing: &Turing<DirectExt>) -> &WasmInterpreter<DirectExt> {
+    match &turing.engine {
+        Some(Engine::Wasm(wasm)) => wasm,
+        _ => panic!("expected a wasm engine"),
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_typed_call_math_ops_wasm() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    let key = turing
+        .get_fn_key("math_ops_test")
+        .expect("math_ops_test not found");
+
+    // (f32, f32) -> f32 is cached as a typed call
+    assert!(wasm_engine(&turing).has_typed_call(key, DataType::F32));
+
+    for i in 0..1000 {
+        let mut params = Params::new();
+        params.push(Param::F32(i as f32));
+        params.push(Param::F32(0.5));
+        let res = turing.call_fn(key, params, DataType::F32);
+        assert_eq!(res, Param::F32(i as f32 * 0.5));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_typed_call_coverage_wasm() -> Result<()> {
+    let wasm = wat::parse_str(
+        r#"(module
+            (memory (export "memory") 1)
+            (func (export "uniform") (param i32 i32 i32) (result i32) local.get 2)
+            (func (export "mixed") (param i32 f32 i32) (result i32) local.get 2))"#,
+    )?;
+    let mut turing = Turing::<DirectExt>::new().build()?;
+    turing.load_script_bytes(&wasm, EngineKind::Wasm, &["test"])?;
+    let uniform = turing.get_fn_key("uniform").expect("uniform not found");
+    let mixed = turing.get_fn_key("mixed").expect("mixed not found");
+
+    // 3 params are only covered when they share a type, the rest take the dynamic call
+    assert!(wasm_engine(&turing).has_typed_call(uniform, DataType::I32));
+    assert!(!wasm_engine(&turing).has_typed_call(mixed, DataType::I32));
+    let res = turing.call_fn(
+        mixed,
+        crate::params![Param::I32(1), Param::F32(2.0), Param::I32(3)],
+        DataType::I32,
+    );
+    assert_eq!(res, Param::I32(3));
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_typed_call_return_mismatch_wasm() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    let key = turing
+        .get_fn_key("flags_test")
+        .expect("flags_test not found");
+    let call = |turing: &mut Turing<DirectExt>, ret_type| {
+        let mut params = Params::new();
+        params.push(Param::U32(0b101));
+        turing.call_fn(key, params, ret_type)
+    };
+
+    // every i32-backed return type takes the typed path and converts like a dynamic call
+    let engine = wasm_engine(&turing);
+    assert!(engine.has_typed_call(key, DataType::U32));
+    assert!(engine.has_typed_call(key, DataType::Bool));
+    assert_eq!(call(&mut turing, DataType::U32), Param::U32(0b101));
+    assert_eq!(call(&mut turing, DataType::Bool), Param::Bool(true));
+    assert_eq!(call(&mut turing, DataType::Flags), Param::Flags(0b101));
+
+    // an f32 expectation can't use the (i32) -> i32 entry, and is rejected before calling
+    assert!(!wasm_engine(&turing).has_typed_call(key, DataType::F32));
+    assert!(matches!(
+        call(&mut turing, DataType::F32),
+        Param::Error(e) if e.contains(TYPE_MISMATCH_MESSAGE)
+    ));
+    Ok(())
+}
+
+#[test]
+pub fn test_export_signature_wasm() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    let signature = turing
+        .export_signature("math_ops_test")
+        .expect("math_ops_test not found");
+    assert_eq!(signature.to_string(), "(f32, f32) -> f32");
+    assert_eq!(turing.export_signature("missing"), None);
+
+    let mut params = Params::new();
+    params.push(Param::F32(1.0));
+    params.push(Param::F32(2.0));
+    let res = turing.call_fn_by_name("math_ops_test", params, DataType::I32);
+    let Param::Error(e) = res else {
+        panic!("expected a type mismatch, got {res:?}");
+    };
+    assert!(e.contains(TYPE_MISMATCH_MESSAGE));
+    assert!(e.contains("(f32, f32) -> f32") && e.contains("2 args expecting I32"));
+
+    let mut params = Params::new();
+    params.push(Param::F32(1.0));
+    let res = turing.call_fn_by_name("math_ops_test", params, DataType::F32);
+    assert!(matches!(res, Param::Error(e) if e.contains("1 args")));
+    Ok(())
+}
+
+#[test]
+pub fn test_export_signature_lua() -> Result<()> {
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+    let signature = turing
+        .export_signature("math_ops_test")
+        .expect("math_ops_test not found");
+    assert_eq!(signature, ExportSignature::default());
+    assert_eq!(signature.to_string(), "(?) -> ?");
+    assert_eq!(turing.export_signature("missing"), None);
+    Ok(())
+}
+
+#[test]
+pub fn test_export_signature_check_call() {
+    let signature = ExportSignature {
+        params: Some(vec![ScriptValType::I32, ScriptValType::F64]),
+        results: Some(vec![ScriptValType::I64]),
+    };
+    assert_eq!(signature.to_string(), "(i32, f64) -> i64");
+    assert!(signature.check_call("f", 2, DataType::U64).is_ok());
+    assert!(signature.check_call("f", 2, DataType::Object).is_ok());
+    assert!(signature.check_call("f", 2, DataType::I32).is_err());
+    assert!(signature.check_call("f", 2, DataType::Void).is_err());
+    assert!(signature.check_call("f", 1, DataType::I64).is_err());
+
+    // strings and buffers come back as i32 pointers, void is no results
+    let signature = ExportSignature {
+        params: Some(Vec::new()),
+        results: Some(Vec::new()),
+    };
+    assert_eq!(signature.to_string(), "() -> ()");
+    assert!(signature.check_call("f", 0, DataType::Void).is_ok());
+    assert!(signature.check_call("f", 0, DataType::RustString).is_err());
+
+    // unknown parts are never checked
+    let err = ExportSignature {
+        params: None,
+        results: Some(vec![ScriptValType::I32]),
+    }
+    .check_call("g", 5, DataType::F32)
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "{TYPE_MISMATCH_MESSAGE}: 'g' is exported as (?) -> i32, but was called with 5 args expecting F32"
+        )
+    );
+}
+
+#[test]
+pub fn test_now_micros_lua() -> Result<()> {
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+    let res = turing.call_fn_by_name("now_micros_test", Params::new(), DataType::Bool);
+    assert_eq!(res, Param::Bool(true));
+    Ok(())
+}
+
+#[test]
+pub fn test_deterministic_clocks_lua() -> Result<()> {
+    let mut setup = Turing::<DirectExt>::new();
+    setup.set_deterministic(true);
+    let mut turing = setup.build()?;
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+
+    turing.set_time(2.5);
+    let res = turing.call_fn_by_name("now_micros_value", Params::new(), DataType::I64);
+    assert_eq!(res, Param::I64(2_500_000));
+    Ok(())
+}
+
+#[test]
+pub fn test_clone_object() -> Result<()> {
+    let turing = Turing::<DirectExt>::new().build()?;
+
+    let clone = turing.clone_object(ObjectId::new(7));
+    assert_eq!(clone, Some(ObjectId::new(1007)));
+    assert_eq!(OBJECT_CLONES.get(), 1);
+
+    let param = Param::Object(ObjectId::new(8)).deep_clone_object::<DirectExt>();
+    assert_eq!(param, Param::Object(ObjectId::new(1008)));
+    assert_eq!(OBJECT_CLONES.get(), 2);
+
+    // non-objects are plain clones and never reach the host
+    assert_eq!(turing.clone_object(ObjectId::null()), None);
+    let param = Param::I32(3).deep_clone_object::<DirectExt>();
+    assert_eq!(param, Param::I32(3));
+    assert_eq!(OBJECT_CLONES.get(), 2);
+    Ok(())
+}
+
+#[test]
+pub fn test_log_prefix_wasm() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    turing.set_log_prefix(Some("wasm_mod".to_string()));
+    setup_test_script(&mut turing, WASM_SCRIPT)?;
+
+    let res = turing.call_fn_by_name("stdout_test", Params::new(), DataType::Void);
+    assert_eq!(res, Param::Void);
+    LOGGED.with_borrow(|logs| {
+        assert!(logs.iter().any(|l| l == "[wasm_mod] hello from stdout"));
+    });
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_wasm_unfinished_line_flushed_on_unload() -> Result<()> {
+    // writes "tail" to stdout without a newline, the iovec at 0 points at the text at 16
+    let wasm = wat::parse_str(
+        r#"(module
+            (import "wasi_snapshot_preview1" "fd_write"
+                (func $fd_write (param i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "\10\00\00\00\04\00\00\00")
+            (data (i32.const 16) "tail")
+            (func (export "write_tail")
+                (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))))"#,
+    )?;
+    let mut turing = Turing::<DirectExt>::new().build()?;
+    turing.load_script_bytes(&wasm, EngineKind::Wasm, &["test"])?;
+
+    LOGGED.take();
+    let res = turing.call_fn_by_name("write_tail", Params::new(), DataType::Void);
+    assert_eq!(res, Param::Void);
+    assert!(LOGGED.with_borrow(|logs| !logs.iter().any(|l| l == "tail")));
+
+    turing.unload_script();
+    assert!(LOGGED.with_borrow(|logs| logs.iter().any(|l| l == "tail")));
+    Ok(())
+}
+
+#[test]
+pub fn test_log_prefix_lua() -> Result<()> {
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+    turing.set_log_prefix(Some("lua_mod".to_string()));
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+
+    let res = turing.call_fn_by_name("panic_test", Params::new(), DataType::Void);
+    assert!(matches!(res, Param::Error(_)));
+    LOGGED.with_borrow(|logs| {
+        assert!(
+            logs.iter()
+                .any(|l| l.starts_with("[lua_mod] Lua function do_panic panicked"))
+        );
+    });
+    Ok(())
+}
+
+fn test_u32_buffer(source: &str) -> Result<()> {
+    let mut turing = common_setup_direct(source)?;
+    let res = turing.call_fn_by_name("buffer_host_test", Params::new(), DataType::U32);
+    assert_eq!(res, Param::U32(10));
+
+    let mut params = Params::new();
+    params.push(Param::U32Buffer(vec![5, 6, 7]));
+    let res = turing.call_fn_by_name("buffer_arg_test", params, DataType::U32);
+    assert_eq!(res, Param::U32(18));
+    Ok(())
+}
+
+#[test]
+pub fn test_u32_buffer_wasm() -> Result<()> {
+    test_u32_buffer(WASM_SCRIPT)
+}
+
+#[test]
+pub fn test_u32_buffer_lua() -> Result<()> {
+    test_u32_buffer(LUA_SCRIPT)
+}
+
+#[test]
+pub fn test_free_u32_buffer_of_type() {
+    let array = Box::leak(vec![1u32, 2, 3].into_boxed_slice()).as_mut_ptr();
+    let mut buf = U32Buffer { size: 3, array };
+    unsafe {
+        FreeableDataType::RustU32Buffer
+            .free_ptr::<DirectExt>(&mut buf as *mut U32Buffer as *mut c_void)
+    };
+}
+
+fn test_f32_buffer(source: &str) -> Result<()> {
+    let mut turing = common_setup_direct(source)?;
+    let res = turing.call_fn_by_name("float_buffer_host_test", Params::new(), DataType::F32);
+    assert_eq!(res, Param::F32(3.0));
+
+    let mut params = Params::new();
+    params.push(Param::F32Buffer(vec![0.25, 0.5, 1.0]));
+    let res = turing.call_fn_by_name("float_buffer_arg_test", params, DataType::F32);
+    assert_eq!(res, Param::F32(1.75));
+
+    let mut params = Params::new();
+    params.push(Param::F32Buffer(Vec::new()));
+    let res = turing.call_fn_by_name("float_buffer_arg_test", params, DataType::F32);
+    assert_eq!(res, Param::F32(0.0));
+    Ok(())
+}
+
+#[test]
+pub fn test_f32_buffer_wasm() -> Result<()> {
+    test_f32_buffer(WASM_SCRIPT)
+}
+
+#[test]
+pub fn test_f32_buffer_lua() -> Result<()> {
+    test_f32_buffer(LUA_SCRIPT)
+}
+
+#[test]
+pub fn test_f32_buffer_ffi_roundtrip() -> Result<()> {
+    let mut params = Params::new();
+    params.push(Param::F32Buffer(vec![1.5, -2.0]));
+    let local = FfiParamArray::from_params_borrowed::<DirectExt, _>(&params, |array| {
+        array.as_params::<DirectExt>()
+    })?;
+    assert_eq!(local.get(0), Some(&Param::F32Buffer(vec![1.5, -2.0])));
+    Ok(())
+}
+
+#[test]
+pub fn test_free_f32_buffer_of_type() {
+    let array = Box::leak(vec![1.0f32, 2.0].into_boxed_slice()).as_mut_ptr();
+    let mut buf = F32Buffer { size: 2, array };
+    unsafe {
+        FreeableDataType::RustF32Buffer
+            .free_ptr::<DirectExt>(&mut buf as *mut F32Buffer as *mut c_void)
+    };
+    assert_eq!(F32_BUFFERS_FREED.get(), 0);
+}
+
+#[test]
+pub fn test_ext_f32_buffer_freed_by_host() -> Result<()> {
+    let ffi = Param::F32Buffer(vec![0.5, 4.0]).to_ext_param();
+    assert_eq!(ffi.type_id, DataType::ExtF32Buffer);
+    assert_eq!(
+        ffi.into_param::<DirectExt>()?,
+        Param::F32Buffer(vec![0.5, 4.0])
+    );
+    assert_eq!(F32_BUFFERS_FREED.get(), 1);
+
+    let mut buf = F32Buffer::from_vec(vec![1.0]);
+    unsafe {
+        FreeableDataType::ExtF32Buffer
+            .free_ptr::<DirectExt>(&mut buf as *mut F32Buffer as *mut c_void)
+    };
+    assert_eq!(F32_BUFFERS_FREED.get(), 2);
+    Ok(())
+}
+
+#[test]
+pub fn test_print_lua() -> Result<()> {
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+    turing.set_log_prefix(Some("lua_mod".to_string()));
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+
+    LEVEL_LOGS.take();
+    let res = turing.call_fn_by_name("print_test", Params::new(), DataType::Void);
+    assert_eq!(res, Param::Void);
+    assert_eq!(
+        LEVEL_LOGS.take(),
+        [
+            (LogLevel::Info, "[lua_mod] a\t1\ttrue".to_string()),
+            (LogLevel::Warn, "[lua_mod] careful!".to_string()),
+        ]
+    );
+    Ok(())
+}
+
+/// `mod_id` is the script's file name, `check_over_quota` checks the result of a write over the quota
+fn test_storage(source: &str, mod_id: &str, check_over_quota: impl Fn(Param)) -> Result<()> {
+    let mut turing = common_setup_direct(source)?;
+    turing.storage_set("other", "runs", "41");
+    turing.storage_set(mod_id, "runs", "1");
+
+    let res = turing.call_fn_by_name("storage_test", Params::new(), DataType::U32);
+    assert_eq!(res, Param::U32(2));
+    assert_eq!(turing.storage_get(mod_id, "runs").as_deref(), Some("2"));
+    // keys of other mods are out of the script's reach
+    assert_eq!(turing.storage_get("other", "runs").as_deref(), Some("41"));
+
+    // storage outlives the script, the host saves and restores it
+    let saved = turing.storage_serialize()?;
+    let mut turing = common_setup_direct(source)?;
+    turing.storage_deserialize(&saved)?;
+    let res = turing.call_fn_by_name("storage_test", Params::new(), DataType::U32);
+    assert_eq!(res, Param::U32(3));
+
+    let res = turing.call_fn_by_name("storage_quota_test", Params::new(), DataType::U32);
+    assert_eq!(res, Param::U32(0));
+    turing.set_storage_quota_bytes(64);
+    turing.storage_deserialize("{}")?;
+    check_over_quota(turing.call_fn_by_name("storage_quota_test", Params::new(), DataType::U32));
+    assert_eq!(turing.storage_get(mod_id, "blob"), None);
+    Ok(())
+}
+
+#[test]
+pub fn test_storage_wasm() -> Result<()> {
+    // wasm gets a status code
+    test_storage(WASM_SCRIPT, "wasm_tests", |res| {
+        assert_eq!(res, Param::U32(1))
+    })
+}
+
+#[test]
+pub fn test_storage_lua() -> Result<()> {
+    // lua gets an error, which ends the call
+    test_storage(LUA_SCRIPT, "lua_test", |res| {
+        assert!(
+            matches!(&res, Param::Error(e) if e.contains(STORAGE_QUOTA_EXCEEDED_MESSAGE)),
+            "unexpected result: {res:?}"
+        )
+    })
+}
+
+fn test_nan_policy(source: &str) -> Result<()> {
+    let mut turing = common_setup_direct(source)?;
+    let mut call = |turing: &mut Turing<DirectExt>| {
+        let mut params = Params::new();
+        params.push(Param::F32(f32::INFINITY));
+        params.push(Param::F32(2.0));
+        turing.call_fn_by_name("math_ops_test", params, DataType::F32)
+    };
+
+    assert_eq!(call(&mut turing), Param::F32(f32::INFINITY));
+    turing.set_nan_policy(NanPolicy::Zero);
+    assert_eq!(call(&mut turing), Param::F32(0.0));
+    turing.set_nan_policy(NanPolicy::Error);
+    assert!(matches!(
+        call(&mut turing),
+        Param::Error(e) if e.contains(NON_FINITE_FLOAT_MESSAGE)
+    ));
+    Ok(())
+}
+
+#[test]
+pub fn test_nan_policy_wasm() -> Result<()> {
+    test_nan_policy(WASM_SCRIPT)
+}
+
+#[test]
+pub fn test_nan_policy_lua() -> Result<()> {
+    test_nan_policy(LUA_SCRIPT)
+}
+
+fn test_queue_call(source: &str) -> Result<()> {
+    let mut turing = common_setup_direct(source)?;
+    let key = turing
+        .get_fn_key("flags_test")
+        .expect("flags_test not found");
+
+    let handle = turing.call_handle();
+
+    let mut params = Params::new();
+    params.push(Param::Flags(3));
+    handle.queue_call(key, params, DataType::Flags);
+    assert!(COMPLETED_CALLS.with_borrow(|calls| calls.is_empty()));
+
+    // the queue is drained once the outer call returns
+    let mut params = Params::new();
+    params.push(Param::Flags(1));
+    let res = turing.call_fn(key, params, DataType::Flags);
+    assert_eq!(res, Param::Flags(1));
+
+    let completed = COMPLETED_CALLS.take();
+    assert_eq!(completed, vec![(key, Param::Flags(3))]);
+
+    // and once a fast call returns
+    let render = turing.register_fast_call("on_render")?;
+    let mut params = Params::new();
+    params.push(Param::Flags(5));
+    handle.queue_call(key, params, DataType::Flags);
+    turing
+        .invoke_fast_call(render, 0.016)
+        .map_err(anyhow::Error::msg)?;
+    assert_eq!(COMPLETED_CALLS.take(), vec![(key, Param::Flags(5))]);
+    Ok(())
+}
+
+#[test]
+pub fn test_queue_call_wasm() -> Result<()> {
+    test_queue_call(WASM_SCRIPT)
+}
+
+#[test]
+pub fn test_queue_call_lua() -> Result<()> {
+    test_queue_call(LUA_SCRIPT)
+}
+
+#[test]
+pub fn test_call_fn_reentrant_lua() -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+
+    let mut metadata = ScriptFnMetadata::new("test".to_owned(), reenter, None);
+    metadata.add_return_type(DataType::Flags)?;
+    turing.add_function("reenter", metadata)?;
+
+    let mut turing = turing.build()?;
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+    let key = turing
+        .get_fn_key("flags_test")
+        .expect("flags_test not found");
+
+    REENTRY_TARGET.set(Some((turing.call_handle(), key)));
+    let res = turing.call_fn_by_name("reenter_test", Params::new(), DataType::Flags);
+    REENTRY_TARGET.set(None);
+
+    assert_eq!(res, Param::Flags(6));
+    Ok(())
+}
+
+#[test]
+pub fn test_call_fn_reentrant_wasm_unsupported() -> Result<()> {
+    let turing = common_setup_direct(WASM_SCRIPT)?;
+    let key = turing
+        .get_fn_key("flags_test")
+        .expect("flags_test not found");
+
+    let mut params = Params::new();
+    params.push(Param::Flags(1));
+    let res = turing
+        .call_handle()
+        .call_fn_reentrant(key, params, DataType::Flags);
+    assert_eq!(res, Param::Error(REENTRY_UNSUPPORTED_MESSAGE.to_string()));
+    Ok(())
+}
+
+fn test_json_wrap(source: &str, json: serde_json::Value) -> Result<()> {
+    let mut turing = common_setup_direct(source)?;
+    let mut params = Params::new();
+    params.push(Param::Json(json.clone()));
+    let res = turing.call_fn_by_name("json_wrap_test", params, DataType::RustJson);
+    assert_eq!(res, Param::Json(serde_json::json!({ "echo": json })));
+    Ok(())
+}
+
+#[test]
+pub fn test_json_wrap_wasm() -> Result<()> {
+    test_json_wrap(
+        WASM_SCRIPT,
+        serde_json::json!({
+            "player": { "name": "\u{3b1}\u{3b2}\u{1f600}", "inventory": [1, 2.5, null] },
+            "empty": null,
+        }),
+    )
+}
+
+#[test]
+pub fn test_json_wrap_lua() -> Result<()> {
+    // lua tables can't hold nil, so null only survives as a missing key
+    test_json_wrap(
+        LUA_SCRIPT,
+        serde_json::json!({
+            "player": { "name": "\u{3b1}\u{3b2}\u{1f600}", "inventory": [1, 2.5, "x"] },
+            "flags": [[true], { "deep": [] }],
+        }),
+    )
+}
+
+#[test]
+pub fn test_json_from_serde() {
+    for json in [
+        serde_json::json!(null),
+        serde_json::json!(-3),
+        serde_json::json!(u64::MAX),
+        serde_json::json!(0.25),
+        serde_json::json!("text"),
+        serde_json::json!({ "a": [1, null] }),
+    ] {
+        assert_eq!(Param::from_serde(json.clone()).to_serde().unwrap(), json);
+    }
+    assert_eq!(Param::from_serde(serde_json::json!(7)), Param::I64(7));
+}
+
+#[test]
+pub fn test_json_ffi_roundtrip() -> Result<()> {
+    let json = serde_json::json!({ "name": "caf\u{e9}\u{0}", "tags": [1, 2.5, null] });
+
+    let ffi = Param::Json(json.clone()).to_rs_param();
+    assert_eq!(ffi.type_id, DataType::RustJson);
+    assert_eq!(ffi.as_param::<DirectExt>()?, Param::Json(json.clone()));
+    assert_eq!(ffi.into_param::<DirectExt>()?, Param::Json(json));
+    Ok(())
+}
+
+#[test]
+pub fn test_ext_json_freed_by_host() -> Result<()> {
+    let json = serde_json::json!({ "hp": [10, 20] });
+
+    let ffi = Param::Json(json.clone()).to_ext_param();
+    assert_eq!(ffi.type_id, DataType::ExtJson);
+    assert_eq!(ffi.as_param::<DirectExt>()?, Param::Json(json.clone()));
+    assert_eq!(STRINGS_FREED.get(), 0);
+    // a host-allocated json string goes back through the host's free_string
+    assert_eq!(ffi.into_param::<DirectExt>()?, Param::Json(json));
+    assert_eq!(STRINGS_FREED.get(), 1);
+    Ok(())
+}
+
+#[test]
+pub fn test_ffi_param_array_borrowed() -> Result<()> {
+    let mut params = Params::new();
+    params.push(Param::String("borrowed".to_string()));
+
+    let res =
+        FfiParamArray::from_params_borrowed::<DirectExt, _>(&params, |array| echo_string(array));
+    assert_eq!(
+        res.into_param::<DirectExt>()?,
+        Param::String("borrowed".to_string())
+    );
+
+    // the params are left intact and can be passed again
+    let res = FfiParamArray::from_params_borrowed::<DirectExt, _>(&params, |array| {
+        assert_eq!(array.len(), 1);
+        echo_string(array)
+    });
+    assert_eq!(
+        res.into_param::<DirectExt>()?,
+        Param::String("borrowed".to_string())
+    );
+
+    let res = FfiParamArray::from_params_borrowed::<DirectExt, _>(&Params::new(), |array| {
+        echo_string(array)
+    });
+    assert!(matches!(res.into_param::<DirectExt>()?, Param::Error(_)));
+    Ok(())
+}
+
+fn test_host_callback_sees_cancel(source: &str) -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+
+    let metadata = ScriptFnMetadata::new("test".to_owned(), wait_for_cancel, None);
+    turing.add_function("wait_for_cancel", metadata)?;
+
+    let mut turing = turing.build()?;
+    setup_test_script(&mut turing, source)?;
+    let handle = turing.interrupt_handle().expect("script should be loaded");
+
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        handle.interrupt();
+    });
+
+    let res = turing.call_fn_by_name("cancel_test", Params::new(), DataType::Void);
+    interrupter.join().unwrap();
+
+    assert!(
+        SAW_CANCEL.get(),
+        "host callback didn't see the cancel, got {res:?}"
+    );
+    assert_eq!(res, Param::Error(INTERRUPTED_MESSAGE.to_string()));
+    assert!(!is_call_cancelled());
+    Ok(())
+}
+
+/// Spins after `wait_for_cancel` returns, so the interrupt lands back in the script
+#[cfg(feature = "wasm")]
+const CANCEL_WAT: &str = r#"(module
+    (import "env" "wait_for_cancel" (func $wait))
+    (memory (export "memory") 1)
+    (func (export "cancel_test")
+        call $wait
+        (loop $spin (br $spin))))"#;
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_host_callback_sees_cancel_wasm() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("turing_cancel_{}.wasm", std::process::id()));
+    std::fs::write(&path, wat::parse_str(CANCEL_WAT)?)?;
+    let res = test_host_callback_sees_cancel(&path.to_string_lossy());
+    std::fs::remove_file(path)?;
+    res
+}
+
+#[test]
+pub fn test_host_callback_sees_cancel_lua() -> Result<()> {
+    test_host_callback_sees_cancel(LUA_SCRIPT)
+}
+
+fn test_worker_thread(source: &str) -> Result<()> {
+    let turing = common_setup_direct(source)?;
+
+    let worker = thread::spawn(move || {
+        let mut turing = turing;
+        let mut params = Params::new();
+        params.push(Param::Flags(9));
+        turing.call_fn_by_name("flags_test", params, DataType::Flags)
+    });
+    assert_eq!(worker.join().unwrap(), Param::Flags(9));
+    Ok(())
+}
+
+#[test]
+pub fn test_worker_thread_wasm() -> Result<()> {
+    test_worker_thread(WASM_SCRIPT)
+}
+
+#[test]
+pub fn test_worker_thread_lua() -> Result<()> {
+    test_worker_thread(LUA_SCRIPT)
+}
+
+#[test]
+pub fn test_hide_inactive_capabilities_wasm() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    turing.set_hide_inactive_capabilities(true);
+
+    // the module imports track_event, which is now absent instead of trapping when called
+    let err = turing.load_script(WASM_SCRIPT, &["test"]).unwrap_err();
+    assert!(format!("{err:#}").contains("env::_analytics_track_event (`track_event`)"));
+
+    turing.load_script(WASM_SCRIPT, &["test", "analytics"])?;
+    let res = turing.call_fn_by_name("analytics_test", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(1));
+    Ok(())
+}
+
+#[test]
+pub fn test_hide_inactive_capabilities_lua() -> Result<()> {
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+    let res = turing.call_fn_by_name("has_track_event", Params::new(), DataType::Bool);
+    assert_eq!(res, Param::Bool(true));
+
+    turing.set_hide_inactive_capabilities(true);
+    turing.load_script(LUA_SCRIPT, &["test"])?;
+    let res = turing.call_fn_by_name("has_track_event", Params::new(), DataType::Bool);
+    assert_eq!(res, Param::Bool(false));
+
+    turing.load_script(LUA_SCRIPT, &["test", "analytics"])?;
+    let res = turing.call_fn_by_name("has_track_event", Params::new(), DataType::Bool);
+    assert_eq!(res, Param::Bool(true));
+    Ok(())
+}
+
+#[test]
+pub fn test_null_object_lua() -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+
+    let mut metadata = ScriptFnMetadata::new("test".to_owned(), is_null_object, None);
+    metadata.add_param_type(DataType::Object, "obj")?;
+    metadata.add_return_type(DataType::Bool)?;
+    turing.add_function("is_null_object", metadata)?;
+
+    let mut turing = turing.build()?;
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+
+    let mut params = Params::new();
+    params.push(Param::I64(0));
+    let res = turing.call_fn_by_name("find_object", params, DataType::Object);
+    assert_eq!(res, Param::Null);
+
+    let mut params = Params::new();
+    params.push(Param::I64(7));
+    let res = turing.call_fn_by_name("find_object", params, DataType::Object);
+    assert_eq!(res, Param::Object(ObjectId::new(7)));
+
+    let res = turing.call_fn_by_name("null_object_test", Params::new(), DataType::Bool);
+    assert_eq!(res, Param::Bool(true));
+    Ok(())
+}
+
+/// Fresh instances number the same objects the same way, wherever the host's objects live
+#[test]
+pub fn test_sequential_object_ids_lua() -> Result<()> {
+    let first = Box::new(1u64);
+    let second = Box::new(2u64);
+    let first = ObjectId::from_ptr(first.as_ref() as *const u64 as *const c_void);
+    let second = ObjectId::from_ptr(second.as_ref() as *const u64 as *const c_void);
+
+    for _ in 0..2 {
+        let mut turing = Turing::<DirectExt>::new();
+        turing.set_sequential_object_ids(true);
+        let mut turing = turing.build()?;
+        setup_test_script(&mut turing, LUA_SCRIPT)?;
+
+        for (object, id) in [(first, 1), (second, 2), (first, 1)] {
+            let res = turing.call_fn_by_name(
+                "find_object",
+                crate::params![Param::Object(object)],
+                DataType::I64,
+            );
+            assert_eq!(res, Param::I64(id));
+        }
+        // ids the script passes back reach the host as the original objects
+        let res = turing.call_fn_by_name("find_object", crate::params![2i64], DataType::Object);
+        assert_eq!(res, Param::Object(second));
+
+        let res = turing.call_fn_by_name("find_object", crate::params![7i64], DataType::Object);
+        assert!(matches!(res, Param::Error(e) if e.contains(UNKNOWN_OBJECT_MESSAGE)));
+    }
+    Ok(())
+}
+
+#[test]
+pub fn test_variadic_lua() -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+
+    let mut metadata = ScriptFnMetadata::new("test".to_owned(), count_args, None);
+    metadata.set_variadic(true);
+    metadata.add_return_type(DataType::I32)?;
+    turing.add_function("count_args", metadata)?;
+
+    let mut turing = turing.build()?;
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+
+    // called with 0, 1 and 3 args
+    let res = turing.call_fn_by_name("variadic_test", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(13));
+    Ok(())
+}
+
+fn test_dispatch(source: &str) -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+    turing.set_dispatch(dispatch_test);
+
+    let mut metadata = ScriptFnMetadata::dispatched("test".to_owned(), None);
+    metadata.add_param_type(DataType::I32, "x")?;
+    metadata.add_return_type(DataType::I32)?;
+    turing.add_function("dispatched_double", metadata)?;
+
+    let mut turing = turing.build()?;
+    setup_test_script(&mut turing, source)?;
+
+    let mut params = Params::new();
+    params.push(Param::I32(21));
+    let res = turing.call_fn_by_name("dispatch_test", params, DataType::I32);
+    assert_eq!(res, Param::I32(42));
+    Ok(())
+}
+
+/// Only imports `dispatched_double`, which has no callback of its own
+#[cfg(feature = "wasm")]
+const DISPATCH_WAT: &str = r#"(module
+    (import "env" "dispatched_double" (func $double (param i32) (result i32)))
+    (memory (export "memory") 1)
+    (func (export "dispatch_test") (param i32) (result i32)
+        local.get 0
+        call $double))"#;
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_dispatch_wasm() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("turing_dispatch_{}.wasm", std::process::id()));
+    std::fs::write(&path, wat::parse_str(DISPATCH_WAT)?)?;
+    let res = test_dispatch(&path.to_string_lossy());
+    std::fs::remove_file(path)?;
+    res
+}
+
+#[test]
+pub fn test_dispatch_lua() -> Result<()> {
+    test_dispatch(LUA_SCRIPT)
+}
+
+#[test]
+pub fn test_missing_function_lua() -> Result<()> {
+    // without a handler the lookup is nil, so calling it fails
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+    let res = turing.call_fn_by_name("missing_fn_test", Params::new(), DataType::I32);
+    assert!(matches!(res, Param::Error(_)));
+
+    let mut turing = Turing::<DirectExt>::new();
+    turing.set_on_missing_function(on_missing_test);
+    let mut turing = turing.build()?;
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+
+    MISSING_CALLS.take();
+    let res = turing.call_fn_by_name("missing_fn_test", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(7));
+    assert_eq!(
+        MISSING_CALLS.take(),
+        [("foo".to_string(), vec![Param::I64(1)])]
+    );
+    Ok(())
+}
+
+#[test]
+pub fn test_engine_kind() -> Result<()> {
+    let turing = Turing::<DirectExt>::new().build()?;
+    assert!(!turing.is_loaded());
+    assert_eq!(turing.engine_kind(), None);
+
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    assert!(turing.is_loaded());
+    assert_eq!(turing.engine_kind(), Some(EngineKind::Wasm));
+
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+    assert!(turing.is_loaded());
+    assert_eq!(turing.engine_kind(), Some(EngineKind::Lua));
+    Ok(())
+}
+
+fn test_load_script_bytes(source: &str, kind: EngineKind) -> Result<()> {
+    let mut turing = common_setup_direct(source)?;
+    let bytes = std::fs::read(source)?;
+    turing.load_script_bytes(&bytes, kind, &["test"])?;
+    assert_eq!(turing.engine_kind(), Some(kind));
+
+    let res = turing.call_fn_by_name("loaded_test", Params::new(), DataType::Bool);
+    assert_eq!(res, Param::Bool(true));
+    test_math(turing)
+}
+
+#[test]
+pub fn test_load_script_bytes_wasm() -> Result<()> {
+    test_load_script_bytes(WASM_SCRIPT, EngineKind::Wasm)
+}
+
+#[test]
+pub fn test_load_script_bytes_lua() -> Result<()> {
+    test_load_script_bytes(LUA_SCRIPT, EngineKind::Lua)
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_module_cache() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    assert_eq!(turing.module_cache().compilations(), 1);
+
+    // reloading the same bytes only instantiates the cached module
+    setup_test_script(&mut turing, WASM_SCRIPT)?;
+    let bytes = std::fs::read(WASM_SCRIPT)?;
+    turing.load_script_bytes(&bytes, EngineKind::Wasm, &["test"])?;
+    assert_eq!(turing.module_cache().compilations(), 1);
+    assert_eq!(turing.module_cache().len(), 1);
+    test_math(turing)?;
+
+    // a cache dir lets another instance skip compiling too
+    let wasm = wat::parse_str(
+        r#"(module
+            (memory (export "memory") 1)
+            (func (export "answer") (result i32) i32.const 42))"#,
+    )?;
+    let dir = std::env::temp_dir().join(format!("turing_module_cache_{}", std::process::id()));
+    for expected_compilations in [1, 0] {
+        let mut turing = Turing::<DirectExt>::new().build()?;
+        turing.module_cache_mut().set_dir(Some(dir.clone()));
+        turing.load_script_bytes(&wasm, EngineKind::Wasm, &["test"])?;
+        assert_eq!(turing.module_cache().compilations(), expected_compilations);
+        let res = turing.call_fn_by_name("answer", Params::new(), DataType::I32);
+        assert_eq!(res, Param::I32(42));
+    }
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_call_stats() -> Result<()> {
+    let wasm = wat::parse_str(
+        r#"(module
+            (memory (export "memory") 1)
+            (func (export "answer") (result i32) i32.const 42)
+            (func (export "on_update") (param f32)))"#,
+    )?;
+    let mut setup = Turing::<DirectExt>::new();
+    setup.set_profiling(true);
+    let mut turing = setup.build()?;
+    turing.load_script_bytes(&wasm, EngineKind::Wasm, &["test"])?;
+
+    for _ in 0..3 {
+        let res = turing.call_fn_by_name("answer", Params::new(), DataType::I32);
+        assert_eq!(res, Param::I32(42));
+    }
+    turing.fast_call_update(0.016).unwrap();
+    // not exported, so nothing is recorded
+    turing.fast_call_fixed_update(0.016).unwrap();
+
+    let mut stats = turing.take_stats();
+    stats.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let calls = stats
+        .iter()
+        .map(|(name, stat)| (name.as_str(), stat.calls))
+        .collect::<Vec<_>>();
+    assert_eq!(calls, vec![("answer", 3), ("on_update", 1)]);
+    assert!(stats.iter().all(|(_, stat)| stat.max <= stat.total));
+    assert!(turing.take_stats().is_empty());
+
+    turing.set_profiling(false);
+    turing.call_fn_by_name("answer", Params::new(), DataType::I32);
+    assert!(turing.take_stats().is_empty());
+    Ok(())
+}
+
+#[test]
+pub fn test_load_script_bytes_wrong_kind() -> Result<()> {
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+    let lua = std::fs::read(LUA_SCRIPT)?;
+    assert!(
+        turing
+            .load_script_bytes(&lua, EngineKind::Wasm, &["test"])
+            .is_err()
+    );
+    assert!(!turing.is_loaded());
+
+    let wasm = std::fs::read(WASM_SCRIPT)?;
+    assert!(
+        turing
+            .load_script_bytes(&wasm, EngineKind::Lua, &["test"])
+            .is_err()
+    );
+    assert!(!turing.is_loaded());
+    Ok(())
+}
+
+#[test]
+pub fn test_fn_keys_across_engines() -> Result<()> {
+    let turing = Turing::<DirectExt>::new().build()?;
+    assert_eq!(turing.get_fn_key("math_ops_test"), None);
+
+    for script in [WASM_SCRIPT, LUA_SCRIPT] {
+        let mut turing = common_setup_direct(script)?;
+        assert_eq!(turing.get_fn_key("not_exported"), None);
+
+        let key = turing
+            .get_fn_key("math_ops_test")
+            .expect("math_ops_test not found");
+        let mut params = Params::new();
+        params.push(Param::F32(2.0));
+        params.push(Param::F32(3.0));
+        assert_eq!(turing.call_fn(key, params, DataType::F32), Param::F32(6.0));
+
+        let res = turing.call_fn(ScriptFnKey::new(10_000), Params::new(), DataType::Void);
+        assert_eq!(res, Param::Error(FUNCTION_NOT_FOUND_MESSAGE.to_string()));
+        let res = turing.call_fn(ScriptFnKey::new(u32::MAX), Params::new(), DataType::Void);
+        assert_eq!(res, Param::Error(FUNCTION_NOT_FOUND_MESSAGE.to_string()));
+    }
+    Ok(())
+}
+
+#[test]
+pub fn test_host_callback_takes_data_lock_lua() -> Result<()> {
+    let mut turing = Turing::<DirectExt>::new();
+
+    let mut metadata = ScriptFnMetadata::new("test".to_owned(), queue_flags, None);
+    metadata.add_param_type(DataType::RustString, "msg")?;
+    turing.add_function("queue_flags", metadata)?;
+
+    let mut turing = turing.build()?;
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+    let key = turing
+        .get_fn_key("flags_test")
+        .expect("flags_test not found");
+
+    REENTRY_TARGET.set(Some((turing.call_handle(), key)));
+    let res = turing.call_fn_by_name("queue_from_host_test", Params::new(), DataType::Void);
+    REENTRY_TARGET.set(None);
+
+    assert_eq!(res, Param::Void);
+    assert_eq!(COMPLETED_CALLS.take(), vec![(key, Param::Flags(4))]);
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_numeric_args_reuse_scratch() -> Result<()> {
+    use crate::EngineDataState;
+    use parking_lot::RwLock;
+
+    let data = Arc::new(RwLock::new(EngineDataState::default()));
+    let mut args = Vec::new();
+    let mut buffer = None;
+    let mut params = Params::of_size(2);
+    for i in 0..1000 {
+        params.push(Param::F32(i as f32));
+        params.push(Param::I64(i));
+        params.drain_into_wasm_args(&data, &mut args)?;
+        assert_eq!(args.len(), 2);
+        assert!(params.is_empty());
+        // the first call sizes the buffer, later ones reuse it
+        assert_eq!(*buffer.get_or_insert(args.as_ptr()), args.as_ptr());
+    }
+    let data = data.read();
+    assert!(data.str_cache.is_empty());
+    assert!(data.f32_queue.is_empty());
+
+    // the same holds for numeric calls through the interpreter
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    let key = turing
+        .get_fn_key("math_ops_test")
+        .expect("math_ops_test should be exported");
+    let mut params = Params::of_size(2);
+    let capacity = params.capacity();
+    for _ in 0..1000 {
+        params.push(Param::F32(3.5));
+        params.push(Param::F32(5.0));
+        let res = turing.call_fn_draining(key, &mut params, DataType::F32);
+        assert_eq!(res, Param::F32(17.5));
+        assert!(params.is_empty());
+    }
+    assert_eq!(params.capacity(), capacity);
+    assert!(turing.data.read().str_cache.is_empty());
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_str_cache_concurrent_conversions() {
+    use crate::EngineDataState;
+    use parking_lot::RwLock;
+
+    const THREADS: usize = 8;
+    const PUSHES: usize = 1000;
+
+    let data = Arc::new(RwLock::new(EngineDataState::default()));
+    let threads = (0..THREADS)
+        .map(|t| {
+            let data = Arc::clone(&data);
+            thread::spawn(move || {
+                for i in 0..PUSHES {
+                    let mut params = Params::new();
+                    params.push(Param::String(format!("{t}:{i}")));
+                    params.push(Param::Vec2(Vec2::new(1.0, 2.0)));
+                    params.to_wasm_args(&data).unwrap();
+                    assert!(data.read().active_capabilities.is_empty());
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    let data = data.read();
+    assert_eq!(data.str_cache.len(), THREADS * PUSHES);
+    assert_eq!(data.f32_queue.len(), THREADS * PUSHES * 2);
+    // every thread's strings are queued in the order it pushed them
+    for t in 0..THREADS {
+        let prefix = format!("{t}:");
+        let queued = data
+            .str_cache
+            .iter()
+            .filter(|s| s.starts_with(&prefix))
+            .collect::<Vec<_>>();
+        assert_eq!(queued.len(), PUSHES);
+        assert!(
+            queued
+                .iter()
+                .enumerate()
+                .all(|(i, s)| **s == format!("{t}:{i}"))
+        );
+    }
+}
+
+#[test]
+pub fn test_param_display_redacts_objects() {
+    let id = 0x7ffd_1234_5678u64;
+    let object = ObjectId::new(id);
+    let param = Param::Object(object);
+    let shown = param.to_string();
+    assert_eq!(shown, format!("Object({object})"));
+    assert!(!shown.contains(&id.to_string()));
+    assert!(!shown.contains(&format!("{id:x}")));
+    assert!(!shown.contains("0x"));
+    // stable within a session, so logs can still tell objects apart
+    assert_eq!(object.to_string(), ObjectId::new(id).to_string());
+    assert_ne!(object.to_string(), ObjectId::new(id + 8).to_string());
+    assert_eq!(Param::Null.to_string(), "null");
+
+    let mut params = Params::new();
+    params.push(Param::I32(-3));
+    params.push(Param::Bool(true));
+    params.push(param);
+    params.push(Param::F32(1.5));
+    let shown = params.to_string();
+    assert_eq!(shown, format!("(-3, true, Object({object}), 1.5)"));
+    assert!(!shown.contains(&id.to_string()));
+}
+
+#[test]
+pub fn test_param_display_truncates_strings() {
+    let long = "a".repeat(100);
+    let shown = Param::String(long.clone()).to_string();
+    assert_eq!(shown, format!("\"{}\"... (36 more chars)", "a".repeat(64)));
+
+    assert_eq!(
+        format!("{:.4}", Param::String(long)),
+        "\"aaaa\"... (96 more chars)"
+    );
+    assert_eq!(Param::String("short".to_string()).to_string(), "\"short\"");
+
+    let mut params = Params::new();
+    params.push(Param::Error("something broke".to_string()));
+    params.push(Param::U64(7));
+    assert_eq!(
+        format!("{params:.9}"),
+        "(Error(\"something\"... (6 more chars)), 7)"
+    );
+}
+
+#[test]
+pub fn test_to_serde_non_finite() {
+    for param in [
+        Param::F64(f64::NAN),
+        Param::F32(f32::INFINITY),
+        Param::Vec2(Vec2::new(1.0, f32::NEG_INFINITY)),
+    ] {
+        let err = param.to_serde().unwrap_err();
+        assert!(err.to_string().contains(NON_FINITE_FLOAT_MESSAGE), "{err}");
+    }
+
+    assert_eq!(Param::F64(1.5).to_serde().unwrap(), serde_json::json!(1.5));
+    assert_eq!(
+        Param::Vec2(Vec2::new(1.0, 2.0)).to_serde().unwrap(),
+        serde_json::json!([1.0, 2.0])
+    );
+    assert!(Param::Object(ObjectId::new(1)).to_serde().is_err());
+}
+
+#[test]
+pub fn test_apply_nan_policy() {
+    let nan = Param::F32(f32::NAN);
+    assert!(matches!(
+        nan.clone().apply_nan_policy(NanPolicy::Passthrough),
+        Param::F32(x) if x.is_nan()
+    ));
+    assert_eq!(
+        Param::F64(f64::INFINITY).apply_nan_policy(NanPolicy::Zero),
+        Param::F64(0.0)
+    );
+    assert!(matches!(
+        nan.apply_nan_policy(NanPolicy::Error),
+        Param::Error(e) if e.contains(NON_FINITE_FLOAT_MESSAGE)
+    ));
+    // finite floats and other params are never touched
+    assert_eq!(
+        Param::F32(2.5).apply_nan_policy(NanPolicy::Error),
+        Param::F32(2.5)
+    );
+    assert_eq!(
+        Param::Error("e".to_string()).apply_nan_policy(NanPolicy::Zero),
+        Param::Error("e".to_string())
+    );
+    // buffers zero only their non-finite elements
+    let buf = Param::F32Buffer(vec![1.0, f32::NAN, f32::NEG_INFINITY]);
+    assert_eq!(
+        buf.clone().apply_nan_policy(NanPolicy::Zero),
+        Param::F32Buffer(vec![1.0, 0.0, 0.0])
+    );
+    assert!(matches!(
+        buf.apply_nan_policy(NanPolicy::Error),
+        Param::Error(e) if e.contains(NON_FINITE_FLOAT_MESSAGE)
+    ));
+}
+
+#[test]
+pub fn test_params_clear_and_truncate() {
+    let mut params = Params::of_size(4);
+    for i in 0..4 {
+        params.push(Param::I32(i));
+    }
+    params.truncate(6);
+    assert_eq!(params.len(), 4);
+    params.truncate(2);
+    assert_eq!(params[..], [Param::I32(0), Param::I32(1)]);
+
+    let capacity = params.capacity();
+    params.clear();
+    assert!(params.is_empty());
+    assert_eq!(params.capacity(), capacity);
+
+    // refilling reuses the same params
+    params.push(Param::String("again".to_string()));
+    assert_eq!(params[..], [Param::String("again".to_string())]);
+}
+
+#[test]
+pub fn test_params_concat() {
+    let mut prefix = Params::new();
+    prefix.push(Param::Object(ObjectId::new(7)));
+
+    let mut args = Params::new();
+    args.push(Param::I32(1));
+    args.push(Param::String("two".to_string()));
+
+    let mut suffix = Params::new();
+    suffix.push(Param::Bool(true));
+    args.extend(suffix);
+    assert_eq!(args.len(), 3);
+
+    let params = prefix.concat(args);
+    assert_eq!(params.len(), 4);
+    assert_eq!(
+        params[..],
+        [
+            Param::Object(ObjectId::new(7)),
+            Param::I32(1),
+            Param::String("two".to_string()),
+            Param::Bool(true),
+        ]
+    );
+}
+
+#[test]
+pub fn test_params_eq_and_same_kind() {
+    let build = || {
+        let mut params = Params::new();
+        params.push(Param::I32(1));
+        params.push(Param::String("two".to_string()));
+        params
+    };
+    assert_eq!(build(), build());
+    let mut longer = build();
+    longer.push(Param::Void);
+    assert_ne!(build(), longer);
+
+    let a = Param::Error("Function not found: foo".to_string());
+    let b = Param::Error("missing export".to_string());
+    assert_ne!(a, b);
+    assert!(a.same_kind(&b));
+    assert!(Param::I32(1).same_kind(&Param::I32(2)));
+    assert!(!a.same_kind(&Param::Void));
+    assert!(!Param::I32(1).same_kind(&Param::I64(1)));
+}
+
+#[test]
+pub fn test_param_from() {
+    assert_eq!(Param::from(-1i8), Param::I8(-1));
+    assert_eq!(Param::from(-2i16), Param::I16(-2));
+    assert_eq!(Param::from(-3i32), Param::I32(-3));
+    assert_eq!(Param::from(-4i64), Param::I64(-4));
+    assert_eq!(Param::from(5u8), Param::U8(5));
+    assert_eq!(Param::from(6u16), Param::U16(6));
+    assert_eq!(Param::from(7u32), Param::U32(7));
+    assert_eq!(Param::from(8u64), Param::U64(8));
+    assert_eq!(Param::from(1.5f32), Param::F32(1.5));
+    assert_eq!(Param::from(2.5f64), Param::F64(2.5));
+    assert_eq!(Param::from(true), Param::Bool(true));
+    assert_eq!(Param::from('x'), Param::Char('x'));
+    assert_eq!(Param::from("str"), Param::String("str".to_string()));
+    assert_eq!(
+        Param::from("string".to_string()),
+        Param::String("string".to_string())
+    );
+    assert_eq!(Param::from(Vec2::ONE), Param::Vec2(Vec2::ONE));
+    assert_eq!(Param::from(Vec3::ONE), Param::Vec3(Vec3::ONE));
+    assert_eq!(Param::from(Vec4::ONE), Param::Vec4(Vec4::ONE));
+    assert_eq!(Param::from(Quat::IDENTITY), Param::Quat(Quat::IDENTITY));
+    assert_eq!(Param::from(Mat4::IDENTITY), Param::Mat4(Mat4::IDENTITY));
+    assert_eq!(
+        Param::from(serde_json::json!({"a": 1})),
+        Param::Json(serde_json::json!({"a": 1}))
+    );
+}
+
+#[test]
+pub fn test_params_builders() -> Result<()> {
+    let mut pushed = Params::new();
+    pushed.push(Param::I32(1));
+    pushed.push(Param::String("hello".to_string()));
+    pushed.push(Param::F32(3.5));
+
+    assert_eq!(crate::params![1i32, "hello", 3.5f32], pushed);
+    assert_eq!(Params::new().with(1i32).with("hello").with(3.5f32), pushed);
+    assert_eq!(pushed.clone().into_iter().collect::<Params>(), pushed);
+    assert!(crate::params![].is_empty());
+
+    assert_eq!(pushed.try_get::<i32>(0)?, 1);
+    assert_eq!(pushed.try_get::<String>(1)?, "hello");
+    assert!(pushed.try_get::<i32>(1).is_err());
+    assert!(pushed.try_get::<f32>(3).is_err());
+    Ok(())
+}
+
+#[test]
+pub fn test_params_accessors() {
+    let params = crate::params![1i32, "hello", 3.5f32];
+    let kinds = params
+        .iter()
+        .map(|p| p.data_type::<RustTypes>())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![DataType::I32, DataType::RustString, DataType::F32]
+    );
+    assert_eq!(params.as_slice().len(), 3);
+    assert_eq!(params[2], Param::F32(3.5));
+    let mut count = 0;
+    for param in &params {
+        assert!(!matches!(param, Param::Void));
+        count += 1;
+    }
+    assert_eq!(count, 3);
+}
+
+#[test]
+pub fn test_free_slice_of_type() {
+    fn boxed_buffers() -> *mut c_void {
+        let buffers = vec![
+            F32Buffer::from_vec(vec![1.0, 2.0]),
+            F32Buffer::from_vec(Vec::new()),
+        ];
+        Box::into_raw(buffers.into_boxed_slice()) as *mut F32Buffer as *mut c_void
+    }
+
+    // leaks and mismatched frees of rust's own values are caught when run under miri
+    let vectors = vec![Vec3::new(1.0, 2.0, 3.0); 4].into_boxed_slice();
+    let ptr = Box::into_raw(vectors) as *mut Vec3 as *mut c_void;
+    unsafe { FreeableDataType::Vec3.free_slice::<DirectExt>(ptr, 4) };
+
+    let strings = vec![c"{}".to_owned().into_raw(), c"[1]".to_owned().into_raw()];
+    let ptr = Box::into_raw(strings.into_boxed_slice()) as *mut *mut c_char as *mut c_void;
+    unsafe { FreeableDataType::RustJson.free_slice::<DirectExt>(ptr, 2) };
+    assert_eq!(STRINGS_FREED.get(), 0);
+
+    // rust's own buffers never reach the host
+    unsafe { FreeableDataType::RustF32Buffer.free_slice::<DirectExt>(boxed_buffers(), 2) };
+    assert_eq!(F32_BUFFERS_FREED.get(), 0);
+
+    // the host's get handed back to it one by one
+    unsafe { FreeableDataType::ExtF32Buffer.free_slice::<DirectExt>(boxed_buffers(), 2) };
+    assert_eq!(F32_BUFFERS_FREED.get(), 2);
+
+    unsafe { FreeableDataType::Vec3.free_slice::<DirectExt>(std::ptr::null_mut(), 4) };
+    assert_eq!(F32_BUFFERS_FREED.get(), 2);
+}
+
+fn draw_randoms(turing: &mut Turing<DirectExt>) -> Result<Vec<i64>> {
+    (0..4)
+        .map(|_| {
+            turing
+                .call_fn_by_name("random_test", Params::new(), DataType::I64)
+                .to_result::<i64>()
+        })
+        .collect()
+}
+
+#[test]
+pub fn test_random_seed_lua() -> Result<()> {
+    let mut setup = Turing::<DirectExt>::new();
+    setup.set_deterministic(true);
+    setup.set_random_seed(42);
+    let mut turing = setup.build()?;
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+    let first = draw_randoms(&mut turing)?;
+
+    // a fresh instance with the same seed draws the same numbers
+    let mut setup = Turing::<DirectExt>::new();
+    setup.set_deterministic(true);
+    setup.set_random_seed(42);
+    let mut turing = setup.build()?;
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+    assert_eq!(draw_randoms(&mut turing)?, first);
+
+    // reseeding restarts the sequence of the loaded script
+    turing.set_random_seed(42)?;
+    assert_eq!(draw_randoms(&mut turing)?, first);
+    turing.set_random_seed(7)?;
+    assert_ne!(draw_randoms(&mut turing)?, first);
+    Ok(())
+}
+
+#[test]
+pub fn test_deterministic_without_seed_lua() -> Result<()> {
+    let mut runs = Vec::new();
+    for _ in 0..2 {
+        let mut setup = Turing::<DirectExt>::new();
+        setup.set_deterministic(true);
+        let mut turing = setup.build()?;
+        setup_test_script(&mut turing, LUA_SCRIPT)?;
+        runs.push(draw_randoms(&mut turing)?);
+    }
+    assert_eq!(runs[0], runs[1]);
+    Ok(())
+}
+
+fn host_rng_run(source: &str) -> Result<(Vec<i32>, f64)> {
+    let mut turing = common_setup_direct(source)?;
+    turing.set_random_seed(99)?;
+    turing.set_time(12.5);
+
+    let draws = (0..8)
+        .map(|_| {
+            turing
+                .call_fn_by_name("random_range_test", Params::new(), DataType::I32)
+                .to_result::<i32>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let random_ok = turing.call_fn_by_name("host_random_test", Params::new(), DataType::Bool);
+    assert_eq!(random_ok, Param::Bool(true));
+    let time = turing
+        .call_fn_by_name("time_test", Params::new(), DataType::F64)
+        .to_result::<f64>()?;
+    Ok((draws, time))
+}
+
+#[test]
+pub fn test_host_time_and_random_match_across_engines() -> Result<()> {
+    let (wasm_draws, wasm_time) = host_rng_run(WASM_SCRIPT)?;
+    let (lua_draws, lua_time) = host_rng_run(LUA_SCRIPT)?;
+
+    assert!(wasm_draws.iter().all(|d| (1..=1000).contains(d)));
+    assert_eq!(wasm_draws, lua_draws);
+    assert_eq!(wasm_time, 12.5);
+    assert_eq!(lua_time, 12.5);
+    Ok(())
+}
+
+fn test_delta_time(turing: &mut Turing<DirectExt>) -> Result<()> {
+    assert_eq!(turing.delta_time(), 0.0);
+    turing.fast_call_update(0.25).unwrap();
+    // fixed updates have their own step and leave it alone
+    turing.fast_call_fixed_update(0.02).unwrap();
+    assert_eq!(turing.delta_time(), 0.25);
+
+    let res = turing.call_fn_by_name("delta_test", Params::new(), DataType::F32);
+    assert_eq!(res, Param::F32(0.25));
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_delta_time_wasm() -> Result<()> {
+    let wasm = wat::parse_str(
+        r#"(module
+            (import "env" "_host_delta_time" (func $delta (result f32)))
+            (memory (export "memory") 1)
+            (func (export "on_update") (param f32))
+            (func (export "delta_test") (result f32) call $delta))"#,
+    )?;
+    let mut turing = Turing::<DirectExt>::new().build()?;
+    turing.load_script_bytes(&wasm, EngineKind::Wasm, &["test"])?;
+    test_delta_time(&mut turing)
+}
+
+#[test]
+pub fn test_delta_time_lua() -> Result<()> {
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+    test_delta_time(&mut turing)
+}
+
+#[cfg(feature = "wasm")]
+extern "C" fn returns_void(_params: FfiParamArray) -> FfiParam {
+    Param::Void.to_ext_param()
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_host_return_mismatch_wasm() -> Result<()> {
+    let wasm = wat::parse_str(
+        r#"(module
+            (import "env" "_test_declared_void" (func $declared_void))
+            (import "env" "_test_declared_i32" (func $declared_i32 (result i32)))
+            (memory (export "memory") 1)
+            (func (export "call_declared_void") call $declared_void)
+            (func (export "call_declared_i32") (result i32) call $declared_i32))"#,
+    )?;
+    let mut turing = Turing::<DirectExt>::new();
+    turing.add_function(
+        "declared_void",
+        ScriptFnMetadata::new("test".to_owned(), track_event, None),
+    )?;
+    let mut metadata = ScriptFnMetadata::new("test".to_owned(), returns_void, None);
+    metadata.add_return_type(DataType::I32)?;
+    turing.add_function("declared_i32", metadata)?;
+    let mut turing = turing.build()?;
+    turing.load_script_bytes(&wasm, EngineKind::Wasm, &["test"])?;
+
+    for (name, ret) in [
+        ("call_declared_void", DataType::Void),
+        ("call_declared_i32", DataType::I32),
+    ] {
+        SCRIPT_ERRORS.take();
+        let res = turing.call_fn_by_name(name, Params::new(), ret);
+        assert!(
+            matches!(&res, Param::Error(e) if e.contains(TYPE_MISMATCH_MESSAGE)),
+            "unexpected result from {name}: {res:?}"
+        );
+        let errors = SCRIPT_ERRORS.take();
+        assert_eq!(errors.len(), 1, "unexpected errors: {errors:?}");
+        assert_eq!(errors[0].1, ErrorKind::InvalidReturn);
+    }
+    Ok(())
+}
+
+/// Packs a generation into the high half of the id the way a versioned handle table would,
+/// so any 32-bit truncation on the way through wasm shows up as a different object.
+#[cfg(feature = "wasm")]
+fn versioned_object_id(version: u32) -> ObjectId {
+    ObjectId::new(((version as u64) << 32) | 3)
+}
+
+#[cfg(feature = "wasm")]
+extern "C" fn versioned_object(params: FfiParamArray) -> FfiParam {
+    let Ok(local) = params.as_params::<DirectExt>() else {
+        return Param::Error("Failed to unpack params".to_string()).to_ext_param();
+    };
+    match local.get(0) {
+        Some(Param::U32(version)) => Param::Object(versioned_object_id(*version)).to_ext_param(),
+        other => Param::Error(format!("Expected U32 version, got {other:?}")).to_ext_param(),
+    }
+}
+
+#[cfg(feature = "wasm")]
+extern "C" fn echo_object(params: FfiParamArray) -> FfiParam {
+    let Ok(local) = params.as_params::<DirectExt>() else {
+        return Param::Error("Failed to unpack params".to_string()).to_ext_param();
+    };
+    match local.get(0) {
+        Some(Param::Object(id)) => Param::Object(*id).to_ext_param(),
+        other => Param::Error(format!("Expected Object, got {other:?}")).to_ext_param(),
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_wasm_object_roundtrip_high_bits() -> Result<()> {
+    let wasm = wat::parse_str(
+        r#"(module
+            (import "env" "_test_versioned_object" (func $versioned (param i32) (result i64)))
+            (import "env" "_test_echo_object" (func $echo (param i64) (result i64)))
+            (memory (export "memory") 1)
+            (func (export "fetch_and_return") (param i32) (result i64)
+                local.get 0
+                call $versioned
+                call $echo)
+            (func (export "identity") (param i64) (result i64) local.get 0))"#,
+    )?;
+    let mut turing = Turing::<DirectExt>::new();
+    let mut metadata = ScriptFnMetadata::new("test".to_owned(), versioned_object, None);
+    metadata.add_param_type(DataType::U32, "version")?;
+    metadata.add_return_type(DataType::Object)?;
+    turing.add_function("versioned_object", metadata)?;
+    let mut metadata = ScriptFnMetadata::new("test".to_owned(), echo_object, None);
+    metadata.add_param_type(DataType::Object, "obj")?;
+    metadata.add_return_type(DataType::Object)?;
+    turing.add_function("echo_object", metadata)?;
+    let mut turing = turing.build()?;
+    turing.load_script_bytes(&wasm, EngineKind::Wasm, &["test"])?;
+
+    // the same slot handed out again and again with its version bumped each time
+    for version in [0, 1, 2, u32::MAX] {
+        let expected = Param::Object(versioned_object_id(version));
+
+        let res = turing.call_fn_by_name(
+            "fetch_and_return",
+            crate::params![Param::U32(version)],
+            DataType::Object,
+        );
+        assert_eq!(res, expected, "host -> guest -> host, version {version}");
+
+        let res = turing.call_fn_by_name(
+            "identity",
+            crate::params![expected.clone()],
+            DataType::Object,
+        );
+        assert_eq!(res, expected, "host -> guest, version {version}");
+    }
+    Ok(())
+}
+
+fn fallible_setup() -> Result<TuringSetup<DirectExt>> {
+    let mut turing = Turing::<DirectExt>::new();
+    let mut metadata = ScriptFnMetadata::from_rust_fn::<DirectExt, _, _>(
+        "test".to_owned(),
+        |(): ()| -> Result<i32> { Err(anyhow::anyhow!("disk is full")) },
+    )?;
+    metadata.set_fallible(true);
+    turing.add_function("always_fails", metadata)?;
+    Ok(turing)
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_fallible_host_fn_wasm() -> Result<()> {
+    let wasm = wat::parse_str(
+        r#"(module
+            (import "env" "_test_always_fails" (func $fails (result i32)))
+            (import "env" "_host_last_error" (func $last_error (result i32)))
+            (import "env" "_host_strcpy" (func $strcpy (param i32 i32)))
+            (memory (export "memory") 1)
+            (func (export "sentinel") (result i32) call $fails)
+            (func (export "fallible_test") (result i32)
+                call $fails
+                drop
+                i32.const 64
+                call $last_error
+                call $strcpy
+                i32.const 64)
+            (func (export "last_error_len") (result i32) call $last_error))"#,
+    )?;
+    let mut turing = fallible_setup()?.build()?;
+    turing.load_script_bytes(&wasm, EngineKind::Wasm, &["test"])?;
+
+    SCRIPT_ERRORS.take();
+    let res = turing.call_fn_by_name("sentinel", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(0));
+    let errors = SCRIPT_ERRORS.take();
+    assert_eq!(errors.len(), 1, "unexpected errors: {errors:?}");
+    assert_eq!(errors[0].1, ErrorKind::HostError);
+
+    let res = turing.call_fn_by_name("fallible_test", Params::new(), DataType::ExtString);
+    assert_eq!(res, Param::String("disk is full".to_string()));
+    // taken by the guest, and cleared when the next script call starts
+    let res = turing.call_fn_by_name("last_error_len", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(0));
+    turing.call_fn_by_name("sentinel", Params::new(), DataType::I32);
+    let res = turing.call_fn_by_name("last_error_len", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(0));
+    Ok(())
+}
+
+#[test]
+pub fn test_lua_return_values() -> Result<()> {
+    let mut turing = common_setup_direct(LUA_SCRIPT)?;
+
+    for name in ["returns_nothing", "returns_nil"] {
+        let res = turing.call_fn_by_name(name, Params::new(), DataType::Void);
+        assert_eq!(res, Param::Void);
+        let res = turing.call_fn_by_name(name, Params::new(), DataType::I32);
+        assert!(
+            matches!(&res, Param::Error(e) if e.contains(TYPE_MISMATCH_MESSAGE) && e.contains(name)),
+            "unexpected result from {name}: {res:?}"
+        );
+    }
+
+    // a wrong-typed return is an error rather than a host panic
+    for ret in [
+        DataType::I32,
+        DataType::F64,
+        DataType::Bool,
+        DataType::ExtU32Buffer,
+        DataType::Object,
+    ] {
+        let res = turing.call_fn_by_name("returns_wrong_type", Params::new(), ret);
+        assert!(
+            matches!(&res, Param::Error(_)),
+            "unexpected result for {ret}: {res:?}"
+        );
+    }
+
+    LEVEL_LOGS.take();
+    let res = turing.call_fn_by_name("returns_two", Params::new(), DataType::I32);
+    assert_eq!(res, Param::I32(1));
+    assert!(
+        LEVEL_LOGS
+            .take()
+            .iter()
+            .any(|(level, msg)| *level == LogLevel::Warn && msg.contains("returned 2 values")),
+    );
+    Ok(())
+}
+
+#[test]
+pub fn test_fallible_host_fn_lua() -> Result<()> {
+    let mut turing = fallible_setup()?.build()?;
+    setup_test_script(&mut turing, LUA_SCRIPT)?;
+
+    // the host error surfaces as a lua error, which ends the call with the host's message
+    SCRIPT_ERRORS.take();
+    let res = turing.call_fn_by_name("fallible_test", Params::new(), DataType::I32);
+    assert!(
+        matches!(&res, Param::Error(e) if e.contains("disk is full")),
+        "unexpected result: {res:?}"
+    );
+    let errors = SCRIPT_ERRORS.take();
+    assert!(
+        errors
+            .iter()
+            .any(|(_, kind, msg)| *kind == ErrorKind::HostError && msg.contains("disk is full")),
+        "unexpected errors: {errors:?}"
+    );
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_wasm_guest_strings() -> Result<()> {
+    // "héllo ✓" at 16, and "abc" without a nul at the very end of the page
+    let wasm = wat::parse_str(
+        r#"(module
+            (import "env" "_test_byte_len" (func $byte_len (param i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 16) "h\c3\a9llo \e2\9c\93\00")
+            (data (i32.const 65533) "abc")
+            (func (export "multibyte") (result i32) i32.const 16)
+            (func (export "unterminated") (result i32) i32.const 65533)
+            (func (export "multibyte_len") (result i32) i32.const 16 call $byte_len)
+            (func (export "unterminated_len") (result i32) i32.const 65533 call $byte_len))"#,
+    )?;
+    let mut turing = Turing::<DirectExt>::new();
+    turing.add_rust_function("byte_len", "test", |(s,): (String,)| Ok(s.len() as u32))?;
+    let mut turing = turing.build()?;
+    turing.load_script_bytes(&wasm, EngineKind::Wasm, &["test"])?;
+
+    let res = turing.call_fn_by_name("multibyte", Params::new(), DataType::ExtString);
+    assert_eq!(res, Param::String("héllo ✓".to_string()));
+    let res = turing.call_fn_by_name("multibyte_len", Params::new(), DataType::U32);
+    assert_eq!(res, Param::U32(10));
+
+    // a string running off the end of memory is an error, not a host panic
+    let res = turing.call_fn_by_name("unterminated", Params::new(), DataType::ExtString);
+    assert!(
+        matches!(&res, Param::Error(e) if e.contains("not nul-terminated")),
+        "unexpected result: {res:?}"
+    );
+    let res = turing.call_fn_by_name("unterminated_len", Params::new(), DataType::U32);
+    assert!(
+        matches!(&res, Param::Error(e) if e.contains("not nul-terminated")),
+        "unexpected result: {res:?}"
+    );
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_wasm_without_memory() -> Result<()> {
+    let wasm = wat::parse_str(
+        r#"(module
+            (func (export "add") (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                i32.add))"#,
+    )?;
+    let mut turing = Turing::<DirectExt>::new().build()?;
+    turing.load_script_bytes(&wasm, EngineKind::Wasm, &["test"])?;
+
+    let res = turing.call_fn_by_name("add", crate::params![2i32, 3i32], DataType::I32);
+    assert_eq!(res, Param::I32(5));
+
+    let res = turing.call_fn_by_name("add", crate::params!["2", 3i32], DataType::I32);
+    assert_eq!(res, Param::Error(NO_MEMORY_MESSAGE.to_string()));
+    Ok(())
+}
+
+#[test]
+pub fn test_recv_string_wasm() -> Result<()> {
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+
+    let ids = turing
+        .call_fn_by_name("send_strings_test", Params::new(), DataType::U64)
+        .to_result::<u64>()?;
+    let (first, second) = ((ids >> 32) as u32, ids as u32);
+    assert_ne!(first, second);
+
+    assert_eq!(
+        turing.take_received_string(first).as_deref(),
+        Some("builder")
+    );
+    assert_eq!(
+        turing.take_received_string(second).as_deref(),
+        Some("pattern")
+    );
+    // each string can only be taken once
+    assert_eq!(turing.take_received_string(first), None);
+    Ok(())
+}
+
+#[test]
+pub fn test_wasm_max_stack_bytes() -> Result<()> {
+    let mut setup = Turing::<DirectExt>::new();
+    assert!(setup.set_wasm_max_stack_bytes(16 * 1024).is_err());
+    setup.set_wasm_max_stack_bytes(2 * 1024 * 1024)?;
+    assert_eq!(setup.config.wasm_max_stack_bytes, Some(2 * 1024 * 1024));
+
+    let mut turing = setup.build()?;
+    assert!(turing.set_wasm_max_stack_bytes(1024).is_err());
+    assert_eq!(turing.config.wasm_max_stack_bytes, Some(2 * 1024 * 1024));
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_wasm_interpreter_custom_stack() -> Result<()> {
+    use crate::EngineDataState;
+    use crate::engine::types::EngineConfig;
+    use parking_lot::RwLock;
+
+    let mut turing = common_setup_direct(WASM_SCRIPT)?;
+    turing.set_wasm_max_stack_bytes(128 * 1024)?;
+    setup_test_script(&mut turing, WASM_SCRIPT)?;
+    test_math(turing)?;
+
+    let config = EngineConfig {
+        wasm_max_stack_bytes: Some(1024),
+        ..Default::default()
+    };
+    let data = Arc::new(RwLock::new(EngineDataState::default()));
+    let res = WasmInterpreter::<DirectExt>::new(&Default::default(), &config, data);
+    assert!(res.is_err());
+    Ok(())
+}
+
+/// Runs `log_levels_test`, which logs "d", "i", "w" and "e" at each level,
+/// returning what reached `DirectExt`
+fn script_logs(turing: &mut Turing<DirectExt>, prefix: &str) -> Vec<(LogLevel, String)> {
+    LEVEL_LOGS.take();
+    let res = turing.call_fn_by_name("log_levels_test", Params::new(), DataType::Void);
+    assert_eq!(res, Param::Void);
+    LEVEL_LOGS
+        .take()
+        .into_iter()
+        .filter(|(_, msg)| msg.starts_with(prefix))
+        .collect()
+}
+
+fn test_script_log_levels(source: &str, script_name: &str) -> Result<()> {
+    let mut turing = common_setup_direct(source)?;
+
+    let prefix = format!("[{script_name}] ");
+    let logs = script_logs(&mut turing, &prefix);
+    assert_eq!(
+        logs,
+        LogLevel::ALL
+            .iter()
+            .zip(["d", "i", "w", "e"])
+            .map(|(level, msg)| (*level, format!("{prefix}{msg}")))
+            .collect::<Vec<_>>()
+    );
+
+    turing.set_log_level(LogLevel::Warn);
+    assert_eq!(
+        script_logs(&mut turing, &prefix),
+        [
+            (LogLevel::Warn, format!("{prefix}w")),
+            (LogLevel::Error, format!("{prefix}e")),
+        ]
+    );
+
+    // the configured log prefix replaces the script name
+    turing.set_log_prefix(Some("my_mod".to_string()));
+    setup_test_script(&mut turing, source)?;
+    turing.set_log_level(LogLevel::Error);
+    assert_eq!(
+        script_logs(&mut turing, "[my_mod] "),
+        [(LogLevel::Error, "[my_mod] e".to_string())]
+    );
+    Ok(())
+}
+
+#[test]
+pub fn test_script_log_levels_wasm() -> Result<()> {
+    test_script_log_levels(WASM_SCRIPT, "wasm_tests")
+}
+
+#[test]
+pub fn test_script_log_levels_lua() -> Result<()> {
+    test_script_log_levels(LUA_SCRIPT, "lua_test")
+}
+
+/// Logs "x" at a level `LogLevel` doesn't have
+#[cfg(feature = "wasm")]
+const BAD_LOG_LEVEL_WAT: &str = r#"(module
+    (import "env" "_host_log" (func $log (param i32 i32)))
+    (memory (export "memory") 1)
+    (data (i32.const 0) "x\00")
+    (func (export "log_bad_level")
+        i32.const 7
+        i32.const 0
+        call $log))"#;
+
+#[cfg(feature = "wasm")]
+#[test]
+pub fn test_script_log_bad_level_wasm() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("turing_bad_log_{}.wasm", std::process::id()));
+    std::fs::write(&path, wat::parse_str(BAD_LOG_LEVEL_WAT)?)?;
+
+    let mut turing = Turing::<DirectExt>::new().build()?;
+    turing.load_script(path.to_string_lossy(), &["test"])?;
+    LEVEL_LOGS.take();
+    let res = turing.call_fn_by_name("log_bad_level", Params::new(), DataType::Void);
+    assert!(matches!(res, Param::Error(_)));
+    // nothing reaches the host, not even at the closest level
+    assert!(!LEVEL_LOGS.take().iter().any(|(_, msg)| msg.ends_with('x')));
+
+    std::fs::remove_file(path)?;
+    Ok(())
+}
@@ -0,0 +1,168 @@
+//! Reloading a script when its file changes, for iterating on mods without restarting the host.
+//!
+//! Nothing here calls into the host from another thread. A `notify` watcher only flags that the
+//! script's directory changed, and `Turing::poll_reload` checks the flag from the host's own loop.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+
+use anyhow::{Result, anyhow};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::interop::types::Semver;
+
+/// How the loaded script was loaded from disk, so a reload can load it the same way
+#[derive(Debug, Clone)]
+pub enum ScriptSource {
+    /// Loaded with `Turing::load_script` or `Turing::load_script_checked`
+    File {
+        path: PathBuf,
+        capabilities: Vec<String>,
+        host_versions: Option<Vec<(String, Semver)>>,
+    },
+    /// Loaded with `Turing::load_mod`, which reads the manifest again on reload
+    Mod {
+        dir: PathBuf,
+        entry: PathBuf,
+        allowed_capabilities: Vec<String>,
+        host_versions: Vec<(String, Semver)>,
+    },
+}
+
+impl ScriptSource {
+    /// The script file that was loaded
+    pub fn path(&self) -> &Path {
+        match self {
+            ScriptSource::File { path, .. } => path,
+            ScriptSource::Mod { entry, .. } => entry,
+        }
+    }
+}
+
+/// Tracks the modification time of a loaded script
+pub struct HotReload {
+    source: ScriptSource,
+    modified: SystemTime,
+    changed: Arc<AtomicBool>,
+    /// None if the platform watcher couldn't start, the mtime is then checked on every poll
+    watcher: Option<RecommendedWatcher>,
+}
+
+impl HotReload {
+    /// Starts watching the script file `source` loaded
+    pub fn new(source: ScriptSource) -> Result<Self> {
+        let path = source.path();
+        let modified =
+            modified_time(path).map_err(|e| anyhow!("can't watch {}: {e}", path.display()))?;
+        let changed = Arc::new(AtomicBool::new(false));
+        Ok(Self {
+            watcher: watch_dir(path, Arc::clone(&changed)).ok(),
+            source,
+            modified,
+            changed,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.source.path()
+    }
+
+    /// How to load the script again
+    pub fn source(&self) -> &ScriptSource {
+        &self.source
+    }
+
+    /// True once per change of the script's mtime.
+    /// A file that's missing mid-save counts as unchanged until it's back.
+    pub fn take_change(&mut self) -> bool {
+        if self.watcher.is_some() && !self.changed.swap(false, Ordering::Relaxed) {
+            return false;
+        }
+        let Ok(modified) = modified_time(self.path()) else {
+            return false;
+        };
+        if self.modified == modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+fn modified_time(path: &Path) -> std::io::Result<SystemTime> {
+    path.metadata()?.modified()
+}
+
+/// Watches the script's directory rather than the file, so editors that save by
+/// replacing the file don't end the watch
+fn watch_dir(path: &Path, changed: Arc<AtomicBool>) -> notify::Result<RecommendedWatcher> {
+    let file_name = path.file_name().map(|n| n.to_os_string());
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            // the mtime check decides whether anything actually changed
+            changed.store(true, Ordering::Relaxed);
+            return;
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        if event
+            .paths
+            .iter()
+            .any(|p| p.file_name().map(|n| n.to_os_string()) == file_name)
+        {
+            changed.store(true, Ordering::Relaxed);
+        }
+    })?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod watch_tests {
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+
+    use super::HotReload;
+    use super::ScriptSource;
+
+    fn source(path: &std::path::Path) -> ScriptSource {
+        ScriptSource::File {
+            path: path.to_path_buf(),
+            capabilities: vec!["test".to_string()],
+            host_versions: None,
+        }
+    }
+
+    #[test]
+    fn test_take_change_once_per_mtime() {
+        let path = std::env::temp_dir().join(format!("turing_watch_{}.lua", std::process::id()));
+        std::fs::write(&path, "return {}").unwrap();
+        let mut watched = HotReload::new(source(&path)).unwrap();
+        assert!(!watched.take_change());
+
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        // the watcher flags the change from its own thread
+        let changed = (0..200).any(|_| {
+            thread::sleep(Duration::from_millis(10));
+            watched.take_change()
+        });
+        assert!(changed);
+        assert!(!watched.take_change());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(!watched.take_change());
+        assert!(HotReload::new(source(&path)).is_err());
+    }
+}